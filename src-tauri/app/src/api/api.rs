@@ -1,13 +1,19 @@
-use reqwest::{Client, Method};
+use std::collections::HashMap;
+use std::path::Path;
+
+use futures::StreamExt;
+use models::*;
 use reqwest::header::HeaderMap;
+use reqwest::{Client, Method, StatusCode};
 use serde::Serialize;
+use tokio::io::AsyncWriteExt;
 use url::Url;
 
-use models::*;
-
 use crate::api::call_event::*;
+use crate::api::{etag, network, rate_limit, schema, signing};
+use crate::connection::{self, Signal};
 use crate::errors::*;
-use crate::errors::Error::ApiError;
+use crate::{bandwidth, markdown};
 
 pub async fn handle_request(
     client: &Client,
@@ -17,17 +23,232 @@ pub async fn handle_request(
 ) -> Result<Response, Error> {
     let server_url = server_url.join("api/v4/").unwrap();
     match event {
-        ApiEvent::Login(login_id, password) => {
-            login(client, server_url, &login_id, &password).await
-        }
+        ApiEvent::Login(login_id, password) => login(client, server_url, login_id, password).await,
+        ApiEvent::Me => fetch_me(client, server_url, token).await,
+        ApiEvent::OpenGraph(url) => fetch_open_graph(client, server_url, token, url).await,
+        ApiEvent::Logout => logout(client, server_url, token).await,
+        ApiEvent::Ping => ping(client, server_url).await,
+        ApiEvent::ClientConfig => fetch_client_config(client, server_url, token).await,
+        ApiEvent::CloudLimits => fetch_cloud_limits(client, server_url, token).await,
+        ApiEvent::StorageUsage => fetch_storage_usage(client, server_url, token).await,
         ApiEvent::MyTeams => my_teams(client, server_url, token).await,
+        ApiEvent::Teams { page, per_page } => {
+            fetch_teams(client, server_url, token, *page, *per_page).await
+        }
         ApiEvent::MyTeamMembers => my_team_members(client, server_url, token).await,
         ApiEvent::MyChannels => my_channels(client, server_url, token).await,
+        ApiEvent::TeamChannels(team_id) => team_channels(client, server_url, token, team_id).await,
+        ApiEvent::Post(post_id) => fetch_post(client, server_url, token, post_id).await,
         ApiEvent::PostThreads(post_id) => {
-            fetch_post_thread(client, server_url, token, post_id).await
+            fetch_post_thread(client, server_url, token, post_id, None).await
+        }
+        ApiEvent::ThreadReplies {
+            post_id,
+            after,
+            after_create_at,
+        } => {
+            fetch_post_thread(
+                client,
+                server_url,
+                token,
+                post_id,
+                Some((after, *after_create_at)),
+            )
+            .await
+        }
+        ApiEvent::ChannelPosts {
+            channel_id,
+            page,
+            per_page,
+        } => {
+            let page = PostsPage::Offset(*page);
+            fetch_channel_posts(client, server_url, token, channel_id, page, *per_page).await
+        }
+        ApiEvent::ChannelPostsBefore {
+            channel_id,
+            before,
+            per_page,
+        } => {
+            let page = PostsPage::Before(before);
+            fetch_channel_posts(client, server_url, token, channel_id, page, *per_page).await
+        }
+        ApiEvent::ChannelPostsSince { channel_id, since } => {
+            fetch_channel_posts_since(client, server_url, token, channel_id, *since).await
+        }
+        ApiEvent::ChannelMembers {
+            channel_id,
+            page,
+            per_page,
+        } => fetch_channel_members(client, server_url, token, channel_id, *page, *per_page).await,
+        ApiEvent::SearchPosts {
+            team_id,
+            terms,
+            is_or_search,
+            page,
+            per_page,
+        } => {
+            search_posts(
+                client,
+                server_url,
+                token,
+                team_id,
+                terms,
+                *is_or_search,
+                (*page, *per_page),
+            )
+            .await
+        }
+        ApiEvent::Channel(channel_id) => fetch_channel(client, server_url, token, channel_id).await,
+        ApiEvent::CallState(channel_id) => {
+            fetch_call_state(client, server_url, token, channel_id).await
+        }
+        ApiEvent::PatchChannel(channel_id, patch) => {
+            patch_channel(client, server_url, token, channel_id, patch).await
+        }
+        ApiEvent::CreateChannel(channel) => {
+            create_channel(client, server_url, token, channel).await
+        }
+        ApiEvent::AddChannelMember {
+            channel_id,
+            user_id,
+        } => add_channel_member(client, server_url, token, channel_id, user_id).await,
+        ApiEvent::RemoveChannelMember {
+            channel_id,
+            user_id,
+        } => remove_channel_member(client, server_url, token, channel_id, user_id).await,
+        ApiEvent::ChannelMember {
+            channel_id,
+            user_id,
+        } => fetch_channel_member(client, server_url, token, channel_id, user_id).await,
+        ApiEvent::ViewChannel {
+            user_id,
+            channel_id,
+        } => view_channel(client, server_url, token, user_id, channel_id).await,
+        ApiEvent::UpdateNotifyProps {
+            channel_id,
+            user_id,
+            patch,
+        } => update_notify_props(client, server_url, token, channel_id, user_id, patch).await,
+        ApiEvent::ChannelStats(channel_id) => {
+            fetch_channel_stats(client, server_url, token, channel_id).await
+        }
+        ApiEvent::PinnedPosts(channel_id) => {
+            fetch_pinned_posts(client, server_url, token, channel_id).await
         }
-        ApiEvent::ChannelPosts(channel_id) => {
-            fetch_channel_posts(client, server_url, token, channel_id).await
+        ApiEvent::PinPost(post_id) => set_pinned(client, server_url, token, post_id, true).await,
+        ApiEvent::UnpinPost(post_id) => set_pinned(client, server_url, token, post_id, false).await,
+        ApiEvent::FlaggedPosts {
+            user_id,
+            page,
+            per_page,
+        } => fetch_flagged_posts(client, server_url, token, user_id, *page, *per_page).await,
+        ApiEvent::User(user_id) => fetch_user(client, server_url, token, user_id).await,
+        ApiEvent::UsersByIds(user_ids) => fetch_users(client, server_url, token, user_ids).await,
+        ApiEvent::Users {
+            in_channel,
+            page,
+            per_page,
+        } => {
+            list_users(
+                client,
+                server_url,
+                token,
+                in_channel.as_ref(),
+                *page,
+                *per_page,
+            )
+            .await
+        }
+        ApiEvent::UserStatus(user_id) => {
+            fetch_user_status(client, server_url, token, user_id).await
+        }
+        ApiEvent::SetUserStatus {
+            user_id,
+            status,
+            dnd_end_time,
+        } => set_user_status(client, server_url, token, user_id, *status, *dnd_end_time).await,
+        ApiEvent::UserStatuses(user_ids) => {
+            fetch_user_statuses(client, server_url, token, user_ids).await
+        }
+        ApiEvent::AutocompleteUsers {
+            channel_id,
+            name,
+            limit,
+        } => autocomplete_users(client, server_url, token, channel_id, name, *limit).await,
+        ApiEvent::CustomEmoji { page, per_page } => {
+            fetch_custom_emoji(client, server_url, token, *page, *per_page).await
+        }
+        ApiEvent::CustomEmojiByName(name) => {
+            fetch_custom_emoji_by_name(client, server_url, token, name).await
+        }
+        ApiEvent::CustomEmojiImage(emoji_id) => {
+            fetch_custom_emoji_image(client, server_url, token, emoji_id).await
+        }
+        ApiEvent::DownloadFile { file_id, path } => {
+            download_file(client, server_url, token, file_id, path).await
+        }
+        ApiEvent::Preferences(user_id) => {
+            fetch_preferences(client, server_url, token, user_id).await
+        }
+        ApiEvent::SavePreferences {
+            user_id,
+            preferences,
+        } => save_preferences(client, server_url, token, user_id, preferences).await,
+        ApiEvent::DeletePreferences {
+            user_id,
+            preferences,
+        } => delete_preferences(client, server_url, token, user_id, preferences).await,
+        ApiEvent::UserChannelMembers {
+            user_id,
+            page,
+            per_page,
+        } => fetch_user_channel_members(client, server_url, token, user_id, *page, *per_page).await,
+        ApiEvent::SidebarCategories { user_id, team_id } => {
+            fetch_sidebar_categories(client, server_url, token, user_id, team_id).await
+        }
+        ApiEvent::UpdateSidebarCategories {
+            user_id,
+            team_id,
+            categories,
+        } => {
+            update_sidebar_categories(client, server_url, token, user_id, team_id, categories).await
+        }
+        ApiEvent::SidebarCategoryOrder {
+            user_id,
+            team_id,
+            order,
+        } => {
+            update_sidebar_category_order(client, server_url, token, user_id, team_id, order).await
+        }
+        ApiEvent::MarkThreadUnread {
+            user_id,
+            team_id,
+            thread_id,
+            post_id,
+        } => {
+            mark_thread_unread(
+                client, server_url, token, user_id, team_id, thread_id, post_id,
+            )
+            .await
+        }
+        ApiEvent::CreatePost(post) => create_post(client, server_url, token, post).await,
+        ApiEvent::UploadFile {
+            channel_id,
+            name,
+            data,
+        } => {
+            let file = upload_file(
+                client,
+                server_url,
+                token,
+                channel_id,
+                name,
+                data.len() as u64,
+                data.clone().into(),
+                None,
+            )
+            .await?;
+            Ok(Response::FileUploaded(file))
         }
     }
 }
@@ -39,16 +260,102 @@ async fn handle<T: Serialize>(
     payload: Option<T>,
     token: Option<&AccessToken>,
 ) -> Result<reqwest::Response, reqwest::Error> {
-    let mut builder = client.request(method, url);
-    builder = match payload {
+    let builder = client.request(method, url);
+    let builder = match payload {
         Some(json) => builder.json(&json),
         _ => builder,
     };
-    builder = match token {
+    send(client, builder, token).await
+}
+
+fn authorized(
+    builder: reqwest::RequestBuilder,
+    token: Option<&AccessToken>,
+) -> reqwest::RequestBuilder {
+    match token {
         Some(bearer_token) => builder.bearer_auth(bearer_token.as_str()),
         _ => builder,
+    }
+}
+
+async fn send(
+    client: &Client,
+    builder: reqwest::RequestBuilder,
+    token: Option<&AccessToken>,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let mut request = authorized(builder, token).build()?;
+    signing::sign(&mut request);
+    execute(client, request).await
+}
+
+/// Send request which is already signed
+async fn execute(
+    client: &Client,
+    mut request: reqwest::Request,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let cache_key = etag::prepare(&mut request);
+    let url = request.url().clone();
+    // Streamed bodies have no bytes to count, declared length is used
+    let sent = match request.body().and_then(|body| body.as_bytes()) {
+        Some(body) => body.len() as u64,
+        None => request
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok()?.parse().ok())
+            .unwrap_or_default(),
+    };
+    // Configured proxy or certificates replace shared client
+    let client = network::client_for(&url).unwrap_or_else(|| client.clone());
+    let result = client
+        .execute(request)
+        .await
+        .map(|response| bandwidth::counted(response, &url));
+    bandwidth::record(&url, sent, 0);
+    connection::report(match &result {
+        Ok(response) if response.status().is_server_error() => Signal::ServerError,
+        Ok(_) => Signal::Reachable,
+        Err(_) => Signal::Unreachable,
+    });
+    if let Ok(response) = &result {
+        rate_limit::record(&url, response.status(), response.headers());
+    }
+    etag::process(cache_key, result?).await
+}
+
+/// Error of rejected request. Denied permission, missing resource and rate
+/// limit get variants of their own, other failures keep error sent by server
+/// and fall back to `fallback` when body isn't one.
+async fn failed(response: reqwest::Response, fallback: NativeError) -> Error {
+    let status = response.status();
+    let retry_after = rate_limit::retry_after(response.headers());
+    let request_id = response
+        .headers()
+        .get("x-request-id")
+        .and_then(|id| id.to_str().ok())
+        .map(str::to_owned);
+    let endpoint = bandwidth::url(&response).path().to_owned();
+    let error = response.json::<ServerApiError>().await.ok();
+    tracing::warn!("Request to {endpoint} failed with {status}: {error:?}");
+    let reported = || {
+        error.clone().unwrap_or_else(|| ServerApiError {
+            id: String::new(),
+            message: status.canonical_reason().unwrap_or_default().to_owned(),
+            request_id: request_id.clone(),
+            status_code: status.as_u16() as i16,
+        })
     };
-    builder.send().await
+    match status {
+        StatusCode::FORBIDDEN => Error::PermissionDenied(reported()),
+        StatusCode::NOT_FOUND => Error::NotFound(reported()),
+        StatusCode::TOO_MANY_REQUESTS => Error::RateLimited {
+            retry_after,
+            error: reported(),
+        },
+        _ => match error {
+            Some(error) => Error::ApiError(error),
+            None => fallback.into(),
+        },
+    }
 }
 
 async fn login(
@@ -59,8 +366,8 @@ async fn login(
 ) -> Result<Response, Error> {
     tracing::info!("Login user: {} to {}", login, uri);
     let login_request = LoginRequest {
-        login_id: Login::new(login.to_string()).expect("Invalid login"),
-        password: Pass::new(password.to_string()).expect("Invalid password"),
+        login_id: Login::new(login.to_string()).map_err(|_| NativeError::InvalidLogin)?,
+        password: Pass::new(password.to_string()).map_err(|_| NativeError::InvalidPassword)?,
     };
     let result = handle(
         client,
@@ -79,17 +386,11 @@ async fn login(
         Ok(response) => {
             if !response.status().is_success() {
                 tracing::error!("Failed to perform Login body: {:?}", &response.status());
-                return match &response.json::<ServerApiError>().await {
-                    Ok(e) => Err(ApiError(e.to_owned()))?,
-                    Err(e) => {
-                        tracing::warn!("Failed to perform login: {e}");
-                        Err(NativeError::PerformLogin)?
-                    }
-                };
+                return Err(failed(response, NativeError::PerformLogin).await)?;
             }
-            let token = AccessToken::new(get_token(&response.headers()).to_owned())
-                .expect("Invalid access token");
-            let user_response = &response.json::<UserResponse>().await;
+            let token = AccessToken::new(get_token(response.headers()).to_owned())
+                .map_err(|_| NativeError::MissingToken)?;
+            let user_response = &schema::json::<UserResponse>(response).await;
             tracing::debug!("user response: {user_response:?}");
             match user_response {
                 Ok(user) => {
@@ -111,6 +412,97 @@ async fn login(
     }
 }
 
+async fn logout(client: &Client, uri: Url, token: Option<&AccessToken>) -> Result<Response, Error> {
+    tracing::info!("Logout: {}", uri);
+    let result = handle(
+        client,
+        Method::POST,
+        uri.join("users/logout").unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                Ok(Response::LoggedOut)
+            } else {
+                tracing::error!("Failed to revoke session!");
+                Err(failed(response, NativeError::Logout).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn ping(client: &Client, uri: Url) -> Result<Response, Error> {
+    tracing::info!("Ping: {}", uri);
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join("system/ping").unwrap(),
+        None as Option<()>,
+        None,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                Ok(Response::Pong)
+            } else {
+                tracing::error!("Server didn't respond to ping: {}", response.status());
+                Err(failed(response, NativeError::ProbeServer).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_client_config(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+) -> Result<Response, Error> {
+    let mut url = uri.join("config/client").unwrap();
+    url.query_pairs_mut().append_pair("format", "old");
+    let result = handle(client, Method::GET, url, None as Option<()>, token)
+        .await
+        .map_err(|error| {
+            Err(Error::RequestFailed(ClientFailed {
+                reason: error.to_string(),
+            }))
+        });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let config = response
+                    .json::<HashMap<String, String>>()
+                    .await
+                    .map_err(|e| {
+                        tracing::error!("Malformed client config: {e}");
+                        NativeError::ProbeServer
+                    })?;
+                tracing::trace!("Received client config: {:?}", config);
+                Ok(Response::ClientConfig(config))
+            } else {
+                tracing::error!("Failed to get client config!");
+                Err(failed(response, NativeError::ProbeServer).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
 fn get_token(headers: &HeaderMap) -> &str {
     headers
         .get("token")
@@ -140,12 +532,45 @@ async fn my_teams(
     match result {
         Ok(response) => {
             if response.status().is_success() {
-                let teams: Vec<Team> = response.json::<Vec<Team>>().await.unwrap();
+                let teams: Vec<Team> = schema::json::<Vec<Team>>(response).await?;
                 tracing::trace!("Received my teams: {:?}", teams);
                 Ok(Response::MyTeams(teams))
             } else {
                 tracing::error!("Failed to get my teams!");
-                Err(NativeError::FetchTeams)?
+                Err(failed(response, NativeError::FetchTeams).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_teams(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    page: u32,
+    per_page: u32,
+) -> Result<Response, Error> {
+    let mut url = uri.join("teams").unwrap();
+    url.query_pairs_mut()
+        .append_pair("page", &page.to_string())
+        .append_pair("per_page", &per_page.to_string());
+    let result = handle(client, Method::GET, url, None as Option<()>, token)
+        .await
+        .map_err(|error| {
+            Err(Error::RequestFailed(ClientFailed {
+                reason: error.to_string(),
+            }))
+        });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let teams = schema::json::<Vec<Team>>(response).await?;
+                tracing::trace!("Received teams: {:?}", teams);
+                Ok(Response::Teams(teams))
+            } else {
+                tracing::error!("Failed to get page {page} of teams!");
+                Err(failed(response, NativeError::FetchTeams).await)?
             }
         }
         Err(error) => error,
@@ -175,12 +600,12 @@ async fn my_team_members(
         Ok(response) => {
             if response.status().is_success() {
                 let team_members: Vec<TeamMember> =
-                    response.json::<Vec<TeamMember>>().await.unwrap();
+                    schema::json::<Vec<TeamMember>>(response).await?;
                 tracing::trace!("Received my team members: {:?}", team_members);
                 Ok(Response::MyTeamMembers(team_members))
             } else {
                 tracing::error!("Failed to get my team members!");
-                Err(NativeError::FetchTeamMembers)?
+                Err(failed(response, NativeError::FetchTeamMembers).await)?
             }
         }
         Err(error) => error,
@@ -209,28 +634,29 @@ async fn my_channels(
     match result {
         Ok(response) => {
             if response.status().is_success() {
-                let channels = response.json::<Vec<Channel>>().await.unwrap();
+                let channels = schema::json::<Vec<Channel>>(response).await?;
                 tracing::trace!("Received my channels: {:?}", channels);
                 Ok(Response::MyChannels(channels))
             } else {
                 tracing::error!("Failed to get my channels!");
-                Err(NativeError::FetchChannels)?
+                Err(failed(response, NativeError::FetchChannels).await)?
             }
         }
         Err(error) => error,
     }
 }
 
-async fn fetch_channel_posts(
+async fn team_channels(
     client: &Client,
     uri: Url,
     token: Option<&AccessToken>,
-    channel_id: &ChannelId,
+    team_id: &TeamId,
 ) -> Result<Response, Error> {
     let result = handle(
         client,
         Method::GET,
-        uri.join(&format!("channels/{channel_id}/posts")).unwrap(),
+        uri.join(&format!("users/me/teams/{team_id}/channels"))
+            .unwrap(),
         None as Option<()>,
         token,
     )
@@ -240,17 +666,143 @@ async fn fetch_channel_posts(
             reason: error.to_string(),
         }))
     });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let channels = schema::json::<Vec<Channel>>(response).await?;
+                tracing::trace!("Received channels of team {team_id}: {:?}", channels);
+                Ok(Response::MyChannels(channels))
+            } else {
+                tracing::error!("Failed to get channels of team {team_id}!");
+                Err(failed(response, NativeError::FetchChannels).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+/// Where page of channel posts starts
+enum PostsPage<'a> {
+    /// Counted from newest post, pages shift as posts arrive
+    Offset(u32),
+    /// Right after post, stable while posts arrive
+    Before(&'a PostId),
+}
+
+async fn fetch_channel_posts(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+    page: PostsPage<'_>,
+    per_page: u32,
+) -> Result<Response, Error> {
+    let mut url = uri.join(&format!("channels/{channel_id}/posts")).unwrap();
+    {
+        let mut query = url.query_pairs_mut();
+        match page {
+            PostsPage::Offset(page) => query.append_pair("page", &page.to_string()),
+            PostsPage::Before(post_id) => query.append_pair("before", post_id.as_str()),
+        };
+        query.append_pair("per_page", &per_page.to_string());
+    }
+    let result = handle(client, Method::GET, url, None as Option<()>, token)
+        .await
+        .map_err(|error| {
+            Err(Error::RequestFailed(ClientFailed {
+                reason: error.to_string(),
+            }))
+        });
 
     match result {
         Ok(response) => {
             if response.status().is_success() {
-                let posts: PostThread = response.json::<PostThread>().await.unwrap();
+                let mut posts: PostThread = schema::json::<PostThread>(response).await?;
+                markdown::annotate(&mut posts);
                 tracing::trace!("Received posts: {:?}", posts);
                 Ok(Response::ChannelPosts(posts))
             } else {
-                let error = response.json::<ServerApiError>().await.unwrap();
-                tracing::error!("Failed to get fetch channel posts! {:?}", error);
-                Err(NativeError::FetchChannels)?
+                tracing::error!("Failed to fetch channel posts!");
+                Err(failed(response, NativeError::FetchChannels).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_channel_posts_since(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+    since: Timestamp,
+) -> Result<Response, Error> {
+    let mut url = uri.join(&format!("channels/{channel_id}/posts")).unwrap();
+    url.query_pairs_mut()
+        .append_pair("since", &since.to_string());
+    let result = handle(client, Method::GET, url, None as Option<()>, token)
+        .await
+        .map_err(|error| {
+            Err(Error::RequestFailed(ClientFailed {
+                reason: error.to_string(),
+            }))
+        });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let mut posts = schema::json::<PostThread>(response).await?;
+                markdown::annotate(&mut posts);
+                tracing::trace!(
+                    "Received posts of channel {channel_id} since {since}: {:?}",
+                    posts
+                );
+                Ok(Response::ChannelPosts(posts))
+            } else {
+                tracing::error!("Failed to get posts of channel {channel_id} since {since}!");
+                Err(failed(response, NativeError::FetchChannels).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn search_posts(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    team_id: &TeamId,
+    terms: &str,
+    is_or_search: bool,
+    (page, per_page): (u32, u32),
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::POST,
+        uri.join(&format!("teams/{team_id}/posts/search")).unwrap(),
+        Some(serde_json::json!({
+            "terms": terms,
+            "is_or_search": is_or_search,
+            "include_deleted_channels": false,
+            "page": page,
+            "per_page": per_page,
+        })),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let mut posts = schema::json::<PostThread>(response).await?;
+                markdown::annotate(&mut posts);
+                tracing::trace!("Found {} posts in team {team_id}", posts.order.len());
+                Ok(Response::SearchResults(posts))
+            } else {
+                Err(failed(response, NativeError::SearchPosts).await)?
             }
         }
         Err(error) => error,
@@ -262,11 +814,82 @@ async fn fetch_post_thread(
     uri: Url,
     token: Option<&AccessToken>,
     post_id: &PostId,
+    after: Option<(&PostId, Timestamp)>,
+) -> Result<Response, Error> {
+    let mut url = uri.join(&format!("posts/{post_id}/thread")).unwrap();
+    if let Some((from_post, from_create_at)) = after {
+        url.query_pairs_mut()
+            .append_pair("fromPost", from_post)
+            .append_pair("fromCreateAt", &from_create_at.to_string())
+            .append_pair("direction", "down");
+    }
+    let result = handle(client, Method::GET, url, None as Option<()>, token)
+        .await
+        .map_err(|error| {
+            Err(Error::RequestFailed(ClientFailed {
+                reason: error.to_string(),
+            }))
+        });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let mut threads: PostThread = schema::json(response).await?;
+                markdown::annotate(&mut threads);
+                tracing::trace!("Received threads: {:?}", threads);
+                Ok(Response::ChannelThreads(threads))
+            } else {
+                tracing::error!("Failed to get my channels!");
+                Err(failed(response, NativeError::FetchPosts).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_channel_members(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+    page: u32,
+    per_page: u32,
+) -> Result<Response, Error> {
+    let mut url = uri.join(&format!("channels/{channel_id}/members")).unwrap();
+    url.query_pairs_mut()
+        .append_pair("page", &page.to_string())
+        .append_pair("per_page", &per_page.to_string());
+    let result = handle(client, Method::GET, url, None as Option<()>, token)
+        .await
+        .map_err(|error| {
+            Err(Error::RequestFailed(ClientFailed {
+                reason: error.to_string(),
+            }))
+        });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let members = schema::json::<Vec<ChannelMember>>(response).await?;
+                tracing::trace!("Received channel members: {:?}", members);
+                Ok(Response::ChannelMembers(members))
+            } else {
+                tracing::error!("Failed to get members of channel {channel_id}!");
+                Err(failed(response, NativeError::FetchChannelMembers).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_channel(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
 ) -> Result<Response, Error> {
     let result = handle(
         client,
         Method::GET,
-        uri.join(&format!("api/v4/posts/{post_id}/thread")).unwrap(),
+        uri.join(&format!("channels/{channel_id}")).unwrap(),
         None as Option<()>,
         token,
     )
@@ -279,14 +902,1634 @@ async fn fetch_post_thread(
     match result {
         Ok(response) => {
             if response.status().is_success() {
-                let threads: PostThread = response.json().await.unwrap();
-                tracing::trace!("Received threads: {:?}", threads);
-                Ok(Response::ChannelThreads(threads))
+                let channel = schema::json::<Channel>(response).await?;
+                tracing::trace!("Received channel: {:?}", channel);
+                Ok(Response::Channel(channel))
             } else {
-                tracing::error!("Failed to get my channels!");
-                Err(NativeError::FetchPosts)?
+                tracing::error!("Failed to get channel {channel_id}!");
+                Err(failed(response, NativeError::FetchChannels).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+/// Plugin routes live next to `api/v4`, not under it
+async fn fetch_call_state(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join(&format!("../../plugins/com.mattermost.calls/{channel_id}"))
+            .unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let state = schema::json::<CallChannelState>(response).await?;
+                tracing::trace!("Received call state: {:?}", state);
+                Ok(Response::CallState(state))
+            } else {
+                Err(failed(response, NativeError::FetchCallState).await)?
             }
         }
         Err(error) => error,
     }
 }
+
+async fn fetch_open_graph(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    url: &str,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::POST,
+        uri.join("opengraph").unwrap(),
+        Some(serde_json::json!({ "url": url })),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let open_graph = schema::json::<OpenGraph>(response).await?;
+                tracing::trace!("Received open graph: {:?}", open_graph);
+                Ok(Response::OpenGraph(open_graph))
+            } else {
+                tracing::error!("Failed to get link preview of {url}: {}", response.status());
+                Err(failed(response, NativeError::FetchLinkPreview).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_cloud_limits(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join("cloud/limits").unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let limits = schema::json::<CloudLimits>(response).await?;
+                tracing::trace!("Received cloud limits: {:?}", limits);
+                Ok(Response::CloudLimits(limits))
+            } else {
+                tracing::error!("Failed to get cloud limits: {}", response.status());
+                Err(failed(response, NativeError::FetchQuota).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_storage_usage(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join("usage/storage").unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let usage = schema::json::<StorageUsage>(response).await?;
+                tracing::trace!("Received storage usage: {:?}", usage);
+                Ok(Response::StorageUsage(usage))
+            } else {
+                tracing::error!("Failed to get storage usage: {}", response.status());
+                Err(failed(response, NativeError::FetchQuota).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_channel_stats(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join(&format!("channels/{channel_id}/stats")).unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let stats = schema::json::<ChannelStats>(response).await?;
+                tracing::trace!("Received channel stats: {:?}", stats);
+                Ok(Response::ChannelStats(stats))
+            } else {
+                tracing::error!("Failed to get stats of channel {channel_id}!");
+                Err(failed(response, NativeError::FetchChannelStats).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_user(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+) -> Result<Response, Error> {
+    tracing::info!("Get user {user_id}: {}", uri);
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join(&format!("users/{user_id}")).unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let user = schema::json::<UserResponse>(response).await?;
+                tracing::trace!("Received user: {:?}", user);
+                Ok(Response::User(user))
+            } else {
+                tracing::error!("Failed to get user {user_id}!");
+                Err(failed(response, NativeError::FetchUser).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_me(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+) -> Result<Response, Error> {
+    tracing::info!("Get current user: {}", uri);
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join("users/me").unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let user = schema::json::<UserResponse>(response).await?;
+                tracing::trace!("Received user: {:?}", user);
+                Ok(Response::User(user))
+            } else if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+                tracing::error!("Access token was rejected");
+                Err(NativeError::InvalidToken)?
+            } else {
+                tracing::error!("Failed to get current user!");
+                Err(failed(response, NativeError::FetchUser).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_pinned_posts(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join(&format!("channels/{channel_id}/pinned")).unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let mut posts = schema::json::<PostThread>(response).await?;
+                markdown::annotate(&mut posts);
+                tracing::trace!("Received pinned posts: {:?}", posts);
+                Ok(Response::ChannelPosts(posts))
+            } else {
+                tracing::error!("Failed to get pinned posts of channel {channel_id}!");
+                Err(failed(response, NativeError::FetchPosts).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn set_pinned(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    post_id: &PostId,
+    pinned: bool,
+) -> Result<Response, Error> {
+    let action = if pinned { "pin" } else { "unpin" };
+    let result = handle(
+        client,
+        Method::POST,
+        uri.join(&format!("posts/{post_id}/{action}")).unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                Ok(Response::Pinned(pinned))
+            } else {
+                Err(failed(response, NativeError::PinPost).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn patch_channel(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+    patch: &ChannelPatch,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::PUT,
+        uri.join(&format!("channels/{channel_id}/patch")).unwrap(),
+        Some(patch),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let channel = schema::json::<Channel>(response).await?;
+                tracing::trace!("Updated channel: {:?}", channel);
+                Ok(Response::Channel(channel))
+            } else {
+                Err(failed(response, NativeError::UpdateChannel).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn create_channel(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel: &CreateChannelRequest,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::POST,
+        uri.join("channels").unwrap(),
+        Some(channel),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let channel = schema::json::<Channel>(response).await?;
+                tracing::trace!("Created channel: {:?}", channel);
+                Ok(Response::Channel(channel))
+            } else {
+                Err(failed(response, NativeError::CreateChannel).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn add_channel_member(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+    user_id: &UserId,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::POST,
+        uri.join(&format!("channels/{channel_id}/members")).unwrap(),
+        Some(serde_json::json!({ "user_id": user_id })),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let member = schema::json::<ChannelMember>(response).await?;
+                tracing::trace!("Added channel member: {:?}", member);
+                Ok(Response::ChannelMember(member))
+            } else {
+                Err(failed(response, NativeError::ChannelMembership).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_channel_member(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+    user_id: &UserId,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join(&format!("channels/{channel_id}/members/{user_id}"))
+            .unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let member = schema::json::<ChannelMember>(response).await?;
+                tracing::trace!("Channel member: {:?}", member);
+                Ok(Response::ChannelMember(member))
+            } else {
+                Err(failed(response, NativeError::FetchChannelMembers).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn view_channel(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+    channel_id: &ChannelId,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::POST,
+        uri.join(&format!("channels/members/{user_id}/view"))
+            .unwrap(),
+        Some(serde_json::json!({ "channel_id": channel_id })),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let viewed = schema::json::<ChannelViewed>(response).await?;
+                tracing::trace!("Channel viewed: {:?}", viewed);
+                Ok(Response::ChannelViewed(
+                    viewed
+                        .last_viewed_at_times
+                        .get(channel_id.as_str())
+                        .copied(),
+                ))
+            } else {
+                Err(failed(response, NativeError::ViewChannel).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn remove_channel_member(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+    user_id: &UserId,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::DELETE,
+        uri.join(&format!("channels/{channel_id}/members/{user_id}"))
+            .unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                Ok(Response::ChannelMemberRemoved)
+            } else {
+                Err(failed(response, NativeError::ChannelMembership).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn update_notify_props(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+    user_id: &UserId,
+    patch: &NotifyPropsPatch,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::PUT,
+        uri.join(&format!(
+            "channels/{channel_id}/members/{user_id}/notify_props"
+        ))
+        .unwrap(),
+        Some(patch),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                Ok(Response::NotifyPropsUpdated)
+            } else {
+                Err(failed(response, NativeError::UpdateNotifyProps).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_flagged_posts(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+    page: u32,
+    per_page: u32,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join(&format!(
+            "users/{user_id}/posts/flagged?page={page}&per_page={per_page}"
+        ))
+        .unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let mut posts = schema::json::<PostThread>(response).await?;
+                markdown::annotate(&mut posts);
+                tracing::trace!("Received flagged posts: {:?}", posts);
+                Ok(Response::ChannelPosts(posts))
+            } else {
+                tracing::error!("Failed to get flagged posts of {user_id}!");
+                Err(failed(response, NativeError::FetchPosts).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_users(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_ids: &[UserId],
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::POST,
+        uri.join("users/ids").unwrap(),
+        Some(user_ids),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let users = schema::json::<Vec<UserResponse>>(response).await?;
+                tracing::trace!("Received users: {:?}", users);
+                Ok(Response::Users(users))
+            } else {
+                tracing::error!("Failed to get users by ids!");
+                Err(failed(response, NativeError::FetchUser).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn list_users(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    in_channel: Option<&ChannelId>,
+    page: u32,
+    per_page: u32,
+) -> Result<Response, Error> {
+    let mut url = uri.join("users").unwrap();
+    {
+        let mut query = url.query_pairs_mut();
+        query
+            .append_pair("active", "true")
+            .append_pair("page", &page.to_string())
+            .append_pair("per_page", &per_page.to_string());
+        if let Some(channel_id) = in_channel {
+            query.append_pair("in_channel", channel_id);
+        }
+    }
+    let result = handle(client, Method::GET, url, None as Option<()>, token)
+        .await
+        .map_err(|error| {
+            Err(Error::RequestFailed(ClientFailed {
+                reason: error.to_string(),
+            }))
+        });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let users = schema::json::<Vec<UserResponse>>(response).await?;
+                tracing::trace!("Received users: {:?}", users);
+                Ok(Response::Users(users))
+            } else {
+                tracing::error!("Failed to get page {page} of users!");
+                Err(failed(response, NativeError::FetchUser).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_user_status(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join(&format!("users/{user_id}/status")).unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let status = schema::json::<UserStatus>(response).await?;
+                tracing::trace!("Received status: {:?}", status);
+                Ok(Response::Status(status))
+            } else {
+                tracing::error!("Failed to get status of user {user_id}!");
+                Err(failed(response, NativeError::FetchStatuses).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn set_user_status(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+    status: Presence,
+    dnd_end_time: Option<Timestamp>,
+) -> Result<Response, Error> {
+    let mut body = serde_json::json!({ "user_id": user_id, "status": status });
+    if let Some(dnd_end_time) = dnd_end_time {
+        body["dnd_end_time"] = dnd_end_time.into();
+    }
+    let result = handle(
+        client,
+        Method::PUT,
+        uri.join(&format!("users/{user_id}/status")).unwrap(),
+        Some(body),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let status = schema::json::<UserStatus>(response).await?;
+                tracing::trace!("Updated status: {:?}", status);
+                Ok(Response::Status(status))
+            } else {
+                tracing::error!("Failed to set status: {}", response.status());
+                Err(failed(response, NativeError::SetStatus).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_user_statuses(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_ids: &[UserId],
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::POST,
+        uri.join("users/status/ids").unwrap(),
+        Some(user_ids),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let statuses = schema::json::<Vec<UserStatus>>(response).await?;
+                tracing::trace!("Received statuses: {:?}", statuses);
+                Ok(Response::Statuses(statuses))
+            } else {
+                tracing::error!("Failed to get statuses of users!");
+                Err(failed(response, NativeError::FetchStatuses).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn autocomplete_users(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+    name: &str,
+    limit: usize,
+) -> Result<Response, Error> {
+    let mut url = uri.join("users/autocomplete").unwrap();
+    url.query_pairs_mut()
+        .append_pair("in_channel", channel_id)
+        .append_pair("name", name)
+        .append_pair("limit", &limit.to_string());
+    let result = handle(client, Method::GET, url, None as Option<()>, token)
+        .await
+        .map_err(|error| {
+            Err(Error::RequestFailed(ClientFailed {
+                reason: error.to_string(),
+            }))
+        });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let users = schema::json::<UserAutocomplete>(response).await?;
+                tracing::trace!("Autocomplete {name:?}: {:?}", users);
+                Ok(Response::UserAutocomplete(users))
+            } else {
+                tracing::error!("Failed to autocomplete users {name:?}!");
+                Err(failed(response, NativeError::AutocompleteUsers).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_custom_emoji(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    page: u32,
+    per_page: u32,
+) -> Result<Response, Error> {
+    let mut url = uri.join("emoji").unwrap();
+    url.query_pairs_mut()
+        .append_pair("page", &page.to_string())
+        .append_pair("per_page", &per_page.to_string())
+        .append_pair("sort", "name");
+    let result = handle(client, Method::GET, url, None as Option<()>, token)
+        .await
+        .map_err(|error| {
+            Err(Error::RequestFailed(ClientFailed {
+                reason: error.to_string(),
+            }))
+        });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let emojis = schema::json::<Vec<MetaEmoji>>(response).await?;
+                tracing::trace!("Received custom emoji: {:?}", emojis);
+                Ok(Response::CustomEmoji(emojis))
+            } else {
+                tracing::error!("Failed to get custom emoji!");
+                Err(failed(response, NativeError::FetchEmoji).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_custom_emoji_by_name(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    name: &EmojiName,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join(&format!("emoji/name/{name}")).unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let emoji = schema::json::<MetaEmoji>(response).await?;
+                tracing::trace!("Received custom emoji: {:?}", emoji);
+                Ok(Response::Emoji(Some(emoji)))
+            } else if response.status() == reqwest::StatusCode::NOT_FOUND {
+                Ok(Response::Emoji(None))
+            } else {
+                tracing::error!("Failed to get custom emoji {name}!");
+                Err(failed(response, NativeError::FetchEmoji).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_custom_emoji_image(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    emoji_id: &EmojiId,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join(&format!("emoji/{emoji_id}/image")).unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                match response.bytes().await {
+                    Ok(image) => Ok(Response::EmojiImage(image.to_vec())),
+                    Err(error) => Err(Error::RequestFailed(ClientFailed {
+                        reason: error.to_string(),
+                    })),
+                }
+            } else {
+                tracing::error!("Failed to get image of custom emoji {emoji_id}!");
+                Err(failed(response, NativeError::FetchEmoji).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn download_file(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    file_id: &FileId,
+    path: &Path,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join(&format!("files/{file_id}")).unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let saved = save_body(response, path).await;
+                if saved.is_err() {
+                    // Partial file would pass for the whole attachment
+                    tokio::fs::remove_file(path).await.ok();
+                }
+                Ok(Response::FileSaved(saved?))
+            } else {
+                Err(failed(response, NativeError::DownloadFile).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+/// Write response body to `path` chunk by chunk, returns its size
+async fn save_body(response: reqwest::Response, path: &Path) -> Result<u64, Error> {
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut body = response.bytes_stream();
+    let mut size = 0;
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|error| ClientFailed {
+            reason: error.to_string(),
+        })?;
+        file.write_all(&chunk).await?;
+        size += chunk.len() as u64;
+    }
+    file.flush().await?;
+    Ok(size)
+}
+
+async fn fetch_preferences(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join(&format!("users/{user_id}/preferences")).unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let preferences = schema::json::<Vec<Preference>>(response).await?;
+                tracing::trace!("Received preferences: {:?}", preferences);
+                Ok(Response::Preferences(preferences))
+            } else {
+                tracing::error!("Failed to get preferences of {user_id}!");
+                Err(failed(response, NativeError::FetchPreferences).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn save_preferences(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+    preferences: &[Preference],
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::PUT,
+        uri.join(&format!("users/{user_id}/preferences")).unwrap(),
+        Some(preferences),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                Ok(Response::Preferences(preferences.to_vec()))
+            } else {
+                Err(failed(response, NativeError::SavePreferences).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn delete_preferences(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+    preferences: &[Preference],
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::POST,
+        uri.join(&format!("users/{user_id}/preferences/delete"))
+            .unwrap(),
+        Some(preferences),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                Ok(Response::Preferences(preferences.to_vec()))
+            } else {
+                Err(failed(response, NativeError::SavePreferences).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_user_channel_members(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+    page: u32,
+    per_page: u32,
+) -> Result<Response, Error> {
+    let mut url = uri
+        .join(&format!("users/{user_id}/channel_members"))
+        .unwrap();
+    url.query_pairs_mut()
+        .append_pair("page", &page.to_string())
+        .append_pair("pageSize", &per_page.to_string());
+    let result = handle(client, Method::GET, url, None as Option<()>, token)
+        .await
+        .map_err(|error| {
+            Err(Error::RequestFailed(ClientFailed {
+                reason: error.to_string(),
+            }))
+        });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let members = schema::json::<Vec<ChannelMember>>(response).await?;
+                tracing::trace!("Received channel memberships: {:?}", members);
+                Ok(Response::ChannelMembers(members))
+            } else {
+                tracing::error!("Failed to get channel memberships of {user_id}!");
+                Err(failed(response, NativeError::FetchChannelMembers).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_sidebar_categories(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+    team_id: &TeamId,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join(&format!(
+            "users/{user_id}/teams/{team_id}/channels/categories"
+        ))
+        .unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let categories = schema::json::<SidebarCategories>(response).await?;
+                tracing::trace!("Received sidebar categories: {:?}", categories);
+                Ok(Response::SidebarCategories(categories))
+            } else {
+                tracing::error!("Failed to get sidebar categories of team {team_id}!");
+                Err(failed(response, NativeError::FetchCategories).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn update_sidebar_categories(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+    team_id: &TeamId,
+    categories: &[SidebarCategory],
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::PUT,
+        uri.join(&format!(
+            "users/{user_id}/teams/{team_id}/channels/categories"
+        ))
+        .unwrap(),
+        Some(categories),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let categories = schema::json::<Vec<SidebarCategory>>(response).await?;
+                tracing::trace!("Updated sidebar categories: {:?}", categories);
+                Ok(Response::UpdatedSidebarCategories(categories))
+            } else {
+                Err(failed(response, NativeError::SaveCategories).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn update_sidebar_category_order(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+    team_id: &TeamId,
+    order: &[CategoryId],
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::PUT,
+        uri.join(&format!(
+            "users/{user_id}/teams/{team_id}/channels/categories/order"
+        ))
+        .unwrap(),
+        Some(order),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let order = schema::json::<Vec<CategoryId>>(response).await?;
+                tracing::trace!("Updated sidebar category order: {:?}", order);
+                Ok(Response::SidebarCategoryOrder(order))
+            } else {
+                Err(failed(response, NativeError::SaveCategories).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn mark_thread_unread(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+    team_id: &TeamId,
+    thread_id: &PostId,
+    post_id: &PostId,
+) -> Result<Response, Error> {
+    tracing::info!("Mark thread {thread_id} unread from {post_id}: {}", uri);
+    let result = handle(
+        client,
+        Method::POST,
+        uri.join(&format!(
+            "users/{user_id}/teams/{team_id}/threads/{thread_id}/set_unread/{post_id}"
+        ))
+        .unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let thread = schema::json::<UserThread>(response).await?;
+                tracing::trace!("Thread marked unread: {:?}", thread);
+                Ok(Response::Thread(thread))
+            } else {
+                tracing::error!("Failed to mark thread {thread_id} unread!");
+                Err(failed(response, NativeError::MarkThreadUnread).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn fetch_post(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    post_id: &PostId,
+) -> Result<Response, Error> {
+    let result = handle(
+        client,
+        Method::GET,
+        uri.join(&format!("posts/{post_id}")).unwrap(),
+        None as Option<()>,
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let post = schema::json::<Post>(response).await?;
+                tracing::trace!("Received post: {:?}", post);
+                Ok(Response::Post(post))
+            } else {
+                tracing::error!("Failed to get post {post_id}!");
+                Err(failed(response, NativeError::FetchPosts).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+async fn create_post(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    post: &CreatePostRequest,
+) -> Result<Response, Error> {
+    tracing::info!("Create post in channel {}: {}", post.channel_id, uri);
+    let result = handle(
+        client,
+        Method::POST,
+        uri.join("posts").unwrap(),
+        Some(post),
+        token,
+    )
+    .await
+    .map_err(|error| {
+        Err(Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        }))
+    });
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let post = schema::json::<Post>(response).await?;
+                tracing::trace!("Created post: {:?}", post);
+                Ok(Response::Post(post))
+            } else {
+                Err(failed(response, NativeError::CreatePost).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+/// Upload file read from `body` while it's sent, so large files aren't held
+/// in memory. Server needs `size` up front as body is streamed, request is
+/// signed from file at `path` the body is read from.
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_stream(
+    client: &Client,
+    server_url: &Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+    name: &str,
+    size: u64,
+    body: reqwest::Body,
+    path: &Path,
+) -> Result<MetaFile, Error> {
+    let uri = server_url.join("api/v4/").unwrap();
+    upload_file(client, uri, token, channel_id, name, size, body, Some(path)).await
+}
+
+/// Server takes raw body as content of single file when channel and file
+/// name are passed in query, no multipart form is needed. Streamed `body`
+/// holds no bytes to sign, it's signed from file at `streamed_from`.
+#[allow(clippy::too_many_arguments)]
+async fn upload_file(
+    client: &Client,
+    uri: Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+    name: &str,
+    size: u64,
+    body: reqwest::Body,
+    streamed_from: Option<&Path>,
+) -> Result<MetaFile, Error> {
+    tracing::info!("Upload {name} ({size} bytes) to channel {channel_id}: {uri}");
+    let mut url = uri.join("files").unwrap();
+    url.query_pairs_mut()
+        .append_pair("channel_id", channel_id.as_str())
+        .append_pair("filename", name);
+    let builder = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+        .header(reqwest::header::CONTENT_LENGTH, size)
+        .body(body);
+    let request_failed = |reason: String| Err(Error::RequestFailed(ClientFailed { reason }));
+    let result = async {
+        let mut request = authorized(builder, token)
+            .build()
+            .map_err(|error| request_failed(error.to_string()))?;
+        match streamed_from {
+            Some(path) => signing::sign_file(&mut request, path, size)
+                .await
+                .map_err(|error| request_failed(error.to_string()))?,
+            None => signing::sign(&mut request),
+        }
+        execute(client, request)
+            .await
+            .map_err(|error| request_failed(error.to_string()))
+    }
+    .await;
+    match result {
+        Ok(response) => {
+            if response.status().is_success() {
+                let uploaded = schema::json::<FileUploadResponse>(response).await?;
+                tracing::trace!("Uploaded files: {:?}", uploaded);
+                let Some(file) = uploaded.file_infos.into_iter().next() else {
+                    return Err(NativeError::UploadFile)?;
+                };
+                Ok(file)
+            } else {
+                Err(failed(response, NativeError::UploadFile).await)?
+            }
+        }
+        Err(error) => error,
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use reqwest::Client;
+
+    use super::*;
+    use crate::api::mock::MockMattermost;
+
+    #[tokio::test]
+    async fn logs_in_and_loads_sidebar() {
+        let mock = MockMattermost::start().await;
+        let client = Client::new();
+        let event = ApiEvent::Login("alice".to_owned(), "hunter2".to_owned());
+        let Response::Login {
+            token, user_name, ..
+        } = handle_request(&client, &mock.url(), &event, None)
+            .await
+            .unwrap()
+        else {
+            panic!("expected login");
+        };
+        assert_eq!(user_name, "alice");
+        assert_eq!(token, MockMattermost::token());
+
+        let Response::MyTeams(teams) =
+            handle_request(&client, &mock.url(), &ApiEvent::MyTeams, Some(&token))
+                .await
+                .unwrap()
+        else {
+            panic!("expected teams");
+        };
+        assert_eq!(teams.len(), 1);
+        let Response::MyChannels(channels) =
+            handle_request(&client, &mock.url(), &ApiEvent::MyChannels, Some(&token))
+                .await
+                .unwrap()
+        else {
+            panic!("expected channels");
+        };
+        assert_eq!(channels.len(), 2);
+
+        let event = ApiEvent::ChannelPosts {
+            channel_id: channels[0].id.clone().unwrap(),
+            page: 0,
+            per_page: 60,
+        };
+        let Response::ChannelPosts(posts) =
+            handle_request(&client, &mock.url(), &event, Some(&token))
+                .await
+                .unwrap()
+        else {
+            panic!("expected posts");
+        };
+        assert_eq!(posts.order.len(), 2);
+        assert!(posts.posts.values().all(|post| post.message_ast.is_some()));
+    }
+
+    #[tokio::test]
+    async fn reports_failures() {
+        let mock = MockMattermost::start().await;
+        let client = Client::new();
+        let error = handle_request(&client, &mock.url(), &ApiEvent::MyTeams, None)
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), "unauthorized");
+
+        mock.respond("/api/v4/users/me/channels", 200, r#"{"id":"c1"}"#)
+            .await;
+        let token = MockMattermost::token();
+        let error = handle_request(&client, &mock.url(), &ApiEvent::MyChannels, Some(&token))
+            .await
+            .unwrap_err();
+        let Error::Deserialization(error) = error else {
+            panic!("expected deserialization error, got {error:?}");
+        };
+        assert_eq!(error.endpoint, "/api/v4/users/me/channels");
+
+        mock.respond(
+            "/api/v4/users/me/teams",
+            403,
+            r#"{"id":"api.context.permissions.app_error","message":"You do not have the appropriate permissions.","status_code":403}"#,
+        )
+        .await;
+        let error = handle_request(&client, &mock.url(), &ApiEvent::MyTeams, Some(&token))
+            .await
+            .unwrap_err();
+        let Error::PermissionDenied(error) = error else {
+            panic!("expected permission denied, got {error:?}");
+        };
+        assert_eq!(error.id, "api.context.permissions.app_error");
+
+        mock.respond("/api/v4/channels/c1/stats", 500, "").await;
+        let event = ApiEvent::ChannelStats(ChannelId::new("c1".to_owned()));
+        let error = handle_request(&client, &mock.url(), &event, Some(&token))
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), "fetch_channel_stats");
+    }
+
+    #[tokio::test]
+    async fn channel_changes_report_server_errors() {
+        let client = Client::new();
+        let token = MockMattermost::token();
+        let (channel_id, user_id) = (
+            ChannelId::new("c1".to_owned()),
+            UserId::new("u1".to_owned()),
+        );
+        let events = [
+            (
+                "PUT",
+                "/api/v4/channels/c1/patch",
+                ApiEvent::PatchChannel(channel_id.clone(), ChannelPatch::default()),
+            ),
+            (
+                "POST",
+                "/api/v4/channels",
+                ApiEvent::CreateChannel(CreateChannelRequest {
+                    team_id: TeamId::new("t1".to_owned()),
+                    name: ChannelName::new("town".to_owned()),
+                    display_name: ChannelDisplayName::new("Town".to_owned()),
+                    r#type: ChannelType::new("O".to_owned()),
+                    purpose: None,
+                    header: None,
+                }),
+            ),
+            (
+                "POST",
+                "/api/v4/channels/c1/members",
+                ApiEvent::AddChannelMember {
+                    channel_id: channel_id.clone(),
+                    user_id: user_id.clone(),
+                },
+            ),
+            (
+                "DELETE",
+                "/api/v4/channels/c1/members/u1",
+                ApiEvent::RemoveChannelMember {
+                    channel_id,
+                    user_id,
+                },
+            ),
+        ];
+        for (status, kind) in [
+            (403, "permission_denied"),
+            (404, "not_found"),
+            (429, "rate_limited"),
+        ] {
+            let mock = MockMattermost::start().await;
+            for (http_method, endpoint, event) in &events {
+                let body = format!(
+                    r#"{{"id":"api.channel.error_{status}","message":"Denied","request_id":"r{status}","status_code":{status}}}"#
+                );
+                mock.respond_to(http_method, endpoint, status, &body).await;
+                let error = handle_request(&client, &mock.url(), event, Some(&token))
+                    .await
+                    .unwrap_err();
+                assert_eq!(error.kind(), kind, "{http_method} {endpoint}");
+                let reported = match error {
+                    Error::PermissionDenied(error) | Error::NotFound(error) => error,
+                    Error::RateLimited { error, .. } => error,
+                    error => panic!("expected server error, got {error:?}"),
+                };
+                assert_eq!(reported.id, format!("api.channel.error_{status}"));
+                assert_eq!(reported.request_id, Some(format!("r{status}")));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn downloads_file_to_disk() {
+        let mock = MockMattermost::start().await;
+        let dir = tempdir::TempDir::new("download").unwrap();
+        let token = MockMattermost::token();
+        let content = "x".repeat(100_000);
+        mock.respond("/api/v4/files/f1", 200, &content).await;
+        let event = ApiEvent::DownloadFile {
+            file_id: FileId::new("f1".to_owned()),
+            path: dir.path().join("report.txt"),
+        };
+        let Response::FileSaved(size) =
+            handle_request(&Client::new(), &mock.url(), &event, Some(&token))
+                .await
+                .unwrap()
+        else {
+            panic!("expected saved file");
+        };
+        assert_eq!(size, 100_000);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("report.txt")).unwrap(),
+            content
+        );
+    }
+
+    #[tokio::test]
+    async fn fetches_call_state_from_plugin() {
+        let mock = MockMattermost::start().await;
+        mock.respond(
+            "/plugins/com.mattermost.calls/c1",
+            200,
+            r#"{"channel_id":"c1","enabled":true,"call":{"id":"k1","start_at":1700000000000,"owner_id":"u1","thread_id":"p1","sessions":[{"session_id":"s1","user_id":"u1"}]}}"#,
+        )
+        .await;
+        let token = MockMattermost::token();
+        let event = ApiEvent::CallState(ChannelId::new("c1".to_owned()));
+        let Response::CallState(state) =
+            handle_request(&Client::new(), &mock.url(), &event, Some(&token))
+                .await
+                .unwrap()
+        else {
+            panic!("expected call state");
+        };
+        assert_eq!(state.enabled, Some(true));
+        assert_eq!(state.call.unwrap().sessions.len(), 1);
+    }
+}
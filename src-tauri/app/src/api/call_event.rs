@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Debug;
 
@@ -6,11 +7,187 @@ use models::*;
 #[derive(Debug)]
 pub enum ApiEvent {
     Login(String, String),
+    /// User owning access token request is sent with
+    Me,
+    /// Revoke session of access token request is sent with
+    Logout,
+    Ping,
+    /// Client configuration in old (flat) format, available without login
+    ClientConfig,
+    /// Limits of cloud workspace, empty for self-hosted servers
+    CloudLimits,
+    /// Storage taken by files uploaded to workspace
+    StorageUsage,
     MyTeams,
+    /// Page of teams user can see, including ones they may join, pages are
+    /// counted from 0
+    Teams {
+        page: u32,
+        per_page: u32,
+    },
     MyTeamMembers,
     MyChannels,
+    /// Channels of user in one team, direct and group messages included
+    TeamChannels(TeamId),
+    Post(PostId),
     PostThreads(PostId),
-    ChannelPosts(ChannelId),
+    /// Replies of thread created after given post
+    ThreadReplies {
+        post_id: PostId,
+        after: PostId,
+        after_create_at: Timestamp,
+    },
+    /// Page of channel posts, newest first, pages are counted from 0
+    ChannelPosts {
+        channel_id: ChannelId,
+        page: u32,
+        per_page: u32,
+    },
+    /// Page of channel posts older than `before`, newest first
+    ChannelPostsBefore {
+        channel_id: ChannelId,
+        before: PostId,
+        per_page: u32,
+    },
+    /// Posts of channel created, edited or deleted after `since`, in
+    /// milliseconds
+    ChannelPostsSince {
+        channel_id: ChannelId,
+        since: Timestamp,
+    },
+    /// Page of channel members, pages are counted from 0
+    ChannelMembers {
+        channel_id: ChannelId,
+        page: u32,
+        per_page: u32,
+    },
+    /// Page of team posts matching search `terms`, any of them when
+    /// `is_or_search`, pages are counted from 0
+    SearchPosts {
+        team_id: TeamId,
+        terms: String,
+        is_or_search: bool,
+        page: u32,
+        per_page: u32,
+    },
+    Channel(ChannelId),
+    PatchChannel(ChannelId, ChannelPatch),
+    CreateChannel(CreateChannelRequest),
+    AddChannelMember {
+        channel_id: ChannelId,
+        user_id: UserId,
+    },
+    RemoveChannelMember {
+        channel_id: ChannelId,
+        user_id: UserId,
+    },
+    /// Membership of user in channel, `last_viewed_at` included
+    ChannelMember {
+        channel_id: ChannelId,
+        user_id: UserId,
+    },
+    /// Mark channel as viewed by user now, it's read on all their devices
+    ViewChannel {
+        user_id: UserId,
+        channel_id: ChannelId,
+    },
+    /// Change notification props of user's membership in channel
+    UpdateNotifyProps {
+        channel_id: ChannelId,
+        user_id: UserId,
+        patch: NotifyPropsPatch,
+    },
+    ChannelStats(ChannelId),
+    PinnedPosts(ChannelId),
+    PinPost(PostId),
+    UnpinPost(PostId),
+    /// Page of posts saved by user, pages are counted from 0
+    FlaggedPosts {
+        user_id: UserId,
+        page: u32,
+        per_page: u32,
+    },
+    User(UserId),
+    UsersByIds(Vec<UserId>),
+    /// Page of active users sorted by username, only members of
+    /// `in_channel` when it's set, pages are counted from 0
+    Users {
+        in_channel: Option<ChannelId>,
+        page: u32,
+        per_page: u32,
+    },
+    UserStatus(UserId),
+    /// Status set by user, it's kept until user changes it again
+    SetUserStatus {
+        user_id: UserId,
+        status: Presence,
+        /// Unix time in seconds, only used with [`Presence::Dnd`]
+        dnd_end_time: Option<Timestamp>,
+    },
+    UserStatuses(Vec<UserId>),
+    AutocompleteUsers {
+        channel_id: ChannelId,
+        name: String,
+        limit: usize,
+    },
+    /// Page of custom emoji, pages are counted from 0
+    CustomEmoji {
+        page: u32,
+        per_page: u32,
+    },
+    CustomEmojiByName(EmojiName),
+    CustomEmojiImage(EmojiId),
+    /// Content of file attached to post, written to `path` as it arrives
+    DownloadFile {
+        file_id: FileId,
+        path: std::path::PathBuf,
+    },
+    Preferences(UserId),
+    SavePreferences {
+        user_id: UserId,
+        preferences: Vec<Preference>,
+    },
+    DeletePreferences {
+        user_id: UserId,
+        preferences: Vec<Preference>,
+    },
+    /// Memberships of user in all channels, pages are counted from 0
+    UserChannelMembers {
+        user_id: UserId,
+        page: u32,
+        per_page: u32,
+    },
+    SidebarCategories {
+        user_id: UserId,
+        team_id: TeamId,
+    },
+    UpdateSidebarCategories {
+        user_id: UserId,
+        team_id: TeamId,
+        categories: Vec<SidebarCategory>,
+    },
+    SidebarCategoryOrder {
+        user_id: UserId,
+        team_id: TeamId,
+        order: Vec<CategoryId>,
+    },
+    MarkThreadUnread {
+        user_id: UserId,
+        team_id: TeamId,
+        thread_id: PostId,
+        post_id: PostId,
+    },
+    CreatePost(CreatePostRequest),
+    /// Raw content of file stored in channel before post refers to it
+    UploadFile {
+        channel_id: ChannelId,
+        name: String,
+        data: Vec<u8>,
+    },
+    /// Call running in channel, served by Calls plugin
+    CallState(ChannelId),
+    /// Metadata of page behind link, fetched by server
+    OpenGraph(String),
 }
 
 #[derive(Debug)]
@@ -20,17 +197,56 @@ pub enum Response {
         user_id: String,
         user_name: String,
     },
+    LoggedOut,
+    Pong,
+    ClientConfig(HashMap<String, String>),
+    CloudLimits(CloudLimits),
+    StorageUsage(StorageUsage),
     /// teams
     MyTeams(Vec<Team>),
+    Teams(Vec<Team>),
     /// team members
     MyTeamMembers(Vec<TeamMember>),
     MyChannels(Vec<Channel>),
+    Channel(Channel),
     ChannelThreads(PostThread),
     ChannelPosts(PostThread),
+    /// Found posts, best match first
+    SearchResults(PostThread),
+    /// Pinned state of post after change
+    Pinned(bool),
+    ChannelMembers(Vec<ChannelMember>),
+    ChannelMember(ChannelMember),
+    ChannelMemberRemoved,
+    /// New `last_viewed_at` of viewed channel, `None` when server didn't
+    /// tell
+    ChannelViewed(Option<Timestamp>),
+    NotifyPropsUpdated,
+    ChannelStats(ChannelStats),
+    User(UserResponse),
+    Users(Vec<UserResponse>),
+    Status(UserStatus),
+    Statuses(Vec<UserStatus>),
+    UserAutocomplete(UserAutocomplete),
+    CustomEmoji(Vec<MetaEmoji>),
+    /// `None` when server has no custom emoji of requested name
+    Emoji(Option<MetaEmoji>),
+    EmojiImage(Vec<u8>),
+    Preferences(Vec<Preference>),
+    SidebarCategories(SidebarCategories),
+    UpdatedSidebarCategories(Vec<SidebarCategory>),
+    SidebarCategoryOrder(Vec<CategoryId>),
+    Thread(UserThread),
+    Post(Post),
+    FileUploaded(MetaFile),
+    /// Size of downloaded file
+    FileSaved(u64),
+    CallState(CallChannelState),
+    OpenGraph(OpenGraph),
 }
 
 impl fmt::Display for Response {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self)
+        write!(f, "{self:?}")
     }
 }
@@ -0,0 +1,191 @@
+use std::sync::Mutex;
+
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::{Method, Request, Response, StatusCode};
+use sha2::{Digest, Sha256};
+
+use crate::bandwidth;
+
+/// Responses kept for revalidation, least recently used are dropped first
+const MAX_ENTRIES: usize = 128;
+/// Larger responses (e.g. emoji images) are not worth keeping in memory
+const MAX_BODY: usize = 1024 * 1024;
+
+struct Entry {
+    /// Endpoint and session response was received for
+    key: String,
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    content_type: Option<HeaderValue>,
+    body: Vec<u8>,
+}
+
+/// Most recently used last
+static CACHE: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+/// Responses to one user must never be served to another, so session is part
+/// of the key. Only its hash is kept.
+fn key(request: &Request) -> Option<String> {
+    if request.method() != Method::GET {
+        return None;
+    }
+    let session = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .map(|value| hex::encode(Sha256::digest(value.as_bytes())))
+        .unwrap_or_default();
+    Some(format!("{session} {}", request.url()))
+}
+
+/// Add validators of cached response to GET request, returns cache key of
+/// request when it can be cached
+pub fn prepare(request: &mut Request) -> Option<String> {
+    let key = key(request)?;
+    let cache = CACHE.lock().unwrap();
+    if let Some(entry) = cache.iter().find(|entry| entry.key == key) {
+        let headers = request.headers_mut();
+        if let Some(etag) = &entry.etag {
+            headers.insert(header::IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            headers.insert(header::IF_MODIFIED_SINCE, last_modified.clone());
+        }
+    }
+    Some(key)
+}
+
+/// Replace `304 Not Modified` with cached response and remember new
+/// responses which carry validators. Response is passed through otherwise.
+pub async fn process(key: Option<String>, response: Response) -> reqwest::Result<Response> {
+    let Some(key) = key else {
+        return Ok(response);
+    };
+    if response.status() == StatusCode::NOT_MODIFIED {
+        let mut cache = CACHE.lock().unwrap();
+        if let Some(index) = cache.iter().position(|entry| entry.key == key) {
+            let entry = cache.remove(index);
+            let cached = rebuild(
+                StatusCode::OK,
+                cached_headers(&entry),
+                response.extensions().clone(),
+                entry.body.clone(),
+            );
+            cache.push(entry);
+            tracing::trace!("Using cached response of {}", bandwidth::url(&response));
+            return Ok(cached);
+        }
+        return Ok(response);
+    }
+    let headers = response.headers();
+    let etag = headers.get(header::ETAG).cloned();
+    let last_modified = headers.get(header::LAST_MODIFIED).cloned();
+    let fits = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok()?.parse::<usize>().ok())
+        .map_or(true, |length| length <= MAX_BODY);
+    if response.status() != StatusCode::OK || (etag.is_none() && last_modified.is_none()) || !fits {
+        return Ok(response);
+    }
+    let status = response.status();
+    let headers = response.headers().clone();
+    let extensions = response.extensions().clone();
+    let body = response.bytes().await?.to_vec();
+    if body.len() <= MAX_BODY {
+        store(Entry {
+            key,
+            etag,
+            last_modified,
+            content_type: headers.get(header::CONTENT_TYPE).cloned(),
+            body: body.clone(),
+        });
+    }
+    Ok(rebuild(status, headers, extensions, body))
+}
+
+/// Drop all cached responses, e.g. after logout
+pub fn clear() {
+    CACHE.lock().unwrap().clear();
+}
+
+fn store(entry: Entry) {
+    let mut cache = CACHE.lock().unwrap();
+    cache.retain(|cached| cached.key != entry.key);
+    if cache.len() >= MAX_ENTRIES {
+        cache.remove(0);
+    }
+    cache.push(entry);
+}
+
+fn cached_headers(entry: &Entry) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in [
+        (header::ETAG, &entry.etag),
+        (header::LAST_MODIFIED, &entry.last_modified),
+        (header::CONTENT_TYPE, &entry.content_type),
+    ] {
+        if let Some(value) = value {
+            headers.insert(name, value.clone());
+        }
+    }
+    headers
+}
+
+fn rebuild(
+    status: StatusCode,
+    headers: HeaderMap,
+    extensions: http::Extensions,
+    body: Vec<u8>,
+) -> Response {
+    let mut response = http::Response::new(body);
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    *response.extensions_mut() = extensions;
+    response.headers_mut().remove(header::CONTENT_ENCODING);
+    response.headers_mut().remove(header::TRANSFER_ENCODING);
+    response.into()
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn request(url: &str, token: &str) -> Request {
+        reqwest::Client::new()
+            .get(url)
+            .bearer_auth(token)
+            .build()
+            .unwrap()
+    }
+
+    fn response(status: u16, etag: Option<&str>, body: &str) -> Response {
+        let mut response = http::Response::builder().status(status);
+        if let Some(etag) = etag {
+            response = response.header(header::ETAG, etag);
+        }
+        response.body(body.to_owned()).unwrap().into()
+    }
+
+    #[tokio::test]
+    async fn revalidates_with_etag() {
+        let url = "https://mm.example.com/api/v4/users/me/teams?etag_test";
+        let mut first = request(url, "token");
+        let key = prepare(&mut first);
+        assert!(first.headers().get(header::IF_NONE_MATCH).is_none());
+        let fresh = process(key, response(200, Some("\"v1\""), "[1]"))
+            .await
+            .unwrap();
+        assert_eq!(fresh.text().await.unwrap(), "[1]");
+
+        let mut second = request(url, "token");
+        let key = prepare(&mut second);
+        assert_eq!(second.headers()[header::IF_NONE_MATCH], "\"v1\"");
+        let cached = process(key, response(304, None, "")).await.unwrap();
+        assert_eq!(cached.status(), StatusCode::OK);
+        assert_eq!(cached.text().await.unwrap(), "[1]");
+
+        // Another session never sees cached response
+        let mut other = request(url, "other token");
+        prepare(&mut other);
+        assert!(other.headers().get(header::IF_NONE_MATCH).is_none());
+    }
+}
@@ -0,0 +1,84 @@
+use models::AccessToken;
+use url::Url;
+use wiremock::matchers::{header, method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub const LOGIN: &str = include_str!("fixtures/login.json");
+pub const TEAMS: &str = include_str!("fixtures/teams.json");
+pub const CHANNELS: &str = include_str!("fixtures/channels.json");
+pub const POSTS: &str = include_str!("fixtures/posts.json");
+
+/// Token handed out by login fixture, other endpoints answer 401 without it
+pub const TOKEN: &str = "mock7token3xbh9qzd5ke8ry4wa";
+
+/// Fake Mattermost server answering endpoints used at start with fixtures
+/// recorded from a real one
+pub struct MockMattermost {
+    pub server: MockServer,
+}
+
+impl MockMattermost {
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v4/users/login"))
+            .respond_with(json(200, LOGIN).insert_header("Token", TOKEN))
+            .mount(&server)
+            .await;
+        let authorized = [
+            ("/api/v4/users/me/teams", TEAMS),
+            ("/api/v4/users/me/channels", CHANNELS),
+        ];
+        for (endpoint, body) in authorized {
+            Mock::given(method("GET"))
+                .and(path(endpoint))
+                .and(header("Authorization", format!("Bearer {TOKEN}").as_str()))
+                .respond_with(json(200, body))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/api/v4/channels/[a-z0-9]+/posts$"))
+            .and(header("Authorization", format!("Bearer {TOKEN}").as_str()))
+            .respond_with(json(200, POSTS))
+            .mount(&server)
+            .await;
+        // Mounted last, so it only answers requests nothing above matched
+        Mock::given(method("GET"))
+            .respond_with(json(
+                401,
+                r#"{"id":"api.context.session_expired.app_error","message":"Invalid or expired session, please login again.","request_id":"mock","status_code":401}"#,
+            ))
+            .mount(&server)
+            .await;
+        Self { server }
+    }
+
+    /// Answer `GET endpoint` with `body` instead of fixture
+    pub async fn respond(&self, endpoint: &str, status: u16, body: &str) {
+        self.respond_to("GET", endpoint, status, body).await;
+    }
+
+    /// Answer requests of `http_method` to `endpoint` with `body`
+    pub async fn respond_to(&self, http_method: &str, endpoint: &str, status: u16, body: &str) {
+        Mock::given(method(http_method))
+            .and(path(endpoint))
+            .respond_with(json(status, body))
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Server URL as user would type it into login form
+    pub fn url(&self) -> Url {
+        Url::parse(&self.server.uri()).unwrap()
+    }
+
+    pub fn token() -> AccessToken {
+        AccessToken::new(TOKEN.to_owned()).unwrap()
+    }
+}
+
+fn json(status: u16, body: &str) -> ResponseTemplate {
+    ResponseTemplate::new(status).set_body_raw(body, "application/json")
+}
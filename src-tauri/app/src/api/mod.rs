@@ -1,4 +1,13 @@
-pub use api::handle_request;
+pub use api::{handle_request, upload_stream};
 
+#[allow(clippy::module_inception)]
 pub mod api;
 pub mod call_event;
+pub mod etag;
+#[cfg(test)]
+pub mod mock;
+pub mod network;
+pub mod paging;
+pub mod rate_limit;
+pub mod schema;
+pub mod signing;
@@ -0,0 +1,324 @@
+use std::io;
+use std::sync::RwLock;
+
+use base64::Engine;
+use models::*;
+use reqwest::{Certificate, Client, Proxy};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_socks::tcp::Socks5Stream;
+use url::Url;
+
+use crate::errors::{Error, NativeError};
+
+/// Clients built from network settings, `None` while defaults are used and
+/// shared client managed by app serves all requests
+static CLIENTS: RwLock<Option<Clients>> = RwLock::new(None);
+
+struct Clients {
+    settings: NetworkSettings,
+    verified: Client,
+    /// Used only for hosts user chose not to verify
+    insecure: Option<Client>,
+}
+
+/// Validate settings and use clients built from them for all further
+/// requests, requests in flight finish with old ones
+pub fn configure(settings: &NetworkSettings) -> Result<(), Error> {
+    if settings == &NetworkSettings::default() {
+        *CLIENTS.write().unwrap() = None;
+        return Ok(());
+    }
+    let verified = builder(settings)?.build().map_err(build_failed)?;
+    let insecure = if settings.insecure_hosts.is_empty() {
+        None
+    } else {
+        let client = builder(settings)?
+            .danger_accept_invalid_certs(true)
+            .build()
+            .map_err(build_failed)?;
+        Some(client)
+    };
+    *CLIENTS.write().unwrap() = Some(Clients {
+        settings: settings.clone(),
+        verified,
+        insecure,
+    });
+    Ok(())
+}
+
+/// Client request to `url` has to be sent with, `None` when shared one is
+/// fine
+pub fn client_for(url: &Url) -> Option<Client> {
+    let clients = CLIENTS.read().unwrap();
+    let clients = clients.as_ref()?;
+    match &clients.insecure {
+        Some(insecure) if is_insecure(&clients.settings, url) => Some(insecure.clone()),
+        _ => Some(clients.verified.clone()),
+    }
+}
+
+/// Longest response to `CONNECT` accepted from HTTP proxy
+const MAX_CONNECT_RESPONSE: usize = 8 * 1024;
+
+/// Stream WebSocket runs over, either TCP connection to server or tunnel
+/// through proxy
+pub trait Tunnel: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Tunnel for T {}
+
+/// Connection to host of WebSocket `url`, through proxy when one is
+/// configured. TLS with server is up to caller.
+pub async fn connect(url: &Url) -> io::Result<Box<dyn Tunnel>> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "URL has no host"))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "URL has no port"))?;
+    let proxy = CLIENTS
+        .read()
+        .unwrap()
+        .as_ref()
+        .and_then(|clients| clients.settings.proxy.clone());
+    match proxy.as_deref().map(Url::parse) {
+        None => Ok(Box::new(TcpStream::connect((host, port)).await?)),
+        Some(Ok(proxy)) => tunnel(&proxy, host, port).await,
+        Some(Err(_)) => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid proxy")),
+    }
+}
+
+async fn tunnel(proxy: &Url, host: &str, port: u16) -> io::Result<Box<dyn Tunnel>> {
+    let proxy_host = proxy
+        .host_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "proxy has no host"))?;
+    let proxy_port = proxy
+        .port_or_known_default()
+        .unwrap_or(match proxy.scheme() {
+            "http" => 80,
+            "https" => 443,
+            _ => 1080,
+        });
+    let stream = TcpStream::connect((proxy_host, proxy_port)).await?;
+    match proxy.scheme() {
+        "http" => Ok(Box::new(http_connect(stream, proxy, host, port).await?)),
+        "https" => {
+            let connector = native_tls::TlsConnector::new()
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            let stream = tokio_native_tls::TlsConnector::from(connector)
+                .connect(proxy_host, stream)
+                .await
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            Ok(Box::new(http_connect(stream, proxy, host, port).await?))
+        }
+        scheme @ ("socks5" | "socks5h") => {
+            // Unlike socks5h, socks5 resolves host here and proxy only
+            // sees the address
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            let target = if scheme == "socks5" {
+                tokio::net::lookup_host((host, port))
+                    .await?
+                    .next()
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?
+                    .to_string()
+            } else {
+                format!("{host}:{port}")
+            };
+            let stream = match proxy.password() {
+                Some(password) => {
+                    Socks5Stream::connect_with_password_and_socket(
+                        stream,
+                        target.as_str(),
+                        proxy.username(),
+                        password,
+                    )
+                    .await
+                }
+                None => Socks5Stream::connect_with_socket(stream, target.as_str()).await,
+            };
+            let stream = stream.map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            Ok(Box::new(stream.into_inner()))
+        }
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid proxy")),
+    }
+}
+
+/// Ask HTTP proxy to open tunnel to `host`, response is read byte by byte so
+/// nothing sent after it is lost
+async fn http_connect<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    proxy: &Url,
+    host: &str,
+    port: u16,
+) -> io::Result<S> {
+    let mut request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+    if !proxy.username().is_empty() {
+        let credentials = format!("{}:{}", proxy.username(), proxy.password().unwrap_or(""));
+        let credentials = base64::engine::general_purpose::STANDARD.encode(credentials);
+        request.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_CONNECT_RESPONSE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "proxy response too long",
+            ));
+        }
+        response.push(stream.read_u8().await?);
+    }
+    let status = String::from_utf8_lossy(&response);
+    let status = status.lines().next().unwrap_or_default();
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(stream),
+        _ => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("proxy refused tunnel: {status}"),
+        )),
+    }
+}
+
+/// TLS connector of WebSocket to `url`, `None` when default one is fine
+pub fn tls_connector(url: &Url) -> Option<native_tls::TlsConnector> {
+    let clients = CLIENTS.read().unwrap();
+    let settings = &clients.as_ref()?.settings;
+    let mut builder = native_tls::TlsConnector::builder();
+    if let Some(pem) = &settings.ca_certificate {
+        match native_tls::Certificate::from_pem(pem.as_bytes()) {
+            Ok(certificate) => {
+                builder.add_root_certificate(certificate);
+            }
+            Err(e) => tracing::warn!("Invalid CA certificate: {e}"),
+        }
+    }
+    if is_insecure(settings, url) {
+        builder.danger_accept_invalid_certs(true);
+    }
+    builder
+        .build()
+        .map_err(|e| tracing::warn!("Failed to build TLS connector: {e}"))
+        .ok()
+}
+
+fn is_insecure(settings: &NetworkSettings, url: &Url) -> bool {
+    url.host_str().is_some_and(|host| {
+        settings
+            .insecure_hosts
+            .iter()
+            .any(|insecure| insecure.eq_ignore_ascii_case(host))
+    })
+}
+
+fn builder(settings: &NetworkSettings) -> Result<reqwest::ClientBuilder, Error> {
+    let mut builder = Client::builder();
+    if let Some(proxy) = &settings.proxy {
+        let scheme = Url::parse(proxy)
+            .map(|url| url.scheme().to_owned())
+            .map_err(|_| NativeError::InvalidProxy)?;
+        if !["http", "https", "socks5", "socks5h"].contains(&scheme.as_str()) {
+            return Err(NativeError::InvalidProxy.into());
+        }
+        builder = builder.proxy(Proxy::all(proxy).map_err(|_| NativeError::InvalidProxy)?);
+    }
+    if let Some(pem) = &settings.ca_certificate {
+        let certificate =
+            Certificate::from_pem(pem.as_bytes()).map_err(|_| NativeError::InvalidCertificate)?;
+        builder = builder.add_root_certificate(certificate);
+    }
+    Ok(builder)
+}
+
+fn build_failed(error: reqwest::Error) -> Error {
+    tracing::error!("Failed to build HTTP client: {error}");
+    Error::RequestFailed(crate::errors::ClientFailed {
+        reason: error.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn validates_proxy() {
+        let settings = |proxy: &str| NetworkSettings {
+            proxy: Some(proxy.to_owned()),
+            ..NetworkSettings::default()
+        };
+        assert!(builder(&settings("http://proxy.corp:3128")).is_ok());
+        assert!(builder(&settings("socks5://127.0.0.1:1080")).is_ok());
+        assert!(matches!(
+            builder(&settings("ftp://proxy.corp")),
+            Err(Error::Native(NativeError::InvalidProxy))
+        ));
+        assert!(matches!(
+            builder(&NetworkSettings {
+                ca_certificate: Some("not a certificate".to_owned()),
+                ..NetworkSettings::default()
+            }),
+            Err(Error::Native(NativeError::InvalidCertificate))
+        ));
+    }
+
+    #[tokio::test]
+    async fn tunnels_through_http_proxy() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let proxy = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\nhello")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        let proxy_url = Url::parse(&format!("http://user:secret@{address}")).unwrap();
+        let mut stream = tunnel(&proxy_url, "mm.example.com", 443).await.unwrap();
+        let mut greeting = String::new();
+        stream.read_to_string(&mut greeting).await.unwrap();
+        assert_eq!(greeting, "hello");
+        let request = proxy.await.unwrap();
+        assert!(request.starts_with("CONNECT mm.example.com:443 HTTP/1.1\r\n"));
+        assert!(request.contains("Proxy-Authorization: Basic dXNlcjpzZWNyZXQ=\r\n"));
+    }
+
+    #[tokio::test]
+    async fn reports_refused_tunnel() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 403 Forbidden\r\n\r\n")
+                .await
+                .unwrap();
+        });
+        let proxy_url = Url::parse(&format!("http://{address}")).unwrap();
+        let Err(e) = tunnel(&proxy_url, "mm.example.com", 443).await else {
+            panic!("tunnel opened");
+        };
+        assert_eq!(e.kind(), io::ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn insecure_hosts() {
+        let settings = NetworkSettings {
+            insecure_hosts: vec!["MM.corp.local".to_owned()],
+            ..NetworkSettings::default()
+        };
+        assert!(is_insecure(
+            &settings,
+            &Url::parse("https://mm.corp.local/api/v4/").unwrap()
+        ));
+        assert!(!is_insecure(
+            &settings,
+            &Url::parse("https://mm.example.com/").unwrap()
+        ));
+    }
+}
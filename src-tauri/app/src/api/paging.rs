@@ -0,0 +1,97 @@
+use std::future::Future;
+
+use serde::Serialize;
+
+use crate::errors::Error;
+
+/// Largest `per_page` accepted by list endpoints, larger values are
+/// silently lowered by server to its default of 60
+pub const MAX_PER_PAGE: u32 = 200;
+
+/// Items of list endpoint collected from one or more pages
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Paged<T> {
+    pub items: Vec<T>,
+    /// Server has more items than the cap let through
+    pub has_more: bool,
+}
+
+/// Request pages counted from 0 with `fetch(page, per_page)` until server
+/// returns short page or `cap` items are collected
+pub async fn fetch_all_pages<T, F, Fut>(
+    per_page: u32,
+    cap: usize,
+    mut fetch: F,
+) -> Result<Paged<T>, Error>
+where
+    F: FnMut(u32, u32) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, Error>>,
+{
+    let per_page = per_page.clamp(1, MAX_PER_PAGE);
+    let mut items = Vec::new();
+    for page in 0.. {
+        let mut batch = fetch(page, per_page).await?;
+        let last = batch.len() < per_page as usize;
+        items.append(&mut batch);
+        if items.len() >= cap {
+            let has_more = !last || items.len() > cap;
+            if has_more {
+                tracing::warn!("Stopped paging after {cap} items, server has more");
+            }
+            items.truncate(cap);
+            return Ok(Paged { items, has_more });
+        }
+        if last {
+            break;
+        }
+    }
+    Ok(Paged {
+        items,
+        has_more: false,
+    })
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    async fn collect(total: u32, per_page: u32, cap: usize) -> (Paged<u32>, Vec<u32>) {
+        let mut requested = Vec::new();
+        let paged = fetch_all_pages(per_page, cap, |page, per_page| {
+            requested.push(page);
+            let start = (page * per_page).min(total);
+            let end = (start + per_page).min(total);
+            async move { Ok((start..end).collect()) }
+        })
+        .await
+        .unwrap();
+        (paged, requested)
+    }
+
+    #[tokio::test]
+    async fn follows_pages() {
+        let (paged, requested) = collect(450, 200, usize::MAX).await;
+        assert_eq!(paged.items.len(), 450);
+        assert!(!paged.has_more);
+        assert_eq!(requested, [0, 1, 2]);
+
+        // Full last page needs one more empty page to be sure
+        let (paged, requested) = collect(400, 200, usize::MAX).await;
+        assert_eq!(paged.items.len(), 400);
+        assert_eq!(requested, [0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn stops_at_cap() {
+        let (paged, requested) = collect(450, 200, 250).await;
+        assert_eq!(paged.items, (0..250).collect::<Vec<_>>());
+        assert!(paged.has_more);
+        assert_eq!(requested, [0, 1]);
+
+        let (paged, requested) = collect(400, 200, 400).await;
+        assert_eq!(paged.items.len(), 400);
+        // Cap reached on full page, server may still have more
+        assert!(paged.has_more);
+        assert_eq!(requested, [0, 1]);
+    }
+}
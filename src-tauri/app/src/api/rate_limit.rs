@@ -0,0 +1,205 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use models::*;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use url::Url;
+
+use crate::commands::now_millis;
+
+/// Background work pauses once less than this share of budget is left,
+/// rest is kept for what user does
+const RESERVE_PERCENT: u64 = 10;
+/// Reserve when server doesn't report limit
+const RESERVE_REQUESTS: u64 = 5;
+/// Longest reset taken from server, anything beyond is a broken header
+const MAX_RESET_SECS: u64 = 60 * 60;
+
+#[derive(Debug, Clone)]
+struct Budget {
+    /// Origin of server
+    server: String,
+    limit: Option<u64>,
+    remaining: u64,
+    reset: Instant,
+    reset_at: Timestamp,
+}
+
+impl Budget {
+    fn is_low(&self) -> bool {
+        match self.limit {
+            Some(limit) => {
+                self.remaining.saturating_mul(100) < limit.saturating_mul(RESERVE_PERCENT)
+            }
+            None => self.remaining < RESERVE_REQUESTS,
+        }
+    }
+
+    /// How long background work should wait, `None` when it can go on
+    fn delay(&self, now: Instant) -> Option<Duration> {
+        let left = self.reset.checked_duration_since(now)?;
+        (self.is_low() && !left.is_zero()).then_some(left)
+    }
+}
+
+/// Last reported budget of each server
+static BUDGETS: Mutex<Vec<Budget>> = Mutex::new(Vec::new());
+
+/// Remember budget reported by response from `url`
+pub fn record(url: &Url, status: StatusCode, headers: &HeaderMap) {
+    let server = url.origin().ascii_serialization();
+    let Some(budget) = parse(server, status, headers, Instant::now(), now_millis()) else {
+        return;
+    };
+    if budget.remaining == 0 {
+        tracing::warn!(
+            "Rate limit of {} exhausted until {}",
+            budget.server,
+            budget.reset_at
+        );
+    }
+    let mut budgets = BUDGETS.lock().unwrap();
+    budgets.retain(|known| known.server != budget.server);
+    budgets.push(budget);
+}
+
+/// Budgets which haven't been replenished yet
+pub fn status() -> Vec<RateLimitStatus> {
+    let now = Instant::now();
+    let mut budgets = BUDGETS.lock().unwrap();
+    budgets.retain(|budget| budget.reset > now);
+    budgets
+        .iter()
+        .filter_map(|budget| {
+            Some(RateLimitStatus {
+                server: ServerUrl::parse(&budget.server).ok()?,
+                limit: budget.limit,
+                remaining: budget.remaining,
+                reset_at: budget.reset_at,
+                throttled: budget.delay(now).is_some(),
+            })
+        })
+        .collect()
+}
+
+/// How long background requests to `server` should wait for budget to be
+/// replenished
+pub fn throttle(server: &Url) -> Option<Duration> {
+    let server = server.origin().ascii_serialization();
+    BUDGETS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|budget| budget.server == server)?
+        .delay(Instant::now())
+}
+
+/// Seconds rejected request should be retried after
+pub fn retry_after(headers: &HeaderMap) -> Option<u64> {
+    ["retry-after", "x-ratelimit-reset"]
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok()?.trim().parse().ok())
+        .map(|secs: u64| secs.min(MAX_RESET_SECS))
+}
+
+/// Mattermost reports `X-Ratelimit-Reset` as seconds until reset, rejected
+/// requests may only carry `Retry-After`
+fn parse(
+    server: String,
+    status: StatusCode,
+    headers: &HeaderMap,
+    now: Instant,
+    now_millis: Timestamp,
+) -> Option<Budget> {
+    let number = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok();
+    let limited = status == StatusCode::TOO_MANY_REQUESTS;
+    let remaining = match number("x-ratelimit-remaining") {
+        Some(remaining) => remaining,
+        None if limited => 0,
+        None => return None,
+    };
+    let reset = number("x-ratelimit-reset")
+        .or_else(|| number("retry-after"))
+        .unwrap_or(1)
+        .min(MAX_RESET_SECS);
+    Some(Budget {
+        server,
+        limit: number("x-ratelimit-limit"),
+        remaining: if limited { 0 } else { remaining },
+        reset: now.checked_add(Duration::from_secs(reset)).unwrap_or(now),
+        reset_at: now_millis.saturating_add(reset.saturating_mul(1000)),
+    })
+}
+
+#[cfg(test)]
+mod check {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn throttles_when_budget_is_low() {
+        let now = Instant::now();
+        let server = "https://mm.example.com".to_owned();
+        let plenty = headers(&[
+            ("x-ratelimit-limit", "100"),
+            ("x-ratelimit-remaining", "42"),
+            ("x-ratelimit-reset", "7"),
+        ]);
+        let budget = parse(server.clone(), StatusCode::OK, &plenty, now, 1_000).unwrap();
+        assert_eq!(budget.reset_at, 8_000);
+        assert_eq!(budget.delay(now), None);
+
+        let low = headers(&[
+            ("x-ratelimit-limit", "100"),
+            ("x-ratelimit-remaining", "9"),
+            ("x-ratelimit-reset", "7"),
+        ]);
+        let budget = parse(server.clone(), StatusCode::OK, &low, now, 0).unwrap();
+        assert_eq!(budget.delay(now), Some(Duration::from_secs(7)));
+        assert_eq!(budget.delay(now + Duration::from_secs(8)), None);
+
+        let rejected = headers(&[("retry-after", "3")]);
+        let budget = parse(
+            server.clone(),
+            StatusCode::TOO_MANY_REQUESTS,
+            &rejected,
+            now,
+            0,
+        );
+        assert_eq!(budget.unwrap().delay(now), Some(Duration::from_secs(3)));
+
+        assert!(parse(server, StatusCode::OK, &HeaderMap::new(), now, 0).is_none());
+    }
+
+    #[test]
+    fn clamps_huge_reset() {
+        let now = Instant::now();
+        let huge = headers(&[
+            ("x-ratelimit-limit", &u64::MAX.to_string()),
+            ("x-ratelimit-remaining", &u64::MAX.to_string()),
+            ("x-ratelimit-reset", &u64::MAX.to_string()),
+        ]);
+        let budget = parse(
+            "https://mm.example.com".to_owned(),
+            StatusCode::OK,
+            &huge,
+            now,
+            u64::MAX - 1,
+        )
+        .unwrap();
+        assert_eq!(budget.reset, now + Duration::from_secs(MAX_RESET_SECS));
+        assert_eq!(budget.reset_at, u64::MAX);
+        assert_eq!(budget.delay(now), None);
+        assert_eq!(retry_after(&huge), Some(MAX_RESET_SECS));
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use reqwest::Response;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::errors::{ClientFailed, DeserializationError, Error};
+use crate::{bandwidth, logging};
+
+/// Characters of body kept in [`DeserializationError`]
+const SNIPPET_LENGTH: usize = 256;
+
+/// Development aid comparing responses with models, off by default
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+pub fn configure(enabled: bool) {
+    STRICT.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    STRICT.load(Ordering::Relaxed)
+}
+
+/// Fields in which response differs from model, paths use `[]` for any
+/// array item
+#[derive(Debug, Default, PartialEq)]
+pub struct Mismatch {
+    /// Sent by server but not known to model
+    pub unknown: BTreeSet<String>,
+    /// Expected by model but not sent, filled with defaults
+    pub missing: BTreeSet<String>,
+}
+
+/// Deserialize response body into model, body which doesn't match it is
+/// reported as [`DeserializationError`].
+///
+/// In strict mode body is also compared with model serialized back, fields
+/// which don't survive the round trip are logged together with endpoint.
+/// It's done this way rather than with `deny_unknown_fields` copies of
+/// models so responses never fail because of it.
+pub async fn json<T: DeserializeOwned + Serialize>(response: Response) -> Result<T, Error> {
+    let endpoint = bandwidth::url(&response).path().to_owned();
+    let status = response.status();
+    let body = response.bytes().await.map_err(|error| {
+        Error::RequestFailed(ClientFailed {
+            reason: error.to_string(),
+        })
+    })?;
+    if !is_enabled() {
+        return serde_json::from_slice::<T>(&body)
+            .map_err(|e| mismatched(&endpoint, &body, e).into());
+    }
+    let raw =
+        serde_json::from_slice::<Value>(&body).map_err(|e| mismatched(&endpoint, &body, e))?;
+    let parsed = T::deserialize(&raw).map_err(|e| mismatched(&endpoint, &body, e))?;
+    let endpoint = format!("{status} {endpoint}");
+    let mismatch = compare(&raw, &serde_json::to_value(&parsed)?);
+    if !mismatch.unknown.is_empty() {
+        tracing::warn!(endpoint, unknown = ?mismatch.unknown, "Unknown fields in response");
+    }
+    if !mismatch.missing.is_empty() {
+        tracing::warn!(endpoint, missing = ?mismatch.missing, "Missing fields in response");
+    }
+    Ok(parsed)
+}
+
+fn mismatched(endpoint: &str, body: &[u8], error: serde_json::Error) -> DeserializationError {
+    let body = String::from_utf8_lossy(body);
+    let snippet: String = logging::scrub(&body).chars().take(SNIPPET_LENGTH).collect();
+    tracing::error!(endpoint, snippet, "Response doesn't match model: {error}");
+    DeserializationError {
+        endpoint: endpoint.to_owned(),
+        reason: error.to_string(),
+        snippet,
+    }
+}
+
+pub fn compare(raw: &Value, model: &Value) -> Mismatch {
+    let mut mismatch = Mismatch::default();
+    compare_at("", raw, model, &mut mismatch);
+    mismatch
+}
+
+fn compare_at(path: &str, raw: &Value, model: &Value, mismatch: &mut Mismatch) {
+    match (raw, model) {
+        (Value::Object(raw), Value::Object(model)) => {
+            for (key, raw_value) in raw {
+                let field = format!("{path}.{key}");
+                match model.get(key) {
+                    Some(model_value) => compare_at(&field, raw_value, model_value, mismatch),
+                    // Skipped `None` looks the same as unknown field
+                    None if raw_value.is_null() => {}
+                    None => {
+                        mismatch.unknown.insert(field);
+                    }
+                }
+            }
+            for key in model.keys().filter(|key| !raw.contains_key(*key)) {
+                mismatch.missing.insert(format!("{path}.{key}"));
+            }
+        }
+        (Value::Array(raw), Value::Array(model)) => {
+            let item = format!("{path}[]");
+            for (raw, model) in raw.iter().zip(model) {
+                compare_at(&item, raw, model, mismatch);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Model {
+        id: String,
+        #[serde(default)]
+        count: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        items: Vec<Item>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Item {
+        name: String,
+    }
+
+    #[test]
+    fn truncated_snippet() {
+        let body = format!(r#"{{"token":"abc","id":{}}}"#, "1".repeat(1000));
+        let Err(error) = serde_json::from_str::<Model>(&body) else {
+            panic!("id is not a string");
+        };
+        let error = mismatched("/api/v4/users/me", body.as_bytes(), error);
+        assert_eq!(error.endpoint, "/api/v4/users/me");
+        assert!(error
+            .snippet
+            .starts_with(r#"{"token":"[redacted]","id":111"#));
+        assert_eq!(error.snippet.chars().count(), SNIPPET_LENGTH);
+    }
+
+    #[test]
+    fn reports_round_trip_differences() {
+        let raw = json!({
+            "id": "a",
+            "note": null,
+            "props": {},
+            "items": [{ "name": "x" }, { "name": "y", "color": "red" }],
+        });
+        let model: Model = serde_json::from_value(raw.clone()).unwrap();
+        let mismatch = compare(&raw, &serde_json::to_value(model).unwrap());
+        assert_eq!(
+            mismatch.unknown.into_iter().collect::<Vec<_>>(),
+            [".items[].color", ".props"]
+        );
+        assert_eq!(mismatch.missing.into_iter().collect::<Vec<_>>(), [".count"]);
+    }
+}
@@ -0,0 +1,201 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use hmac::{Hmac, Mac};
+use models::*;
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::Request;
+use sha2::{Sha256, Sha512};
+
+/// Signing configuration of all servers, looked up for every outgoing request
+static SIGNERS: RwLock<Vec<RequestSigning>> = RwLock::new(Vec::new());
+
+/// Replace signing configuration used by api layer
+pub fn configure(signers: Vec<RequestSigning>) {
+    *SIGNERS.write().unwrap() = signers;
+}
+
+/// Add signature header if request targets server with signing enabled.
+///
+/// Signature is hex encoded HMAC of `{METHOD}\n{path?query}\n{timestamp}\n`
+/// followed by request body, unix timestamp in seconds is sent in
+/// `{header_name}-Timestamp` header.
+pub fn sign(request: &mut Request) {
+    let Some(signing) = signer(request) else {
+        return;
+    };
+    let timestamp = now();
+    let signature = signature(&signing, request, timestamp);
+    apply(&signing, request, &signature, timestamp);
+}
+
+/// Like [`sign`] for request whose body streams first `size` bytes of file
+/// at `path`, file is read once more to compute signature before request
+/// is sent
+pub async fn sign_file(request: &mut Request, path: &Path, size: u64) -> std::io::Result<()> {
+    let Some(signing) = signer(request) else {
+        return Ok(());
+    };
+    let timestamp = now();
+    let signature = file_signature(&signing, request, path.to_owned(), size, timestamp).await?;
+    apply(&signing, request, &signature, timestamp);
+    Ok(())
+}
+
+fn signer(request: &Request) -> Option<RequestSigning> {
+    let signers = SIGNERS.read().unwrap();
+    signers
+        .iter()
+        .find(|signing| targets(signing, request))
+        .cloned()
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn apply(signing: &RequestSigning, request: &mut Request, signature: &str, timestamp: u64) {
+    let (Ok(name), Ok(timestamp_name)) = (
+        HeaderName::try_from(signing.header_name.as_str()),
+        HeaderName::try_from(format!("{}-Timestamp", signing.header_name)),
+    ) else {
+        tracing::warn!("Invalid signature header name {:?}", signing.header_name);
+        return;
+    };
+    let headers = request.headers_mut();
+    headers.insert(name, HeaderValue::from_str(signature).unwrap());
+    headers.insert(timestamp_name, HeaderValue::from(timestamp));
+}
+
+fn targets(signing: &RequestSigning, request: &Request) -> bool {
+    let url = request.url();
+    url.origin() == signing.server.origin() && url.path().starts_with(signing.server.path())
+}
+
+fn canonical(request: &Request, timestamp: u64) -> String {
+    let url = request.url();
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_owned(),
+    };
+    format!("{}\n{path}\n{timestamp}\n", request.method())
+}
+
+fn signature(signing: &RequestSigning, request: &Request, timestamp: u64) -> String {
+    let mut body = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .unwrap_or_default();
+    mac(signing, &canonical(request, timestamp), &mut body).expect("reading slice can't fail")
+}
+
+async fn file_signature(
+    signing: &RequestSigning,
+    request: &Request,
+    path: PathBuf,
+    size: u64,
+    timestamp: u64,
+) -> std::io::Result<String> {
+    let signing = signing.clone();
+    let canonical = canonical(request, timestamp);
+    tokio::task::spawn_blocking(move || {
+        let mut body = std::fs::File::open(path)?.take(size);
+        mac(&signing, &canonical, &mut body)
+    })
+    .await?
+}
+
+fn mac(signing: &RequestSigning, canonical: &str, body: &mut impl Read) -> std::io::Result<String> {
+    let secret = signing.secret.as_bytes();
+    match signing.algorithm {
+        SigningAlgorithm::HmacSha256 => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key");
+            mac.update(canonical.as_bytes());
+            feed(body, |chunk| mac.update(chunk))?;
+            Ok(hex::encode(mac.finalize().into_bytes()))
+        }
+        SigningAlgorithm::HmacSha512 => {
+            let mut mac = Hmac::<Sha512>::new_from_slice(secret).expect("HMAC accepts any key");
+            mac.update(canonical.as_bytes());
+            feed(body, |chunk| mac.update(chunk))?;
+            Ok(hex::encode(mac.finalize().into_bytes()))
+        }
+    }
+}
+
+fn feed(body: &mut impl Read, mut update: impl FnMut(&[u8])) -> std::io::Result<()> {
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        match body.read(&mut buffer)? {
+            0 => return Ok(()),
+            read => update(&buffer[..read]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use reqwest::Method;
+
+    use super::*;
+
+    #[test]
+    fn sign_request() {
+        let signing = RequestSigning {
+            server: ServerUrl::parse("https://mm.example.com").unwrap(),
+            algorithm: SigningAlgorithm::HmacSha256,
+            header_name: "X-Audit-Signature".to_owned(),
+            secret: "secret".to_owned(),
+        };
+        let mut request = Request::new(
+            Method::POST,
+            "https://mm.example.com/api/v4/posts?a=1".parse().unwrap(),
+        );
+        *request.body_mut() = Some("{}".into());
+        assert_eq!(
+            signature(&signing, &request, 1700000000),
+            "5406de8a2e8244189ef5f83f022e285753f9e8a8c88b90028955802a6b2777c2"
+        );
+
+        assert!(targets(&signing, &request));
+        let other = Request::new(Method::GET, "https://other.com/api/v4".parse().unwrap());
+        assert!(!targets(&signing, &other));
+    }
+
+    #[tokio::test]
+    async fn sign_streamed_upload() {
+        let signing = RequestSigning {
+            server: ServerUrl::parse("https://mm.example.com").unwrap(),
+            algorithm: SigningAlgorithm::HmacSha512,
+            header_name: "X-Audit-Signature".to_owned(),
+            secret: "secret".to_owned(),
+        };
+        let dir = tempdir::TempDir::new("signing").unwrap();
+        let path = dir.path().join("report.bin");
+        let content: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        // Bytes past announced size aren't sent, so they aren't signed
+        std::fs::write(&path, [content.as_slice(), b"grown"].concat()).unwrap();
+        let url: reqwest::Url = "https://mm.example.com/api/v4/files?channel_id=c1"
+            .parse()
+            .unwrap();
+
+        let mut buffered = Request::new(Method::POST, url.clone());
+        *buffered.body_mut() = Some(content.clone().into());
+        let mut streamed = Request::new(Method::POST, url);
+        let chunks = futures::stream::iter([Ok::<_, std::io::Error>(content.clone())]);
+        *streamed.body_mut() = Some(reqwest::Body::wrap_stream(chunks));
+
+        let expected = signature(&signing, &buffered, 1700000000);
+        assert_ne!(expected, signature(&signing, &streamed, 1700000000));
+        assert_eq!(
+            file_signature(&signing, &streamed, path, content.len() as u64, 1700000000)
+                .await
+                .unwrap(),
+            expected
+        );
+    }
+}
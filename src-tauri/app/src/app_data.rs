@@ -0,0 +1,142 @@
+use models::*;
+use serde::{Deserialize, Serialize};
+
+use crate::envelope::{self, OpenError};
+use crate::errors::NativeError;
+use crate::states::Server;
+
+/// Start of every export file, tells it apart from anything user picks by
+/// mistake
+const MAGIC: &[u8; 4] = b"LMDX";
+const FORMAT_VERSION: u8 = 1;
+
+/// Everything needed to continue on another machine. Cached posts are left
+/// out, they are fetched again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct AppData {
+    pub(crate) exported_at: Timestamp,
+    pub(crate) servers: Vec<Server>,
+    pub(crate) credentials: Vec<ServerCredentials>,
+    pub(crate) settings: Settings,
+    pub(crate) post_density: PostDensity,
+    /// Posts written while offline and not delivered yet
+    pub(crate) outbox: Vec<OutboxItem>,
+    pub(crate) scheduled_posts: Vec<ScheduledPost>,
+}
+
+/// What import added to data of this machine
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ImportedAppData {
+    pub servers: usize,
+    pub credentials: usize,
+    pub outbox_posts: usize,
+    pub scheduled_posts: usize,
+}
+
+/// Export file content, `data` encrypted with key derived from passphrase.
+/// Key derivation is slow on purpose, so it's to be called off async
+/// runtime.
+pub(crate) fn seal(data: &AppData, passphrase: &str) -> Result<Vec<u8>, NativeError> {
+    if passphrase.is_empty() {
+        return Err(NativeError::AppDataPassphrase);
+    }
+    let plain = serde_json::to_vec(data).map_err(|e| {
+        tracing::error!("Failed to serialize app data: {e}");
+        NativeError::AppDataEncrypt
+    })?;
+    envelope::seal(MAGIC, FORMAT_VERSION, &plain, passphrase).map_err(|e| {
+        tracing::error!("Failed to encrypt app data: {e}");
+        NativeError::AppDataEncrypt
+    })
+}
+
+/// Data of export file written by [`seal`]
+pub(crate) fn open(file: &[u8], passphrase: &str) -> Result<AppData, NativeError> {
+    let plain = envelope::open(file, MAGIC, FORMAT_VERSION, passphrase).map_err(|e| match e {
+        OpenError::Malformed => NativeError::NotAppDataExport,
+        OpenError::Decrypt => NativeError::AppDataDecrypt,
+    })?;
+    serde_json::from_slice(&plain).map_err(|e| {
+        tracing::warn!("Export file holds unknown data: {e}");
+        NativeError::NotAppDataExport
+    })
+}
+
+/// Merge imported servers into known ones, server with the same URL is
+/// replaced. Returns how many were added.
+pub(crate) fn merge_servers(known: &mut Vec<Server>, imported: Vec<Server>) -> usize {
+    let mut added = 0;
+    for server in imported {
+        match known.iter_mut().find(|known| known.url == server.url) {
+            Some(known) => *known = server,
+            None => {
+                known.push(server);
+                added += 1;
+            }
+        }
+    }
+    added
+}
+
+#[cfg(test)]
+mod check {
+    use url::Url;
+
+    use super::*;
+
+    fn server(name: &str, url: &str) -> Server {
+        Server {
+            name: name.to_owned(),
+            url: Url::parse(url).unwrap(),
+            capabilities: None,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = AppData {
+            exported_at: 1_700_000_000_000,
+            servers: vec![server("Work", "https://mm.example.com")],
+            credentials: vec![ServerCredentials {
+                url: ServerUrl::parse("https://mm.example.com").unwrap(),
+                access_token: AccessToken::try_from("hs8das8dg8asgd").unwrap(),
+            }],
+            settings: Settings::default(),
+            post_density: PostDensity::High,
+            outbox: Vec::new(),
+            scheduled_posts: Vec::new(),
+        };
+        let file = seal(&data, "correct horse").unwrap();
+        assert!(file.starts_with(MAGIC));
+
+        let opened = open(&file, "correct horse").unwrap();
+        assert_eq!(opened.servers[0].url, data.servers[0].url);
+        assert_eq!(opened.credentials, data.credentials);
+        assert_eq!(opened.post_density, PostDensity::High);
+
+        assert!(matches!(
+            open(&file, "wrong horse"),
+            Err(NativeError::AppDataDecrypt)
+        ));
+        assert!(matches!(
+            open(b"{\"servers\": []}", "correct horse"),
+            Err(NativeError::NotAppDataExport)
+        ));
+        assert!(matches!(
+            seal(&data, ""),
+            Err(NativeError::AppDataPassphrase)
+        ));
+    }
+
+    #[test]
+    fn merges_servers_by_url() {
+        let mut known = vec![server("Old name", "https://mm.example.com")];
+        let imported = vec![
+            server("Work", "https://mm.example.com"),
+            server("Community", "https://community.mattermost.com"),
+        ];
+        assert_eq!(merge_servers(&mut known, imported), 1);
+        let names: Vec<&str> = known.iter().map(|server| server.name.as_str()).collect();
+        assert_eq!(names, ["Work", "Community"]);
+    }
+}
@@ -0,0 +1,165 @@
+use models::*;
+use serde::Serialize;
+
+use crate::errors::NativeError;
+
+/// Upload rules of server, limits which are `None` aren't enforced
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AttachmentPolicy {
+    pub uploads_enabled: bool,
+    pub max_file_size: Option<u64>,
+    pub allowed_extensions: Option<Vec<String>>,
+    /// Storage left in cloud workspace
+    pub remaining_storage: Option<u64>,
+}
+
+impl AttachmentPolicy {
+    pub fn new(
+        capabilities: &ServerCapabilities,
+        limits: Option<&CloudLimits>,
+        usage: Option<&StorageUsage>,
+    ) -> Self {
+        let total_storage = limits
+            .and_then(|limits| limits.files.as_ref())
+            .and_then(|files| files.total_storage);
+        Self {
+            uploads_enabled: capabilities.file_uploads,
+            max_file_size: capabilities.max_file_size,
+            allowed_extensions: capabilities.allowed_file_extensions.clone(),
+            remaining_storage: total_storage.map(|total| {
+                total.saturating_sub(usage.map(|usage| usage.bytes_used).unwrap_or_default())
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Attachment {
+    pub name: String,
+    pub size: u64,
+}
+
+impl Attachment {
+    /// Lowercase extension without dot, empty when file has none
+    fn extension(&self) -> String {
+        std::path::Path::new(&self.name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+}
+
+/// Fail on first rule attachments break, so user learns about it before
+/// anything is uploaded
+pub fn check(policy: &AttachmentPolicy, attachments: &[Attachment]) -> Result<(), NativeError> {
+    if !policy.uploads_enabled {
+        return Err(NativeError::AttachmentsDisabled);
+    }
+    if let Some(allowed) = &policy.allowed_extensions {
+        if let Some(attachment) = attachments
+            .iter()
+            .find(|file| !allowed.contains(&file.extension()))
+        {
+            return Err(NativeError::FileTypeNotAllowed {
+                name: attachment.name.clone(),
+                allowed: allowed.join(", "),
+            });
+        }
+    }
+    if let Some(limit) = policy.max_file_size {
+        if let Some(attachment) = attachments.iter().find(|file| file.size > limit) {
+            return Err(NativeError::AttachmentTooLarge {
+                name: attachment.name.clone(),
+                size: attachment.size,
+                limit,
+            });
+        }
+    }
+    let size = attachments.iter().map(|file| file.size).sum();
+    match policy.remaining_storage {
+        Some(remaining) if size > remaining => {
+            Err(NativeError::StorageQuotaExceeded { size, remaining })
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn attachment(name: &str, size: u64) -> Attachment {
+        Attachment {
+            name: name.to_owned(),
+            size,
+        }
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let capabilities = ServerCapabilities {
+            file_uploads: true,
+            max_file_size: Some(100),
+            ..ServerCapabilities::default()
+        };
+        let limits = CloudLimits {
+            files: Some(FilesLimits {
+                total_storage: Some(1000),
+            }),
+        };
+        let policy = AttachmentPolicy::new(
+            &capabilities,
+            Some(&limits),
+            Some(&StorageUsage { bytes_used: 850 }),
+        );
+        assert_eq!(policy.remaining_storage, Some(150));
+
+        assert!(check(&policy, &[attachment("a.png", 100)]).is_ok());
+        assert!(matches!(
+            check(&policy, &[attachment("a.png", 10), attachment("b.mp4", 101)]),
+            Err(NativeError::AttachmentTooLarge { name, limit: 100, .. }) if name == "b.mp4"
+        ));
+        assert!(matches!(
+            check(&policy, &[attachment("a.png", 90), attachment("b.png", 90)]),
+            Err(NativeError::StorageQuotaExceeded {
+                size: 180,
+                remaining: 150
+            })
+        ));
+    }
+
+    #[test]
+    fn file_types() {
+        let policy = AttachmentPolicy {
+            uploads_enabled: true,
+            allowed_extensions: Some(vec!["png".to_owned(), "pdf".to_owned()]),
+            ..AttachmentPolicy::default()
+        };
+        assert!(check(
+            &policy,
+            &[attachment("Scan.PDF", 1), attachment("a.png", 1)]
+        )
+        .is_ok());
+        assert!(matches!(
+            check(&policy, &[attachment("a.png", 1), attachment("setup.exe", 1)]),
+            Err(NativeError::FileTypeNotAllowed { name, .. }) if name == "setup.exe"
+        ));
+        assert!(check(&policy, &[attachment("Makefile", 1)]).is_err());
+    }
+
+    #[test]
+    fn self_hosted() {
+        let capabilities = ServerCapabilities {
+            file_uploads: true,
+            ..ServerCapabilities::default()
+        };
+        let policy = AttachmentPolicy::new(&capabilities, Some(&CloudLimits::default()), None);
+        assert_eq!(policy.allowed_extensions, None);
+        assert!(check(&policy, &[attachment("big.iso", u64::MAX)]).is_ok());
+        let disabled = AttachmentPolicy::default();
+        assert!(matches!(
+            check(&disabled, &[]),
+            Err(NativeError::AttachmentsDisabled)
+        ));
+    }
+}
@@ -0,0 +1,122 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use models::*;
+use tauri::{AppHandle, Manager};
+
+use crate::commands::now_millis;
+use crate::errors::{Error, StorageError};
+use crate::shutdown;
+use crate::storage::Storage;
+use crate::storage_handle::StorageHandle;
+
+const PERSIST_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_ENTRIES: usize = 2000;
+const RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Entries recorded since last persist, oldest first
+static PENDING: Mutex<Vec<AuditEntry>> = Mutex::new(Vec::new());
+
+pub fn configure(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Record outcome of command, `target` should be an id or server name so
+/// nothing user wrote ends up in the log
+pub fn record<T>(command: &str, target: Option<&str>, result: &Result<T, Error>) {
+    if !is_enabled() {
+        return;
+    }
+    PENDING.lock().unwrap().push(AuditEntry {
+        at: now_millis(),
+        command: command.to_owned(),
+        target: target.map(str::to_owned),
+        outcome: match result {
+            Ok(_) => AuditOutcome::Succeeded,
+            Err(e) => AuditOutcome::Failed {
+                reason: e.to_string(),
+            },
+        },
+    });
+}
+
+/// Persisted entries followed by not yet persisted ones, newest first
+pub fn entries(storage: &Storage) -> Result<Vec<AuditEntry>, StorageError> {
+    let mut all = storage.audit_log()?;
+    all.extend(PENDING.lock().unwrap().iter().cloned());
+    all.reverse();
+    Ok(all)
+}
+
+pub fn persist(storage: &Storage) -> Result<(), StorageError> {
+    let pending = std::mem::take(&mut *PENDING.lock().unwrap());
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let oldest = now_millis().saturating_sub(RETENTION.as_millis() as Timestamp);
+    storage.update_audit_log(|all| {
+        all.extend(pending);
+        retain(all, oldest);
+    })
+}
+
+/// Drop entries pending persist and all persisted ones
+pub fn clear(storage: &Storage) -> Result<(), StorageError> {
+    PENDING.lock().unwrap().clear();
+    storage.update_audit_log(Vec::clear)
+}
+
+fn retain(all: &mut Vec<AuditEntry>, oldest: Timestamp) {
+    all.retain(|entry| entry.at >= oldest);
+    if all.len() > MAX_ENTRIES {
+        all.drain(..all.len() - MAX_ENTRIES);
+    }
+}
+
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(PERSIST_INTERVAL).await;
+            // Shutdown sequence persists the rest
+            if shutdown::is_shutting_down() {
+                break;
+            }
+            if let Err(e) = app.state::<StorageHandle>().run(persist).await {
+                tracing::warn!("Failed to persist audit log: {e}");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn entry(at: Timestamp) -> AuditEntry {
+        AuditEntry {
+            at,
+            command: "create_post".to_owned(),
+            target: None,
+            outcome: AuditOutcome::Succeeded,
+        }
+    }
+
+    #[test]
+    fn retention() {
+        let mut all: Vec<AuditEntry> = (0..MAX_ENTRIES as Timestamp + 10).map(entry).collect();
+        retain(&mut all, 5);
+        assert_eq!(all.len(), MAX_ENTRIES);
+        assert_eq!(all[0].at, 10);
+
+        let mut all = vec![entry(1), entry(2), entry(3)];
+        retain(&mut all, 2);
+        assert_eq!(all, [entry(2), entry(3)]);
+    }
+}
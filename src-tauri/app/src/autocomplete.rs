@@ -0,0 +1,199 @@
+use std::time::{Duration, Instant};
+
+use models::*;
+
+/// Maximum number of users requested from server for single term
+pub const AUTOCOMPLETE_LIMIT: usize = 25;
+/// Results older than this are fetched again
+const RESULT_TTL: Duration = Duration::from_secs(30);
+/// Keep only recent terms, composer rarely needs more than a few
+const MAX_ENTRIES: usize = 32;
+
+#[derive(Debug, Clone)]
+struct Entry {
+    channel_id: ChannelId,
+    term: String,
+    fetched_at: Instant,
+    result: UserAutocomplete,
+}
+
+impl Entry {
+    fn is_fresh(&self, now: Instant) -> bool {
+        now.duration_since(self.fetched_at) < RESULT_TTL
+    }
+
+    /// Server returned everything matching the term, so results for longer
+    /// terms can be computed locally
+    fn is_complete(&self) -> bool {
+        self.result.users.len() + self.result.out_of_channel.len() < AUTOCOMPLETE_LIMIT
+    }
+}
+
+/// Recent `@mention` autocomplete results kept per channel.
+///
+/// Typing `@jo` right after `@j` doesn't hit server when `@j` result wasn't
+/// truncated, matching users are filtered from it instead.
+#[derive(Debug, Clone, Default)]
+pub struct AutocompleteCache {
+    entries: Vec<Entry>,
+}
+
+impl AutocompleteCache {
+    pub fn lookup(
+        &self,
+        channel_id: &ChannelId,
+        term: &str,
+        now: Instant,
+    ) -> Option<UserAutocomplete> {
+        let term = normalize(term);
+        let fresh = self
+            .entries
+            .iter()
+            .filter(|entry| entry.is_fresh(now) && &entry.channel_id == channel_id);
+        let mut narrowest: Option<&Entry> = None;
+        for entry in fresh {
+            if entry.term == term {
+                return Some(entry.result.clone());
+            }
+            if entry.is_complete()
+                && term.starts_with(&entry.term)
+                && narrowest.map_or(true, |current| current.term.len() < entry.term.len())
+            {
+                narrowest = Some(entry);
+            }
+        }
+        let narrowest = narrowest?;
+        let filter = |users: &[UserResponse]| {
+            users
+                .iter()
+                .filter(|user| matches(user, &term))
+                .cloned()
+                .collect()
+        };
+        Some(UserAutocomplete {
+            users: filter(&narrowest.result.users),
+            out_of_channel: filter(&narrowest.result.out_of_channel),
+        })
+    }
+
+    pub fn insert(
+        &mut self,
+        channel_id: ChannelId,
+        term: &str,
+        now: Instant,
+        result: UserAutocomplete,
+    ) {
+        let term = normalize(term);
+        self.entries.retain(|entry| {
+            entry.is_fresh(now) && !(entry.channel_id == channel_id && entry.term == term)
+        });
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.entries.push(Entry {
+            channel_id,
+            term,
+            fetched_at: now,
+            result,
+        });
+    }
+}
+
+/// Composer passes term as typed, including leading `@`
+pub fn normalize(term: &str) -> String {
+    term.trim().trim_start_matches('@').to_lowercase()
+}
+
+/// Same rule as server uses: term is a prefix of username, any part of name,
+/// full name or nickname
+fn matches(user: &UserResponse, term: &str) -> bool {
+    let full_name = format!("{} {}", user.first_name, user.last_name);
+    [
+        &user.username,
+        &user.first_name,
+        &user.last_name,
+        &user.nickname,
+        &full_name,
+    ]
+    .iter()
+    .any(|name| name.to_lowercase().starts_with(term))
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn user(username: &str, first_name: &str, last_name: &str) -> UserResponse {
+        UserResponse {
+            id: username.into(),
+            username: username.into(),
+            auth_data: String::new(),
+            auth_service: String::new(),
+            email: String::new(),
+            nickname: String::new(),
+            first_name: first_name.into(),
+            last_name: last_name.into(),
+            position: String::new(),
+            roles: String::new(),
+            timezone: None,
+            notify_props: UserNotifyProps::default(),
+        }
+    }
+
+    fn usernames(result: &UserAutocomplete) -> Vec<&str> {
+        result
+            .users
+            .iter()
+            .map(|user| user.username.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn narrows_complete_result() {
+        let channel = ChannelId::new("town-square".to_owned());
+        let now = Instant::now();
+        let mut cache = AutocompleteCache::default();
+        cache.insert(
+            channel.clone(),
+            "@j",
+            now,
+            UserAutocomplete {
+                users: vec![
+                    user("john", "John", "Doe"),
+                    user("jane", "Jane", "Roe"),
+                    user("jdoe", "Joanna", "Doe"),
+                ],
+                out_of_channel: vec![],
+            },
+        );
+
+        let result = cache.lookup(&channel, "@Jo", now).unwrap();
+        assert_eq!(usernames(&result), ["john", "jdoe"]);
+        let result = cache.lookup(&channel, "joanna d", now).unwrap();
+        assert_eq!(usernames(&result), ["jdoe"]);
+
+        let other = ChannelId::new("off-topic".to_owned());
+        assert!(cache.lookup(&other, "@jo", now).is_none());
+        assert!(cache.lookup(&channel, "@jo", now + RESULT_TTL).is_none());
+    }
+
+    #[test]
+    fn truncated_result_is_not_narrowed() {
+        let channel = ChannelId::new("town-square".to_owned());
+        let now = Instant::now();
+        let mut cache = AutocompleteCache::default();
+        cache.insert(
+            channel.clone(),
+            "j",
+            now,
+            UserAutocomplete {
+                users: (0..AUTOCOMPLETE_LIMIT)
+                    .map(|n| user(&format!("j{n}"), "", ""))
+                    .collect(),
+                out_of_channel: vec![],
+            },
+        );
+        assert!(cache.lookup(&channel, "j", now).is_some());
+        assert!(cache.lookup(&channel, "jo", now).is_none());
+    }
+}
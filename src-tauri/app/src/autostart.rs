@@ -0,0 +1,49 @@
+use auto_launch::{AutoLaunch, AutoLaunchBuilder};
+
+use crate::errors::{Error, NativeError};
+
+/// Name of login item, same as config directory of application
+const APP_NAME: &str = "worryless";
+/// Added to command line of login item so start at login can be told apart
+/// from user opening application
+const AUTOSTART_ARG: &str = "--autostart";
+
+fn launcher() -> Result<AutoLaunch, Error> {
+    let exe = std::env::current_exe()?;
+    AutoLaunchBuilder::new()
+        .set_app_name(APP_NAME)
+        .set_app_path(&exe.display().to_string())
+        // Login items of macOS can't pass arguments
+        .set_use_launch_agent(true)
+        .set_args(&[AUTOSTART_ARG])
+        .build()
+        .map_err(|e| {
+            tracing::warn!("Unable to set up launch at login: {e}");
+            NativeError::AutoStart.into()
+        })
+}
+
+/// Register application with login items of OS (registry run key, launch
+/// agent or autostart desktop entry), or remove it from them.
+///
+/// Registering again updates path of executable, e.g. after application was
+/// moved.
+pub fn set_enabled(enabled: bool) -> Result<(), Error> {
+    let launcher = launcher()?;
+    let result = if enabled {
+        launcher.enable()
+    } else if launcher.is_enabled().unwrap_or(true) {
+        launcher.disable()
+    } else {
+        Ok(())
+    };
+    result.map_err(|e| {
+        tracing::warn!("Unable to change launch at login: {e}");
+        NativeError::AutoStart.into()
+    })
+}
+
+/// Application was started by OS at login
+pub fn launched_at_login(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == AUTOSTART_ARG)
+}
@@ -0,0 +1,181 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{Days, Local};
+use futures::{future, TryStreamExt};
+use http_body_util::BodyStream;
+use models::*;
+use tauri::{AppHandle, Manager};
+use url::Url;
+
+use crate::errors::StorageError;
+use crate::shutdown;
+use crate::storage::Storage;
+use crate::storage_handle::StorageHandle;
+
+/// How often accumulated usage is written to vault
+const PERSIST_INTERVAL: Duration = Duration::from_secs(60);
+/// Daily rollups older than this are dropped
+const RETENTION_DAYS: u64 = 90;
+
+/// Usage recorded since last persist, writing vault on every request would be
+/// far too expensive
+static PENDING: Mutex<Vec<BandwidthUsage>> = Mutex::new(Vec::new());
+
+/// Account request sent to `url`, sizes are in bytes
+pub fn record(url: &Url, sent: u64, received: u64) {
+    let Ok(server) = ServerUrl::parse(&url.origin().ascii_serialization()) else {
+        return;
+    };
+    merge(
+        &mut PENDING.lock().unwrap(),
+        BandwidthUsage {
+            server,
+            day: today(),
+            sent_bytes: sent,
+            received_bytes: received,
+        },
+    );
+}
+
+/// URL request was sent to, kept with response by [`counted`]
+#[derive(Clone)]
+struct RequestUrl(Url);
+
+/// Response which accounts its body to `url` as it's read, chunked bodies
+/// declare no length to count up front. Rebuilt response loses its URL,
+/// [`url`] still has it.
+pub fn counted(response: reqwest::Response, url: &Url) -> reqwest::Response {
+    let mut response = http::Response::<reqwest::Body>::from(response);
+    response.extensions_mut().insert(RequestUrl(url.clone()));
+    let (parts, body) = response.into_parts();
+    let url = url.clone();
+    let body = BodyStream::new(body)
+        .try_filter_map(|frame| future::ready(Ok(frame.into_data().ok())))
+        .inspect_ok(move |chunk| record(&url, 0, chunk.len() as u64));
+    http::Response::from_parts(parts, reqwest::Body::wrap_stream(body)).into()
+}
+
+/// URL of request response answers
+pub fn url(response: &reqwest::Response) -> &Url {
+    response
+        .extensions()
+        .get::<RequestUrl>()
+        .map_or_else(|| response.url(), |url| &url.0)
+}
+
+/// Persisted daily usage combined with not yet persisted one
+pub fn usage(storage: &Storage) -> Result<Vec<BandwidthUsage>, StorageError> {
+    let mut all = storage.bandwidth_usage()?;
+    for usage in PENDING.lock().unwrap().iter() {
+        merge(&mut all, usage.clone());
+    }
+    Ok(all)
+}
+
+pub fn persist(storage: &Storage) -> Result<(), StorageError> {
+    let pending = std::mem::take(&mut *PENDING.lock().unwrap());
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let oldest = days_ago(RETENTION_DAYS);
+    storage.update_bandwidth_usage(|all| {
+        for usage in pending {
+            merge(all, usage);
+        }
+        all.retain(|usage| usage.day >= oldest);
+    })
+}
+
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(PERSIST_INTERVAL).await;
+            // Pending usage is persisted by shutdown sequence itself
+            if shutdown::is_shutting_down() {
+                break;
+            }
+            if let Err(e) = app.state::<StorageHandle>().run(persist).await {
+                tracing::warn!("Failed to persist bandwidth usage: {e}");
+            }
+        }
+    });
+}
+
+fn merge(all: &mut Vec<BandwidthUsage>, usage: BandwidthUsage) {
+    match all
+        .iter_mut()
+        .find(|current| current.server == usage.server && current.day == usage.day)
+    {
+        Some(current) => {
+            current.sent_bytes += usage.sent_bytes;
+            current.received_bytes += usage.received_bytes;
+        }
+        None => all.push(usage),
+    }
+}
+
+fn today() -> String {
+    days_ago(0)
+}
+
+/// Local date formatted like rollup days, e.g. `2024-05-01`
+pub fn days_ago(days: u64) -> String {
+    (Local::now().date_naive() - Days::new(days))
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn daily_rollup() {
+        let server = ServerUrl::parse("https://mm.example.com").unwrap();
+        let other = ServerUrl::parse("https://other.example.com").unwrap();
+        let usage = |server: &ServerUrl, day: &str, sent, received| BandwidthUsage {
+            server: server.clone(),
+            day: day.to_owned(),
+            sent_bytes: sent,
+            received_bytes: received,
+        };
+        let mut all = vec![];
+        merge(&mut all, usage(&server, "2024-05-01", 10, 100));
+        merge(&mut all, usage(&server, "2024-05-01", 5, 50));
+        merge(&mut all, usage(&server, "2024-05-02", 1, 1));
+        merge(&mut all, usage(&other, "2024-05-01", 2, 2));
+        assert_eq!(
+            all,
+            vec![
+                usage(&server, "2024-05-01", 15, 150),
+                usage(&server, "2024-05-02", 1, 1),
+                usage(&other, "2024-05-01", 2, 2),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn counts_chunked_body() {
+        let url = Url::parse("https://chunked.example.com/api/v4/posts").unwrap();
+        let chunks = futures::stream::iter(["12345", "678"].map(Ok::<_, std::io::Error>));
+        let response = http::Response::new(reqwest::Body::wrap_stream(chunks)).into();
+        let response = counted(response, &url);
+        assert_eq!(response.content_length(), None);
+        assert_eq!(super::url(&response), &url);
+        assert_eq!(response.text().await.unwrap(), "12345678");
+        let received: u64 = PENDING
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|usage| {
+                usage
+                    .server
+                    .as_str()
+                    .starts_with("https://chunked.example.com")
+            })
+            .map(|usage| usage.received_bytes)
+            .sum();
+        assert_eq!(received, 8);
+    }
+}
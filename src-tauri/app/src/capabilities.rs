@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use models::*;
+
+/// Read capabilities from client configuration in old (flat) format,
+/// missing keys mean disabled feature
+pub fn from_client_config(config: &HashMap<String, String>) -> ServerCapabilities {
+    let text = |key: &str| config.get(key).cloned().unwrap_or_default();
+    let enabled = |key: &str| config.get(key).is_some_and(|value| value == "true");
+    ServerCapabilities {
+        version: text("Version"),
+        build_number: text("BuildNumber"),
+        custom_emoji: enabled("EnableCustomEmoji"),
+        file_uploads: enabled("EnableFileAttachments"),
+        max_file_size: config.get("MaxFileSize").and_then(|size| size.parse().ok()),
+        allowed_file_extensions: config
+            .get("AllowedFileExtensions")
+            .map(|list| file_extensions(list))
+            .filter(|extensions| !extensions.is_empty()),
+        // Other values are `default_on`, `default_off` and `always_on`,
+        // servers older than 6.0 don't report it at all
+        collapsed_threads: config
+            .get("CollapsedThreads")
+            .is_some_and(|value| value != "disabled"),
+        password_login: enabled("EnableSignInWithEmail") || enabled("EnableSignInWithUsername"),
+        gitlab_login: enabled("EnableSignUpWithGitLab"),
+        saml_login: enabled("EnableSaml"),
+    }
+}
+
+/// Comma separated list like `.png, JPG,pdf` normalized to lowercase
+/// extensions without dot
+fn file_extensions(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+        .filter(|extension| !extension.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn client_config() {
+        let config: HashMap<String, String> = [
+            ("Version", "9.5.1"),
+            ("BuildNumber", "9.5.1"),
+            ("EnableCustomEmoji", "true"),
+            ("EnableFileAttachments", "false"),
+            ("MaxFileSize", "104857600"),
+            ("AllowedFileExtensions", ".PNG, jpg,,pdf"),
+            ("CollapsedThreads", "default_off"),
+            ("EnableSignInWithUsername", "true"),
+            ("EnableSaml", "false"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .collect();
+        let capabilities = from_client_config(&config);
+        assert_eq!(
+            capabilities,
+            ServerCapabilities {
+                version: "9.5.1".to_owned(),
+                build_number: "9.5.1".to_owned(),
+                custom_emoji: true,
+                file_uploads: false,
+                max_file_size: Some(104857600),
+                allowed_file_extensions: Some(vec![
+                    "png".to_owned(),
+                    "jpg".to_owned(),
+                    "pdf".to_owned()
+                ]),
+                collapsed_threads: true,
+                password_login: true,
+                gitlab_login: false,
+                saml_login: false,
+            }
+        );
+        assert!(capabilities.version_at_least(9, 5));
+        assert!(capabilities.version_at_least(7, 10));
+        assert!(!capabilities.version_at_least(9, 6));
+        assert!(!capabilities.version_at_least(10, 0));
+    }
+
+    #[test]
+    fn unknown_version() {
+        let capabilities = from_client_config(&HashMap::new());
+        assert!(!capabilities.collapsed_threads);
+        assert!(!capabilities.version_at_least(0, 1));
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use models::*;
+use reqwest::Client;
+use tokio::sync::Mutex;
+use url::Url;
+
+use crate::api::call_event::*;
+use crate::api::handle_request;
+use crate::errors::{Error, NativeError};
+use crate::working_hours::dm_recipient;
+
+/// Group messages have at most 8 members, all fit on one page
+const GROUP_MEMBERS_PER_PAGE: u32 = 10;
+
+/// Users shown as names of direct and group message channels. Participants
+/// of group message never change, so they are kept as well.
+#[derive(Default)]
+pub struct UserCache {
+    /// Keyed by server URL and user id
+    users: Mutex<HashMap<(String, String), UserResponse>>,
+    /// Keyed by server URL and channel id
+    group_members: Mutex<HashMap<(String, String), Vec<UserId>>>,
+}
+
+impl UserCache {
+    pub async fn remember(&self, server: &Url, users: &[UserResponse]) {
+        let mut known = self.users.lock().await;
+        for user in users {
+            known.insert((server.to_string(), user.id.clone()), user.clone());
+        }
+    }
+
+    /// Known users of `ids`, keyed by user id
+    async fn lookup(&self, server: &Url, ids: &[UserId]) -> HashMap<String, UserResponse> {
+        let known = self.users.lock().await;
+        ids.iter()
+            .filter_map(|id| known.get(&(server.to_string(), id.to_string())))
+            .map(|user| (user.id.clone(), user.clone()))
+            .collect()
+    }
+}
+
+/// Participants of direct or group message channel other than `me`, `None`
+/// for other channels and group messages with members not known yet
+fn participants(
+    channel: &Channel,
+    me: &UserId,
+    group_members: &HashMap<(String, String), Vec<UserId>>,
+    server: &Url,
+) -> Option<Vec<UserId>> {
+    match channel.r#type.as_deref().map(String::as_str) {
+        Some("D") => {
+            let other = dm_recipient(channel.name.as_ref()?, me)?;
+            Some(vec![other])
+        }
+        Some("G") => {
+            let key = (server.to_string(), channel.id.as_ref()?.to_string());
+            let members = group_members.get(&key)?;
+            Some(members.iter().filter(|id| *id != me).cloned().collect())
+        }
+        _ => None,
+    }
+}
+
+/// Names of `participants` sorted and joined, users not known are left out.
+/// Direct message with oneself has no other participant and is named after
+/// user.
+fn display_name(
+    participants: &[UserId],
+    me: &UserId,
+    users: &HashMap<String, UserResponse>,
+) -> Option<String> {
+    let mut names: Vec<&str> = participants
+        .iter()
+        .filter_map(|id| users.get(id.as_str()))
+        .map(UserResponse::display_name)
+        .collect();
+    if participants.is_empty() || participants == [me.clone()] {
+        names = users
+            .get(me.as_str())
+            .map(UserResponse::display_name)
+            .into_iter()
+            .collect();
+    }
+    if names.is_empty() {
+        return None;
+    }
+    names.sort_unstable_by_key(|name| name.to_lowercase());
+    Some(names.join(", "))
+}
+
+/// Replace machine names of direct and group message channels by names of
+/// other participants. Users and group members not in `cache` are fetched
+/// first, channels which still can't be resolved keep name from server.
+pub async fn resolve(
+    client: &Client,
+    server_url: &Url,
+    token: Option<&AccessToken>,
+    me: &UserId,
+    cache: &UserCache,
+    channels: &mut [Channel],
+) -> Result<(), Error> {
+    let unknown_groups: Vec<ChannelId> = {
+        let group_members = cache.group_members.lock().await;
+        channels
+            .iter()
+            .filter(|channel| channel.r#type.as_deref().map(String::as_str) == Some("G"))
+            .filter_map(|channel| channel.id.clone())
+            .filter(|id| !group_members.contains_key(&(server_url.to_string(), id.to_string())))
+            .collect()
+    };
+    for channel_id in unknown_groups {
+        let event = ApiEvent::ChannelMembers {
+            channel_id: channel_id.clone(),
+            page: 0,
+            per_page: GROUP_MEMBERS_PER_PAGE,
+        };
+        let Response::ChannelMembers(members) =
+            handle_request(client, server_url, &event, token).await?
+        else {
+            return Err(NativeError::UnexpectedResponse)?;
+        };
+        cache.group_members.lock().await.insert(
+            (server_url.to_string(), channel_id.to_string()),
+            members.into_iter().map(|member| member.user_id).collect(),
+        );
+    }
+
+    let by_channel: Vec<Option<Vec<UserId>>> = {
+        let group_members = cache.group_members.lock().await;
+        channels
+            .iter()
+            .map(|channel| participants(channel, me, &group_members, server_url))
+            .collect()
+    };
+    let mut ids: Vec<UserId> = by_channel.iter().flatten().flatten().cloned().collect();
+    ids.push(me.clone());
+    ids.sort_unstable_by(|a, b| a.as_str().cmp(b.as_str()));
+    ids.dedup();
+    let mut users = cache.lookup(server_url, &ids).await;
+    let missing: Vec<UserId> = ids
+        .into_iter()
+        .filter(|id| !users.contains_key(id.as_str()))
+        .collect();
+    if !missing.is_empty() {
+        let Response::Users(fetched) =
+            handle_request(client, server_url, &ApiEvent::UsersByIds(missing), token).await?
+        else {
+            return Err(NativeError::UnexpectedResponse)?;
+        };
+        cache.remember(server_url, &fetched).await;
+        users.extend(fetched.into_iter().map(|user| (user.id.clone(), user)));
+    }
+
+    for (channel, participants) in channels.iter_mut().zip(by_channel) {
+        let Some(participants) = participants else {
+            continue;
+        };
+        if let Some(name) = display_name(&participants, me, &users) {
+            channel.display_name = Some(ChannelDisplayName::from(name));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn user(id: &str, username: &str, nickname: &str) -> UserResponse {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "username": username,
+            "nickname": nickname,
+            "roles": "system_user",
+        }))
+        .unwrap()
+    }
+
+    fn channel(id: &str, kind: &str, name: &str) -> Channel {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "team_id": "",
+            "type": kind,
+            "display_name": "",
+            "name": name,
+            "header": null,
+            "purpose": null,
+            "creator_id": null,
+            "scheme_id": null,
+            "props": null,
+            "group_constrained": null,
+            "total_msg_count_root": null,
+            "last_root_post_at": null,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn names_other_participants() {
+        let server = Url::parse("https://mm.example.com").unwrap();
+        let me = UserId::new("me".to_owned());
+        let users: HashMap<String, UserResponse> = [
+            user("me", "alice", ""),
+            user("u2", "bob", "Bobby"),
+            user("u3", "carol", ""),
+        ]
+        .into_iter()
+        .map(|user| (user.id.clone(), user))
+        .collect();
+        let group_members = HashMap::from([(
+            (server.to_string(), "g1".to_owned()),
+            ["u3", "me", "u2"]
+                .map(|id| UserId::new(id.to_owned()))
+                .to_vec(),
+        )]);
+        let name = |channel: &Channel| {
+            let participants = participants(channel, &me, &group_members, &server)?;
+            display_name(&participants, &me, &users)
+        };
+
+        assert_eq!(
+            name(&channel("d1", "D", "u2__me")).as_deref(),
+            Some("Bobby")
+        );
+        assert_eq!(
+            name(&channel("d2", "D", "me__me")).as_deref(),
+            Some("alice")
+        );
+        assert_eq!(
+            name(&channel("g1", "G", "0f3a9c")).as_deref(),
+            Some("Bobby, carol")
+        );
+        assert_eq!(name(&channel("g2", "G", "5be1d7")), None);
+        assert_eq!(name(&channel("c1", "O", "town-square")), None);
+    }
+}
@@ -0,0 +1,50 @@
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+/// Longest channel name server accepts
+const MAX_NAME_LENGTH: usize = 64;
+
+/// URL name of new channel derived from its display name the way webapp
+/// does it: lowercase ASCII letters and digits separated by single dashes.
+///
+/// Display names without any of those, e.g. in non-Latin scripts, get
+/// random name since server rejects empty ones.
+pub fn slug(display_name: &str) -> String {
+    let mut slug = String::with_capacity(display_name.len());
+    for c in display_name.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(MAX_NAME_LENGTH);
+    let slug = slug.trim_end_matches('-');
+    // Two letter names are reserved for direct message channels
+    if slug.len() < 2 {
+        return thread_rng()
+            .sample_iter(Alphanumeric)
+            .take(26)
+            .map(|c| char::from(c).to_ascii_lowercase())
+            .collect();
+    }
+    slug.to_owned()
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn names_from_display_name() {
+        assert_eq!(slug("Release Planning 2024"), "release-planning-2024");
+        assert_eq!(slug("  Q&A -- Backend!  "), "q-a-backend");
+        assert_eq!(slug("dev_ops"), "dev_ops");
+        assert_eq!(slug(&"long ".repeat(20)).len(), MAX_NAME_LENGTH);
+
+        let random = slug("Отдел продаж");
+        assert_eq!(random.len(), 26);
+        assert!(random.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(random, slug("Отдел продаж"));
+    }
+}
@@ -0,0 +1,66 @@
+use crate::errors::{Error, NativeError};
+
+/// Name screenshots pasted from clipboard are uploaded under, the official
+/// client names them the same way
+pub const IMAGE_NAME: &str = "image.png";
+
+/// Image on OS clipboard encoded as PNG. Clipboard access blocks on some
+/// platforms, so it's read off async runtime.
+pub async fn read_image() -> Result<Vec<u8>, Error> {
+    tokio::task::spawn_blocking(|| {
+        let mut clipboard = arboard::Clipboard::new().map_err(|error| {
+            tracing::warn!("Clipboard is not available: {error}");
+            NativeError::ClipboardImage
+        })?;
+        let image = clipboard.get_image().map_err(|error| {
+            tracing::debug!("No image on clipboard: {error}");
+            NativeError::ClipboardImage
+        })?;
+        encode_png(image.width, image.height, &image.bytes)
+    })
+    .await
+    .map_err(|_| NativeError::ClipboardImage)?
+}
+
+/// PNG of `rgba` pixels, 4 bytes per pixel row by row
+fn encode_png(width: usize, height: usize, rgba: &[u8]) -> Result<Vec<u8>, Error> {
+    let (Ok(png_width), Ok(png_height)) = (u32::try_from(width), u32::try_from(height)) else {
+        return Err(NativeError::ClipboardImage.into());
+    };
+    if width == 0 || height == 0 || rgba.len() != width * height * 4 {
+        return Err(NativeError::ClipboardImage.into());
+    }
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, png_width, png_height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(rgba))
+        .map_err(|error| {
+            tracing::warn!("Unable to encode clipboard image: {error}");
+            NativeError::ClipboardImage
+        })?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn encodes_rgba_as_png() {
+        let pixels = [255, 0, 0, 255, 0, 255, 0, 128];
+        let data = encode_png(2, 1, &pixels).unwrap();
+        assert_eq!(&data[..8], b"\x89PNG\r\n\x1a\n");
+
+        let decoder = png::Decoder::new(data.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut decoded).unwrap();
+        assert_eq!(decoded, pixels);
+
+        assert!(encode_png(2, 2, &pixels).is_err());
+        assert!(encode_png(0, 0, &[]).is_err());
+    }
+}
@@ -1,13 +1,57 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
 use models::*;
 use reqwest::Client;
-use tauri::State;
-use tokio::sync::Mutex;
+use tauri::{Manager, State};
+use tokio::sync::{Mutex, RwLock};
 use url::Url;
 
 use crate::api::call_event::*;
-use crate::api::handle_request;
+use crate::api::paging::{self, fetch_all_pages, Paged};
+use crate::api::{etag, handle_request, rate_limit, schema, signing};
+use crate::app_data::{self, ImportedAppData};
+use crate::attachments::{self, Attachment, AttachmentPolicy};
+use crate::channel_names::{self, UserCache};
+use crate::composer::{self, LinkSuggestion};
+use crate::connection::{self, ConnectionState};
+use crate::dnd::{self, Dnd, DndManager};
+use crate::emoji::{self, EmojiCache, EmojiImage, EmojiMatch};
 use crate::errors::{Error, NativeError};
+use crate::export::{
+    self, ChannelExportFormat, ExportRange, ExportedChannel, ThreadExportFormat, MAX_EXPORT_POSTS,
+};
+use crate::header_links::{header_links, HeaderLink};
+use crate::link_preview::LinkPreviews;
+use crate::mentions::RecentMentions;
+use crate::navigation::{NavigationEntry, NavigationHistory, NavigationSnapshot};
+use crate::notifications::{self, NavigationTarget};
+use crate::outbox::Outbox;
+use crate::patch::Snapshots;
+use crate::permalinks::{self, ResolvedPermalink};
+use crate::post_stream::{self, PostsDone};
+use crate::post_types::{self, RenderHint};
+use crate::scheduler::Scheduler;
+use crate::secrets::{self, SecretFinding, SecretGuard};
+use crate::sessions::{
+    self, BootstrapOutput, BootstrapProgress, Sessions, BOOTSTRAP_PROGRESS_EVENT,
+};
+use crate::settings::{self, SettingsState};
+use crate::single_instance::DeepLinks;
+use crate::sso::{self, SsoProvider};
 use crate::states::{Server, ServerState, UserState};
+use crate::status::StatusManager;
+use crate::storage::{ForgottenData, Storage, StorageHealth};
+use crate::storage_handle::StorageHandle;
+use crate::switcher::{self, SwitchTarget, SWITCHER_RESULTS};
+use crate::timeline::{TimelinePreview, TimelineRefreshed, TIMELINE_REFRESHED_EVENT};
+use crate::websocket::{WebSocket, WsAction};
+use crate::working_hours::{dm_recipient, RecipientLocalTime, WorkingHours};
+use crate::{
+    audit, autocomplete, bandwidth, capabilities, channels, clipboard, diagnostics, digest, i18n,
+    link_preview, logging, mentions, post_store, preferences, saved_posts, servers, snippets,
+    threads, unread,
+};
 
 #[tauri::command]
 pub async fn login(
@@ -29,13 +73,7 @@ pub async fn login(
             .clone()
     };
     tracing::debug!("current url: {url:?}");
-    let result = handle_request(
-        &http_client,
-        &url,
-        &ApiEvent::Login(login, password),
-        None,
-    )
-    .await;
+    let result = handle_request(&http_client, &url, &ApiEvent::Login(login, password), None).await;
     tracing::info!("result: {:?}", result);
     let Response::Login {
         token,
@@ -46,12 +84,184 @@ pub async fn login(
         return Err(NativeError::UnexpectedResponse)?;
     };
     tracing::info!("Authorized");
-    {
-        user_state_mutex.lock().await.token = Some(token.to_owned());
-    }
-    Ok(UserDetails {
+    let user_details = UserDetails {
         id: user_id.to_owned(),
         username: user_name.to_owned(),
+    };
+    {
+        let mut user_state = user_state_mutex.lock().await;
+        user_state.token = Some(token.to_owned());
+        user_state.id = Some(UserId::new(user_id));
+        user_state.user_details = Some(user_details.clone());
+    }
+    Ok(user_details)
+}
+
+/// Log in with personal access token, for servers where password login is
+/// disabled. Token is checked against server and remembered for current
+/// server.
+#[tauri::command]
+pub async fn login_with_token(
+    token: String,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+) -> Result<UserDetails, Error> {
+    tracing::info!("User login with access token");
+    let token = AccessToken::new(token).map_err(|_| NativeError::InvalidToken)?;
+    let server_url = current_server_url(&server_state_mutex).await?;
+    authorize_token(token, server_url, &user_state_mutex, &http_client, &storage).await
+}
+
+/// Log in through SSO provider configured on current server.
+///
+/// Login page is opened in system browser which is redirected back to
+/// local callback with session token, fails when user doesn't finish within
+/// [`sso::SSO_TIMEOUT`].
+#[tauri::command]
+pub async fn login_with_sso(
+    provider: SsoProvider,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    app: tauri::AppHandle,
+) -> Result<UserDetails, Error> {
+    tracing::info!("User login with {provider:?} SSO");
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let callback = sso::Callback::bind().await?;
+    let login_url = sso::login_url(&server_url, provider, &callback.redirect_to)?;
+    tauri::api::shell::open(&app.shell_scope(), login_url.as_str(), None).map_err(|e| {
+        tracing::error!("Failed to open browser: {e}");
+        NativeError::SsoFailed
+    })?;
+    let token = callback.token().await?;
+    authorize_token(token, server_url, &user_state_mutex, &http_client, &storage).await
+}
+
+/// Check token against server, remember it and log user in
+async fn authorize_token(
+    token: AccessToken,
+    server_url: Url,
+    user_state_mutex: &Mutex<UserState>,
+    http_client: &Client,
+    storage: &StorageHandle,
+) -> Result<UserDetails, Error> {
+    let Response::User(user) =
+        handle_request(http_client, &server_url, &ApiEvent::Me, Some(&token)).await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    tracing::info!("Authorized");
+
+    let credentials = ServerCredentials {
+        url: server_url.into(),
+        access_token: token.clone(),
+    };
+    storage
+        .run(move |storage| storage.store_credentials(&credentials))
+        .await?;
+
+    let user_details = UserDetails {
+        id: user.id.clone(),
+        username: user.username,
+    };
+    {
+        let mut user_state = user_state_mutex.lock().await;
+        user_state.token = Some(token);
+        user_state.id = Some(UserId::new(user.id));
+        user_state.user_details = Some(user_details.clone());
+    }
+    Ok(user_details)
+}
+
+/// Fetch user, teams, channels, preferences and unread counts of server in
+/// one go and make it current. Requests run in parallel and report
+/// `bootstrap-progress` as they finish; snapshots of teams and channels are
+/// published, so later syncs send only their changes.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn bootstrap(
+    server_name: &str,
+    app: tauri::AppHandle,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    sessions: State<'_, Sessions>,
+    user_cache: State<'_, UserCache>,
+    snapshots: State<'_, Snapshots>,
+) -> Result<BootstrapOutput, Error> {
+    let (server, is_current) = {
+        let state = server_state_mutex.lock().await;
+        let server = state
+            .servers
+            .iter()
+            .find(|server| server.name == server_name)
+            .cloned()
+            .ok_or(NativeError::UnknownServer)?;
+        let is_current = state
+            .current
+            .as_ref()
+            .is_some_and(|current| current.url == server.url);
+        (server, is_current)
+    };
+    // Freshly logged in user has token in user state only, other servers
+    // use session bootstrapped at startup
+    let current_token = if is_current {
+        user_state_mutex.lock().await.token.clone()
+    } else {
+        None
+    };
+    let token = match current_token {
+        Some(token) => token,
+        None => {
+            sessions
+                .get(&server.url)
+                .await
+                .ok_or(NativeError::NotLoggedIn)?
+                .token
+        }
+    };
+    let credentials = ServerCredentials {
+        url: server.url.clone().into(),
+        access_token: token,
+    };
+    let progress = |progress: BootstrapProgress| {
+        app.emit_all(BOOTSTRAP_PROGRESS_EVENT, progress).ok();
+    };
+    let mut session = sessions::bootstrap(&http_client, &credentials, &progress).await?;
+    resolve_channel_names(
+        &http_client,
+        &server.url,
+        Some(&credentials.access_token),
+        Some(&session.user_id),
+        &user_cache,
+        &mut session.channels,
+    )
+    .await;
+
+    session.apply(&mut *user_state_mutex.lock().await);
+    server_state_mutex.lock().await.current = Some(server.clone());
+    let badge = sessions::badge(
+        credentials.url.clone(),
+        &session.channels,
+        &session.channel_members,
+    );
+    unread::update(&app, &badge);
+    sessions.insert(&server.url, session.clone()).await;
+    snapshots.publish(&app, "teams", &session.teams).await?;
+    snapshots
+        .publish(&app, "channels", &session.channels)
+        .await?;
+    tracing::info!("Bootstrapped {} on request: {badge:?}", server.url);
+    Ok(BootstrapOutput {
+        user_details: session.user_details,
+        teams: session.teams,
+        team_members: session.team_members,
+        channels: session.channels,
+        preferences: session.preferences,
+        badge,
     })
 }
 
@@ -103,13 +313,37 @@ pub async fn my_team_members(
     Ok(team_members.to_owned())
 }
 
+/// Name direct and group messages after their participants, they keep
+/// names from server when it fails
+async fn resolve_channel_names(
+    http_client: &Client,
+    server_url: &Url,
+    token: Option<&AccessToken>,
+    me: Option<&UserId>,
+    user_cache: &UserCache,
+    channels: &mut [Channel],
+) {
+    let Some(me) = me else {
+        return;
+    };
+    if let Err(e) =
+        channel_names::resolve(http_client, server_url, token, me, user_cache, channels).await
+    {
+        tracing::warn!("Failed to resolve names of direct messages: {e}");
+    }
+}
+
 #[tauri::command]
 pub async fn my_channels(
     user_state_mutex: State<'_, Mutex<UserState>>,
     server_state_mutex: State<'_, Mutex<ServerState>>,
     http_client: State<'_, Client>,
+    user_cache: State<'_, UserCache>,
 ) -> Result<Vec<Channel>, Error> {
-    let token_option = { user_state_mutex.lock().await.token.as_ref().cloned() };
+    let (token_option, me) = {
+        let user_state = user_state_mutex.lock().await;
+        (user_state.token.clone(), user_state.id.clone())
+    };
     let server_state = server_state_mutex.lock().await;
     let current_url = server_state.current.as_ref().unwrap();
     let result = handle_request(
@@ -119,56 +353,475 @@ pub async fn my_channels(
         token_option.as_ref(),
     )
     .await?;
-    let Response::MyChannels(channels) = result else {
+    let Response::MyChannels(mut channels) = result else {
         return Err(NativeError::UnexpectedResponse)?;
     };
+    resolve_channel_names(
+        &http_client,
+        &current_url.url,
+        token_option.as_ref(),
+        me.as_ref(),
+        &user_cache,
+        &mut channels,
+    )
+    .await;
     let mut user_state = user_state_mutex.lock().await;
     user_state.channels = Some(channels.to_owned());
     Ok(channels.to_owned())
 }
 
+/// Channels of user in one team together with direct and group messages
+#[tauri::command]
+pub async fn my_team_channels(
+    team_id: TeamId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    user_cache: State<'_, UserCache>,
+) -> Result<Vec<Channel>, Error> {
+    let (token, me) = {
+        let user_state = user_state_mutex.lock().await;
+        (user_state.token.clone(), user_state.id.clone())
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::MyChannels(mut channels) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::TeamChannels(team_id.clone()),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    resolve_channel_names(
+        &http_client,
+        &server_url,
+        token.as_ref(),
+        me.as_ref(),
+        &user_cache,
+        &mut channels,
+    )
+    .await;
+    user_state_mutex
+        .lock()
+        .await
+        .store_team_channels(&team_id, &channels);
+    Ok(channels)
+}
+
+/// Show `team_id` in sidebar, returns its channels
+#[tauri::command]
+pub async fn change_team(
+    team_id: TeamId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    user_cache: State<'_, UserCache>,
+) -> Result<Vec<Channel>, Error> {
+    let member = user_state_mutex
+        .lock()
+        .await
+        .teams
+        .iter()
+        .flatten()
+        .any(|team| team.id.as_ref() == Some(&team_id));
+    if !member {
+        return Err(NativeError::UnknownTeam)?;
+    }
+    let channels = my_team_channels(
+        team_id.clone(),
+        user_state_mutex.clone(),
+        server_state_mutex,
+        http_client,
+        user_cache,
+    )
+    .await?;
+    user_state_mutex.lock().await.current_team = Some(team_id);
+    Ok(channels)
+}
+
+/// Revoke session on server and forget it locally. Local state is cleared
+/// even when server can't be reached, session then simply expires there.
 #[tauri::command]
-pub async fn logout(state_mutex: State<'_, Mutex<UserState>>) -> Result<(), Error> {
-    let mut server_state = state_mutex.lock().await;
-    server_state.user_details = None;
-    server_state.token = None;
+pub async fn logout(
+    app: tauri::AppHandle,
+    state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    sessions: State<'_, Sessions>,
+    websocket: State<'_, WebSocket>,
+) -> Result<(), Error> {
+    let server_url = server_state_mutex
+        .lock()
+        .await
+        .current
+        .as_ref()
+        .map(|server| server.url.clone());
+    let result = run_logout(
+        app,
+        state_mutex,
+        server_url.clone(),
+        http_client,
+        storage,
+        sessions,
+        websocket,
+    )
+    .await;
+    audit::record("logout", server_url.as_ref().map(Url::as_str), &result);
+    result
+}
+
+async fn run_logout(
+    app: tauri::AppHandle,
+    state_mutex: State<'_, Mutex<UserState>>,
+    server_url: Option<Url>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    sessions: State<'_, Sessions>,
+    websocket: State<'_, WebSocket>,
+) -> Result<(), Error> {
+    let token = {
+        let mut server_state = state_mutex.lock().await;
+        server_state.user_details = None;
+        server_state.preferences = None;
+        server_state.autocomplete = Default::default();
+        server_state.token.take()
+    };
+    websocket.disconnect().await;
+    etag::clear();
+    let Some(server_url) = &server_url else {
+        return Ok(());
+    };
+    if let Some(token) = &token {
+        if let Err(e) =
+            handle_request(&http_client, server_url, &ApiEvent::Logout, Some(token)).await
+        {
+            tracing::warn!("Failed to revoke session on {server_url}: {e}");
+        }
+    }
+    sessions.remove(server_url).await;
+    unread::remove(&app, &server_url.clone().into());
+    remove_credentials(&storage, server_url.clone().into()).await
+}
+
+async fn remove_credentials(storage: &StorageHandle, server: ServerUrl) -> Result<(), Error> {
+    storage
+        .run(move |storage| storage.remove_credentials(&server))
+        .await?;
     Ok(())
 }
 
+#[derive(Debug, serde::Serialize, Clone)]
+pub struct ForgottenAccount {
+    /// Session was revoked on server, it stays valid there when server was
+    /// unreachable
+    pub session_revoked: bool,
+    #[serde(flatten)]
+    pub data: ForgottenData,
+    pub emoji_images: bool,
+}
+
+/// Log out of server and wipe everything stored locally for the account,
+/// server itself stays in the list of servers
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn forget_server_account(
+    server_name: &str,
+    app: tauri::AppHandle,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    emoji_cache: State<'_, EmojiCache>,
+    sessions: State<'_, Sessions>,
+) -> Result<ForgottenAccount, Error> {
+    let target = server_name.to_string();
+    let result = run_forget_server_account(
+        server_name,
+        app,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+        storage,
+        emoji_cache,
+        sessions,
+    )
+    .await;
+    audit::record("forget_server_account", Some(&target), &result);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_forget_server_account(
+    server_name: &str,
+    app: tauri::AppHandle,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    emoji_cache: State<'_, EmojiCache>,
+    sessions: State<'_, Sessions>,
+) -> Result<ForgottenAccount, Error> {
+    let (server_url, is_current) = {
+        let state = server_state_mutex.lock().await;
+        let server = state
+            .servers
+            .iter()
+            .find(|server| server.name == server_name)
+            .ok_or(NativeError::UnknownServer)?;
+        let is_current = state
+            .current
+            .as_ref()
+            .is_some_and(|current| current.url == server.url);
+        (server.url.clone(), is_current)
+    };
+    let server: ServerUrl = server_url.clone().into();
+
+    let session_token = if is_current {
+        user_state_mutex.lock().await.token.clone()
+    } else {
+        None
+    };
+    let stored_token = {
+        let server = server.clone();
+        storage
+            .run(move |storage| storage.server_credentials(&server))
+            .await?
+            .map(|stored| stored.access_token)
+    };
+    let mut tokens: Vec<AccessToken> = session_token.into_iter().chain(stored_token).collect();
+    tokens.dedup();
+    let mut session_revoked = false;
+    for token in &tokens {
+        match handle_request(&http_client, &server_url, &ApiEvent::Logout, Some(token)).await {
+            Ok(_) => session_revoked = true,
+            Err(e) => tracing::warn!("Failed to revoke session on {server_url}: {e}"),
+        }
+    }
+    sessions.remove(&server_url).await;
+    unread::remove(&app, &server);
+    if is_current {
+        let mut user_state = user_state_mutex.lock().await;
+        *user_state = UserState::default();
+    }
+
+    let data = {
+        let server = server.clone();
+        storage
+            .run(move |storage| storage.forget_server(&server))
+            .await?
+    };
+    let emoji_images = emoji_cache.forget_server(&server).await;
+    tracing::info!("Forgot account on {server_url}: {data:?}");
+    Ok(ForgottenAccount {
+        session_revoked,
+        data,
+        emoji_images,
+    })
+}
+
+/// Add server and make it current one. URL is normalized and must point at
+/// running mattermost server, neither name nor URL may be used by other
+/// server already.
 #[tauri::command]
 pub async fn add_server(
     name: &str,
     url: &str,
     state_mutex: State<'_, Mutex<ServerState>>,
-) -> Result<Vec<Server>, ()> {
-    let current = match Url::parse(url) {
-        Ok(url) => Server {
-            name: name.to_owned(),
-            url,
-        },
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Vec<Server>, Error> {
+    let target = name.to_string();
+    let result = run_add_server(name, url, state_mutex, http_client, storage).await;
+    audit::record("add_server", Some(&target), &result);
+    result
+}
+
+async fn run_add_server(
+    name: &str,
+    url: &str,
+    state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Vec<Server>, Error> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(NativeError::InvalidServerName)?;
+    }
+    let url = servers::normalize_url(url)?;
+    {
+        let state = state_mutex.lock().await;
+        if state
+            .servers
+            .iter()
+            .any(|server| server.name == name || server.url == url)
+        {
+            return Err(NativeError::DuplicateServer)?;
+        }
+    }
+    handle_request(&http_client, &url, &ApiEvent::Ping, None).await?;
+    // Features are only informative here, server is added even without them
+    let capabilities = match handle_request(&http_client, &url, &ApiEvent::ClientConfig, None).await
+    {
+        Ok(Response::ClientConfig(config)) => Some(capabilities::from_client_config(&config)),
+        Ok(_) => None,
         Err(e) => {
-            tracing::warn!("Invalid url {url:?}: {e}");
-            return Err(());
+            tracing::warn!("Failed to probe {url}: {e}");
+            None
         }
     };
+    let current = Server {
+        name: name.to_owned(),
+        url,
+        capabilities,
+    };
+
     let mut state = state_mutex.lock().await;
+    // Checked again since lock was released while server was pinged
+    if state
+        .servers
+        .iter()
+        .any(|server| server.name == current.name || server.url == current.url)
+    {
+        return Err(NativeError::DuplicateServer)?;
+    }
     state.current = Some(current.clone());
     state.servers.push(current.clone());
     tracing::info!("{:?}", state.current);
     tracing::info!("{:?}", state.servers);
+    store_servers(&storage, &state.servers).await?;
+    Ok(state.servers.clone())
+}
+
+/// Remove server from the list together with its stored session, otherwise
+/// it would be added back by bootstrap on next start. When it was current
+/// server the one following it becomes current, `None` once list is empty.
+#[tauri::command]
+pub async fn remove_server(
+    server_name: &str,
+    app: tauri::AppHandle,
+    state_mutex: State<'_, Mutex<ServerState>>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    storage: State<'_, StorageHandle>,
+    sessions: State<'_, Sessions>,
+) -> Result<Vec<Server>, Error> {
+    let target = server_name.to_string();
+    let result = run_remove_server(
+        server_name,
+        app,
+        state_mutex,
+        user_state_mutex,
+        storage,
+        sessions,
+    )
+    .await;
+    audit::record("remove_server", Some(&target), &result);
+    result
+}
+
+async fn run_remove_server(
+    server_name: &str,
+    app: tauri::AppHandle,
+    state_mutex: State<'_, Mutex<ServerState>>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    storage: State<'_, StorageHandle>,
+    sessions: State<'_, Sessions>,
+) -> Result<Vec<Server>, Error> {
+    let mut state = state_mutex.lock().await;
+    let (removed, was_current) = state.remove(server_name)?;
+    if was_current {
+        let mut user_state = user_state_mutex.lock().await;
+        *user_state = UserState::default();
+        if let Some(current) = &state.current {
+            if let Some(session) = sessions.get(&current.url).await {
+                session.apply(&mut user_state);
+            }
+        }
+    }
+    sessions.remove(&removed.url).await;
+    unread::remove(&app, &removed.url.clone().into());
+    store_servers(&storage, &state.servers).await?;
+    remove_credentials(&storage, removed.url.clone().into()).await?;
+    tracing::info!("Removed server {:?}", removed);
+    Ok(state.servers.clone())
+}
+
+#[tauri::command]
+pub async fn rename_server(
+    server_name: &str,
+    new_name: &str,
+    state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Vec<Server>, Error> {
+    let target = server_name.to_string();
+    let result = run_rename_server(server_name, new_name, state_mutex, storage).await;
+    audit::record("rename_server", Some(&target), &result);
+    result
+}
+
+async fn run_rename_server(
+    server_name: &str,
+    new_name: &str,
+    state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Vec<Server>, Error> {
+    let mut state = state_mutex.lock().await;
+    state.rename(server_name, new_name)?;
+    store_servers(&storage, &state.servers).await?;
     Ok(state.servers.clone())
 }
 
+/// Check that server responds and read its version and enabled features,
+/// they are kept with server so other commands can adapt to it
+#[tauri::command]
+pub async fn probe_server(
+    server_name: &str,
+    state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<ServerCapabilities, Error> {
+    let url = {
+        let state = state_mutex.lock().await;
+        state
+            .servers
+            .iter()
+            .find(|server| server.name == server_name)
+            .ok_or(NativeError::UnknownServer)?
+            .url
+            .clone()
+    };
+    handle_request(&http_client, &url, &ApiEvent::Ping, None).await?;
+    let Response::ClientConfig(config) =
+        handle_request(&http_client, &url, &ApiEvent::ClientConfig, None).await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let probed = capabilities::from_client_config(&config);
+    tracing::info!("Server {url} runs version {:?}", probed.version);
+
+    let state = &mut *state_mutex.lock().await;
+    let servers = state.servers.iter_mut().chain(state.current.as_mut());
+    for server in servers.filter(|server| server.url == url) {
+        server.capabilities = Some(probed.clone());
+    }
+    Ok(probed)
+}
+
 #[derive(Debug, serde::Serialize, Clone)]
 pub struct ChangeServerOutput {
     pub current: Server,
     pub list: Vec<Server>,
 }
 
+/// Switch current server, session bootstrapped at startup is used right
+/// away when there is one for it
 #[tauri::command]
 pub async fn change_server(
     server_name: &str,
     state_mutex: State<'_, Mutex<ServerState>>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    sessions: State<'_, Sessions>,
 ) -> Result<ChangeServerOutput, Error> {
     let mut state = state_mutex.lock().await;
     let Some(current) = state
@@ -179,6 +832,9 @@ pub async fn change_server(
     else {
         return Err(NativeError::UnknownServer)?;
     };
+    if let Some(session) = sessions.get(&current.url).await {
+        session.apply(&mut *user_state_mutex.lock().await);
+    }
     state.current = Some(current.clone());
     tracing::info!("{:?}", current);
     tracing::info!("{:?}", state.servers);
@@ -196,7 +852,7 @@ pub async fn get_current_server(
     let current = state
         .current
         .as_ref()
-        .ok_or_else(|| NativeError::ServerNotSelected)?
+        .ok_or(NativeError::ServerNotSelected)?
         .to_owned();
     tracing::debug!("Current selected server {:?}", current);
     Ok(current)
@@ -225,7 +881,7 @@ pub async fn post_threads(
     let server_url = state
         .current
         .as_ref()
-        .ok_or_else(|| NativeError::ServerNotSelected)?
+        .ok_or(NativeError::ServerNotSelected)?
         .url
         .to_owned();
     let v = handle_request(
@@ -241,31 +897,4687 @@ pub async fn post_threads(
     Ok(v)
 }
 
+/// Bring thread received earlier up to date by fetching only replies newer
+/// than the ones it already contains. Falls back to fetching whole thread
+/// when replies can't be merged.
 #[tauri::command]
-pub async fn channel_posts(
-    channel_id: ChannelId,
+pub async fn thread_updates(
+    post_id: PostId,
+    mut thread: PostThread,
     user_state_mutex: State<'_, Mutex<UserState>>,
     server_state_mutex: State<'_, Mutex<ServerState>>,
     http_client: State<'_, Client>,
 ) -> Result<PostThread, Error> {
-    let state = server_state_mutex.lock().await;
     let token = user_state_mutex.lock().await.token.clone();
-    let client = &*http_client;
-    let server_url = state
-        .current
-        .as_ref()
-        .ok_or_else(|| NativeError::ServerNotSelected)?
-        .url
-        .to_owned();
-    let v = handle_request(
-        client,
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let event = match threads::newest_reply(&thread) {
+        Some(newest) => ApiEvent::ThreadReplies {
+            post_id: post_id.clone(),
+            after: newest.id.clone(),
+            after_create_at: newest.create_at,
+        },
+        None => ApiEvent::PostThreads(post_id.clone()),
+    };
+    let Response::ChannelThreads(page) =
+        handle_request(&http_client, &server_url, &event, token.as_ref()).await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    if matches!(event, ApiEvent::PostThreads(_)) {
+        return Ok(page);
+    }
+    if threads::merge_replies(&mut thread, page) {
+        return Ok(thread);
+    }
+    tracing::debug!("Replies of {post_id} don't continue known thread, fetching whole");
+    let Response::ChannelThreads(thread) = handle_request(
+        &http_client,
         &server_url,
-        &ApiEvent::ChannelPosts(channel_id),
+        &ApiEvent::PostThreads(post_id),
         token.as_ref(),
     )
-    .await?;
-    let Response::ChannelPosts(v) = v else {
-        return Err(Error::Native(NativeError::UnexpectedResponse));
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
     };
-    Ok(v)
+    Ok(thread)
+}
+
+/// Page of channel posts, newest first. Page size follows post density
+/// setting, `page` is counted from 0.
+///
+/// First page replaces cached posts of channel, older pages are appended to
+/// them up to the number of posts retained by density.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn channel_posts(
+    channel_id: ChannelId,
+    page: Option<u32>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    density: State<'_, RwLock<PostDensity>>,
+    settings: State<'_, SettingsState>,
+) -> Result<PostThread, Error> {
+    fetch_channel_page(
+        channel_id,
+        page.unwrap_or_default(),
+        &user_state_mutex,
+        &server_state_mutex,
+        &http_client,
+        &storage,
+        *density.read().await,
+        settings.get().memory,
+    )
+    .await
+}
+
+/// Same as [`channel_posts`] but page is sent as `channel-posts-chunk`
+/// events of at most `chunk_size` posts followed by `channel-posts-done`,
+/// so large pages don't block webview with single huge payload. Returns
+/// the same summary as the last event.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn stream_channel_posts(
+    channel_id: ChannelId,
+    page: Option<u32>,
+    chunk_size: Option<usize>,
+    app: tauri::AppHandle,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    density: State<'_, RwLock<PostDensity>>,
+    settings: State<'_, SettingsState>,
+) -> Result<PostsDone, Error> {
+    let page = page.unwrap_or_default();
+    let thread = fetch_channel_page(
+        channel_id.clone(),
+        page,
+        &user_state_mutex,
+        &server_state_mutex,
+        &http_client,
+        &storage,
+        *density.read().await,
+        settings.get().memory,
+    )
+    .await?;
+    let (chunks, done) = post_stream::split(
+        &channel_id,
+        page,
+        thread,
+        chunk_size.unwrap_or(post_stream::DEFAULT_CHUNK_SIZE),
+    );
+    for chunk in chunks {
+        app.emit_all(post_stream::CHANNEL_POSTS_CHUNK_EVENT, chunk)
+            .ok();
+        // Gives webview a chance to render before next chunk arrives
+        tokio::task::yield_now().await;
+    }
+    app.emit_all(post_stream::CHANNEL_POSTS_DONE_EVENT, done.clone())
+        .ok();
+    Ok(done)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn fetch_channel_page(
+    channel_id: ChannelId,
+    page: u32,
+    user_state_mutex: &Mutex<UserState>,
+    server_state_mutex: &Mutex<ServerState>,
+    client: &Client,
+    storage: &StorageHandle,
+    density: PostDensity,
+    memory: MemorySettings,
+) -> Result<PostThread, Error> {
+    let ticket = user_state_mutex
+        .lock()
+        .await
+        .fetches
+        .begin(channel_page_key(&channel_id, page));
+    let result = ticket
+        .run(request_channel_page(
+            &channel_id,
+            page,
+            user_state_mutex,
+            server_state_mutex,
+            client,
+            density,
+        ))
+        .await;
+    let latest = user_state_mutex.lock().await.fetches.finish(&ticket);
+    let (server_url, posts) = result?;
+    if !latest {
+        return Err(NativeError::Superseded)?;
+    }
+    let server: ServerUrl = server_url.into();
+    if page == 0 {
+        remember_posts(
+            user_state_mutex,
+            storage,
+            &server,
+            &channel_id,
+            &posts,
+            memory,
+            density,
+        )
+        .await;
+    }
+    cache_channel_page(storage, server, channel_id, page, posts.clone(), density);
+    Ok(posts)
+}
+
+/// Keep newest posts of channel in memory, posts pushed out of it are moved
+/// to disk cache
+async fn remember_posts(
+    user_state_mutex: &Mutex<UserState>,
+    storage: &StorageHandle,
+    server: &ServerUrl,
+    channel_id: &ChannelId,
+    posts: &PostThread,
+    memory: MemorySettings,
+    density: PostDensity,
+) {
+    let mut evicted = user_state_mutex.lock().await.posts.insert(
+        server,
+        channel_id,
+        posts.posts.values().cloned(),
+        memory,
+    );
+    // Posts of this channel are being cached together with the rest of them
+    evicted.retain(|evicted| &evicted.channel_id != channel_id || &evicted.server != server);
+    post_store::persist(storage, evicted, density.retained_posts());
+}
+
+/// Fetch of channel page is superseded by newer fetch of the same page
+fn channel_page_key(channel_id: &ChannelId, page: u32) -> String {
+    format!("posts/{channel_id}/{page}")
+}
+
+async fn request_channel_page(
+    channel_id: &ChannelId,
+    page: u32,
+    user_state_mutex: &Mutex<UserState>,
+    server_state_mutex: &Mutex<ServerState>,
+    client: &Client,
+    density: PostDensity,
+) -> Result<(Url, PostThread), Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(server_state_mutex).await?;
+    let v = handle_request(
+        client,
+        &server_url,
+        &ApiEvent::ChannelPosts {
+            channel_id: channel_id.clone(),
+            page,
+            per_page: density.per_page(),
+        },
+        token.as_ref(),
+    )
+    .await?;
+    let Response::ChannelPosts(v) = v else {
+        return Err(Error::Native(NativeError::UnexpectedResponse));
+    };
+    Ok((server_url, v))
+}
+
+/// Fetch posts of channel changed since they were cached on disk, or first
+/// page when there's nothing cached to merge them into
+async fn request_channel_refresh(
+    channel_id: &ChannelId,
+    user_state_mutex: &Mutex<UserState>,
+    server_state_mutex: &Mutex<ServerState>,
+    client: &Client,
+    storage: &StorageHandle,
+    density: PostDensity,
+) -> Result<(Url, ChannelRefresh), Error> {
+    let server: ServerUrl = current_server_url(server_state_mutex).await?.into();
+    let (cached, since) = {
+        let channel_id = channel_id.clone();
+        storage
+            .run(move |storage| {
+                Ok::<_, crate::errors::StorageError>((
+                    storage.cached_posts(&server, &channel_id)?,
+                    storage.channel_watermark(&server, &channel_id)?,
+                ))
+            })
+            .await?
+    };
+    let (Some(cached), Some(since)) = (cached, since) else {
+        let (server_url, posts) = request_channel_page(
+            channel_id,
+            0,
+            user_state_mutex,
+            server_state_mutex,
+            client,
+            density,
+        )
+        .await?;
+        return Ok((server_url, ChannelRefresh::Page(posts)));
+    };
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(server_state_mutex).await?;
+    let Response::ChannelPosts(changes) = handle_request(
+        client,
+        &server_url,
+        &ApiEvent::ChannelPostsSince {
+            channel_id: channel_id.clone(),
+            since,
+        },
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let watermark = threads::watermark(&changes).map_or(since, |changed| changed.max(since));
+    let mut posts = cached;
+    threads::merge_since(&mut posts, changes, density.retained_posts());
+    Ok((server_url, ChannelRefresh::Merged { posts, watermark }))
+}
+
+/// Posts of channel refreshed on switching to it
+enum ChannelRefresh {
+    /// Newest page, there were no cached posts to merge into
+    Page(PostThread),
+    /// Cached posts with changes since `watermark` merged in
+    Merged {
+        posts: PostThread,
+        watermark: Timestamp,
+    },
+}
+
+/// First page replaces cached posts, older ones are appended to them
+fn cache_channel_page(
+    storage: &StorageHandle,
+    server: ServerUrl,
+    channel_id: ChannelId,
+    page: u32,
+    posts: PostThread,
+    density: PostDensity,
+) {
+    storage.spawn(move |storage| {
+        let cached = if page == 0 {
+            let mut posts = posts;
+            threads::retain_newest(&mut posts, density.retained_posts());
+            match threads::watermark(&posts) {
+                Some(since) => storage
+                    .store_channel_watermark(&server, &channel_id, since)
+                    .map(|_| posts),
+                None => Ok(posts),
+            }
+        } else {
+            storage.cached_posts(&server, &channel_id).map(|cached| {
+                let mut cached = cached.unwrap_or_default();
+                threads::append_older(&mut cached, posts, density.retained_posts());
+                cached
+            })
+        };
+        if let Err(e) =
+            cached.and_then(|cached| storage.store_cached_posts(&server, &channel_id, &cached))
+        {
+            tracing::warn!("Failed to cache posts of channel {channel_id}: {e}");
+        }
+    });
+}
+
+/// Replace cached posts of channel with ones merged by
+/// [`threads::merge_since`] and move its watermark
+fn cache_merged_posts(
+    storage: &StorageHandle,
+    server: ServerUrl,
+    channel_id: ChannelId,
+    posts: PostThread,
+    watermark: Timestamp,
+) {
+    storage.spawn(move |storage| {
+        let stored = storage
+            .store_cached_posts(&server, &channel_id, &posts)
+            .and_then(|_| storage.store_channel_watermark(&server, &channel_id, watermark));
+        if let Err(e) = stored {
+            tracing::warn!("Failed to cache posts of channel {channel_id}: {e}");
+        }
+    });
+}
+
+/// Rendering hint of post with custom type added by plugin, `None` for
+/// posts frontend renders itself
+#[tauri::command]
+pub fn post_render_hint(post_type: String, props: serde_json::Value) -> Option<RenderHint> {
+    post_types::hint(&post_type, &props)
+}
+
+/// Switch to channel without waiting for server. Cached posts are returned
+/// right away while channel is refreshed in background and sent as
+/// `timeline-refreshed` event. Only posts changed since the cache was last
+/// synced are fetched and merged into it, so frontend keeps its scroll
+/// position, first page is fetched when nothing is cached yet. Refresh
+/// overtaken by newer fetch of the same channel is aborted and no event is
+/// sent for it.
+#[tauri::command]
+pub async fn switch_channel_timeline(
+    channel_id: ChannelId,
+    app: tauri::AppHandle,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<TimelinePreview, Error> {
+    let ticket = {
+        let mut user_state = user_state_mutex.lock().await;
+        user_state.open_channel(&channel_id);
+        user_state.fetches.begin(channel_page_key(&channel_id, 0))
+    };
+    let generation = ticket.generation;
+    let server: ServerUrl = current_server_url(&server_state_mutex).await?.into();
+    let remembered = user_state_mutex
+        .lock()
+        .await
+        .posts
+        .get(&server, &channel_id);
+    let posts = match remembered {
+        Some(posts) => Some(posts),
+        None => {
+            let channel_id = channel_id.clone();
+            storage
+                .run(move |storage| storage.cached_posts(&server, &channel_id))
+                .await?
+        }
+    };
+
+    let refreshed_channel = channel_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let channel_id = refreshed_channel;
+        let density = *app.state::<RwLock<PostDensity>>().read().await;
+        let user_state_mutex = app.state::<Mutex<UserState>>();
+        let storage = app.state::<StorageHandle>();
+        let result = ticket
+            .run(request_channel_refresh(
+                &channel_id,
+                &user_state_mutex,
+                &app.state::<Mutex<ServerState>>(),
+                &app.state::<Client>(),
+                &storage,
+                density,
+            ))
+            .await;
+        if !user_state_mutex.lock().await.fetches.finish(&ticket) {
+            tracing::debug!("Dropping outdated refresh {generation} of channel {channel_id}");
+            return;
+        }
+        let refreshed = match result {
+            Ok((server_url, refresh)) => {
+                let server: ServerUrl = server_url.into();
+                let memory = app.state::<SettingsState>().get().memory;
+                let posts = match &refresh {
+                    ChannelRefresh::Page(posts) | ChannelRefresh::Merged { posts, .. } => posts,
+                };
+                remember_posts(
+                    &user_state_mutex,
+                    &storage,
+                    &server,
+                    &channel_id,
+                    posts,
+                    memory,
+                    density,
+                )
+                .await;
+                let posts = match refresh {
+                    ChannelRefresh::Page(posts) => {
+                        cache_channel_page(
+                            &storage,
+                            server,
+                            channel_id.clone(),
+                            0,
+                            posts.clone(),
+                            density,
+                        );
+                        posts
+                    }
+                    ChannelRefresh::Merged { posts, watermark } => {
+                        cache_merged_posts(
+                            &storage,
+                            server,
+                            channel_id.clone(),
+                            posts.clone(),
+                            watermark,
+                        );
+                        posts
+                    }
+                };
+                TimelineRefreshed {
+                    channel_id,
+                    generation,
+                    posts: Some(posts),
+                    error: None,
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Failed to refresh channel {channel_id}: {e}");
+                TimelineRefreshed {
+                    channel_id,
+                    generation,
+                    posts: None,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+        app.emit_all(TIMELINE_REFRESHED_EVENT, refreshed).ok();
+    });
+
+    Ok(TimelinePreview {
+        channel_id,
+        generation,
+        stale: posts.is_some(),
+        posts,
+    })
+}
+
+/// Fetch channels and send only their changes since last sync to frontend
+/// as `state-patch` event of `channels` topic
+#[tauri::command]
+pub async fn sync_channels(
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    user_cache: State<'_, UserCache>,
+    snapshots: State<'_, Snapshots>,
+    app: tauri::AppHandle,
+) -> Result<(), Error> {
+    let channels = my_channels(
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+        user_cache,
+    )
+    .await?;
+    snapshots.publish(&app, "channels", &channels).await
+}
+
+/// Fetch newest posts of channel like [`channel_posts`] does for first page
+/// and send their changes as `state-patch` event of `posts/{channel_id}`
+/// topic
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn sync_channel_posts(
+    channel_id: ChannelId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    density: State<'_, RwLock<PostDensity>>,
+    settings: State<'_, SettingsState>,
+    snapshots: State<'_, Snapshots>,
+    app: tauri::AppHandle,
+) -> Result<(), Error> {
+    let topic = format!("posts/{channel_id}");
+    let posts = channel_posts(
+        channel_id,
+        None,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+        storage,
+        density,
+        settings,
+    )
+    .await?;
+    snapshots.publish(&app, &topic, &posts).await
+}
+
+/// Next sync of `topic`, or of every topic when not given, sends whole
+/// document instead of changes
+#[tauri::command]
+pub async fn reset_state_patches(
+    topic: Option<String>,
+    snapshots: State<'_, Snapshots>,
+) -> Result<(), Error> {
+    snapshots.reset(topic.as_deref()).await;
+    Ok(())
+}
+
+#[derive(Debug, serde::Serialize, Clone)]
+pub struct PostDensityInfo {
+    pub density: PostDensity,
+    pub per_page: u32,
+    /// Frontend should drop older posts of channel beyond this number
+    pub retained_posts: usize,
+}
+
+impl From<PostDensity> for PostDensityInfo {
+    fn from(density: PostDensity) -> Self {
+        Self {
+            density,
+            per_page: density.per_page(),
+            retained_posts: density.retained_posts(),
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn post_density(
+    density: State<'_, RwLock<PostDensity>>,
+) -> Result<PostDensityInfo, Error> {
+    Ok((*density.read().await).into())
+}
+
+/// Change how many posts are fetched per page and retained per channel,
+/// applies to subsequent fetches
+#[tauri::command]
+pub async fn set_post_density(
+    value: PostDensity,
+    density: State<'_, RwLock<PostDensity>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<PostDensityInfo, Error> {
+    let result = run_set_post_density(value, density, storage).await;
+    audit::record("set_post_density", None, &result);
+    result
+}
+
+async fn run_set_post_density(
+    value: PostDensity,
+    density: State<'_, RwLock<PostDensity>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<PostDensityInfo, Error> {
+    storage
+        .run(move |storage| storage.set_post_density(value))
+        .await?;
+    *density.write().await = value;
+    Ok(value.into())
+}
+
+/// Maximum page size of custom emoji list accepted by server
+const EMOJI_PER_PAGE: u32 = 200;
+
+/// Emoji returned by search unless caller asks for other amount
+const EMOJI_MATCHES: usize = 100;
+
+/// Default page size of server
+const MEMBERS_PER_PAGE: u32 = 60;
+/// Items collected by commands returning whole lists, unless caller asks
+/// for fewer or more
+const LIST_CAP: usize = 1000;
+const MAX_LIST_CAP: usize = 10_000;
+
+/// Page of channel members, `page` is counted from 0. Fewer members than
+/// `per_page` means there are no more pages.
+#[tauri::command]
+pub async fn channel_members(
+    channel_id: ChannelId,
+    page: Option<u32>,
+    per_page: Option<u32>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Vec<ChannelMember>, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::ChannelMembers(members) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::ChannelMembers {
+            channel_id,
+            page: page.unwrap_or_default(),
+            per_page: per_page
+                .unwrap_or(MEMBERS_PER_PAGE)
+                .clamp(1, paging::MAX_PER_PAGE),
+        },
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(members)
+}
+
+/// Members of channel from all pages, at most `limit` of them
+#[tauri::command]
+pub async fn all_channel_members(
+    channel_id: ChannelId,
+    limit: Option<usize>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Paged<ChannelMember>, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let cap = limit.unwrap_or(LIST_CAP).clamp(1, MAX_LIST_CAP);
+    fetch_all_pages(paging::MAX_PER_PAGE, cap, |page, per_page| {
+        let event = ApiEvent::ChannelMembers {
+            channel_id: channel_id.clone(),
+            page,
+            per_page,
+        };
+        let (http_client, server_url, token) = (&http_client, &server_url, token.as_ref());
+        async move {
+            match handle_request(http_client, server_url, &event, token).await? {
+                Response::ChannelMembers(members) => Ok(members),
+                _ => Err(NativeError::UnexpectedResponse)?,
+            }
+        }
+    })
+    .await
+}
+
+/// Active users of server, or only members of `in_channel`, at most
+/// `limit` of them
+#[tauri::command]
+pub async fn list_users(
+    in_channel: Option<ChannelId>,
+    limit: Option<usize>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Paged<UserResponse>, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let cap = limit.unwrap_or(LIST_CAP).clamp(1, MAX_LIST_CAP);
+    fetch_all_pages(paging::MAX_PER_PAGE, cap, |page, per_page| {
+        let event = ApiEvent::Users {
+            in_channel: in_channel.clone(),
+            page,
+            per_page,
+        };
+        let (http_client, server_url, token) = (&http_client, &server_url, token.as_ref());
+        async move {
+            match handle_request(http_client, server_url, &event, token).await? {
+                Response::Users(users) => Ok(users),
+                _ => Err(NativeError::UnexpectedResponse)?,
+            }
+        }
+    })
+    .await
+}
+
+/// Teams user can see on server, including ones they may join
+#[tauri::command]
+pub async fn list_teams(
+    limit: Option<usize>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Paged<Team>, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let cap = limit.unwrap_or(LIST_CAP).clamp(1, MAX_LIST_CAP);
+    fetch_all_pages(paging::MAX_PER_PAGE, cap, |page, per_page| {
+        let event = ApiEvent::Teams { page, per_page };
+        let (http_client, server_url, token) = (&http_client, &server_url, token.as_ref());
+        async move {
+            match handle_request(http_client, server_url, &event, token).await? {
+                Response::Teams(teams) => Ok(teams),
+                _ => Err(NativeError::UnexpectedResponse)?,
+            }
+        }
+    })
+    .await
+}
+
+/// Member count and other statistics of channel
+#[tauri::command]
+pub async fn channel_stats(
+    channel_id: ChannelId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<ChannelStats, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::ChannelStats(stats) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::ChannelStats(channel_id),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(stats)
+}
+
+/// Change display name, header or purpose of channel. Fails with
+/// `permission_denied` when user isn't allowed to, e.g. isn't admin of
+/// private channel.
+#[tauri::command]
+pub async fn update_channel(
+    channel_id: ChannelId,
+    patch: ChannelPatch,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Channel, Error> {
+    let target = channel_id.to_string();
+    let result = run_update_channel(
+        channel_id,
+        patch,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+    )
+    .await;
+    audit::record("update_channel", Some(&target), &result);
+    result
+}
+
+async fn run_update_channel(
+    channel_id: ChannelId,
+    patch: ChannelPatch,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Channel, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::Channel(channel) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::PatchChannel(channel_id, patch),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    user_state_mutex.lock().await.store_channel(channel.clone());
+    Ok(channel)
+}
+
+/// Change notification props of channel for logged in user, muting is
+/// `mark_unread` set to `mention`
+#[tauri::command]
+pub async fn update_channel_notify_props(
+    channel_id: ChannelId,
+    patch: NotifyPropsPatch,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<NotifyProps, Error> {
+    let target = channel_id.to_string();
+    let result = run_update_channel_notify_props(
+        channel_id,
+        patch,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+    )
+    .await;
+    audit::record("update_channel_notify_props", Some(&target), &result);
+    result
+}
+
+async fn run_update_channel_notify_props(
+    channel_id: ChannelId,
+    patch: NotifyPropsPatch,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<NotifyProps, Error> {
+    let (token, user_id) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (user_state.token.clone(), user_id)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::NotifyPropsUpdated = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::UpdateNotifyProps {
+            channel_id: channel_id.clone(),
+            user_id,
+            patch: patch.clone(),
+        },
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let mut user_state = user_state_mutex.lock().await;
+    user_state.apply_notify_props(&channel_id, &patch);
+    Ok(user_state.notify_props(&channel_id))
+}
+
+#[tauri::command]
+pub async fn set_channel_muted(
+    channel_id: ChannelId,
+    muted: bool,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<NotifyProps, Error> {
+    update_channel_notify_props(
+        channel_id,
+        NotifyPropsPatch::muted(muted),
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+    )
+    .await
+}
+
+/// Whether desktop notification should be shown for new message in channel,
+/// taking into account notification settings, do not disturb and notify
+/// props of user's channel membership
+#[tauri::command]
+pub async fn should_notify(
+    channel_id: ChannelId,
+    mentioned: bool,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    settings: State<'_, SettingsState>,
+    dnd_manager: State<'_, DndManager>,
+) -> Result<bool, Error> {
+    if !settings.get().notifications.enabled || dnd_manager.suppresses_notifications(now_millis()) {
+        return Ok(false);
+    }
+    let props = user_state_mutex.lock().await.notify_props(&channel_id);
+    Ok(props.notifies_desktop(mentioned))
+}
+
+/// Show desktop notification of message in channel of current server.
+/// Clicking it focuses window and sends
+/// [`NOTIFICATION_CLICKED_EVENT`](notifications::NOTIFICATION_CLICKED_EVENT)
+/// to open the conversation.
+#[tauri::command]
+pub async fn show_notification(
+    title: String,
+    body: String,
+    channel_id: ChannelId,
+    post_id: Option<PostId>,
+    app: tauri::AppHandle,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+) -> Result<(), Error> {
+    let server = current_server_url(&server_state_mutex).await?;
+    let team_id = user_state_mutex
+        .lock()
+        .await
+        .channels
+        .iter()
+        .flatten()
+        .find(|channel| channel.id.as_ref() == Some(&channel_id))
+        .and_then(|channel| channel.team_id.clone())
+        .filter(|team_id| !team_id.is_empty())
+        .map(TeamId::new);
+    let target = NavigationTarget {
+        server: server.into(),
+        team_id,
+        channel_id,
+        post_id,
+    };
+    notifications::show(&app, title, body, target);
+    Ok(())
+}
+
+/// Create public or private channel in team and join it. URL name is derived
+/// from display name unless given.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_channel(
+    team_id: TeamId,
+    display_name: String,
+    private: bool,
+    name: Option<String>,
+    purpose: Option<String>,
+    header: Option<String>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Channel, Error> {
+    let result = run_create_channel(
+        team_id,
+        display_name,
+        private,
+        name,
+        purpose,
+        header,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+    )
+    .await;
+    let target = result
+        .as_ref()
+        .ok()
+        .and_then(|channel| channel.id.as_ref())
+        .map(ToString::to_string);
+    audit::record("create_channel", target.as_deref(), &result);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_create_channel(
+    team_id: TeamId,
+    display_name: String,
+    private: bool,
+    name: Option<String>,
+    purpose: Option<String>,
+    header: Option<String>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Channel, Error> {
+    let display_name = display_name.trim();
+    if display_name.is_empty() {
+        return Err(NativeError::InvalidChannelName)?;
+    }
+    let name = name
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| channels::slug(display_name));
+    let non_empty = |text: Option<String>| text.filter(|text| !text.trim().is_empty());
+    let request = CreateChannelRequest {
+        team_id,
+        name: name.into(),
+        display_name: display_name.to_owned().into(),
+        r#type: if private { "P" } else { "O" }.to_owned().into(),
+        purpose: non_empty(purpose).map(Into::into),
+        header: non_empty(header).map(Into::into),
+    };
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::Channel(channel) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::CreateChannel(request),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    user_state_mutex.lock().await.store_channel(channel.clone());
+    // Channel list also tells which channels user is member of
+    match handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::MyChannels,
+        token.as_ref(),
+    )
+    .await
+    {
+        Ok(Response::MyChannels(channels)) => {
+            user_state_mutex.lock().await.channels = Some(channels);
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Failed to refresh channels after creating one: {e}"),
+    }
+    Ok(channel)
+}
+
+/// Add user to channel, adding oneself joins the channel
+#[tauri::command]
+pub async fn add_channel_member(
+    channel_id: ChannelId,
+    user_id: UserId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<ChannelMember, Error> {
+    let target = format!("{channel_id}/{user_id}");
+    let result = run_add_channel_member(
+        channel_id,
+        user_id,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+    )
+    .await;
+    audit::record("add_channel_member", Some(&target), &result);
+    result
+}
+
+async fn run_add_channel_member(
+    channel_id: ChannelId,
+    user_id: UserId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<ChannelMember, Error> {
+    let (token, me) = {
+        let user_state = user_state_mutex.lock().await;
+        (user_state.token.clone(), user_state.id.clone())
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::ChannelMember(member) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::AddChannelMember {
+            channel_id: channel_id.clone(),
+            user_id: user_id.clone(),
+        },
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    if me.as_ref() == Some(&user_id) {
+        let Response::Channel(channel) = handle_request(
+            &http_client,
+            &server_url,
+            &ApiEvent::Channel(channel_id),
+            token.as_ref(),
+        )
+        .await?
+        else {
+            return Err(NativeError::UnexpectedResponse)?;
+        };
+        let mut user_state = user_state_mutex.lock().await;
+        user_state.store_channel(channel);
+        user_state.store_channel_member(member.clone());
+    }
+    Ok(member)
+}
+
+/// Remove user from channel, removing oneself leaves the channel
+#[tauri::command]
+pub async fn remove_channel_member(
+    channel_id: ChannelId,
+    user_id: UserId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<(), Error> {
+    let target = format!("{channel_id}/{user_id}");
+    let result = remove_member(
+        channel_id,
+        user_id,
+        &user_state_mutex,
+        &server_state_mutex,
+        &http_client,
+    )
+    .await;
+    audit::record("remove_channel_member", Some(&target), &result);
+    result
+}
+
+#[tauri::command]
+pub async fn leave_channel(
+    channel_id: ChannelId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<(), Error> {
+    let target = channel_id.to_string();
+    let result = run_leave_channel(
+        channel_id,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+    )
+    .await;
+    audit::record("leave_channel", Some(&target), &result);
+    result
+}
+
+async fn run_leave_channel(
+    channel_id: ChannelId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<(), Error> {
+    let me = user_state_mutex
+        .lock()
+        .await
+        .id
+        .clone()
+        .ok_or(NativeError::NotLoggedIn)?;
+    remove_member(
+        channel_id,
+        me,
+        &user_state_mutex,
+        &server_state_mutex,
+        &http_client,
+    )
+    .await
+}
+
+async fn remove_member(
+    channel_id: ChannelId,
+    user_id: UserId,
+    user_state_mutex: &Mutex<UserState>,
+    server_state_mutex: &Mutex<ServerState>,
+    client: &Client,
+) -> Result<(), Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(server_state_mutex).await?;
+    let Response::ChannelMemberRemoved = handle_request(
+        client,
+        &server_url,
+        &ApiEvent::RemoveChannelMember {
+            channel_id: channel_id.clone(),
+            user_id: user_id.clone(),
+        },
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let mut user_state = user_state_mutex.lock().await;
+    if user_state.id.as_ref() == Some(&user_id) {
+        user_state.remove_channel(&channel_id);
+    }
+    Ok(())
+}
+
+/// Users matching `@mention` typed in composer, `name` may include the `@`.
+///
+/// Results are cached for a short time, so keystrokes narrowing the same
+/// mention are mostly served without asking server.
+#[tauri::command]
+pub async fn autocomplete_users(
+    channel_id: ChannelId,
+    name: String,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<UserAutocomplete, Error> {
+    let token = {
+        let user_state = user_state_mutex.lock().await;
+        let cached = user_state
+            .autocomplete
+            .lookup(&channel_id, &name, Instant::now());
+        if let Some(cached) = cached {
+            return Ok(cached);
+        }
+        user_state.token.clone()
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::UserAutocomplete(users) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::AutocompleteUsers {
+            channel_id: channel_id.clone(),
+            name: autocomplete::normalize(&name),
+            limit: autocomplete::AUTOCOMPLETE_LIMIT,
+        },
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    user_state_mutex.lock().await.autocomplete.insert(
+        channel_id,
+        &name,
+        Instant::now(),
+        users.clone(),
+    );
+    Ok(users)
+}
+
+/// Markdown digest of posts pinned in channel, with authors and dates in
+/// local time
+#[tauri::command]
+pub async fn export_pinned_digest(
+    channel_id: ChannelId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<String, Error> {
+    let (token, channel_name) = {
+        let user_state = user_state_mutex.lock().await;
+        let channel_name = user_state
+            .channels
+            .iter()
+            .flatten()
+            .find(|channel| channel.id.as_ref() == Some(&channel_id))
+            .and_then(|channel| channel.display_name.as_ref())
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| channel_id.to_string());
+        (user_state.token.clone(), channel_name)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::ChannelPosts(pinned) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::PinnedPosts(channel_id),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+
+    let authors = author_names(
+        &http_client,
+        &server_url,
+        token.as_ref(),
+        pinned.posts.values(),
+    )
+    .await?;
+    Ok(digest::pinned_digest(
+        &channel_name,
+        &pinned,
+        &authors,
+        &chrono::Local,
+    ))
+}
+
+/// Display names of authors of `posts` by user id
+async fn author_names(
+    client: &Client,
+    server_url: &Url,
+    token: Option<&AccessToken>,
+    posts: impl IntoIterator<Item = &Post>,
+) -> Result<HashMap<String, String>, Error> {
+    let mut author_ids: Vec<UserId> = posts
+        .into_iter()
+        .filter_map(|post| post.user_id.clone())
+        .collect();
+    author_ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+    author_ids.dedup();
+    if author_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let Response::Users(users) =
+        handle_request(client, server_url, &ApiEvent::UsersByIds(author_ids), token).await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(users
+        .iter()
+        .map(|user| (user.id.clone(), user.display_name().to_owned()))
+        .collect())
+}
+
+/// Thread of `post_id` as Markdown or HTML with author names and times
+/// resolved, for pasting into tickets or documentation
+#[tauri::command]
+pub async fn export_thread(
+    post_id: PostId,
+    format: ThreadExportFormat,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<String, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::ChannelThreads(thread) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::PostThreads(post_id),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let authors = author_names(
+        &http_client,
+        &server_url,
+        token.as_ref(),
+        thread.posts.values(),
+    )
+    .await?;
+    Ok(export::render_thread(
+        format,
+        &thread,
+        &authors,
+        &chrono::Local,
+    ))
+}
+
+/// Walk channel history newest first until `range` starts and return posts
+/// within it oldest first
+async fn channel_history(
+    client: &Client,
+    server_url: &Url,
+    token: Option<&AccessToken>,
+    channel_id: &ChannelId,
+    range: ExportRange,
+) -> Result<Vec<Post>, Error> {
+    let mut posts: Vec<Post> = Vec::new();
+    // Pages continue from oldest post seen, offsets would shift as posts
+    // arrive during export
+    let mut before: Option<PostId> = None;
+    loop {
+        let event = match before.take() {
+            Some(before) => ApiEvent::ChannelPostsBefore {
+                channel_id: channel_id.clone(),
+                before,
+                per_page: paging::MAX_PER_PAGE,
+            },
+            None => ApiEvent::ChannelPosts {
+                channel_id: channel_id.clone(),
+                page: 0,
+                per_page: paging::MAX_PER_PAGE,
+            },
+        };
+        let Response::ChannelPosts(mut thread) =
+            handle_request(client, server_url, &event, token).await?
+        else {
+            return Err(NativeError::UnexpectedResponse)?;
+        };
+        let last = thread.order.len() < paging::MAX_PER_PAGE as usize;
+        before = thread.order.last().cloned();
+        let mut reached_start = false;
+        for id in &thread.order {
+            let Some(post) = thread.posts.remove(id.as_str()) else {
+                continue;
+            };
+            reached_start |= range.is_before(post.create_at);
+            if range.contains(post.create_at) && post.delete_at == 0 {
+                posts.push(post);
+            }
+        }
+        if posts.len() >= MAX_EXPORT_POSTS {
+            tracing::warn!("Stopped export of channel {channel_id} after {MAX_EXPORT_POSTS} posts");
+            break;
+        }
+        if last || reached_start || before.is_none() {
+            break;
+        }
+    }
+    posts.sort_by(|a, b| (a.create_at, a.id.as_str()).cmp(&(b.create_at, b.id.as_str())));
+    posts.dedup_by(|a, b| a.id == b.id);
+    Ok(posts)
+}
+
+/// Save files attached to `posts` into `dir`, returns how many were saved
+/// and how many failed
+async fn download_attachments(
+    client: &Client,
+    server_url: &Url,
+    token: Option<&AccessToken>,
+    posts: &[Post],
+    dir: &std::path::Path,
+) -> Result<(usize, usize), Error> {
+    tokio::fs::create_dir_all(dir).await?;
+    let (mut saved, mut failed) = (0, 0);
+    for file in posts.iter().flat_map(export::attached_files) {
+        let event = ApiEvent::DownloadFile {
+            file_id: file.id.clone(),
+            path: dir.join(export::attachment_file_name(file)),
+        };
+        let written = match handle_request(client, server_url, &event, token).await {
+            Ok(Response::FileSaved(_)) => Ok(()),
+            Ok(_) => Err(NativeError::UnexpectedResponse.into()),
+            Err(e) => Err(e),
+        };
+        match written {
+            Ok(()) => saved += 1,
+            Err(e) => {
+                tracing::warn!("Failed to save attachment {}: {e}", file.id);
+                failed += 1;
+            }
+        }
+    }
+    Ok((saved, failed))
+}
+
+/// Download history of channel within `range`, all of it by default, and
+/// write it to `path` as JSON, CSV or plain text. Attached files are saved
+/// into `attachments_dir` when given.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_channel(
+    channel_id: ChannelId,
+    format: ChannelExportFormat,
+    range: Option<ExportRange>,
+    path: String,
+    attachments_dir: Option<String>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<ExportedChannel, Error> {
+    let target = channel_id.to_string();
+    let result = run_export_channel(
+        channel_id,
+        format,
+        range,
+        path,
+        attachments_dir,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+    )
+    .await;
+    audit::record("export_channel", Some(&target), &result);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_export_channel(
+    channel_id: ChannelId,
+    format: ChannelExportFormat,
+    range: Option<ExportRange>,
+    path: String,
+    attachments_dir: Option<String>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<ExportedChannel, Error> {
+    let (token, channel_name) = {
+        let user_state = user_state_mutex.lock().await;
+        let channel_name = user_state
+            .channels
+            .iter()
+            .flatten()
+            .find(|channel| channel.id.as_ref() == Some(&channel_id))
+            .and_then(|channel| channel.display_name.as_ref())
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| channel_id.to_string());
+        (user_state.token.clone(), channel_name)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let posts = channel_history(
+        &http_client,
+        &server_url,
+        token.as_ref(),
+        &channel_id,
+        range.unwrap_or_default(),
+    )
+    .await?;
+    let authors = author_names(&http_client, &server_url, token.as_ref(), &posts).await?;
+    let content = export::render_channel(format, &channel_name, &posts, &authors, &chrono::Local)?;
+    tokio::fs::write(&path, content).await?;
+    let (attachments, failed_attachments) = match &attachments_dir {
+        Some(dir) => {
+            download_attachments(
+                &http_client,
+                &server_url,
+                token.as_ref(),
+                &posts,
+                std::path::Path::new(dir),
+            )
+            .await?
+        }
+        None => (0, 0),
+    };
+    tracing::info!(
+        "Exported {} posts of channel {channel_id} to {path}",
+        posts.len()
+    );
+    Ok(ExportedChannel {
+        path,
+        posts: posts.len(),
+        attachments,
+        failed_attachments,
+    })
+}
+
+/// Posts pinned to channel, newest first
+#[tauri::command]
+pub async fn get_pinned_posts(
+    channel_id: ChannelId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<PostsPanel, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::ChannelPosts(pinned) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::PinnedPosts(channel_id),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(saved_posts::panel(pinned, false))
+}
+
+/// Pin post to its channel or unpin it, returns new pinned state
+#[tauri::command]
+pub async fn set_post_pinned(
+    post_id: PostId,
+    pinned: bool,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<bool, Error> {
+    let target = post_id.to_string();
+    let result = run_set_post_pinned(
+        post_id,
+        pinned,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+    )
+    .await;
+    audit::record("set_post_pinned", Some(&target), &result);
+    result
+}
+
+async fn run_set_post_pinned(
+    post_id: PostId,
+    pinned: bool,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<bool, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let event = if pinned {
+        ApiEvent::PinPost(post_id)
+    } else {
+        ApiEvent::UnpinPost(post_id)
+    };
+    let Response::Pinned(pinned) =
+        handle_request(&http_client, &server_url, &event, token.as_ref()).await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(pinned)
+}
+
+/// Default page size of saved posts
+const SAVED_POSTS_PER_PAGE: u32 = 60;
+
+/// Page of posts saved by user across all channels, `page` is counted from 0
+#[tauri::command]
+pub async fn get_saved_posts(
+    page: Option<u32>,
+    per_page: Option<u32>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<PostsPanel, Error> {
+    let (token, user_id) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (user_state.token.clone(), user_id)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let per_page = per_page.unwrap_or(SAVED_POSTS_PER_PAGE).clamp(1, 200);
+    let Response::ChannelPosts(saved) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::FlaggedPosts {
+            user_id,
+            page: page.unwrap_or_default(),
+            per_page,
+        },
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let has_more = saved.order.len() >= per_page as usize;
+    Ok(saved_posts::panel(saved, has_more))
+}
+
+/// Save post for later or remove it from saved posts, returns new saved
+/// state
+#[tauri::command]
+pub async fn set_post_saved(
+    post_id: PostId,
+    saved: bool,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<bool, Error> {
+    let target = post_id.to_string();
+    let result = run_set_post_saved(
+        post_id,
+        saved,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+    )
+    .await;
+    audit::record("set_post_saved", Some(&target), &result);
+    result
+}
+
+async fn run_set_post_saved(
+    post_id: PostId,
+    saved: bool,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<bool, Error> {
+    let (token, user_id) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (user_state.token.clone(), user_id)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let preferences = vec![saved_posts::flag(&user_id, &post_id)];
+    let event = if saved {
+        ApiEvent::SavePreferences {
+            user_id,
+            preferences,
+        }
+    } else {
+        ApiEvent::DeletePreferences {
+            user_id,
+            preferences,
+        }
+    };
+    let Response::Preferences(_) =
+        handle_request(&http_client, &server_url, &event, token.as_ref()).await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(saved)
+}
+
+/// Page of custom emoji defined on current server, sorted by name
+#[tauri::command]
+pub async fn list_custom_emoji(
+    page: Option<u32>,
+    per_page: Option<u32>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    emoji_cache: State<'_, EmojiCache>,
+) -> Result<Vec<MetaEmoji>, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::CustomEmoji(emojis) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::CustomEmoji {
+            page: page.unwrap_or_default(),
+            per_page: per_page.unwrap_or(EMOJI_PER_PAGE).clamp(1, EMOJI_PER_PAGE),
+        },
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    emoji_cache.remember_all(&server_url.into(), &emojis).await;
+    Ok(emojis)
+}
+
+/// Standard and custom emoji matching `query` for picker. Custom emoji of
+/// server are fetched on first search and again once cached list expires.
+#[tauri::command]
+pub async fn search_emoji(
+    query: String,
+    limit: Option<usize>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    emoji_cache: State<'_, EmojiCache>,
+) -> Result<Vec<EmojiMatch>, Error> {
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let server: ServerUrl = server_url.clone().into();
+    let custom = match emoji_cache.all(&server).await {
+        Some(custom) => custom,
+        None => {
+            let token = user_state_mutex.lock().await.token.clone();
+            let fetched = fetch_all_pages(EMOJI_PER_PAGE, MAX_LIST_CAP, |page, per_page| {
+                let event = ApiEvent::CustomEmoji { page, per_page };
+                let (http_client, server_url, token) = (&http_client, &server_url, token.as_ref());
+                async move {
+                    match handle_request(http_client, server_url, &event, token).await? {
+                        Response::CustomEmoji(emojis) => Ok(emojis),
+                        _ => Err(NativeError::UnexpectedResponse)?,
+                    }
+                }
+            })
+            .await;
+            // Servers with custom emoji turned off reject the list, search
+            // still covers standard emoji then
+            match fetched {
+                Ok(Paged { items, .. }) => {
+                    emoji_cache.store_all(&server, items.clone()).await;
+                    items
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to list custom emoji: {e}");
+                    emoji_cache.store_failed(&server).await;
+                    Vec::new()
+                }
+            }
+        }
+    };
+    Ok(emoji::search(
+        &query,
+        &custom,
+        limit.unwrap_or(EMOJI_MATCHES),
+    ))
+}
+
+/// Image of custom emoji as `data:` URL, downloaded once and then served
+/// from disk cache
+#[tauri::command]
+pub async fn get_custom_emoji_image(
+    emoji_id: EmojiId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    emoji_cache: State<'_, EmojiCache>,
+) -> Result<String, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let image = custom_emoji_image(
+        &http_client,
+        &server_url,
+        token.as_ref(),
+        &emoji_cache,
+        &emoji_id,
+    )
+    .await?;
+    Ok(emoji::data_url(&image))
+}
+
+/// Custom emoji used as `:shortcode:` in message with their images. Standard
+/// emoji are skipped, frontend renders them by itself.
+#[tauri::command]
+pub async fn resolve_message_emoji(
+    message: String,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    emoji_cache: State<'_, EmojiCache>,
+) -> Result<Vec<EmojiImage>, Error> {
+    let custom_emoji = current_capabilities(&server_state_mutex)
+        .await
+        .map_or(true, |capabilities| capabilities.custom_emoji);
+    if !custom_emoji {
+        return Ok(Vec::new());
+    }
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let server: ServerUrl = server_url.clone().into();
+    let mut resolved = Vec::new();
+    for name in emoji::shortcodes(&message) {
+        let emoji = match emoji_cache.lookup(&server, name).await {
+            Some(emoji) => emoji,
+            None => {
+                let Response::Emoji(emoji) = handle_request(
+                    &http_client,
+                    &server_url,
+                    &ApiEvent::CustomEmojiByName(EmojiName::new(name.to_owned())),
+                    token.as_ref(),
+                )
+                .await?
+                else {
+                    return Err(NativeError::UnexpectedResponse)?;
+                };
+                emoji_cache.remember(&server, name, emoji.clone()).await;
+                emoji
+            }
+        };
+        let Some(emoji) = emoji else {
+            continue;
+        };
+        let image = custom_emoji_image(
+            &http_client,
+            &server_url,
+            token.as_ref(),
+            &emoji_cache,
+            &emoji.id,
+        )
+        .await?;
+        resolved.push(EmojiImage {
+            id: emoji.id,
+            name: emoji.name,
+            image: emoji::data_url(&image),
+        });
+    }
+    Ok(resolved)
+}
+
+async fn custom_emoji_image(
+    http_client: &Client,
+    server_url: &Url,
+    token: Option<&AccessToken>,
+    emoji_cache: &EmojiCache,
+    emoji_id: &EmojiId,
+) -> Result<Vec<u8>, Error> {
+    let server: ServerUrl = server_url.clone().into();
+    if let Some(image) = emoji_cache.image(&server, emoji_id).await {
+        return Ok(image);
+    }
+    let Response::EmojiImage(image) = handle_request(
+        http_client,
+        server_url,
+        &ApiEvent::CustomEmojiImage(emoji_id.clone()),
+        token,
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    if let Err(e) = emoji_cache.store_image(&server, emoji_id, &image).await {
+        tracing::warn!("Failed to cache image of emoji {emoji_id}: {e}");
+    }
+    Ok(image)
+}
+
+/// Posts stored during last visit of channel, available before network
+/// fetch completes or when server is unreachable
+#[tauri::command]
+pub async fn load_cached_posts(
+    channel_id: ChannelId,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Option<PostThread>, Error> {
+    let server_url: ServerUrl = current_server_url(&server_state_mutex).await?.into();
+    let posts = storage
+        .run(move |storage| storage.cached_posts(&server_url, &channel_id))
+        .await?;
+    Ok(posts)
+}
+
+/// Local time of the other participant of direct message channel.
+///
+/// Returns `None` when recipient didn't configure timezone in profile.
+#[tauri::command]
+pub async fn dm_recipient_local_time(
+    channel_id: ChannelId,
+    working_hours: Option<WorkingHours>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Option<RecipientLocalTime>, Error> {
+    let (token, recipient) = {
+        let user_state = user_state_mutex.lock().await;
+        let me = user_state.id.as_ref().ok_or(NativeError::NotLoggedIn)?;
+        let recipient = user_state
+            .channels
+            .iter()
+            .flatten()
+            .find(|channel| channel.id.as_ref() == Some(&channel_id))
+            .filter(|channel| channel.r#type.as_deref().map(String::as_str) == Some("D"))
+            .and_then(|channel| channel.name.as_ref())
+            .and_then(|name| dm_recipient(name, me))
+            .ok_or(NativeError::NotDirectChannel)?;
+        (user_state.token.clone(), recipient)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::User(user) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::User(recipient),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(RecipientLocalTime::resolve(
+        &user,
+        chrono::Utc::now(),
+        &working_hours.unwrap_or_default(),
+    ))
+}
+
+/// Links from channel header, so they can be shown without parsing
+/// Markdown in UI. Channel is fetched when it's not among loaded channels.
+#[tauri::command]
+pub async fn get_channel_header_links(
+    channel_id: ChannelId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Vec<HeaderLink>, Error> {
+    let (token, cached) = {
+        let user_state = user_state_mutex.lock().await;
+        let cached = user_state
+            .channels
+            .iter()
+            .flatten()
+            .find(|channel| channel.id.as_ref() == Some(&channel_id))
+            .map(|channel| channel.header.clone());
+        (user_state.token.clone(), cached)
+    };
+    let header = match cached {
+        Some(header) => header,
+        None => {
+            let server_url = current_server_url(&server_state_mutex).await?;
+            let Response::Channel(channel) = handle_request(
+                &http_client,
+                &server_url,
+                &ApiEvent::Channel(channel_id),
+                token.as_ref(),
+            )
+            .await?
+            else {
+                return Err(NativeError::UnexpectedResponse)?;
+            };
+            channel.header
+        }
+    };
+    Ok(header
+        .map(|header| header_links(&header))
+        .unwrap_or_default())
+}
+
+/// Upload rules of server, with storage left when workspace has quota
+pub(crate) async fn attachment_policy(
+    http_client: &Client,
+    server_url: &Url,
+    token: Option<&AccessToken>,
+) -> Result<AttachmentPolicy, Error> {
+    let Response::ClientConfig(config) =
+        handle_request(http_client, server_url, &ApiEvent::ClientConfig, token).await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    // Self-hosted servers have no quota, failures mean there's nothing to
+    // enforce rather than broken server
+    let limits = match handle_request(http_client, server_url, &ApiEvent::CloudLimits, token).await
+    {
+        Ok(Response::CloudLimits(limits)) => Some(limits),
+        _ => None,
+    };
+    let has_storage_limit = limits
+        .as_ref()
+        .and_then(|limits| limits.files.as_ref())
+        .is_some_and(|files| files.total_storage.is_some());
+    let usage = if has_storage_limit {
+        let Response::StorageUsage(usage) =
+            handle_request(http_client, server_url, &ApiEvent::StorageUsage, token).await?
+        else {
+            return Err(NativeError::UnexpectedResponse)?;
+        };
+        Some(usage)
+    } else {
+        None
+    };
+    Ok(AttachmentPolicy::new(
+        &capabilities::from_client_config(&config),
+        limits.as_ref(),
+        usage.as_ref(),
+    ))
+}
+
+/// Check files against upload policy of current server before any of them
+/// is uploaded. Policy is read from server every time, returns size of
+/// every file when all of them can be uploaded.
+#[tauri::command]
+pub async fn check_attachments(
+    paths: Vec<std::path::PathBuf>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Vec<Attachment>, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let mut files = Vec::with_capacity(paths.len());
+    for path in &paths {
+        files.push(Attachment {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string()),
+            size: tokio::fs::metadata(path).await?.len(),
+        });
+    }
+    let policy = attachment_policy(&http_client, &server_url, token.as_ref()).await?;
+    attachments::check(&policy, &files)?;
+    Ok(files)
+}
+
+/// Upload image on clipboard to channel as PNG, so pasted screenshot can be
+/// attached to next post by returned file id
+#[tauri::command]
+pub async fn upload_clipboard_image(
+    channel_id: ChannelId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<FileId, Error> {
+    let target = channel_id.to_string();
+    let result = run_upload_clipboard_image(
+        channel_id,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+    )
+    .await;
+    audit::record("upload_clipboard_image", Some(&target), &result);
+    result
+}
+
+async fn run_upload_clipboard_image(
+    channel_id: ChannelId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<FileId, Error> {
+    let data = clipboard::read_image().await?;
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let policy = attachment_policy(&http_client, &server_url, token.as_ref()).await?;
+    attachments::check(
+        &policy,
+        &[Attachment {
+            name: clipboard::IMAGE_NAME.to_owned(),
+            size: data.len() as u64,
+        }],
+    )?;
+    let event = ApiEvent::UploadFile {
+        channel_id: channel_id.clone(),
+        name: clipboard::IMAGE_NAME.to_owned(),
+        data,
+    };
+    let Response::FileUploaded(file) =
+        handle_request(&http_client, &server_url, &event, token.as_ref()).await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(file.id)
+}
+
+/// Markdown link offered by composer when bare URL is pasted, `None` for
+/// any other text. With `fetch_title` page title is read by server through
+/// its link preview service, link itself is used as title when it's off or
+/// page has no title.
+#[tauri::command]
+pub async fn suggest_link_markdown(
+    pasted: &str,
+    fetch_title: bool,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Option<LinkSuggestion>, Error> {
+    let Some(url) = composer::pasted_url(pasted) else {
+        return Ok(None);
+    };
+    if !fetch_title {
+        return Ok(Some(composer::suggestion(&url, None)));
+    }
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let title = match handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::OpenGraph(url.to_string()),
+        token.as_ref(),
+    )
+    .await
+    {
+        Ok(Response::OpenGraph(open_graph)) => Some(open_graph.title),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!("No title for {url}: {e}");
+            None
+        }
+    };
+    Ok(Some(composer::suggestion(&url, title.as_deref())))
+}
+
+/// Preview of link in message, `None` when page has nothing to show.
+///
+/// Preview included in post metadata is used when frontend passes `embeds`
+/// of the post, server is asked otherwise. Results are cached per server.
+#[tauri::command]
+pub async fn get_link_metadata(
+    url: String,
+    embeds: Option<Vec<MetaEmbed>>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    previews: State<'_, LinkPreviews>,
+) -> Result<Option<OpenGraph>, Error> {
+    if let Some(preview) = link_preview::from_embeds(&url, embeds.as_deref().unwrap_or_default()) {
+        return Ok(Some(preview));
+    }
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let server: ServerUrl = server_url.clone().into();
+    if let Some(preview) = previews.get(&server, &url).await {
+        return Ok(preview);
+    }
+    let token = user_state_mutex.lock().await.token.clone();
+    let Response::OpenGraph(preview) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::OpenGraph(url.clone()),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let preview = Some(preview).filter(|preview| !link_preview::is_empty(preview));
+    previews.insert(&server, &url, preview.clone()).await;
+    Ok(preview)
+}
+
+/// Move read marker of followed thread back so replies starting with
+/// `post_id` show up as unread again
+#[tauri::command]
+pub async fn mark_thread_unread(
+    thread_id: PostId,
+    post_id: PostId,
+    team_id: TeamId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<UserThread, Error> {
+    let target = thread_id.to_string();
+    let result = run_mark_thread_unread(
+        thread_id,
+        post_id,
+        team_id,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+    )
+    .await;
+    audit::record("mark_thread_unread", Some(&target), &result);
+    result
+}
+
+async fn run_mark_thread_unread(
+    thread_id: PostId,
+    post_id: PostId,
+    team_id: TeamId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<UserThread, Error> {
+    let (token, user_id) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (user_state.token.clone(), user_id)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::Thread(thread) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::MarkThreadUnread {
+            user_id,
+            team_id,
+            thread_id,
+            post_id,
+        },
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(thread)
+}
+
+/// Remember `post_id` as the one in view when user leaves channel and mark
+/// channel viewed on server, so other devices count it as read too. Local
+/// position is kept when server is unreachable. Returns time channel is
+/// viewed at.
+#[tauri::command]
+pub async fn set_last_viewed(
+    channel_id: ChannelId,
+    post_id: PostId,
+    app: tauri::AppHandle,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Timestamp, Error> {
+    let (token, user_id) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (user_state.token.clone(), user_id)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let viewed = LastViewed {
+        post_id,
+        viewed_at: now_millis(),
+    };
+    {
+        let (server, channel_id, viewed) = (
+            server_url.clone().into(),
+            channel_id.clone(),
+            viewed.clone(),
+        );
+        storage
+            .run(move |storage| storage.store_last_viewed(&server, &channel_id, &viewed))
+            .await?;
+    }
+    let result = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::ViewChannel {
+            user_id,
+            channel_id: channel_id.clone(),
+        },
+        token.as_ref(),
+    )
+    .await;
+    let viewed_at = match result {
+        Ok(Response::ChannelViewed(last_viewed_at)) => last_viewed_at.unwrap_or(viewed.viewed_at),
+        Ok(_) => Err(NativeError::UnexpectedResponse)?,
+        Err(e) => {
+            tracing::warn!("Failed to mark channel {channel_id} viewed on server: {e}");
+            viewed.viewed_at
+        }
+    };
+    user_state_mutex
+        .lock()
+        .await
+        .mark_viewed(&channel_id, viewed_at);
+    unread::recount(&app, server_url.into()).await;
+    Ok(viewed_at)
+}
+
+/// Where to put "New messages" divider and scroll to when channel is
+/// opened again. Read marker of server is used when channel was viewed
+/// later on another device, local one when server is unreachable. Posts
+/// kept in memory or disk cache are searched, so channel is to be switched
+/// to first.
+#[tauri::command]
+pub async fn get_scroll_anchor(
+    channel_id: ChannelId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+) -> Result<ScrollAnchor, Error> {
+    let (token, user_id) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (user_state.token.clone(), user_id)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let server: ServerUrl = server_url.clone().into();
+    let member = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::ChannelMember {
+            channel_id: channel_id.clone(),
+            user_id: user_id.clone(),
+        },
+        token.as_ref(),
+    )
+    .await;
+    let server_viewed_at = match member {
+        Ok(Response::ChannelMember(member)) => member.last_viewed_at,
+        Ok(_) => return Err(NativeError::UnexpectedResponse)?,
+        Err(e) => {
+            tracing::warn!("Failed to fetch read marker of channel {channel_id}: {e}");
+            0
+        }
+    };
+    let remembered = user_state_mutex
+        .lock()
+        .await
+        .posts
+        .get(&server, &channel_id);
+    let (last_viewed, cached) = {
+        let channel_id = channel_id.clone();
+        let load_cached = remembered.is_none();
+        storage
+            .run(move |storage| {
+                let cached = if load_cached {
+                    storage.cached_posts(&server, &channel_id)?
+                } else {
+                    None
+                };
+                Ok::<_, crate::errors::StorageError>((
+                    storage.last_viewed(&server, &channel_id)?,
+                    cached,
+                ))
+            })
+            .await?
+    };
+    let last_viewed_at = last_viewed
+        .as_ref()
+        .map_or(0, |viewed| viewed.viewed_at)
+        .max(server_viewed_at);
+    let first_unread = remembered
+        .or(cached)
+        .and_then(|posts| threads::first_unread(&posts, last_viewed_at, Some(&user_id)));
+    Ok(ScrollAnchor {
+        first_unread,
+        last_viewed_post: last_viewed.map(|viewed| viewed.post_id),
+        last_viewed_at,
+    })
+}
+
+/// Mentions found by one search, older ones aren't shown in panel
+const MENTIONS_PAGE_SIZE: u32 = 60;
+
+/// Posts mentioning current user for recent mentions panel, newest first.
+/// Results of last search are returned until `refresh`, together with
+/// mentions which came over WebSocket since. Mentions are marked seen.
+#[tauri::command]
+pub async fn my_mentions(
+    refresh: Option<bool>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<RecentMentions, Error> {
+    let (token, team_ids) = {
+        let mut user_state = user_state_mutex.lock().await;
+        if !refresh.unwrap_or_default() {
+            if let Some(cached) = user_state.mentions.take_cached() {
+                return Ok(cached);
+            }
+        }
+        let team_ids: Vec<TeamId> = user_state
+            .teams
+            .iter()
+            .flatten()
+            .filter_map(|team| team.id.clone())
+            .collect();
+        (user_state.token.clone(), team_ids)
+    };
+    if team_ids.is_empty() {
+        return Err(NativeError::UnknownTeam)?;
+    }
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::User(user) =
+        handle_request(&http_client, &server_url, &ApiEvent::Me, token.as_ref()).await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let terms = mentions::search_terms(&user);
+    // Search is scoped to team, mentions in every team are listed
+    let searches = team_ids.into_iter().map(|team_id| {
+        let event = ApiEvent::SearchPosts {
+            team_id,
+            terms: terms.clone(),
+            is_or_search: true,
+            page: 0,
+            per_page: MENTIONS_PAGE_SIZE,
+        };
+        let (http_client, server_url, token) = (&http_client, &server_url, token.as_ref());
+        async move {
+            match handle_request(http_client, server_url, &event, token).await? {
+                Response::SearchResults(posts) => Ok::<_, Error>(posts),
+                _ => Err(NativeError::UnexpectedResponse)?,
+            }
+        }
+    });
+    let results = futures::future::try_join_all(searches).await?;
+    Ok(user_state_mutex.lock().await.mentions.replace(results))
+}
+
+#[derive(Debug, serde::Serialize, Clone)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum PostDelivery {
+    Sent {
+        post: Post,
+    },
+    /// Server is unreachable, post will be sent by outbox once it's back
+    Queued {
+        pending_post_id: PostId,
+    },
+    /// Secret guard found credentials in message, nothing was sent. Submit
+    /// again with `confirmed` to send anyway.
+    NeedsConfirmation {
+        findings: Vec<SecretFinding>,
+    },
+}
+
+#[derive(Debug, serde::Serialize, Clone)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum PostScheduling {
+    Scheduled {
+        scheduled: ScheduledPost,
+    },
+    /// Like [`PostDelivery::NeedsConfirmation`], nothing was scheduled
+    NeedsConfirmation {
+        findings: Vec<SecretFinding>,
+    },
+}
+
+/// Credentials secret guard finds in message user didn't confirm yet
+fn unconfirmed_secrets(
+    secret_guard: &SecretGuard,
+    message: &str,
+    confirmed: bool,
+) -> Option<Vec<SecretFinding>> {
+    if !secret_guard.is_enabled() || confirmed {
+        return None;
+    }
+    let findings = secrets::scan(message);
+    if findings.is_empty() {
+        return None;
+    }
+    tracing::info!(
+        "Message looks like it contains {} secret(s)",
+        findings.len()
+    );
+    Some(findings)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_post(
+    channel_id: ChannelId,
+    message: String,
+    root_id: Option<PostId>,
+    confirmed: Option<bool>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    outbox: State<'_, Outbox>,
+    secret_guard: State<'_, SecretGuard>,
+) -> Result<PostDelivery, Error> {
+    let target = channel_id.to_string();
+    let result = run_create_post(
+        channel_id,
+        message,
+        root_id,
+        confirmed,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+        storage,
+        outbox,
+        secret_guard,
+    )
+    .await;
+    audit::record("create_post", Some(&target), &result);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_create_post(
+    channel_id: ChannelId,
+    message: String,
+    root_id: Option<PostId>,
+    confirmed: Option<bool>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    outbox: State<'_, Outbox>,
+    secret_guard: State<'_, SecretGuard>,
+) -> Result<PostDelivery, Error> {
+    submit_post(
+        channel_id,
+        message,
+        None,
+        root_id,
+        confirmed.unwrap_or_default(),
+        &user_state_mutex,
+        &server_state_mutex,
+        &http_client,
+        &storage,
+        &outbox,
+        &secret_guard,
+    )
+    .await
+}
+
+/// Reply to thread started by `root_id`
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn reply_in_thread(
+    channel_id: ChannelId,
+    root_id: PostId,
+    message: String,
+    confirmed: Option<bool>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    outbox: State<'_, Outbox>,
+    secret_guard: State<'_, SecretGuard>,
+) -> Result<PostDelivery, Error> {
+    let target = root_id.to_string();
+    let result = run_reply_in_thread(
+        channel_id,
+        root_id,
+        message,
+        confirmed,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+        storage,
+        outbox,
+        secret_guard,
+    )
+    .await;
+    audit::record("reply_in_thread", Some(&target), &result);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_reply_in_thread(
+    channel_id: ChannelId,
+    root_id: PostId,
+    message: String,
+    confirmed: Option<bool>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    outbox: State<'_, Outbox>,
+    secret_guard: State<'_, SecretGuard>,
+) -> Result<PostDelivery, Error> {
+    submit_post(
+        channel_id,
+        message,
+        None,
+        Some(root_id),
+        confirmed.unwrap_or_default(),
+        &user_state_mutex,
+        &server_state_mutex,
+        &http_client,
+        &storage,
+        &outbox,
+        &secret_guard,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn submit_post(
+    channel_id: ChannelId,
+    message: String,
+    props: Option<serde_json::Value>,
+    root_id: Option<PostId>,
+    confirmed: bool,
+    user_state_mutex: &Mutex<UserState>,
+    server_state_mutex: &Mutex<ServerState>,
+    http_client: &Client,
+    storage: &StorageHandle,
+    outbox: &Outbox,
+    secret_guard: &SecretGuard,
+) -> Result<PostDelivery, Error> {
+    if let Some(findings) = unconfirmed_secrets(secret_guard, &message, confirmed) {
+        return Ok(PostDelivery::NeedsConfirmation { findings });
+    }
+    let (token, user_id) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (user_state.token.clone(), user_id)
+    };
+    let server_url = current_server_url(server_state_mutex).await?;
+    let now = now_millis();
+    let post = CreatePostRequest {
+        channel_id,
+        message: Message::new(message),
+        root_id,
+        pending_post_id: PostId::new(format!("{user_id}:{now}")),
+        props,
+    };
+    match handle_request(
+        http_client,
+        &server_url,
+        &ApiEvent::CreatePost(post.clone()),
+        token.as_ref(),
+    )
+    .await
+    {
+        Ok(Response::Post(post)) => Ok(PostDelivery::Sent { post }),
+        Ok(_) => Err(NativeError::UnexpectedResponse)?,
+        Err(Error::RequestFailed(e)) => {
+            tracing::warn!("Server unreachable, queueing post: {e}");
+            let pending_post_id = post.pending_post_id.clone();
+            outbox
+                .enqueue(
+                    storage,
+                    OutboxItem {
+                        server: server_url.into(),
+                        post,
+                        queued_at: now,
+                    },
+                )
+                .await?;
+            Ok(PostDelivery::Queued { pending_post_id })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Send `text` encrypted with passphrase to direct message channel. Only
+/// placeholder message is readable in other clients, recipient needs this
+/// client and passphrase shared some other way to read it.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn send_secure_snippet(
+    channel_id: ChannelId,
+    text: String,
+    passphrase: String,
+    root_id: Option<PostId>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    outbox: State<'_, Outbox>,
+    secret_guard: State<'_, SecretGuard>,
+) -> Result<PostDelivery, Error> {
+    let target = channel_id.to_string();
+    let result = run_send_secure_snippet(
+        channel_id,
+        text,
+        passphrase,
+        root_id,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+        storage,
+        outbox,
+        secret_guard,
+    )
+    .await;
+    audit::record("send_secure_snippet", Some(&target), &result);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_send_secure_snippet(
+    channel_id: ChannelId,
+    text: String,
+    passphrase: String,
+    root_id: Option<PostId>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    outbox: State<'_, Outbox>,
+    secret_guard: State<'_, SecretGuard>,
+) -> Result<PostDelivery, Error> {
+    let is_direct = user_state_mutex
+        .lock()
+        .await
+        .channels
+        .iter()
+        .flatten()
+        .find(|channel| channel.id.as_ref() == Some(&channel_id))
+        .is_some_and(|channel| channel.r#type.as_deref().map(String::as_str) == Some("D"));
+    if !is_direct {
+        return Err(NativeError::NotDirectChannel)?;
+    }
+    // Key derivation is deliberately slow
+    let encoded =
+        tokio::task::spawn_blocking(move || snippets::encrypt(&text, &passphrase)).await??;
+    submit_post(
+        channel_id,
+        snippets::SNIPPET_MESSAGE.to_owned(),
+        Some(snippets::props(encoded)),
+        root_id,
+        // Nothing readable is sent, so there's nothing to guard
+        true,
+        &user_state_mutex,
+        &server_state_mutex,
+        &http_client,
+        &storage,
+        &outbox,
+        &secret_guard,
+    )
+    .await
+}
+
+/// Link to post which opens it in web and desktop apps. Direct and group
+/// messages belong to no team, their links use current team.
+#[tauri::command]
+pub async fn get_post_permalink(
+    post_id: PostId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<String, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let link = post_permalink(
+        &post_id,
+        &user_state_mutex,
+        &http_client,
+        &server_url,
+        token.as_ref(),
+    )
+    .await?;
+    Ok(link.to_string())
+}
+
+/// Share post in another channel the way web app forwards it: permalink
+/// under optional comment, which server renders as embedded post. Post is
+/// fetched first, so forwarding fails for posts user can't read.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn forward_post(
+    post_id: PostId,
+    target_channel_id: ChannelId,
+    comment: Option<String>,
+    confirmed: Option<bool>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    outbox: State<'_, Outbox>,
+    secret_guard: State<'_, SecretGuard>,
+) -> Result<PostDelivery, Error> {
+    let target = post_id.to_string();
+    let result = run_forward_post(
+        post_id,
+        target_channel_id,
+        comment,
+        confirmed,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+        storage,
+        outbox,
+        secret_guard,
+    )
+    .await;
+    audit::record("forward_post", Some(&target), &result);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_forward_post(
+    post_id: PostId,
+    target_channel_id: ChannelId,
+    comment: Option<String>,
+    confirmed: Option<bool>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+    outbox: State<'_, Outbox>,
+    secret_guard: State<'_, SecretGuard>,
+) -> Result<PostDelivery, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let link = post_permalink(
+        &post_id,
+        &user_state_mutex,
+        &http_client,
+        &server_url,
+        token.as_ref(),
+    )
+    .await?;
+    submit_post(
+        target_channel_id,
+        permalinks::forward_message(comment.as_deref(), &link),
+        None,
+        None,
+        confirmed.unwrap_or_default(),
+        &user_state_mutex,
+        &server_state_mutex,
+        &http_client,
+        &storage,
+        &outbox,
+        &secret_guard,
+    )
+    .await
+}
+
+/// Permalink of post, team is taken from its channel
+async fn post_permalink(
+    post_id: &PostId,
+    user_state_mutex: &Mutex<UserState>,
+    http_client: &Client,
+    server_url: &Url,
+    token: Option<&AccessToken>,
+) -> Result<Url, Error> {
+    let Response::Post(post) = handle_request(
+        http_client,
+        server_url,
+        &ApiEvent::Post(post_id.clone()),
+        token,
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let channel = channel_by_id(
+        &post.channel_id,
+        user_state_mutex,
+        http_client,
+        server_url,
+        token,
+    )
+    .await?;
+    let team_name = team_name(&*user_state_mutex.lock().await, &channel)?;
+    Ok(permalinks::build(server_url, &team_name, post_id).ok_or(NativeError::InvalidPermalink)?)
+}
+
+/// Channel known to user, fetched from server when it isn't
+async fn channel_by_id(
+    channel_id: &ChannelId,
+    user_state_mutex: &Mutex<UserState>,
+    http_client: &Client,
+    server_url: &Url,
+    token: Option<&AccessToken>,
+) -> Result<Channel, Error> {
+    let known = user_state_mutex
+        .lock()
+        .await
+        .channels
+        .iter()
+        .flatten()
+        .find(|channel| channel.id.as_ref() == Some(channel_id))
+        .cloned();
+    if let Some(channel) = known {
+        return Ok(channel);
+    }
+    let Response::Channel(channel) = handle_request(
+        http_client,
+        server_url,
+        &ApiEvent::Channel(channel_id.clone()),
+        token,
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(channel)
+}
+
+/// Name of team `channel` belongs to in links. Direct and group messages
+/// belong to no team, their links use current team.
+fn team_name(user_state: &UserState, channel: &Channel) -> Result<String, Error> {
+    let team_id = channel
+        .team_id
+        .clone()
+        .filter(|team_id| !team_id.is_empty())
+        .map(TeamId::from)
+        .or_else(|| user_state.current_team.clone());
+    let teams = user_state.teams.iter().flatten();
+    let team_name = teams
+        .clone()
+        .find(|team| team_id.is_some() && team.id == team_id)
+        .or_else(|| teams.clone().next())
+        .and_then(|team| team.name.clone())
+        .ok_or(NativeError::UnknownTeam)?;
+    Ok(team_name.to_string())
+}
+
+/// Post and channel of pasted permalink, so it can be opened in application.
+/// Public channel user isn't member of is joined, post in private one fails
+/// with `permission_denied`.
+#[tauri::command]
+pub async fn resolve_permalink(
+    url: String,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<ResolvedPermalink, Error> {
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let (_, post_id) = permalinks::parse(&server_url, &url).ok_or(NativeError::InvalidPermalink)?;
+    let (token, me, known) = {
+        let user_state = user_state_mutex.lock().await;
+        let me = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (
+            user_state.token.clone(),
+            me,
+            user_state.channels.clone().unwrap_or_default(),
+        )
+    };
+    let Response::Post(post) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::Post(post_id),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    if let Some(channel) = known
+        .into_iter()
+        .find(|channel| channel.id.as_ref() == Some(&post.channel_id))
+    {
+        return Ok(ResolvedPermalink {
+            post,
+            channel,
+            joined: false,
+        });
+    }
+
+    let target = post.channel_id.to_string();
+    let joined: Result<Channel, Error> = async {
+        let Response::ChannelMember(_) = handle_request(
+            &http_client,
+            &server_url,
+            &ApiEvent::AddChannelMember {
+                channel_id: post.channel_id.clone(),
+                user_id: me,
+            },
+            token.as_ref(),
+        )
+        .await?
+        else {
+            return Err(NativeError::UnexpectedResponse)?;
+        };
+        let Response::Channel(channel) = handle_request(
+            &http_client,
+            &server_url,
+            &ApiEvent::Channel(post.channel_id.clone()),
+            token.as_ref(),
+        )
+        .await?
+        else {
+            return Err(NativeError::UnexpectedResponse)?;
+        };
+        user_state_mutex.lock().await.store_channel(channel.clone());
+        Ok(channel)
+    }
+    .await;
+    audit::record("join_channel_of_permalink", Some(&target), &joined);
+    Ok(ResolvedPermalink {
+        post,
+        channel: joined?,
+        joined: true,
+    })
+}
+
+/// Call state of channel kept by Calls plugin, fails with `not_found` when
+/// plugin isn't installed on server
+#[tauri::command]
+pub async fn get_call_state(
+    channel_id: ChannelId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<CallChannelState, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::CallState(state) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::CallState(channel_id),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(state)
+}
+
+/// Open channel in system browser, where call is started or joined through
+/// Calls plugin of web app. Returns call state of channel seen before
+/// opening it.
+#[tauri::command]
+pub async fn open_call(
+    channel_id: ChannelId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<CallChannelState, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::CallState(state) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::CallState(channel_id.clone()),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    if state.enabled == Some(false) && state.call.is_none() {
+        return Err(NativeError::CallsDisabled)?;
+    }
+    let channel = channel_by_id(
+        &channel_id,
+        &user_state_mutex,
+        &http_client,
+        &server_url,
+        token.as_ref(),
+    )
+    .await?;
+    let team_name = team_name(&*user_state_mutex.lock().await, &channel)?;
+    let channel_name = channel.name.ok_or(NativeError::UnexpectedResponse)?;
+    let url = permalinks::channel(&server_url, &team_name, &channel_name)
+        .ok_or(NativeError::InvalidServerUrl)?;
+    tracing::info!("Opening call of channel {channel_id} in browser");
+    open::that(url.as_str())?;
+    Ok(state)
+}
+
+/// Decrypt secure snippet of post locally, passphrase never leaves device
+#[tauri::command]
+pub async fn decrypt_snippet(
+    post_id: PostId,
+    passphrase: String,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<String, Error> {
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::Post(post) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::Post(post_id),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let encoded = snippets::from_props(&post.props)
+        .ok_or(NativeError::NotSecureSnippet)?
+        .to_owned();
+    Ok(tokio::task::spawn_blocking(move || snippets::decrypt(&encoded, &passphrase)).await??)
+}
+
+/// Whether messages are checked for pasted credentials before sending
+#[tauri::command]
+pub async fn secret_guard(secret_guard: State<'_, SecretGuard>) -> Result<bool, Error> {
+    Ok(secret_guard.is_enabled())
+}
+
+#[tauri::command]
+pub async fn set_secret_guard(
+    enabled: bool,
+    secret_guard: State<'_, SecretGuard>,
+    storage: State<'_, StorageHandle>,
+) -> Result<bool, Error> {
+    let result = run_set_secret_guard(enabled, secret_guard, storage).await;
+    audit::record("set_secret_guard", None, &result);
+    result
+}
+
+async fn run_set_secret_guard(
+    enabled: bool,
+    secret_guard: State<'_, SecretGuard>,
+    storage: State<'_, StorageHandle>,
+) -> Result<bool, Error> {
+    storage
+        .run(move |storage| storage.set_secret_guard(enabled))
+        .await?;
+    secret_guard.set_enabled(enabled);
+    Ok(enabled)
+}
+
+/// Fetch preferences of logged in user from server, including muted
+/// channels, and keep them in user state
+#[tauri::command]
+pub async fn sync_preferences(
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Preferences, Error> {
+    let (token, user_id) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (user_state.token.clone(), user_id)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::Preferences(list) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::Preferences(user_id.clone()),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let members =
+        sessions::all_channel_members(&http_client, &server_url, token.as_ref(), &user_id).await?;
+    let preferences = preferences::decode(&list, &members);
+    let mut user_state = user_state_mutex.lock().await;
+    user_state.preferences = Some(preferences.clone());
+    user_state.channel_members = Some(members);
+    Ok(preferences)
+}
+
+/// Preferences fetched by last `sync_preferences`
+#[tauri::command]
+pub async fn get_preferences(
+    user_state_mutex: State<'_, Mutex<UserState>>,
+) -> Result<Option<Preferences>, Error> {
+    Ok(user_state_mutex.lock().await.preferences.clone())
+}
+
+/// Save changed favorites, display settings and theme on server. Muted
+/// channels are changed through channel notify props instead.
+#[tauri::command]
+pub async fn update_preferences(
+    preferences: Preferences,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Preferences, Error> {
+    let result = run_update_preferences(
+        preferences,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+    )
+    .await;
+    audit::record("update_preferences", None, &result);
+    result
+}
+
+async fn run_update_preferences(
+    preferences: Preferences,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Preferences, Error> {
+    let (token, user_id, current) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (
+            user_state.token.clone(),
+            user_id,
+            user_state.preferences.clone().unwrap_or_default(),
+        )
+    };
+    let changes = preferences::changes(&user_id, &current, &preferences);
+    if !changes.is_empty() {
+        let server_url = current_server_url(&server_state_mutex).await?;
+        handle_request(
+            &http_client,
+            &server_url,
+            &ApiEvent::SavePreferences {
+                user_id,
+                preferences: changes,
+            },
+            token.as_ref(),
+        )
+        .await?;
+    }
+    let preferences = Preferences {
+        muted_channels: current.muted_channels,
+        ..preferences
+    };
+    user_state_mutex.lock().await.preferences = Some(preferences.clone());
+    Ok(preferences)
+}
+
+/// Sidebar categories of team in the order they should be displayed
+#[tauri::command]
+pub async fn sidebar_categories(
+    team_id: TeamId,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Vec<SidebarCategory>, Error> {
+    let (token, user_id) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (user_state.token.clone(), user_id)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::SidebarCategories(categories) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::SidebarCategories { user_id, team_id },
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(categories.into_ordered())
+}
+
+/// Save changed categories, e.g. renamed, collapsed or with channels moved
+/// between them
+#[tauri::command]
+pub async fn update_sidebar_categories(
+    team_id: TeamId,
+    categories: Vec<SidebarCategory>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Vec<SidebarCategory>, Error> {
+    let target = team_id.to_string();
+    let result = run_update_sidebar_categories(
+        team_id,
+        categories,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+    )
+    .await;
+    audit::record("update_sidebar_categories", Some(&target), &result);
+    result
+}
+
+async fn run_update_sidebar_categories(
+    team_id: TeamId,
+    categories: Vec<SidebarCategory>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Vec<SidebarCategory>, Error> {
+    let (token, user_id) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (user_state.token.clone(), user_id)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::UpdatedSidebarCategories(categories) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::UpdateSidebarCategories {
+            user_id,
+            team_id,
+            categories,
+        },
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(categories)
+}
+
+/// Change order of categories in sidebar, returns order accepted by server
+#[tauri::command]
+pub async fn reorder_sidebar_categories(
+    team_id: TeamId,
+    order: Vec<CategoryId>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Vec<CategoryId>, Error> {
+    let (token, user_id) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (user_state.token.clone(), user_id)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::SidebarCategoryOrder(order) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::SidebarCategoryOrder {
+            user_id,
+            team_id,
+            order,
+        },
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(order)
+}
+
+/// Posts waiting in outbox, so they can be rendered as pending
+#[tauri::command]
+pub async fn pending_posts(
+    storage: State<'_, StorageHandle>,
+    outbox: State<'_, Outbox>,
+) -> Result<Vec<OutboxItem>, Error> {
+    outbox.items(&storage).await
+}
+
+/// Keep post in vault and send it at `send_at` (milliseconds since epoch).
+/// Post whose time passes while application is closed is sent on next
+/// start. Secret guard checks message now, like when it's sent right away.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn schedule_post(
+    channel_id: ChannelId,
+    message: String,
+    send_at: Timestamp,
+    root_id: Option<PostId>,
+    confirmed: Option<bool>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+    secret_guard: State<'_, SecretGuard>,
+) -> Result<PostScheduling, Error> {
+    let target = channel_id.to_string();
+    let result = run_schedule_post(
+        channel_id,
+        message,
+        send_at,
+        root_id,
+        confirmed,
+        user_state_mutex,
+        server_state_mutex,
+        storage,
+        secret_guard,
+    )
+    .await;
+    audit::record("schedule_post", Some(&target), &result);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_schedule_post(
+    channel_id: ChannelId,
+    message: String,
+    send_at: Timestamp,
+    root_id: Option<PostId>,
+    confirmed: Option<bool>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+    secret_guard: State<'_, SecretGuard>,
+) -> Result<PostScheduling, Error> {
+    let now = now_millis();
+    if send_at <= now {
+        return Err(NativeError::InvalidScheduleTime)?;
+    }
+    let confirmed = confirmed.unwrap_or_default();
+    if let Some(findings) = unconfirmed_secrets(&secret_guard, &message, confirmed) {
+        return Ok(PostScheduling::NeedsConfirmation { findings });
+    }
+    let user_id = user_state_mutex
+        .lock()
+        .await
+        .id
+        .clone()
+        .ok_or(NativeError::NotLoggedIn)?;
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let item = ScheduledPost {
+        server: server_url.into(),
+        post: CreatePostRequest {
+            channel_id,
+            message: Message::new(message),
+            root_id,
+            pending_post_id: PostId::new(format!("{user_id}:{now}")),
+            props: None,
+        },
+        send_at,
+        scheduled_at: now,
+    };
+    let stored = item.clone();
+    storage
+        .run(move |storage| storage.add_scheduled_post(stored))
+        .await?;
+    Ok(PostScheduling::Scheduled { scheduled: item })
+}
+
+/// Scheduled posts of current server, soonest first
+#[tauri::command]
+pub async fn list_scheduled_posts(
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Vec<ScheduledPost>, Error> {
+    let server: ServerUrl = current_server_url(&server_state_mutex).await?.into();
+    let mut items = storage.run(|storage| storage.scheduled_posts()).await?;
+    items.retain(|item| item.server == server);
+    items.sort_by_key(|item| item.send_at);
+    Ok(items)
+}
+
+/// Drop scheduled post before it's sent, returns whether it was still
+/// waiting
+#[tauri::command]
+pub async fn cancel_scheduled_post(
+    pending_post_id: PostId,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<bool, Error> {
+    let target = pending_post_id.to_string();
+    let result = run_cancel_scheduled_post(pending_post_id, server_state_mutex, storage).await;
+    audit::record("cancel_scheduled_post", Some(&target), &result);
+    result
+}
+
+async fn run_cancel_scheduled_post(
+    pending_post_id: PostId,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<bool, Error> {
+    let server: ServerUrl = current_server_url(&server_state_mutex).await?.into();
+    let removed = storage
+        .run(move |storage| storage.remove_scheduled_posts(&server, &[pending_post_id]))
+        .await?;
+    Ok(removed > 0)
+}
+
+/// Remind user about post at `remind_at` with desktop notification. Post is
+/// kept with reminder, so notification shows it even when server is
+/// unreachable. Earlier reminder about the same post is replaced.
+#[tauri::command]
+pub async fn add_reminder(
+    post_id: PostId,
+    remind_at: Timestamp,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Reminder, Error> {
+    let target = post_id.to_string();
+    let result = run_add_reminder(
+        post_id,
+        remind_at,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+        storage,
+    )
+    .await;
+    audit::record("add_reminder", Some(&target), &result);
+    result
+}
+
+async fn run_add_reminder(
+    post_id: PostId,
+    remind_at: Timestamp,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Reminder, Error> {
+    let now = now_millis();
+    if remind_at <= now {
+        return Err(NativeError::InvalidScheduleTime)?;
+    }
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::Post(post) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::Post(post_id),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let reminder = Reminder {
+        server: server_url.into(),
+        post,
+        remind_at,
+        created_at: now,
+        fired: false,
+    };
+    let stored = reminder.clone();
+    storage
+        .run(move |storage| storage.add_reminder(stored))
+        .await?;
+    Ok(reminder)
+}
+
+/// Reminders of current server, fired ones included, soonest first
+#[tauri::command]
+pub async fn list_reminders(
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Vec<Reminder>, Error> {
+    let server: ServerUrl = current_server_url(&server_state_mutex).await?.into();
+    let mut reminders = storage.run(|storage| storage.reminders()).await?;
+    reminders.retain(|reminder| reminder.server == server);
+    reminders.sort_by_key(|reminder| reminder.remind_at);
+    Ok(reminders)
+}
+
+/// Move reminder to `until`, fired reminder fires once more then. `None`
+/// when there's no reminder about post.
+#[tauri::command]
+pub async fn snooze_reminder(
+    post_id: PostId,
+    until: Timestamp,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Option<Reminder>, Error> {
+    if until <= now_millis() {
+        return Err(NativeError::InvalidScheduleTime)?;
+    }
+    let server: ServerUrl = current_server_url(&server_state_mutex).await?.into();
+    let snoozed = storage
+        .run(move |storage| {
+            storage.update_reminders(&server, &[post_id], |reminder| {
+                reminder.remind_at = until;
+                reminder.fired = false;
+                true
+            })
+        })
+        .await?;
+    Ok(snoozed.into_iter().next())
+}
+
+/// Returns whether there was reminder about post
+#[tauri::command]
+pub async fn delete_reminder(
+    post_id: PostId,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<bool, Error> {
+    let target = post_id.to_string();
+    let result = run_delete_reminder(post_id, server_state_mutex, storage).await;
+    audit::record("delete_reminder", Some(&target), &result);
+    result
+}
+
+async fn run_delete_reminder(
+    post_id: PostId,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<bool, Error> {
+    let server: ServerUrl = current_server_url(&server_state_mutex).await?.into();
+    let deleted = storage
+        .run(move |storage| storage.update_reminders(&server, &[post_id], |_| false))
+        .await?;
+    Ok(!deleted.is_empty())
+}
+
+/// Save post in local watch later queue. With `remind_at` reminder about
+/// it is added as by [`add_reminder`].
+#[tauri::command]
+pub async fn add_to_watch_later(
+    post: Post,
+    remind_at: Option<Timestamp>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Vec<WatchLaterItem>, Error> {
+    let target = post.id.to_string();
+    let result = run_add_to_watch_later(post, remind_at, server_state_mutex, storage).await;
+    audit::record("add_to_watch_later", Some(&target), &result);
+    result
+}
+
+async fn run_add_to_watch_later(
+    post: Post,
+    remind_at: Option<Timestamp>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Vec<WatchLaterItem>, Error> {
+    let now = now_millis();
+    if remind_at.is_some_and(|remind_at| remind_at <= now) {
+        return Err(NativeError::InvalidScheduleTime)?;
+    }
+    let server: ServerUrl = current_server_url(&server_state_mutex).await?.into();
+    let items = storage
+        .run(move |storage| {
+            if let Some(remind_at) = remind_at {
+                storage.add_reminder(Reminder {
+                    server: server.clone(),
+                    post: post.clone(),
+                    remind_at,
+                    created_at: now,
+                    fired: false,
+                })?;
+            }
+            storage.add_watch_later(WatchLaterItem {
+                server: server.clone(),
+                post,
+                added_at: now,
+            })?;
+            watch_later_of(storage, &server)
+        })
+        .await?;
+    Ok(items)
+}
+
+#[tauri::command]
+pub async fn remove_from_watch_later(
+    post_id: PostId,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Vec<WatchLaterItem>, Error> {
+    let target = post_id.to_string();
+    let result = run_remove_from_watch_later(post_id, server_state_mutex, storage).await;
+    audit::record("remove_from_watch_later", Some(&target), &result);
+    result
+}
+
+async fn run_remove_from_watch_later(
+    post_id: PostId,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Vec<WatchLaterItem>, Error> {
+    let server: ServerUrl = current_server_url(&server_state_mutex).await?.into();
+    let items = storage
+        .run(move |storage| {
+            storage.remove_watch_later(&server, &post_id)?;
+            watch_later_of(storage, &server)
+        })
+        .await?;
+    Ok(items)
+}
+
+/// Watch later queue of current server, oldest first
+#[tauri::command]
+pub async fn watch_later(
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Vec<WatchLaterItem>, Error> {
+    let server: ServerUrl = current_server_url(&server_state_mutex).await?.into();
+    let items = storage
+        .run(move |storage| watch_later_of(storage, &server))
+        .await?;
+    Ok(items)
+}
+
+#[derive(Debug, serde::Serialize, Clone)]
+pub struct RequestSigningInfo {
+    pub algorithm: SigningAlgorithm,
+    pub header_name: String,
+}
+
+/// Sign every request sent to current server with HMAC of shared secret.
+/// Pass `None` to disable signing.
+#[tauri::command]
+pub async fn set_request_signing(
+    signing: Option<RequestSigningInput>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Option<RequestSigningInfo>, Error> {
+    let result = run_set_request_signing(signing, server_state_mutex, storage).await;
+    audit::record("set_request_signing", None, &result);
+    result
+}
+
+async fn run_set_request_signing(
+    signing: Option<RequestSigningInput>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Option<RequestSigningInfo>, Error> {
+    let server: ServerUrl = current_server_url(&server_state_mutex).await?.into();
+    let info = signing.as_ref().map(|signing| RequestSigningInfo {
+        algorithm: signing.algorithm,
+        header_name: signing.header_name.clone(),
+    });
+    let signing = signing.map(|signing| RequestSigning {
+        server: server.clone(),
+        algorithm: signing.algorithm,
+        header_name: signing.header_name,
+        secret: signing.secret,
+    });
+    let all = storage
+        .run(move |storage| storage.set_request_signing(&server, signing))
+        .await?;
+    signing::configure(all);
+    Ok(info)
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct RequestSigningInput {
+    pub algorithm: SigningAlgorithm,
+    pub header_name: String,
+    pub secret: String,
+}
+
+/// Signing configuration of current server, secret is never sent back
+#[tauri::command]
+pub async fn request_signing(
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Option<RequestSigningInfo>, Error> {
+    let server: ServerUrl = current_server_url(&server_state_mutex).await?.into();
+    let all = storage.run(|storage| storage.request_signing()).await?;
+    Ok(all
+        .into_iter()
+        .find(|signing| signing.server == server)
+        .map(|signing| RequestSigningInfo {
+            algorithm: signing.algorithm,
+            header_name: signing.header_name,
+        }))
+}
+
+/// Whether responses are compared with models and differences logged
+#[tauri::command]
+pub async fn strict_schema() -> Result<bool, Error> {
+    Ok(schema::is_enabled())
+}
+
+/// Debug setting for keeping models in sync with newer servers, responses
+/// are still accepted when they don't match
+#[tauri::command]
+pub async fn set_strict_schema(
+    enabled: bool,
+    storage: State<'_, StorageHandle>,
+) -> Result<bool, Error> {
+    let result = run_set_strict_schema(enabled, storage).await;
+    audit::record("set_strict_schema", None, &result);
+    result
+}
+
+async fn run_set_strict_schema(
+    enabled: bool,
+    storage: State<'_, StorageHandle>,
+) -> Result<bool, Error> {
+    storage
+        .run(move |storage| storage.set_strict_schema(enabled))
+        .await?;
+    schema::configure(enabled);
+    tracing::info!("Strict schema validation enabled: {enabled}");
+    Ok(enabled)
+}
+
+#[tauri::command]
+pub async fn get_network_settings(
+    settings: State<'_, SettingsState>,
+) -> Result<NetworkSettings, Error> {
+    Ok(settings.get().network)
+}
+
+/// Proxy and certificates of all further requests, settings are only saved
+/// when client can be built from them
+#[tauri::command]
+pub async fn configure_network(
+    settings: NetworkSettings,
+    app: tauri::AppHandle,
+) -> Result<NetworkSettings, Error> {
+    let result = run_configure_network(settings, app).await;
+    audit::record("configure_network", None, &result);
+    result
+}
+
+async fn run_configure_network(
+    settings: NetworkSettings,
+    app: tauri::AppHandle,
+) -> Result<NetworkSettings, Error> {
+    let settings = settings::modify(&app, |current| current.network = settings)
+        .await?
+        .network;
+    tracing::info!(
+        "Network configured, proxy: {}, custom CA: {}, unverified hosts: {:?}",
+        settings.proxy.is_some(),
+        settings.ca_certificate.is_some(),
+        settings.insecure_hosts
+    );
+    Ok(settings)
+}
+
+/// Status of user, of logged in user when `user_id` isn't given
+#[tauri::command]
+pub async fn get_user_status(
+    user_id: Option<UserId>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<UserStatus, Error> {
+    let (token, user_id) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_id
+            .or_else(|| user_state.id.clone())
+            .ok_or(NativeError::NotLoggedIn)?;
+        (user_state.token.clone(), user_id)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::Status(status) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::UserStatus(user_id),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(status)
+}
+
+#[tauri::command]
+pub async fn get_user_statuses(
+    user_ids: Vec<UserId>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+) -> Result<Vec<UserStatus>, Error> {
+    if user_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let token = user_state_mutex.lock().await.token.clone();
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let Response::Statuses(statuses) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::UserStatuses(user_ids),
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(statuses)
+}
+
+/// Set status of logged in user manually, automatic away detection doesn't
+/// change it until user does
+#[tauri::command]
+pub async fn set_user_status(
+    status: Presence,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    dnd_manager: State<'_, DndManager>,
+) -> Result<UserStatus, Error> {
+    let result = run_set_user_status(
+        status,
+        user_state_mutex,
+        server_state_mutex,
+        http_client,
+        dnd_manager,
+    )
+    .await;
+    audit::record("set_user_status", None, &result);
+    result
+}
+
+async fn run_set_user_status(
+    status: Presence,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    http_client: State<'_, Client>,
+    dnd_manager: State<'_, DndManager>,
+) -> Result<UserStatus, Error> {
+    let (token, user_id) = {
+        let user_state = user_state_mutex.lock().await;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (user_state.token.clone(), user_id)
+    };
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let generation = dnd_manager.generation();
+    let Response::Status(status) = handle_request(
+        &http_client,
+        &server_url,
+        &ApiEvent::SetUserStatus {
+            user_id,
+            status,
+            dnd_end_time: None,
+        },
+        token.as_ref(),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    dnd_manager.adopt(generation, Dnd::from_status(&status));
+    Ok(status)
+}
+
+#[derive(Debug, serde::Serialize, Clone)]
+pub struct DndState {
+    #[serde(flatten)]
+    pub dnd: Dnd,
+    pub suppresses_notifications: bool,
+}
+
+#[tauri::command]
+pub async fn get_dnd(dnd_manager: State<'_, DndManager>) -> Result<DndState, Error> {
+    let dnd = dnd_manager.current();
+    Ok(DndState {
+        dnd,
+        suppresses_notifications: dnd_manager.suppresses_notifications(now_millis()),
+    })
+}
+
+/// Turn do not disturb on until `until` in milliseconds or for `minutes`
+/// (until turned off when neither is given) or off. It applies locally
+/// right away and is set as server status with the same expiry, when server
+/// can't be reached it's retried by background sync.
+#[tauri::command]
+pub async fn set_dnd(
+    enabled: bool,
+    minutes: Option<u64>,
+    until: Option<Timestamp>,
+    app: tauri::AppHandle,
+    dnd_manager: State<'_, DndManager>,
+) -> Result<DndState, Error> {
+    let result = run_set_dnd(enabled, minutes, until, app, dnd_manager).await;
+    audit::record("set_dnd", None, &result);
+    result
+}
+
+async fn run_set_dnd(
+    enabled: bool,
+    minutes: Option<u64>,
+    until: Option<Timestamp>,
+    app: tauri::AppHandle,
+    dnd_manager: State<'_, DndManager>,
+) -> Result<DndState, Error> {
+    let now = now_millis();
+    if until.is_some_and(|until| until <= now) {
+        return Err(NativeError::InvalidScheduleTime)?;
+    }
+    let dnd = Dnd {
+        enabled,
+        until: until
+            .or(minutes.map(|minutes| now.saturating_add(minutes.saturating_mul(60_000))))
+            .filter(|_| enabled),
+    };
+    dnd::set(&app, dnd).await?;
+    get_dnd(dnd_manager).await
+}
+
+/// Called by frontend on user input, keeps user from being marked away
+#[tauri::command]
+pub async fn report_user_activity(status: State<'_, StatusManager>) -> Result<(), Error> {
+    status.activity();
+    Ok(())
+}
+
+/// Resume background refresh of active server, it's running from start
+#[tauri::command]
+pub async fn start_sync_scheduler(scheduler: State<'_, Scheduler>) -> Result<bool, Error> {
+    scheduler.start();
+    Ok(scheduler.is_running())
+}
+
+#[tauri::command]
+pub async fn stop_sync_scheduler(scheduler: State<'_, Scheduler>) -> Result<bool, Error> {
+    scheduler.stop();
+    Ok(scheduler.is_running())
+}
+
+#[tauri::command]
+pub async fn sync_intervals(scheduler: State<'_, Scheduler>) -> Result<SyncIntervals, Error> {
+    Ok(scheduler.intervals())
+}
+
+/// Change how often unread counts, channels and statuses are refreshed,
+/// returns intervals after raising too short ones
+#[tauri::command]
+pub async fn set_sync_intervals(
+    intervals: SyncIntervals,
+    app: tauri::AppHandle,
+) -> Result<SyncIntervals, Error> {
+    let result = run_set_sync_intervals(intervals, app).await;
+    audit::record("set_sync_intervals", None, &result);
+    result
+}
+
+async fn run_set_sync_intervals(
+    intervals: SyncIntervals,
+    app: tauri::AppHandle,
+) -> Result<SyncIntervals, Error> {
+    let settings = settings::modify(&app, |current| current.sync_intervals = intervals).await?;
+    Ok(settings.sync_intervals)
+}
+
+/// Change how many posts are kept in memory, posts beyond new limits are
+/// moved to disk cache right away. Returns limits after raising too low
+/// ones.
+#[tauri::command]
+pub async fn set_memory_limits(
+    limits: MemorySettings,
+    app: tauri::AppHandle,
+) -> Result<MemorySettings, Error> {
+    let result = run_set_memory_limits(limits, app).await;
+    audit::record("set_memory_limits", None, &result);
+    result
+}
+
+async fn run_set_memory_limits(
+    limits: MemorySettings,
+    app: tauri::AppHandle,
+) -> Result<MemorySettings, Error> {
+    let limits = settings::modify(&app, |current| current.memory = limits)
+        .await?
+        .memory;
+    let evicted = app
+        .state::<Mutex<UserState>>()
+        .lock()
+        .await
+        .posts
+        .trim(limits);
+    let density = *app.state::<RwLock<PostDensity>>().read().await;
+    post_store::persist(
+        &app.state::<StorageHandle>(),
+        evicted,
+        density.retained_posts(),
+    );
+    Ok(limits)
+}
+
+/// Start application when user logs in to OS, returns settings with the
+/// change applied
+#[tauri::command]
+pub async fn set_auto_start(enabled: bool, app: tauri::AppHandle) -> Result<Settings, Error> {
+    let result = run_set_auto_start(enabled, app).await;
+    audit::record("set_auto_start", None, &result);
+    result
+}
+
+async fn run_set_auto_start(enabled: bool, app: tauri::AppHandle) -> Result<Settings, Error> {
+    settings::modify(&app, |settings| settings.startup.launch_at_login = enabled).await
+}
+
+/// Turn spellcheck of composer on or off and pick its dictionaries, language
+/// of OS is used when `languages` are empty
+#[tauri::command]
+pub async fn set_spellcheck(
+    enabled: bool,
+    languages: Vec<String>,
+    app: tauri::AppHandle,
+) -> Result<SpellcheckSettings, Error> {
+    let result = run_set_spellcheck(enabled, languages, app).await;
+    audit::record("set_spellcheck", None, &result);
+    result
+}
+
+async fn run_set_spellcheck(
+    enabled: bool,
+    languages: Vec<String>,
+    app: tauri::AppHandle,
+) -> Result<SpellcheckSettings, Error> {
+    let spellcheck = SpellcheckSettings { enabled, languages };
+    Ok(
+        settings::modify(&app, |settings| settings.spellcheck = spellcheck)
+            .await?
+            .spellcheck,
+    )
+}
+
+/// Language of errors and notifications, language of OS when `None`.
+/// Returns messages of locale which ended up in effect.
+#[tauri::command]
+pub async fn set_locale(
+    locale: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<i18n::Translations, Error> {
+    let result = run_set_locale(locale, app).await;
+    audit::record("set_locale", None, &result);
+    result
+}
+
+async fn run_set_locale(
+    locale: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<i18n::Translations, Error> {
+    let settings = settings::modify(&app, |settings| settings.locale = locale).await?;
+    // Don't wait for settings watcher, errors of next command should
+    // already be translated
+    i18n::apply(settings.locale.as_deref());
+    Ok(i18n::translations())
+}
+
+/// Messages of current locale keyed like `key` of errors
+#[tauri::command]
+pub async fn get_translations() -> Result<i18n::Translations, Error> {
+    Ok(i18n::translations())
+}
+
+/// Global shortcut showing and hiding main window, `None` removes it
+#[tauri::command]
+pub async fn set_global_shortcut(
+    accelerator: Option<String>,
+    open_switcher: bool,
+    app: tauri::AppHandle,
+) -> Result<ShortcutSettings, Error> {
+    let result = run_set_global_shortcut(accelerator, open_switcher, app).await;
+    audit::record("set_global_shortcut", None, &result);
+    result
+}
+
+async fn run_set_global_shortcut(
+    accelerator: Option<String>,
+    open_switcher: bool,
+    app: tauri::AppHandle,
+) -> Result<ShortcutSettings, Error> {
+    let shortcut = ShortcutSettings {
+        accelerator,
+        open_switcher,
+    };
+    Ok(
+        settings::modify(&app, |settings| settings.shortcut = shortcut)
+            .await?
+            .shortcut,
+    )
+}
+
+/// Open developer tools of calling window or close them when they are open,
+/// returns whether they are open now
+#[tauri::command]
+pub fn toggle_devtools(window: tauri::Window) -> bool {
+    if window.is_devtools_open() {
+        window.close_devtools();
+        false
+    } else {
+        window.open_devtools();
+        true
+    }
+}
+
+/// Newest `limit` lines of application log (500 by default), for attaching
+/// to support requests
+#[tauri::command]
+pub async fn get_recent_logs(limit: Option<usize>) -> Result<Vec<String>, Error> {
+    let limit = limit.unwrap_or(500);
+    Ok(tokio::task::spawn_blocking(move || logging::recent(limit)).await??)
+}
+
+/// Show directory with log files in file manager
+#[tauri::command]
+pub async fn open_log_dir() -> Result<(), Error> {
+    let dir = logging::log_dir();
+    std::fs::create_dir_all(&dir)?;
+    open::that(&dir)?;
+    Ok(())
+}
+
+/// Check that vault can be read and repair its super block when needed.
+///
+/// With `rebuild`, damaged vault is created anew from documents which are
+/// still readable. Servers, settings and credentials of open sessions are
+/// then written again from memory, cached posts are fetched from server as
+/// channels are opened.
+#[tauri::command]
+pub async fn storage_doctor(
+    rebuild: Option<bool>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    sessions: State<'_, Sessions>,
+    storage: State<'_, StorageHandle>,
+    settings: State<'_, SettingsState>,
+    density: State<'_, RwLock<PostDensity>>,
+) -> Result<StorageHealth, Error> {
+    let result = run_storage_doctor(
+        rebuild,
+        user_state_mutex,
+        server_state_mutex,
+        sessions,
+        storage,
+        settings,
+        density,
+    )
+    .await;
+    audit::record("storage_doctor", None, &result);
+    result
+}
+
+async fn run_storage_doctor(
+    rebuild: Option<bool>,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    sessions: State<'_, Sessions>,
+    storage: State<'_, StorageHandle>,
+    settings: State<'_, SettingsState>,
+    density: State<'_, RwLock<PostDensity>>,
+) -> Result<StorageHealth, Error> {
+    let rebuild = rebuild.unwrap_or_default();
+    let health = storage.run(move |storage| storage.doctor(rebuild)).await?;
+    tracing::info!("Storage checked: {health:?}");
+    if !health.rebuilt {
+        return Ok(health);
+    }
+    let (servers, current) = {
+        let state = server_state_mutex.lock().await;
+        let current = state.current.as_ref().map(|server| server.url.clone());
+        (state.servers.clone(), current)
+    };
+    let current_token = user_state_mutex.lock().await.token.clone();
+    let mut credentials = Vec::new();
+    for server in &servers {
+        let token = match sessions.get(&server.url).await {
+            Some(session) => Some(session.token),
+            None if current.as_ref() == Some(&server.url) => current_token.clone(),
+            None => None,
+        };
+        if let Some(access_token) = token {
+            credentials.push(ServerCredentials {
+                url: server.url.clone().into(),
+                access_token,
+            });
+        }
+    }
+    let settings = settings.get();
+    let post_density = *density.read().await;
+    storage
+        .run(move |storage| {
+            for credentials in credentials {
+                if storage.server_credentials(&credentials.url)?.is_none() {
+                    storage.store_credentials(&credentials)?;
+                }
+            }
+            storage.store_servers(&servers)?;
+            storage.set_settings(&settings)?;
+            storage.set_post_density(post_density)
+        })
+        .await?;
+    Ok(health)
+}
+
+/// Write servers, credentials, settings and undelivered posts to `path`
+/// encrypted with `passphrase`, so they can be imported on another machine
+#[tauri::command]
+pub async fn export_app_data(
+    path: String,
+    passphrase: String,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+    settings: State<'_, SettingsState>,
+    density: State<'_, RwLock<PostDensity>>,
+) -> Result<(), Error> {
+    let result = run_export_app_data(
+        path,
+        passphrase,
+        server_state_mutex,
+        storage,
+        settings,
+        density,
+    )
+    .await;
+    audit::record("export_app_data", None, &result);
+    result
+}
+
+async fn run_export_app_data(
+    path: String,
+    passphrase: String,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+    settings: State<'_, SettingsState>,
+    density: State<'_, RwLock<PostDensity>>,
+) -> Result<(), Error> {
+    let servers = server_state_mutex.lock().await.servers.clone();
+    let settings = settings.get();
+    let post_density = *density.read().await;
+    let (credentials, outbox, scheduled_posts) = storage
+        .run(|storage| {
+            Ok::<_, crate::errors::StorageError>((
+                storage.credentials()?,
+                storage.outbox()?,
+                storage.scheduled_posts()?,
+            ))
+        })
+        .await?;
+    let path = std::path::PathBuf::from(&path);
+    tokio::task::spawn_blocking(move || {
+        let data = app_data::AppData {
+            exported_at: now_millis(),
+            servers,
+            credentials,
+            settings,
+            post_density,
+            outbox,
+            scheduled_posts,
+        };
+        let file = app_data::seal(&data, &passphrase)?;
+        std::fs::write(&path, file)?;
+        tracing::info!(
+            "Exported data of {} servers to {}",
+            data.servers.len(),
+            path.display()
+        );
+        Ok::<_, Error>(())
+    })
+    .await??;
+    Ok(())
+}
+
+/// Import data written by [`export_app_data`]. Servers and credentials are
+/// added to known ones, replacing those with the same URL, and settings of
+/// export take effect. Sessions of imported credentials are opened on next
+/// start.
+#[tauri::command]
+pub async fn import_app_data(
+    path: String,
+    passphrase: String,
+    app: tauri::AppHandle,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+    density: State<'_, RwLock<PostDensity>>,
+) -> Result<ImportedAppData, Error> {
+    let result =
+        run_import_app_data(path, passphrase, app, server_state_mutex, storage, density).await;
+    audit::record("import_app_data", None, &result);
+    result
+}
+
+async fn run_import_app_data(
+    path: String,
+    passphrase: String,
+    app: tauri::AppHandle,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    storage: State<'_, StorageHandle>,
+    density: State<'_, RwLock<PostDensity>>,
+) -> Result<ImportedAppData, Error> {
+    let data = tokio::task::spawn_blocking(move || {
+        let file = std::fs::read(path)?;
+        Ok::<_, Error>(app_data::open(&file, &passphrase)?)
+    })
+    .await??;
+    let app_data::AppData {
+        servers,
+        credentials,
+        settings,
+        post_density,
+        outbox,
+        scheduled_posts,
+        ..
+    } = data;
+    let mut imported = storage
+        .run(move |storage| storage.import_app_data(credentials, outbox, scheduled_posts))
+        .await?;
+    {
+        let mut state = server_state_mutex.lock().await;
+        imported.servers = app_data::merge_servers(&mut state.servers, servers);
+        store_servers(&storage, &state.servers).await?;
+    }
+    settings::update(&app, settings).await?;
+    storage
+        .run(move |storage| storage.set_post_density(post_density))
+        .await?;
+    *density.write().await = post_density;
+    tracing::info!("Imported app data: {imported:?}");
+    Ok(imported)
+}
+
+/// Write zip with versions, connection state, settings and recent logs for
+/// bug reports, secrets are redacted. Saved to `path` or downloads
+/// directory, returns where it was saved.
+#[tauri::command]
+pub async fn export_diagnostics(
+    path: Option<String>,
+    app: tauri::AppHandle,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    settings: State<'_, SettingsState>,
+) -> Result<String, Error> {
+    let servers = server_state_mutex
+        .lock()
+        .await
+        .servers
+        .iter()
+        .map(|server| diagnostics::ServerDiagnostics {
+            url: server.url.to_string(),
+            version: server.capabilities.as_ref().map(|c| c.version.clone()),
+            build_number: server.capabilities.as_ref().map(|c| c.build_number.clone()),
+        })
+        .collect();
+    let generated_at = now_millis();
+    let report = diagnostics::Diagnostics {
+        app_version: app.package_info().version.to_string(),
+        os: std::env::consts::OS.to_owned(),
+        os_version: sysinfo::System::long_os_version(),
+        arch: std::env::consts::ARCH.to_owned(),
+        generated_at,
+        connection: connection::current(),
+        servers,
+        rate_limits: rate_limit::status(),
+        settings: diagnostics::redacted_settings(settings.get()),
+    };
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => directories::UserDirs::new()
+            .and_then(|dirs| dirs.download_dir().map(ToOwned::to_owned))
+            .unwrap_or_else(logging::log_dir)
+            .join(format!("worryless-diagnostics-{generated_at}.zip")),
+    };
+    let saved = path.clone();
+    tokio::task::spawn_blocking(move || {
+        let logs = logging::recent(diagnostics::LOG_LINES)?;
+        diagnostics::write_bundle(&saved, &report, &logs)
+    })
+    .await??;
+    tracing::info!("Diagnostics exported to {}", path.display());
+    Ok(path.display().to_string())
+}
+
+/// Application configuration, later changes are emitted as
+/// `settings-changed` events
+#[tauri::command]
+pub async fn get_settings(settings: State<'_, SettingsState>) -> Result<Settings, Error> {
+    Ok(settings.get())
+}
+
+/// Replace application configuration, returns it after too short
+/// intervals were raised and input was trimmed
+#[tauri::command]
+pub async fn update_settings(settings: Settings, app: tauri::AppHandle) -> Result<Settings, Error> {
+    let result = settings::update(&app, settings).await;
+    audit::record("update_settings", None, &result);
+    result
+}
+
+/// Current connectivity, later changes are emitted as
+/// `connection-state-changed` events
+#[tauri::command]
+pub async fn get_connection_state() -> Result<ConnectionState, Error> {
+    Ok(connection::current())
+}
+
+/// Request budgets reported by servers, only those not replenished yet
+#[tauri::command]
+pub async fn get_rate_limit_status() -> Result<Vec<RateLimitStatus>, Error> {
+    Ok(rate_limit::status())
+}
+
+/// Daily traffic per server over last `days` days (today only for `0`),
+/// all retained history when not specified
+#[tauri::command]
+pub async fn get_bandwidth_usage(
+    days: Option<u64>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Vec<BandwidthUsage>, Error> {
+    let mut usage = storage.run(bandwidth::usage).await?;
+    if let Some(days) = days {
+        let oldest = bandwidth::days_ago(days);
+        usage.retain(|usage| usage.day >= oldest);
+    }
+    Ok(usage)
+}
+
+/// Recorded state-changing commands, newest first
+#[tauri::command]
+pub async fn get_audit_log(
+    limit: Option<usize>,
+    storage: State<'_, StorageHandle>,
+) -> Result<Vec<AuditEntry>, Error> {
+    let mut entries = storage.run(audit::entries).await?;
+    if let Some(limit) = limit {
+        entries.truncate(limit);
+    }
+    Ok(entries)
+}
+
+#[tauri::command]
+pub async fn clear_audit_log(storage: State<'_, StorageHandle>) -> Result<(), Error> {
+    let result = run_clear_audit_log(storage).await;
+    audit::record("clear_audit_log", None, &result);
+    result
+}
+
+async fn run_clear_audit_log(storage: State<'_, StorageHandle>) -> Result<(), Error> {
+    storage.run(audit::clear).await?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audit_log_enabled() -> bool {
+    audit::is_enabled()
+}
+
+/// Turning audit log off stops recording, already recorded entries are kept
+/// until cleared
+#[tauri::command]
+pub async fn set_audit_log_enabled(
+    enabled: bool,
+    storage: State<'_, StorageHandle>,
+) -> Result<bool, Error> {
+    let result = storage
+        .run(move |storage| storage.set_audit_log_enabled(enabled))
+        .await
+        .map(|()| enabled)
+        .map_err(Error::from);
+    // Recorded before log is turned off, so the last entry tells it was
+    audit::record("set_audit_log_enabled", None, &result);
+    if result.is_ok() {
+        audit::configure(enabled);
+    }
+    result
+}
+
+#[tauri::command]
+pub async fn vault_account_binding(storage: State<'_, StorageHandle>) -> Result<bool, Error> {
+    storage
+        .run(|storage| Ok::<_, Error>(storage.account_bound()))
+        .await
+}
+
+/// Bind vault to OS account on this machine, so copied config directory
+/// can't unlock stored tokens elsewhere
+#[tauri::command]
+pub async fn set_vault_account_binding(
+    enabled: bool,
+    storage: State<'_, StorageHandle>,
+) -> Result<bool, Error> {
+    let result = run_set_vault_account_binding(enabled, storage).await;
+    audit::record("set_vault_account_binding", None, &result);
+    result
+}
+
+async fn run_set_vault_account_binding(
+    enabled: bool,
+    storage: State<'_, StorageHandle>,
+) -> Result<bool, Error> {
+    storage
+        .run(move |storage| storage.set_account_bound(enabled))
+        .await?;
+    Ok(enabled)
+}
+
+/// `mattermost://` link application was started with, given out only once.
+/// Links passed to it while running come as `deep-link` event.
+#[tauri::command]
+pub fn take_deep_link(deep_links: State<'_, DeepLinks>) -> Option<String> {
+    deep_links.take()
+}
+
+/// Remember that conversation was opened on current server. Opening it
+/// again right away only refreshes its timestamp.
+#[tauri::command]
+pub async fn record_navigation(
+    team_id: Option<TeamId>,
+    channel_id: ChannelId,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    navigation: State<'_, Mutex<NavigationHistory>>,
+) -> Result<NavigationSnapshot, Error> {
+    let server = current_server_url(&server_state_mutex).await?.into();
+    let mut navigation = navigation.lock().await;
+    navigation.visit(NavigationEntry {
+        server,
+        team_id,
+        channel_id,
+        visited_at: now_millis(),
+    });
+    Ok(navigation.snapshot())
+}
+
+#[tauri::command]
+pub async fn get_navigation_history(
+    navigation: State<'_, Mutex<NavigationHistory>>,
+) -> Result<NavigationSnapshot, Error> {
+    Ok(navigation.lock().await.snapshot())
+}
+
+/// Previous conversation, `None` at the beginning of history. Entry may
+/// belong to other server than the current one.
+#[tauri::command]
+pub async fn navigate_back(
+    navigation: State<'_, Mutex<NavigationHistory>>,
+) -> Result<Option<NavigationEntry>, Error> {
+    Ok(navigation.lock().await.back().cloned())
+}
+
+#[tauri::command]
+pub async fn navigate_forward(
+    navigation: State<'_, Mutex<NavigationHistory>>,
+) -> Result<Option<NavigationEntry>, Error> {
+    Ok(navigation.lock().await.forward().cloned())
+}
+
+/// Channels, direct messages and teams of current server matching `query`
+/// for quick switcher. Only cached data is searched, so it never waits for
+/// server.
+#[tauri::command]
+pub async fn quick_switch(
+    query: String,
+    user_state_mutex: State<'_, Mutex<UserState>>,
+    server_state_mutex: State<'_, Mutex<ServerState>>,
+    sessions: State<'_, Sessions>,
+    navigation: State<'_, Mutex<NavigationHistory>>,
+) -> Result<Vec<SwitchTarget>, Error> {
+    let server_url = current_server_url(&server_state_mutex).await?;
+    let members = sessions
+        .get(&server_url)
+        .await
+        .map(|session| session.channel_members)
+        .unwrap_or_default();
+    let recent = {
+        let server: ServerUrl = server_url.into();
+        let mut entries: Vec<NavigationEntry> = navigation
+            .lock()
+            .await
+            .snapshot()
+            .entries
+            .into_iter()
+            .filter(|entry| entry.server == server)
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.visited_at));
+        let mut recent: Vec<ChannelId> = Vec::new();
+        for entry in entries {
+            if !recent.contains(&entry.channel_id) {
+                recent.push(entry.channel_id);
+            }
+        }
+        recent
+    };
+    let user_state = user_state_mutex.lock().await;
+    Ok(switcher::rank(
+        &query,
+        user_state.channels.as_deref().unwrap_or_default(),
+        user_state.teams.as_deref().unwrap_or_default(),
+        &members,
+        &recent,
+        SWITCHER_RESULTS,
+    ))
+}
+
+/// Send action over WebSocket, it's queued while connection is down and
+/// dropped if it gets stale before connection is back
+#[tauri::command]
+pub async fn send_websocket_action(
+    action: WsAction,
+    websocket: State<'_, WebSocket>,
+) -> Result<(), Error> {
+    websocket.send(action).await;
+    Ok(())
+}
+
+/// Show typing indicator to other members of channel, or of thread when
+/// `parent_id` is given. Calls made within a few seconds of the last one
+/// are ignored, so it can be called on every keystroke.
+#[tauri::command]
+pub async fn send_typing(
+    channel_id: ChannelId,
+    parent_id: Option<PostId>,
+    websocket: State<'_, WebSocket>,
+) -> Result<bool, Error> {
+    let parent_id = parent_id.map(|id| id.to_string()).unwrap_or_default();
+    Ok(websocket.send_typing(channel_id, parent_id).await)
+}
+
+fn watch_later_of(
+    storage: &Storage,
+    server: &ServerUrl,
+) -> Result<Vec<WatchLaterItem>, crate::errors::StorageError> {
+    Ok(storage
+        .watch_later()?
+        .into_iter()
+        .filter(|item| &item.server == server)
+        .collect())
+}
+
+async fn store_servers(storage: &StorageHandle, servers: &[Server]) -> Result<(), Error> {
+    let servers = servers.to_vec();
+    storage
+        .run(move |storage| storage.store_servers(&servers))
+        .await?;
+    Ok(())
+}
+
+pub(crate) fn now_millis() -> Timestamp {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as Timestamp
+}
+
+/// `None` when no server is selected or it wasn't probed yet
+async fn current_capabilities(
+    server_state_mutex: &Mutex<ServerState>,
+) -> Option<ServerCapabilities> {
+    server_state_mutex
+        .lock()
+        .await
+        .current
+        .as_ref()?
+        .capabilities
+        .clone()
+}
+
+pub(crate) async fn current_server_url(
+    server_state_mutex: &Mutex<ServerState>,
+) -> Result<Url, Error> {
+    Ok(server_state_mutex
+        .lock()
+        .await
+        .current
+        .as_ref()
+        .ok_or(NativeError::ServerNotSelected)?
+        .url
+        .to_owned())
 }
@@ -0,0 +1,87 @@
+use serde::Serialize;
+use url::Url;
+
+/// Markdown offered in place of pasted link
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LinkSuggestion {
+    pub url: String,
+    pub title: String,
+    pub markdown: String,
+}
+
+/// Link when pasted text is nothing else but single http(s) URL
+pub fn pasted_url(text: &str) -> Option<Url> {
+    let text = text.trim();
+    if text.is_empty() || text.contains(char::is_whitespace) {
+        return None;
+    }
+    let url = Url::parse(text).ok()?;
+    (matches!(url.scheme(), "http" | "https") && url.host_str().is_some()).then_some(url)
+}
+
+/// Title used when page title isn't known, link without scheme, e.g.
+/// `github.com/rust-lang/rust`
+pub fn fallback_title(url: &Url) -> String {
+    let text = url.as_str();
+    let text = text.split_once("://").map_or(text, |(_, rest)| rest);
+    text.strip_suffix('/').unwrap_or(text).to_owned()
+}
+
+pub fn suggestion(url: &Url, title: Option<&str>) -> LinkSuggestion {
+    let title = title
+        .map(|title| title.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| fallback_title(url));
+    LinkSuggestion {
+        url: url.to_string(),
+        markdown: markdown_link(&title, url),
+        title,
+    }
+}
+
+/// `[title](url)` with characters which would end either part early
+/// escaped
+fn markdown_link(title: &str, url: &Url) -> String {
+    let mut text = String::with_capacity(title.len());
+    for c in title.chars() {
+        if matches!(c, '\\' | '[' | ']') {
+            text.push('\\');
+        }
+        text.push(c);
+    }
+    let target = url.as_str().replace('(', "%28").replace(')', "%29");
+    format!("[{text}]({target})")
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn only_bare_links() {
+        assert!(pasted_url(" https://example.com/a \n").is_some());
+        assert!(pasted_url("see https://example.com").is_none());
+        assert!(pasted_url("mailto:ops@example.com").is_none());
+        assert!(pasted_url("example.com").is_none());
+    }
+
+    #[test]
+    fn builds_markdown() {
+        let url = pasted_url("https://en.wikipedia.org/wiki/Rust_(programming_language)").unwrap();
+        assert_eq!(
+            suggestion(&url, Some("  Rust [programming\nlanguage] ")).markdown,
+            "[Rust \\[programming language\\]]\
+             (https://en.wikipedia.org/wiki/Rust_%28programming_language%29)"
+        );
+        let url = pasted_url("https://github.com/rust-lang/rust/").unwrap();
+        assert_eq!(
+            suggestion(&url, Some(" ")),
+            LinkSuggestion {
+                url: "https://github.com/rust-lang/rust/".to_owned(),
+                title: "github.com/rust-lang/rust".to_owned(),
+                markdown: "[github.com/rust-lang/rust](https://github.com/rust-lang/rust/)"
+                    .to_owned(),
+            }
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use std::sync::{Mutex, OnceLock};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+
+use crate::outbox::Outbox;
+use crate::shutdown;
+
+pub const CONNECTION_STATE_EVENT: &str = "connection-state-changed";
+
+/// Consecutive network failures after which server is considered offline
+const OFFLINE_AFTER_FAILURES: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    Connected,
+    /// No request has completed yet
+    Connecting,
+    Offline,
+    /// Server is reachable but misbehaves: 5xx responses, single network
+    /// failures or broken WebSocket
+    Degraded,
+}
+
+/// Observation reported by api layer or WebSocket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signal {
+    Reachable,
+    ServerError,
+    Unreachable,
+    WebSocket { healthy: bool },
+}
+
+#[derive(Debug)]
+struct Tracker {
+    state: ConnectionState,
+    failures: u32,
+    websocket_healthy: bool,
+}
+
+impl Default for Tracker {
+    fn default() -> Self {
+        Self {
+            state: ConnectionState::Connecting,
+            failures: 0,
+            websocket_healthy: true,
+        }
+    }
+}
+
+impl Tracker {
+    fn apply(&mut self, signal: Signal) -> ConnectionState {
+        self.state = match signal {
+            Signal::Reachable => {
+                self.failures = 0;
+                if self.websocket_healthy {
+                    ConnectionState::Connected
+                } else {
+                    ConnectionState::Degraded
+                }
+            }
+            Signal::ServerError => {
+                self.failures = 0;
+                ConnectionState::Degraded
+            }
+            Signal::Unreachable => {
+                self.failures += 1;
+                match self.state {
+                    ConnectionState::Connecting | ConnectionState::Offline => {
+                        ConnectionState::Offline
+                    }
+                    _ if self.failures >= OFFLINE_AFTER_FAILURES => ConnectionState::Offline,
+                    _ => ConnectionState::Degraded,
+                }
+            }
+            Signal::WebSocket { healthy } => {
+                self.websocket_healthy = healthy;
+                match self.state {
+                    ConnectionState::Connected if !healthy => ConnectionState::Degraded,
+                    ConnectionState::Degraded if healthy && self.failures == 0 => {
+                        ConnectionState::Connected
+                    }
+                    state => state,
+                }
+            }
+        };
+        self.state
+    }
+}
+
+struct Monitor {
+    tracker: Mutex<Tracker>,
+    sender: watch::Sender<ConnectionState>,
+}
+
+fn monitor() -> &'static Monitor {
+    static MONITOR: OnceLock<Monitor> = OnceLock::new();
+    MONITOR.get_or_init(|| Monitor {
+        tracker: Mutex::new(Tracker::default()),
+        sender: watch::channel(ConnectionState::Connecting).0,
+    })
+}
+
+/// Update connection state, subscribers are notified only on transitions
+pub fn report(signal: Signal) {
+    let monitor = monitor();
+    let state = monitor.tracker.lock().unwrap().apply(signal);
+    monitor.sender.send_if_modified(|current| {
+        let changed = *current != state;
+        if changed {
+            tracing::info!("Connection state {current:?} -> {state:?}");
+            *current = state;
+        }
+        changed
+    });
+}
+
+pub fn current() -> ConnectionState {
+    *monitor().sender.borrow()
+}
+
+/// Emit transitions to frontend and flush outbox when connection comes back
+pub fn spawn(app: AppHandle) {
+    let mut receiver = monitor().sender.subscribe();
+    tauri::async_runtime::spawn(async move {
+        while receiver.changed().await.is_ok() {
+            let state = *receiver.borrow_and_update();
+            app.emit_all(CONNECTION_STATE_EVENT, state).ok();
+            if state == ConnectionState::Connected && !shutdown::is_shutting_down() {
+                if let Err(e) = app.state::<Outbox>().flush(&app).await {
+                    tracing::warn!("Failed to flush outbox: {e}");
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn transitions() {
+        let mut tracker = Tracker::default();
+        assert_eq!(tracker.apply(Signal::Reachable), ConnectionState::Connected);
+        assert_eq!(
+            tracker.apply(Signal::Unreachable),
+            ConnectionState::Degraded
+        );
+        assert_eq!(
+            tracker.apply(Signal::Unreachable),
+            ConnectionState::Degraded
+        );
+        assert_eq!(tracker.apply(Signal::Unreachable), ConnectionState::Offline);
+        assert_eq!(tracker.apply(Signal::Reachable), ConnectionState::Connected);
+        assert_eq!(
+            tracker.apply(Signal::ServerError),
+            ConnectionState::Degraded
+        );
+        assert_eq!(tracker.apply(Signal::Reachable), ConnectionState::Connected);
+    }
+
+    #[test]
+    fn websocket_health() {
+        let mut tracker = Tracker::default();
+        tracker.apply(Signal::Reachable);
+        assert_eq!(
+            tracker.apply(Signal::WebSocket { healthy: false }),
+            ConnectionState::Degraded
+        );
+        assert_eq!(tracker.apply(Signal::Reachable), ConnectionState::Degraded);
+        assert_eq!(
+            tracker.apply(Signal::WebSocket { healthy: true }),
+            ConnectionState::Connected
+        );
+    }
+
+    #[test]
+    fn offline_from_start() {
+        let mut tracker = Tracker::default();
+        assert_eq!(tracker.apply(Signal::Unreachable), ConnectionState::Offline);
+        assert_eq!(
+            tracker.apply(Signal::WebSocket { healthy: true }),
+            ConnectionState::Offline
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Write};
+
+use chrono::{TimeZone, Utc};
+use models::*;
+
+/// Render pinned posts of channel as Markdown document, oldest post first.
+///
+/// `authors` maps user id to display name, posts of unknown authors are
+/// attributed to their user id. Dates are shown in `timezone`.
+pub fn pinned_digest<Tz>(
+    channel_name: &str,
+    pinned: &PostThread,
+    authors: &HashMap<String, String>,
+    timezone: &Tz,
+) -> String
+where
+    Tz: TimeZone,
+    Tz::Offset: Display,
+{
+    let mut posts: Vec<&Post> = pinned
+        .order
+        .iter()
+        .filter_map(|id| pinned.posts.get(id.as_str()))
+        .filter(|post| post.delete_at == 0)
+        .collect();
+    posts.sort_by_key(|post| post.create_at);
+
+    let mut out = format!("# Pinned messages in {channel_name}\n");
+    if posts.is_empty() {
+        out.push_str("\nNo pinned messages.\n");
+        return out;
+    }
+    for post in posts {
+        let author = post
+            .user_id
+            .as_ref()
+            .map(|id| {
+                authors
+                    .get(id.as_str())
+                    .map(String::as_str)
+                    .unwrap_or(id.as_str())
+            })
+            .unwrap_or("unknown");
+        let date = Utc
+            .timestamp_millis_opt(post.create_at as i64)
+            .single()
+            .map(|date| {
+                date.with_timezone(timezone)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        // Writing into String can't fail
+        let _ = write!(out, "\n## {author} — {date}\n\n");
+        for line in post.message.lines() {
+            let _ = writeln!(out, "> {line}");
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+    use crate::test_support;
+
+    fn post(id: &str, user_id: &str, create_at: Timestamp, message: &str) -> Post {
+        Post {
+            user_id: Some(UserId::new(user_id.to_owned())),
+            message: Message::new(message.to_owned()),
+            ..test_support::post(id, create_at)
+        }
+    }
+
+    #[test]
+    fn oldest_first_with_authors() {
+        let posts = [
+            post(
+                "b",
+                "u2",
+                1_715_774_400_000,
+                "Deploy checklist:\n1. tag\n2. push",
+            ),
+            post("a", "u1", 1_715_688_000_000, "Wiki lives at https://wiki"),
+        ];
+        let pinned = PostThread {
+            order: posts.iter().map(|post| post.id.clone()).collect(),
+            posts: posts
+                .into_iter()
+                .map(|post| (post.id.to_string(), post))
+                .collect(),
+            next_post_id: None,
+            prev_post_id: None,
+            has_next: false,
+        };
+        let authors = HashMap::from([("u1".to_owned(), "Maria".to_owned())]);
+
+        assert_eq!(
+            pinned_digest("Town Square", &pinned, &authors, &Utc),
+            "# Pinned messages in Town Square\n\
+             \n## Maria — 2024-05-14 12:00\n\n\
+             > Wiki lives at https://wiki\n\
+             \n## u2 — 2024-05-15 12:00\n\n\
+             > Deploy checklist:\n\
+             > 1. tag\n\
+             > 2. push\n"
+        );
+    }
+
+    #[test]
+    fn nothing_pinned() {
+        let pinned = PostThread {
+            order: vec![],
+            posts: HashMap::new(),
+            next_post_id: None,
+            prev_post_id: None,
+            has_next: false,
+        };
+        assert_eq!(
+            pinned_digest("Town Square", &pinned, &HashMap::new(), &Utc),
+            "# Pinned messages in Town Square\n\nNo pinned messages.\n"
+        );
+    }
+}
@@ -0,0 +1,436 @@
+use std::cmp::Ordering;
+use std::sync::Mutex;
+
+use chrono::{Datelike, Days, NaiveDateTime, NaiveTime, TimeZone, Timelike, Weekday};
+use models::*;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use url::Url;
+
+use crate::api::call_event::*;
+use crate::api::handle_request;
+use crate::commands::current_server_url;
+use crate::errors::{Error, NativeError};
+use crate::settings::SettingsState;
+use crate::states::{ServerState, UserState};
+
+pub const DND_CHANGED_EVENT: &str = "dnd-changed";
+
+/// Do not disturb of logged in user, notifications are suppressed while it's
+/// active
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dnd {
+    pub enabled: bool,
+    /// Milliseconds, `None` until turned off
+    pub until: Option<Timestamp>,
+}
+
+impl Dnd {
+    pub fn from_status(status: &UserStatus) -> Self {
+        Self {
+            enabled: status.status == Presence::Dnd,
+            until: (status.status == Presence::Dnd && status.dnd_end_time > 0)
+                .then(|| status.dnd_end_time.saturating_mul(1000)),
+        }
+    }
+
+    pub fn is_active(&self, now: Timestamp) -> bool {
+        self.enabled && self.until.map_or(true, |until| now < until)
+    }
+
+    /// Same effect at `now`, expired do not disturb is the same as disabled
+    fn matches(&self, other: &Self, now: Timestamp) -> bool {
+        match (self.is_active(now), other.is_active(now)) {
+            (true, true) => self.until == other.until,
+            (false, false) => true,
+            _ => false,
+        }
+    }
+}
+
+/// What to do with local state after comparing it with server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Already in sync
+    Keep,
+    /// Take state from server, e.g. it was changed in another client
+    Adopt(Dnd),
+    /// Local change didn't reach server yet, send it again
+    Push(Dnd),
+}
+
+/// Conflict rules:
+///
+/// 1. Local change not yet confirmed by server wins, it's the newest one user
+///    made on this device.
+/// 2. Otherwise server wins, change made in another client shows up here.
+/// 3. Expired do not disturb is disabled one, no matter which side reports it,
+///    so expiry alone never causes push.
+pub fn resolve(local: Dnd, pending: bool, server: &UserStatus, now: Timestamp) -> Resolution {
+    let remote = Dnd::from_status(server);
+    match (pending, local.matches(&remote, now)) {
+        (true, true) => Resolution::Adopt(remote),
+        (true, false) => Resolution::Push(local),
+        (false, true) => Resolution::Keep,
+        (false, false) => Resolution::Adopt(remote),
+    }
+}
+
+#[derive(Debug, Default)]
+struct Local {
+    dnd: Dnd,
+    /// Changed on this device and not confirmed by server yet
+    pending: bool,
+    /// Bumped by each local change, answer of server to older state must not
+    /// overwrite newer one
+    generation: u64,
+}
+
+#[derive(Default)]
+pub struct DndManager(Mutex<Local>);
+
+impl DndManager {
+    pub fn current(&self) -> Dnd {
+        self.0.lock().unwrap().dnd
+    }
+
+    pub fn suppresses_notifications(&self, now: Timestamp) -> bool {
+        self.current().is_active(now)
+    }
+
+    /// Changed by user on this device, kept pending until server confirms.
+    /// Returns generation of the change.
+    pub fn set_local(&self, dnd: Dnd) -> u64 {
+        let mut local = self.0.lock().unwrap();
+        local.dnd = dnd;
+        local.pending = true;
+        local.generation += 1;
+        local.generation
+    }
+
+    /// State confirmed by server or changed elsewhere, adopted only when
+    /// there was no local change since `generation`
+    pub fn adopt(&self, generation: u64, dnd: Dnd) -> bool {
+        let mut local = self.0.lock().unwrap();
+        if local.generation != generation {
+            return false;
+        }
+        local.dnd = dnd;
+        local.pending = false;
+        true
+    }
+
+    /// Generation of last local change, for [`adopt`](Self::adopt)
+    pub fn generation(&self) -> u64 {
+        self.0.lock().unwrap().generation
+    }
+
+    /// State, whether it's pending and its generation
+    fn snapshot(&self) -> (Dnd, bool, u64) {
+        let local = self.0.lock().unwrap();
+        (local.dnd, local.pending, local.generation)
+    }
+}
+
+/// Send do not disturb to server as user status, disabling it makes user
+/// online
+pub async fn push(
+    client: &Client,
+    server_url: &Url,
+    token: &AccessToken,
+    user_id: &UserId,
+    dnd: Dnd,
+) -> Result<UserStatus, Error> {
+    let event = ApiEvent::SetUserStatus {
+        user_id: user_id.clone(),
+        status: if dnd.enabled {
+            Presence::Dnd
+        } else {
+            Presence::Online
+        },
+        dnd_end_time: dnd.until.filter(|_| dnd.enabled).map(|until| until / 1000),
+    };
+    let Response::Status(status) = handle_request(client, server_url, &event, Some(token)).await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    Ok(status)
+}
+
+/// Apply do not disturb locally right away and set it as server status.
+/// When server can't be reached background [`sync`] sends it later.
+pub async fn set(app: &AppHandle, dnd: Dnd) -> Result<(), Error> {
+    let manager = app.state::<DndManager>();
+    let (token, user_id) = {
+        let user_state = app.state::<tokio::sync::Mutex<UserState>>();
+        let user_state = user_state.lock().await;
+        let token = user_state.token.clone().ok_or(NativeError::NotLoggedIn)?;
+        let user_id = user_state.id.clone().ok_or(NativeError::NotLoggedIn)?;
+        (token, user_id)
+    };
+    let server_url = current_server_url(&app.state::<tokio::sync::Mutex<ServerState>>()).await?;
+    let generation = manager.set_local(dnd);
+    let client = app.state::<Client>();
+    match push(&client, &server_url, &token, &user_id, dnd).await {
+        Ok(status) => {
+            manager.adopt(generation, Dnd::from_status(&status));
+        }
+        Err(Error::RequestFailed(e)) => tracing::warn!("Do not disturb not sent yet: {e}"),
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
+
+/// Whether `schedule` keeps do not disturb on at local time `at`
+fn scheduled_at(schedule: &DndSchedule, at: NaiveDateTime) -> bool {
+    if !schedule.enabled {
+        return false;
+    }
+    if schedule.weekends && matches!(at.weekday(), Weekday::Sat | Weekday::Sun) {
+        return true;
+    }
+    let minute = (at.hour() * 60 + at.minute()) as u16;
+    match schedule.start.cmp(&schedule.end) {
+        Ordering::Less => (schedule.start..schedule.end).contains(&minute),
+        Ordering::Greater => minute >= schedule.start || minute < schedule.end,
+        Ordering::Equal => false,
+    }
+}
+
+/// End of scheduled window local time `at` falls in, e.g. Monday morning
+/// for Friday evening when weekends are included. `None` outside of
+/// schedule.
+pub fn scheduled_until(schedule: &DndSchedule, at: NaiveDateTime) -> Option<NaiveDateTime> {
+    if !scheduled_at(schedule, at) {
+        return None;
+    }
+    let end = NaiveTime::from_hms_opt(
+        u32::from(schedule.end / 60),
+        u32::from(schedule.end % 60),
+        0,
+    )?;
+    // Window can only end at its end time or when weekend is over
+    (0..=8)
+        .filter_map(|days| at.date().checked_add_days(Days::new(days)))
+        .flat_map(|day| [day.and_time(NaiveTime::MIN), day.and_time(end)])
+        .filter(|boundary| *boundary > at)
+        .find(|boundary| !scheduled_at(schedule, *boundary))
+}
+
+/// Turn do not disturb on when scheduled window starts, until its end.
+/// `applied` is end of window do not disturb was turned on for, so user can
+/// still turn it off inside the window without schedule turning it back on.
+pub async fn follow_schedule(app: &AppHandle, applied: &mut Option<Timestamp>) {
+    let schedule = app
+        .state::<SettingsState>()
+        .get()
+        .notifications
+        .dnd_schedule;
+    let until = scheduled_until(&schedule, chrono::Local::now().naive_local()).and_then(|until| {
+        let until = chrono::Local.from_local_datetime(&until);
+        until.earliest().or(until.latest())
+    });
+    let Some(until) = until.map(|until| until.timestamp_millis() as Timestamp) else {
+        *applied = None;
+        return;
+    };
+    if *applied == Some(until) {
+        return;
+    }
+    *applied = Some(until);
+    let manager = app.state::<DndManager>();
+    if manager.current().is_active(until - 1) {
+        return;
+    }
+    let dnd = Dnd {
+        enabled: true,
+        until: Some(until),
+    };
+    tracing::info!("Scheduled do not disturb until {until}");
+    match set(app, dnd).await {
+        Ok(()) => {
+            app.emit_all(DND_CHANGED_EVENT, manager.current()).ok();
+        }
+        Err(e) => tracing::warn!("Failed to turn on scheduled do not disturb: {e}"),
+    }
+}
+
+/// Bring local do not disturb and server status in sync, see [`resolve`]
+pub async fn sync(
+    app: &AppHandle,
+    client: &Client,
+    server_url: &Url,
+    token: &AccessToken,
+    user_id: &UserId,
+    now: Timestamp,
+) -> Result<(), Error> {
+    let Response::Status(status) = handle_request(
+        client,
+        server_url,
+        &ApiEvent::UserStatus(user_id.clone()),
+        Some(token),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let manager = app.state::<DndManager>();
+    let (local, pending, generation) = manager.snapshot();
+    let adopted = match resolve(local, pending, &status, now) {
+        Resolution::Keep => return Ok(()),
+        Resolution::Adopt(dnd) => dnd,
+        Resolution::Push(dnd) => {
+            Dnd::from_status(&push(client, server_url, token, user_id, dnd).await?)
+        }
+    };
+    // User changed it while server was asked, next sync sends the change
+    if !manager.adopt(generation, adopted) {
+        return Ok(());
+    }
+    if adopted != local {
+        tracing::info!("Do not disturb changed: {adopted:?}");
+        app.emit_all(DND_CHANGED_EVENT, adopted).ok();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    const NOW: Timestamp = 1_700_000_000_000;
+
+    fn status(presence: Presence, dnd_end_time: Timestamp) -> UserStatus {
+        UserStatus {
+            user_id: UserId::new("me".to_owned()),
+            status: presence,
+            manual: true,
+            last_activity_at: 0,
+            dnd_end_time,
+        }
+    }
+
+    fn dnd(until: Option<Timestamp>) -> Dnd {
+        Dnd {
+            enabled: true,
+            until,
+        }
+    }
+
+    #[test]
+    fn server_wins_without_local_change() {
+        let hour_later = NOW / 1000 + 3600;
+        assert_eq!(
+            resolve(
+                Dnd::default(),
+                false,
+                &status(Presence::Dnd, hour_later),
+                NOW
+            ),
+            Resolution::Adopt(dnd(Some(hour_later * 1000)))
+        );
+        assert_eq!(
+            resolve(dnd(None), false, &status(Presence::Online, 0), NOW),
+            Resolution::Adopt(Dnd::default())
+        );
+        assert_eq!(
+            resolve(dnd(None), false, &status(Presence::Dnd, 0), NOW),
+            Resolution::Keep
+        );
+    }
+
+    #[test]
+    fn pending_local_change_wins() {
+        let local = dnd(Some(NOW + 60_000));
+        assert_eq!(
+            resolve(local, true, &status(Presence::Away, 0), NOW),
+            Resolution::Push(local)
+        );
+        assert_eq!(
+            resolve(Dnd::default(), true, &status(Presence::Dnd, 0), NOW),
+            Resolution::Push(Dnd::default())
+        );
+        // Server already has it, pending flag is cleared by adopting
+        assert_eq!(
+            resolve(
+                local,
+                true,
+                &status(Presence::Dnd, (NOW + 60_000) / 1000),
+                NOW
+            ),
+            Resolution::Adopt(local)
+        );
+    }
+
+    #[test]
+    fn schedule_window() {
+        let at = |day: u32, hour: u32, minute: u32| {
+            // 2024-01-01 is Monday
+            chrono::NaiveDate::from_ymd_opt(2024, 1, day)
+                .unwrap()
+                .and_hms_opt(hour, minute, 0)
+                .unwrap()
+        };
+        let schedule = DndSchedule {
+            enabled: true,
+            ..DndSchedule::default()
+        };
+        assert_eq!(scheduled_until(&schedule, at(2, 12, 0)), None);
+        assert_eq!(scheduled_until(&schedule, at(2, 18, 0)), Some(at(3, 9, 0)));
+        assert_eq!(scheduled_until(&schedule, at(3, 8, 59)), Some(at(3, 9, 0)));
+        assert_eq!(scheduled_until(&schedule, at(3, 9, 0)), None);
+        // Friday evening lasts until Monday morning
+        assert_eq!(scheduled_until(&schedule, at(5, 20, 0)), Some(at(8, 9, 0)));
+        assert_eq!(scheduled_until(&schedule, at(7, 12, 0)), Some(at(8, 9, 0)));
+
+        let lunch = DndSchedule {
+            enabled: true,
+            start: 12 * 60,
+            end: 13 * 60,
+            weekends: false,
+        };
+        assert_eq!(scheduled_until(&lunch, at(2, 12, 30)), Some(at(2, 13, 0)));
+        assert_eq!(scheduled_until(&lunch, at(6, 11, 0)), None);
+        assert_eq!(scheduled_until(&DndSchedule::default(), at(2, 20, 0)), None);
+    }
+
+    #[test]
+    fn keeps_newer_local_change() {
+        let manager = DndManager::default();
+        let (_, _, generation) = manager.snapshot();
+        // Sync asks server while user turns do not disturb on
+        let changed = manager.set_local(dnd(None));
+        assert!(!manager.adopt(generation, Dnd::default()));
+        assert_eq!(manager.snapshot(), (dnd(None), true, changed));
+
+        assert!(manager.adopt(changed, dnd(None)));
+        assert_eq!(manager.snapshot(), (dnd(None), false, changed));
+    }
+
+    #[test]
+    fn huge_end_time_saturates() {
+        let remote = Dnd::from_status(&status(Presence::Dnd, Timestamp::MAX / 10));
+        assert_eq!(remote.until, Some(Timestamp::MAX));
+        assert!(remote.is_active(NOW));
+    }
+
+    #[test]
+    fn expired_is_disabled() {
+        let expired = dnd(Some(NOW - 1));
+        assert!(!expired.is_active(NOW));
+        assert_eq!(
+            resolve(expired, true, &status(Presence::Online, 0), NOW),
+            Resolution::Adopt(Dnd::default())
+        );
+        assert_eq!(
+            resolve(
+                Dnd::default(),
+                false,
+                &status(Presence::Dnd, NOW / 1000 - 60),
+                NOW
+            ),
+            Resolution::Keep
+        );
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use models::*;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+/// Category custom emoji are listed under in picker
+const CUSTOM_CATEGORY: &str = "custom";
+
+/// Custom emoji created meanwhile show up in picker after this long
+const LIST_TTL: Duration = Duration::from_secs(10 * 60);
+/// Failed list isn't fetched again before this long, otherwise every
+/// keystroke of search would retry it
+const FAILED_LIST_TTL: Duration = Duration::from_secs(60);
+
+/// Emoji offered by picker, standard ones come from bundled index
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EmojiMatch {
+    /// Shortcode inserted into message, without colons
+    pub name: String,
+    /// Other shortcodes of the same emoji
+    pub aliases: Vec<String>,
+    /// Category of picker, e.g. `smileys-emotion` or `custom`
+    pub category: &'static str,
+    /// Emoji character, `None` for custom emoji
+    pub unicode: Option<&'static str>,
+    /// Image of custom emoji is fetched by its id
+    pub custom_id: Option<EmojiId>,
+}
+
+/// Custom emoji referenced in message together with image which can be used
+/// directly as `src` of `img`
+#[derive(Debug, Clone, Serialize)]
+pub struct EmojiImage {
+    pub id: EmojiId,
+    pub name: EmojiName,
+    /// `data:` URL of emoji image
+    pub image: String,
+}
+
+/// Custom emoji looked up by name and their images.
+///
+/// Images are kept on disk in application cache directory since they never
+/// change, emoji can only be deleted and created again with new id. Lookups
+/// by name are kept in memory only, including names which turned out to be
+/// standard emoji.
+pub struct EmojiCache {
+    dir: PathBuf,
+    /// Keyed by server URL and emoji name
+    by_name: Mutex<HashMap<(String, String), Option<MetaEmoji>>>,
+    /// All custom emoji of server and when the list expires, keyed by
+    /// server URL
+    all: Mutex<HashMap<String, (Instant, Vec<MetaEmoji>)>>,
+}
+
+impl Default for EmojiCache {
+    fn default() -> Self {
+        let dir = directories::BaseDirs::new()
+            .map(|dirs| dirs.cache_dir().to_owned())
+            .unwrap_or_else(std::env::temp_dir)
+            .join("worryless")
+            .join("emoji");
+        Self::with_dir(dir)
+    }
+}
+
+impl EmojiCache {
+    pub fn with_dir(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            by_name: Mutex::new(HashMap::new()),
+            all: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `None` means name was not looked up yet, `Some(None)` that server has
+    /// no custom emoji of that name
+    pub async fn lookup(&self, server: &ServerUrl, name: &str) -> Option<Option<MetaEmoji>> {
+        self.by_name
+            .lock()
+            .await
+            .get(&(server.as_str().to_owned(), name.to_owned()))
+            .cloned()
+    }
+
+    pub async fn remember(&self, server: &ServerUrl, name: &str, emoji: Option<MetaEmoji>) {
+        self.by_name
+            .lock()
+            .await
+            .insert((server.as_str().to_owned(), name.to_owned()), emoji);
+    }
+
+    pub async fn remember_all(&self, server: &ServerUrl, emojis: &[MetaEmoji]) {
+        let mut by_name = self.by_name.lock().await;
+        for emoji in emojis {
+            by_name.insert(
+                (server.as_str().to_owned(), emoji.name.to_string()),
+                Some(emoji.clone()),
+            );
+        }
+    }
+
+    /// Complete list of custom emoji, `None` until it's stored and once it
+    /// expires
+    pub async fn all(&self, server: &ServerUrl) -> Option<Vec<MetaEmoji>> {
+        self.all_at(server, Instant::now()).await
+    }
+
+    async fn all_at(&self, server: &ServerUrl, now: Instant) -> Option<Vec<MetaEmoji>> {
+        let all = self.all.lock().await;
+        let (expires, emojis) = all.get(server.as_str())?;
+        (*expires > now).then(|| emojis.clone())
+    }
+
+    pub async fn store_all(&self, server: &ServerUrl, emojis: Vec<MetaEmoji>) {
+        self.remember_all(server, &emojis).await;
+        self.all.lock().await.insert(
+            server.as_str().to_owned(),
+            (Instant::now() + LIST_TTL, emojis),
+        );
+    }
+
+    /// Server has custom emoji turned off or list couldn't be fetched,
+    /// search goes on without them for a while
+    pub async fn store_failed(&self, server: &ServerUrl) {
+        self.all.lock().await.insert(
+            server.as_str().to_owned(),
+            (Instant::now() + FAILED_LIST_TTL, Vec::new()),
+        );
+    }
+
+    pub async fn image(&self, server: &ServerUrl, id: &EmojiId) -> Option<Vec<u8>> {
+        tokio::fs::read(self.image_path(server, id)?).await.ok()
+    }
+
+    pub async fn store_image(
+        &self,
+        server: &ServerUrl,
+        id: &EmojiId,
+        image: &[u8],
+    ) -> std::io::Result<()> {
+        let path = self.image_path(server, id).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{id} isn't emoji id"),
+            )
+        })?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, image).await
+    }
+
+    /// Drop everything cached for server, returns whether there were any
+    /// images on disk
+    pub async fn forget_server(&self, server: &ServerUrl) -> bool {
+        self.by_name
+            .lock()
+            .await
+            .retain(|(cached, _), _| cached != server.as_str());
+        self.all.lock().await.remove(server.as_str());
+        tokio::fs::remove_dir_all(self.server_dir(server))
+            .await
+            .is_ok()
+    }
+
+    /// `None` when id of server isn't Mattermost id, which could name file
+    /// outside of cache
+    fn image_path(&self, server: &ServerUrl, id: &EmojiId) -> Option<PathBuf> {
+        let id = id.as_str();
+        let valid = id.len() == 26
+            && id
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit());
+        valid.then(|| self.server_dir(server).join(id))
+    }
+
+    fn server_dir(&self, server: &ServerUrl) -> PathBuf {
+        let server: String = server
+            .as_str()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        self.dir.join(server)
+    }
+}
+
+/// Names of all `:emoji:` shortcodes used in message, in order of first
+/// occurrence
+pub fn shortcodes(message: &str) -> Vec<&str> {
+    let mut names: Vec<&str> = Vec::new();
+    let mut rest = message;
+    while let Some(start) = rest.find(':') {
+        let candidate = &rest[start + 1..];
+        let len = candidate
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+')))
+            .unwrap_or(candidate.len());
+        if len > 0 && candidate[len..].starts_with(':') {
+            let name = &candidate[..len];
+            if !names.contains(&name) {
+                names.push(name);
+            }
+            rest = &candidate[len + 1..];
+        } else {
+            rest = candidate;
+        }
+    }
+    names
+}
+
+/// Category slug the web app uses for group of standard emoji
+fn category(group: emojis::Group) -> &'static str {
+    match group {
+        emojis::Group::SmileysAndEmotion => "smileys-emotion",
+        emojis::Group::PeopleAndBody => "people-body",
+        emojis::Group::AnimalsAndNature => "animals-nature",
+        emojis::Group::FoodAndDrink => "food-drink",
+        emojis::Group::TravelAndPlaces => "travel-places",
+        emojis::Group::Activities => "activities",
+        emojis::Group::Objects => "objects",
+        emojis::Group::Symbols => "symbols",
+        emojis::Group::Flags => "flags",
+    }
+}
+
+/// Standard emoji which have a shortcode followed by `custom` emoji, in
+/// order of picker
+fn index(custom: &[MetaEmoji]) -> impl Iterator<Item = EmojiMatch> + '_ {
+    let standard = emojis::iter().filter_map(|emoji| {
+        let mut shortcodes = emoji.shortcodes().map(str::to_owned);
+        Some(EmojiMatch {
+            name: shortcodes.next()?,
+            aliases: shortcodes.collect(),
+            category: category(emoji.group()),
+            unicode: Some(emoji.as_str()),
+            custom_id: None,
+        })
+    });
+    let custom = custom.iter().map(|emoji| EmojiMatch {
+        name: emoji.name.to_string(),
+        aliases: Vec::new(),
+        category: CUSTOM_CATEGORY,
+        unicode: None,
+        custom_id: Some(emoji.id.clone()),
+    });
+    standard.chain(custom)
+}
+
+/// Up to `limit` emoji matching `query` in name, alias or category. Exact
+/// names come first, then prefixes, then names containing query and
+/// finally emoji of matching category. Empty query lists all emoji.
+pub fn search(query: &str, custom: &[MetaEmoji], limit: usize) -> Vec<EmojiMatch> {
+    let query = query.trim().trim_matches(':').to_lowercase();
+    let rank = |emoji: &EmojiMatch| {
+        if query.is_empty() {
+            return Some(0);
+        }
+        let names = || std::iter::once(&emoji.name).chain(&emoji.aliases);
+        if names().any(|name| *name == query) {
+            Some(0)
+        } else if names().any(|name| name.starts_with(&query)) {
+            Some(1)
+        } else if names().any(|name| name.contains(&query)) {
+            Some(2)
+        } else if emoji.category.contains(&query) {
+            Some(3)
+        } else {
+            None
+        }
+    };
+    let mut matches: Vec<(u8, EmojiMatch)> = index(custom)
+        .filter_map(|emoji| Some((rank(&emoji)?, emoji)))
+        .collect();
+    // Stable sort keeps order of picker within rank
+    matches.sort_by_key(|(rank, _)| *rank);
+    matches
+        .into_iter()
+        .take(limit)
+        .map(|(_, emoji)| emoji)
+        .collect()
+}
+
+/// Encode image as `data:` URL, type is guessed from content since server
+/// accepts only a few image formats
+pub fn data_url(image: &[u8]) -> String {
+    let mime = match image {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [b'G', b'I', b'F', ..] => "image/gif",
+        [0xFF, 0xD8, ..] => "image/jpeg",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        _ => "application/octet-stream",
+    };
+    format!(
+        "data:{mime};base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(image)
+    )
+}
+
+#[cfg(test)]
+mod check {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn message_shortcodes() {
+        assert_eq!(
+            shortcodes(":parrot: deployed at 10:30 :+1: :party-parrot: :parrot:"),
+            ["parrot", "+1", "party-parrot"]
+        );
+        assert_eq!(shortcodes("ratio 1:2:3"), ["2"]);
+        assert!(shortcodes("no emoji: here").is_empty());
+    }
+
+    #[test]
+    fn searches_standard_and_custom() {
+        let custom: MetaEmoji = serde_json::from_value(serde_json::json!({
+            "id": "e1",
+            "creator_id": "u1",
+            "name": "party-parrot",
+            "create_at": 0,
+        }))
+        .unwrap();
+        let custom = [custom];
+
+        let found = search(":thumbsup", &custom, 5);
+        assert_eq!(found[0].unicode, Some("👍"));
+        assert!(found[0].name == "thumbsup" || found[0].aliases.contains(&"thumbsup".to_owned()));
+
+        let found = search("parrot", &custom, 50);
+        let parrot = found.iter().position(|emoji| emoji.name == "parrot");
+        let party = found.iter().position(|emoji| emoji.name == "party-parrot");
+        assert!(parrot.unwrap() < party.unwrap());
+        assert_eq!(found[party.unwrap()].category, "custom");
+        assert_eq!(
+            found[party.unwrap()].custom_id.as_ref().unwrap().as_str(),
+            "e1"
+        );
+
+        let flags = search("flags", &[], 1000);
+        assert!(flags.len() > 200);
+        assert!(flags.iter().all(|emoji| emoji.category == "flags"
+            || emoji.name.contains("flags")
+            || emoji.aliases.iter().any(|alias| alias.contains("flags"))));
+
+        assert_eq!(search("", &custom, 10).len(), 10);
+        assert!(search("no-such-emoji", &custom, 10).is_empty());
+    }
+
+    #[test]
+    fn image_type() {
+        assert!(data_url(b"GIF89a...").starts_with("data:image/gif;base64,R0lG"));
+        assert!(data_url(&[0x89, b'P', b'N', b'G']).starts_with("data:image/png;"));
+    }
+
+    #[tokio::test]
+    async fn list_expires() {
+        let cache = EmojiCache::with_dir(std::env::temp_dir());
+        let server = ServerUrl::parse("https://mm.example.com").unwrap();
+        assert!(cache.all(&server).await.is_none());
+
+        cache.store_failed(&server).await;
+        let stored = Instant::now();
+        assert_eq!(cache.all(&server).await, Some(Vec::new()));
+        let later = stored + FAILED_LIST_TTL;
+        assert!(cache.all_at(&server, later).await.is_none());
+
+        cache.store_all(&server, Vec::new()).await;
+        let stored = Instant::now();
+        assert!(cache
+            .all_at(&server, stored + FAILED_LIST_TTL)
+            .await
+            .is_some());
+        assert!(cache.all_at(&server, stored + LIST_TTL).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn disk_cache() {
+        let dir = TempDir::new("emoji").unwrap();
+        let cache = EmojiCache::with_dir(dir.path().to_owned());
+        let server = ServerUrl::parse("https://mm.example.com").unwrap();
+        let id = EmojiId::new("ab5dw6dqstnmdkmxpx3zzijfwc".to_owned());
+
+        assert!(cache.image(&server, &id).await.is_none());
+        cache.store_image(&server, &id, b"GIF89a").await.unwrap();
+        assert_eq!(cache.image(&server, &id).await.unwrap(), b"GIF89a");
+
+        assert!(cache.forget_server(&server).await);
+        assert!(cache.image(&server, &id).await.is_none());
+
+        for id in ["../../escaped", "ab5dw6dqstnmdkmxpx3zzijf/c", "abc"] {
+            let id = EmojiId::new(id.to_owned());
+            assert!(cache.store_image(&server, &id, b"GIF89a").await.is_err());
+            assert!(cache.image(&server, &id).await.is_none());
+        }
+        assert!(!dir.path().parent().unwrap().join("escaped").exists());
+    }
+}
@@ -0,0 +1,123 @@
+use zbox::{Cipher, Cost, Crypto, Salt, SALT_SIZE};
+
+/// Version, cipher, key derivation cost and salt following prefix of
+/// envelope, authenticated together with ciphertext
+const HEADER_LEN: usize = 3 + SALT_SIZE;
+
+/// Why envelope didn't open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OpenError {
+    /// Not an envelope of this format and version, or it asks for key
+    /// derivation cost this client doesn't write
+    Malformed,
+    /// Wrong passphrase or tampered ciphertext
+    Decrypt,
+}
+
+/// Encrypt `plain` with key derived from passphrase, result is `prefix`,
+/// header and ciphertext.
+///
+/// AES-256-GCM is used when CPU supports it, XChaCha20-Poly1305 otherwise.
+/// Cipher is recorded in header so both can be decrypted anywhere. Key
+/// derivation is slow on purpose.
+pub(crate) fn seal(
+    prefix: &[u8],
+    version: u8,
+    plain: &[u8],
+    passphrase: &str,
+) -> Result<Vec<u8>, zbox::Error> {
+    zbox::init_env();
+    let cost = Cost::default();
+    let crypto = Crypto::new(cost, Cipher::Aes).or_else(|_| Crypto::new(cost, Cipher::Xchacha))?;
+    let salt = Salt::new();
+    let mut envelope = prefix.to_vec();
+    envelope.extend([version, crypto.cipher.into(), cost.to_u8()]);
+    envelope.extend_from_slice(salt.as_ref());
+    let key = crypto.hash_pwd(passphrase, &salt)?;
+    let ciphertext = crypto.encrypt_with_ad(plain, &key.value, &envelope)?;
+    envelope.extend(ciphertext);
+    Ok(envelope)
+}
+
+/// Plain data of envelope written by [`seal`] with the same `prefix` and
+/// `version`
+pub(crate) fn open(
+    envelope: &[u8],
+    prefix: &[u8],
+    version: u8,
+    passphrase: &str,
+) -> Result<Vec<u8>, OpenError> {
+    let header_len = prefix.len() + HEADER_LEN;
+    if envelope.len() <= header_len || !envelope.starts_with(prefix) {
+        return Err(OpenError::Malformed);
+    }
+    let (header, ciphertext) = envelope.split_at(header_len);
+    let [found, cipher, cost] = header[prefix.len()..prefix.len() + 3] else {
+        return Err(OpenError::Malformed);
+    };
+    let cipher = match cipher {
+        0 => Cipher::Xchacha,
+        1 => Cipher::Aes,
+        _ => return Err(OpenError::Malformed),
+    };
+    // Cost comes from whoever wrote envelope, one above what this client
+    // writes would pin CPU and memory of reader
+    let known = Cost::default();
+    if found != version || cost != known.to_u8() {
+        return Err(OpenError::Malformed);
+    }
+    zbox::init_env();
+    let crypto = Crypto::new(known, cipher).map_err(|e| {
+        tracing::error!("Envelope cipher unavailable: {e}");
+        OpenError::Decrypt
+    })?;
+    if ciphertext.len() < crypto.encrypted_len(0) {
+        return Err(OpenError::Decrypt);
+    }
+    let key = crypto
+        .hash_pwd(passphrase, &Salt::from_slice(&header[prefix.len() + 3..]))
+        .map_err(|_| OpenError::Decrypt)?;
+    crypto
+        .decrypt_with_ad(ciphertext, &key.value, header)
+        .map_err(|_| OpenError::Decrypt)
+}
+
+#[cfg(test)]
+mod check {
+    use zbox::{MemLimit, OpsLimit};
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let sealed = seal(b"PRE", 1, b"secret", "correct horse").unwrap();
+        assert!(sealed.starts_with(b"PRE"));
+        assert_eq!(
+            open(&sealed, b"PRE", 1, "correct horse").unwrap(),
+            b"secret"
+        );
+        assert_eq!(
+            open(&sealed, b"PRE", 1, "wrong horse"),
+            Err(OpenError::Decrypt)
+        );
+        assert_eq!(
+            open(&sealed, b"PRE", 2, "correct horse"),
+            Err(OpenError::Malformed)
+        );
+        assert_eq!(
+            open(&sealed, b"OTH", 1, "correct horse"),
+            Err(OpenError::Malformed)
+        );
+        assert_eq!(open(b"PRE", b"PRE", 1, "x"), Err(OpenError::Malformed));
+    }
+
+    #[test]
+    fn refuses_unknown_cost() {
+        let mut sealed = seal(&[], 1, b"secret", "correct horse").unwrap();
+        sealed[2] = Cost::new(OpsLimit::Sensitive, MemLimit::Sensitive).to_u8();
+        assert_eq!(
+            open(&sealed, &[], 1, "correct horse"),
+            Err(OpenError::Malformed)
+        );
+    }
+}
@@ -1,6 +1,9 @@
 use std::fmt;
 
 use models::*;
+use serde::Serialize;
+
+use crate::i18n::{self, Params};
 
 #[derive(thiserror::Error, Debug)]
 pub enum StorageError {
@@ -10,6 +13,24 @@ pub enum StorageError {
     Io(#[from] std::io::Error),
     #[error("Failed to deserialize credentials: {_0}")]
     De(#[from] bincode::Error),
+    #[error("Failed to (de)serialize cached data: {_0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Storage is already closed")]
+    Closed,
+    #[error("Unable to access OS keyring: {_0}")]
+    Keyring(String),
+    #[error(
+        "Data was stored by newer version of application (layout {found}, supported {supported})"
+    )]
+    UnsupportedSchema { found: u32, supported: u32 },
+    #[error("Application is already running (process {pid})")]
+    AlreadyRunning { pid: u32 },
+    #[error("Storage is damaged, it can be repaired in settings")]
+    Damaged,
+    #[error("Vault password doesn't match, storage stays locked")]
+    WrongPassword,
+    #[error("Unable to access configuration directory: {_0}")]
+    ConfigDir(String),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -26,12 +47,324 @@ pub enum NativeError {
     FetchChannels,
     #[error("Unable to fetch posts from mattermost server")]
     FetchPosts,
+    #[error("Unable to fetch channel members from mattermost server")]
+    FetchChannelMembers,
+    #[error("Unable to fetch channel statistics from mattermost server")]
+    FetchChannelStats,
+    #[error("Unable to fetch user from mattermost server")]
+    FetchUser,
+    #[error("Unable to fetch user statuses from mattermost server")]
+    FetchStatuses,
+    #[error("Unable to set user status")]
+    SetStatus,
+    #[error("Unable to autocomplete users")]
+    AutocompleteUsers,
+    #[error("Unable to fetch custom emoji from mattermost server")]
+    FetchEmoji,
+    #[error("Unable to fetch preferences from mattermost server")]
+    FetchPreferences,
+    #[error("Unable to save preferences")]
+    SavePreferences,
+    #[error("Unable to fetch sidebar categories from mattermost server")]
+    FetchCategories,
+    #[error("Unable to save sidebar categories")]
+    SaveCategories,
+    #[error("Unable to create post")]
+    CreatePost,
+    #[error("Unable to fetch link preview from mattermost server")]
+    FetchLinkPreview,
+    #[error("Unable to fetch storage quota from mattermost server")]
+    FetchQuota,
+    #[error("File attachments are disabled on this server")]
+    AttachmentsDisabled,
+    #[error("{name} has {size} bytes, server accepts files of at most {limit} bytes")]
+    AttachmentTooLarge { name: String, size: u64, limit: u64 },
+    #[error("{name} can't be uploaded, server accepts only {allowed} files")]
+    FileTypeNotAllowed { name: String, allowed: String },
+    #[error("Attachments take {size} bytes, only {remaining} bytes of storage are left")]
+    StorageQuotaExceeded { size: u64, remaining: u64 },
+    #[error("Passphrase of secure snippet can't be empty")]
+    SnippetPassphrase,
+    #[error("Unable to encrypt secure snippet")]
+    SnippetEncrypt,
+    #[error("Wrong passphrase or damaged secure snippet")]
+    SnippetDecrypt,
+    #[error("Post is not a secure snippet")]
+    NotSecureSnippet,
+    #[error("Passphrase of exported data can't be empty")]
+    AppDataPassphrase,
+    #[error("Unable to encrypt exported data")]
+    AppDataEncrypt,
+    #[error("Wrong passphrase or damaged export file")]
+    AppDataDecrypt,
+    #[error("File is not data exported by this application")]
+    NotAppDataExport,
+    #[error("Unable to download attached file")]
+    DownloadFile,
+    #[error("Unable to change pinned state of post")]
+    PinPost,
+    #[error("Unable to update channel")]
+    UpdateChannel,
+    #[error("Unable to create channel")]
+    CreateChannel,
+    #[error("Unable to change channel members")]
+    ChannelMembership,
+    #[error("Unable to update notification settings of channel")]
+    UpdateNotifyProps,
+    #[error("Channel name can't be empty")]
+    InvalidChannelName,
+    #[error("Unable to mark thread as unread")]
+    MarkThreadUnread,
+    #[error("Unable to mark channel as read")]
+    ViewChannel,
+    #[error("Unable to search posts")]
+    SearchPosts,
+    #[error("Channel is not a direct message channel")]
+    NotDirectChannel,
     #[error("Unable to perform login, mattermost server return an error")]
     PerformLogin,
+    #[error("Login can't be empty")]
+    InvalidLogin,
+    #[error("Password can't be empty")]
+    InvalidPassword,
+    #[error("Mattermost server didn't send access token on login")]
+    MissingToken,
+    #[error("Access token is invalid or expired")]
+    InvalidToken,
+    #[error("Unable to log in with SSO")]
+    SsoFailed,
+    #[error("SSO login was not finished in time")]
+    SsoTimeout,
+    #[error("Unable to log out from mattermost server")]
+    Logout,
+    #[error("Server doesn't look like mattermost server")]
+    ProbeServer,
+    #[error("Server address is not valid http(s) URL")]
+    InvalidServerUrl,
+    #[error("Server name can't be empty")]
+    InvalidServerName,
+    #[error("Server with the same name or address already exists")]
+    DuplicateServer,
+    #[error("User is not member of this team")]
+    UnknownTeam,
+    #[error("Link doesn't point to post on current server")]
+    InvalidPermalink,
+    #[error("Clipboard doesn't hold an image")]
+    ClipboardImage,
+    #[error("Unable to upload file")]
+    UploadFile,
+    #[error("Unable to fetch call state, Calls plugin may not be installed")]
+    FetchCallState,
+    #[error("Calls are disabled in this channel")]
+    CallsDisabled,
+    #[error("Scheduled time must be in the future")]
+    InvalidScheduleTime,
     #[error("Unknown server")]
     UnknownServer,
+    #[error("User is not logged in")]
+    NotLoggedIn,
+    #[error("Proxy is not valid http(s) or socks5 URL")]
+    InvalidProxy,
+    #[error("CA certificate is not valid PEM certificate")]
+    InvalidCertificate,
+    #[error("Unable to change launch at login")]
+    AutoStart,
+    #[error("Shortcut is not valid or is used by another application")]
+    InvalidShortcut,
+    #[error("WebSocket connection was closed by server")]
+    WebSocketClosed,
+    #[error("Request was superseded by newer one")]
+    Superseded,
+}
+
+impl NativeError {
+    /// Stable identifier frontend can match on, unlike message it never
+    /// changes
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NativeError::ServerNotSelected => "server_not_selected",
+            NativeError::UnexpectedResponse => "unexpected_response",
+            NativeError::FetchTeams => "fetch_teams",
+            NativeError::FetchTeamMembers => "fetch_team_members",
+            NativeError::FetchChannels => "fetch_channels",
+            NativeError::FetchPosts => "fetch_posts",
+            NativeError::FetchChannelMembers => "fetch_channel_members",
+            NativeError::FetchChannelStats => "fetch_channel_stats",
+            NativeError::FetchUser => "fetch_user",
+            NativeError::FetchStatuses => "fetch_statuses",
+            NativeError::SetStatus => "set_status",
+            NativeError::AutocompleteUsers => "autocomplete_users",
+            NativeError::FetchEmoji => "fetch_emoji",
+            NativeError::FetchPreferences => "fetch_preferences",
+            NativeError::SavePreferences => "save_preferences",
+            NativeError::FetchCategories => "fetch_categories",
+            NativeError::SaveCategories => "save_categories",
+            NativeError::CreatePost => "create_post",
+            NativeError::FetchLinkPreview => "fetch_link_preview",
+            NativeError::FetchQuota => "fetch_quota",
+            NativeError::AttachmentsDisabled => "attachments_disabled",
+            NativeError::AttachmentTooLarge { .. } => "attachment_too_large",
+            NativeError::FileTypeNotAllowed { .. } => "file_type_not_allowed",
+            NativeError::StorageQuotaExceeded { .. } => "storage_quota_exceeded",
+            NativeError::SnippetPassphrase => "snippet_passphrase",
+            NativeError::SnippetEncrypt => "snippet_encrypt",
+            NativeError::SnippetDecrypt => "snippet_decrypt",
+            NativeError::NotSecureSnippet => "not_secure_snippet",
+            NativeError::AppDataPassphrase => "app_data_passphrase",
+            NativeError::AppDataEncrypt => "app_data_encrypt",
+            NativeError::AppDataDecrypt => "app_data_decrypt",
+            NativeError::NotAppDataExport => "not_app_data_export",
+            NativeError::DownloadFile => "download_file",
+            NativeError::PinPost => "pin_post",
+            NativeError::UpdateChannel => "update_channel",
+            NativeError::CreateChannel => "create_channel",
+            NativeError::ChannelMembership => "channel_membership",
+            NativeError::UpdateNotifyProps => "update_notify_props",
+            NativeError::InvalidChannelName => "invalid_channel_name",
+            NativeError::MarkThreadUnread => "mark_thread_unread",
+            NativeError::ViewChannel => "view_channel",
+            NativeError::SearchPosts => "search_posts",
+            NativeError::NotDirectChannel => "not_direct_channel",
+            NativeError::PerformLogin => "perform_login",
+            NativeError::InvalidLogin => "invalid_login",
+            NativeError::InvalidPassword => "invalid_password",
+            NativeError::MissingToken => "missing_token",
+            NativeError::InvalidToken => "invalid_token",
+            NativeError::SsoFailed => "sso_failed",
+            NativeError::SsoTimeout => "sso_timeout",
+            NativeError::Logout => "logout",
+            NativeError::ProbeServer => "probe_server",
+            NativeError::InvalidServerUrl => "invalid_server_url",
+            NativeError::InvalidServerName => "invalid_server_name",
+            NativeError::DuplicateServer => "duplicate_server",
+            NativeError::UnknownTeam => "unknown_team",
+            NativeError::InvalidPermalink => "invalid_permalink",
+            NativeError::ClipboardImage => "clipboard_image",
+            NativeError::UploadFile => "upload_file",
+            NativeError::FetchCallState => "fetch_call_state",
+            NativeError::CallsDisabled => "calls_disabled",
+            NativeError::InvalidScheduleTime => "invalid_schedule_time",
+            NativeError::UnknownServer => "unknown_server",
+            NativeError::NotLoggedIn => "not_logged_in",
+            NativeError::InvalidProxy => "invalid_proxy",
+            NativeError::InvalidCertificate => "invalid_certificate",
+            NativeError::AutoStart => "auto_start",
+            NativeError::InvalidShortcut => "invalid_shortcut",
+            NativeError::WebSocketClosed => "web_socket_closed",
+            NativeError::Superseded => "superseded",
+        }
+    }
+
+    /// Values message is built from, for translations to place
+    pub fn params(&self) -> Params {
+        match self {
+            NativeError::AttachmentTooLarge { name, size, limit } => Params::from([
+                ("name", name.as_str().into()),
+                ("size", (*size).into()),
+                ("limit", (*limit).into()),
+            ]),
+            NativeError::FileTypeNotAllowed { name, allowed } => Params::from([
+                ("name", name.as_str().into()),
+                ("allowed", allowed.as_str().into()),
+            ]),
+            NativeError::StorageQuotaExceeded { size, remaining } => {
+                Params::from([("size", (*size).into()), ("remaining", (*remaining).into())])
+            }
+            _ => Params::new(),
+        }
+    }
 }
 
+/// Kinds of errors whose message comes from this application, server sends
+/// its own message with the rest. Catalogs of other languages translate each.
+#[cfg(test)]
+pub(crate) const LOCAL_KINDS: &[&str] = &[
+    "server_not_selected",
+    "unexpected_response",
+    "fetch_teams",
+    "fetch_team_members",
+    "fetch_channels",
+    "fetch_posts",
+    "fetch_channel_members",
+    "fetch_channel_stats",
+    "fetch_user",
+    "fetch_statuses",
+    "set_status",
+    "autocomplete_users",
+    "fetch_emoji",
+    "fetch_preferences",
+    "save_preferences",
+    "fetch_categories",
+    "save_categories",
+    "create_post",
+    "fetch_link_preview",
+    "fetch_quota",
+    "attachments_disabled",
+    "attachment_too_large",
+    "file_type_not_allowed",
+    "storage_quota_exceeded",
+    "snippet_passphrase",
+    "snippet_encrypt",
+    "snippet_decrypt",
+    "not_secure_snippet",
+    "app_data_passphrase",
+    "app_data_encrypt",
+    "app_data_decrypt",
+    "not_app_data_export",
+    "download_file",
+    "pin_post",
+    "update_channel",
+    "create_channel",
+    "channel_membership",
+    "update_notify_props",
+    "invalid_channel_name",
+    "mark_thread_unread",
+    "view_channel",
+    "search_posts",
+    "not_direct_channel",
+    "perform_login",
+    "invalid_login",
+    "invalid_password",
+    "missing_token",
+    "invalid_token",
+    "sso_failed",
+    "sso_timeout",
+    "logout",
+    "probe_server",
+    "invalid_server_url",
+    "invalid_server_name",
+    "duplicate_server",
+    "unknown_team",
+    "invalid_permalink",
+    "clipboard_image",
+    "upload_file",
+    "fetch_call_state",
+    "calls_disabled",
+    "invalid_schedule_time",
+    "unknown_server",
+    "not_logged_in",
+    "invalid_proxy",
+    "invalid_certificate",
+    "auto_start",
+    "invalid_shortcut",
+    "web_socket_closed",
+    "superseded",
+    "network",
+    "websocket",
+    "storage_closed",
+    "keyring",
+    "unsupported_schema",
+    "already_running",
+    "storage_damaged",
+    "wrong_password",
+    "config_dir",
+    "storage",
+    "io",
+    "invalid_url",
+    "invalid_response",
+    "internal",
+];
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
@@ -44,15 +377,351 @@ pub enum Error {
     PoisonError(String),
     #[error(transparent)]
     ApiError(#[from] ServerApiError),
+    #[error("Permission denied: {}", .0.message)]
+    PermissionDenied(ServerApiError),
+    #[error("Not found: {}", .0.message)]
+    NotFound(ServerApiError),
+    /// `retry_after` is in seconds
+    #[error("Too many requests: {}", .error.message)]
+    RateLimited {
+        retry_after: Option<u64>,
+        error: ServerApiError,
+    },
     #[error(transparent)]
     Url(#[from] url::ParseError),
     #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
     Storage(#[from] StorageError),
     #[error(transparent)]
     RequestFailed(#[from] ClientFailed),
+    #[error(transparent)]
+    Deserialization(#[from] DeserializationError),
+    #[error(transparent)]
+    Task(#[from] tokio::task::JoinError),
+    #[error(transparent)]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
 }
 
 #[derive(Debug, derive_more::Display, thiserror::Error)]
 pub struct ClientFailed {
     pub(crate) reason: String,
 }
+
+/// Response body which doesn't match model
+#[derive(Debug, thiserror::Error)]
+#[error("Unexpected response from {endpoint}: {reason}")]
+pub struct DeserializationError {
+    /// Path of request, e.g. `/api/v4/users/me`
+    pub(crate) endpoint: String,
+    pub(crate) reason: String,
+    /// Start of body with secrets scrubbed
+    pub(crate) snippet: String,
+}
+
+/// Error as it's sent to frontend
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IpcError {
+    pub kind: &'static str,
+    /// Catalog key of message, `error-` followed by kind in kebab case
+    pub key: String,
+    /// Values of placeholders in message of `key`
+    pub params: Params,
+    /// Message in locale of settings when it has translation
+    pub message: String,
+    /// HTTP status of failed request, `None` when error didn't come from
+    /// server
+    pub status_code: Option<u16>,
+    /// Id of request in server log
+    pub request_id: Option<String>,
+    /// Id of error message of server, e.g. `api.context.permissions.app_error`
+    pub error_id: Option<String>,
+    /// Seconds after which rate limited request can be retried
+    pub retry_after: Option<u64>,
+}
+
+impl Error {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Native(e) => e.kind(),
+            Error::ApiError(e) => match e.status_code {
+                400 => "bad_request",
+                401 => "unauthorized",
+                403 => "permission_denied",
+                404 => "not_found",
+                413 => "too_large",
+                429 => "rate_limited",
+                500.. => "server_error",
+                _ => "api_error",
+            },
+            Error::PermissionDenied(_) => "permission_denied",
+            Error::NotFound(_) => "not_found",
+            Error::RateLimited { .. } => "rate_limited",
+            Error::RequestFailed(_) => "network",
+            Error::WebSocket(_) => "websocket",
+            Error::Storage(StorageError::Closed) => "storage_closed",
+            Error::Storage(StorageError::Keyring(_)) => "keyring",
+            Error::Storage(StorageError::UnsupportedSchema { .. }) => "unsupported_schema",
+            Error::Storage(StorageError::AlreadyRunning { .. }) => "already_running",
+            Error::Storage(StorageError::Damaged) => "storage_damaged",
+            Error::Storage(StorageError::WrongPassword) => "wrong_password",
+            Error::Storage(StorageError::ConfigDir(_)) => "config_dir",
+            Error::Storage(_) => "storage",
+            Error::Io(_) => "io",
+            Error::Url(_) => "invalid_url",
+            Error::Json(_) | Error::Deserialization(_) => "invalid_response",
+            Error::FormatError(_) | Error::PoisonError(_) | Error::Task(_) => "internal",
+        }
+    }
+
+    pub fn params(&self) -> Params {
+        match self {
+            Error::Native(e) => e.params(),
+            Error::Storage(StorageError::Keyring(reason) | StorageError::ConfigDir(reason)) => {
+                Params::from([("reason", reason.as_str().into())])
+            }
+            Error::Storage(StorageError::UnsupportedSchema { found, supported }) => Params::from([
+                ("found", (*found).into()),
+                ("supported", (*supported).into()),
+            ]),
+            Error::Storage(StorageError::AlreadyRunning { pid }) => {
+                Params::from([("pid", (*pid).into())])
+            }
+            // Translated message keeps what exactly failed
+            Error::Io(_)
+            | Error::Url(_)
+            | Error::Json(_)
+            | Error::Deserialization(_)
+            | Error::RequestFailed(_)
+            | Error::WebSocket(_)
+            | Error::Storage(_)
+            | Error::FormatError(_)
+            | Error::PoisonError(_)
+            | Error::Task(_) => Params::from([("reason", self.to_string().into())]),
+            _ => Params::new(),
+        }
+    }
+}
+
+impl From<&Error> for IpcError {
+    fn from(error: &Error) -> Self {
+        let key = format!("error-{}", error.kind().replace('_', "-"));
+        let params = error.params();
+        match error {
+            // Message of server is meant for user and server already
+            // translated it, rest of it is in other fields
+            Error::ApiError(e)
+            | Error::PermissionDenied(e)
+            | Error::NotFound(e)
+            | Error::RateLimited { error: e, .. } => Self {
+                kind: error.kind(),
+                key,
+                params,
+                message: e.message.clone(),
+                status_code: u16::try_from(e.status_code).ok(),
+                request_id: e.request_id.clone().filter(|id| !id.is_empty()),
+                error_id: Some(e.id.clone()).filter(|id| !id.is_empty()),
+                retry_after: match error {
+                    Error::RateLimited { retry_after, .. } => *retry_after,
+                    _ => None,
+                },
+            },
+            _ => Self {
+                kind: error.kind(),
+                message: i18n::translate(&key, &params).unwrap_or_else(|| error.to_string()),
+                key,
+                params,
+                status_code: None,
+                request_id: None,
+                error_id: None,
+                retry_after: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn structured() {
+        let error = Error::ApiError(ServerApiError {
+            id: "api.context.permissions.app_error".to_owned(),
+            message: "You do not have the appropriate permissions.".to_owned(),
+            request_id: Some("8nbs6zx4rjd5".to_owned()),
+            status_code: 403,
+        });
+        assert_eq!(
+            serde_json::to_value(IpcError::from(&error)).unwrap(),
+            serde_json::json!({
+                "kind": "permission_denied",
+                "key": "error-permission-denied",
+                "params": {},
+                "message": "You do not have the appropriate permissions.",
+                "status_code": 403,
+                "request_id": "8nbs6zx4rjd5",
+                "error_id": "api.context.permissions.app_error",
+                "retry_after": null,
+            })
+        );
+
+        let error = Error::RateLimited {
+            retry_after: Some(3),
+            error: ServerApiError {
+                id: String::new(),
+                message: "Too Many Requests".to_owned(),
+                request_id: None,
+                status_code: 429,
+            },
+        };
+        let ipc = IpcError::from(&error);
+        assert_eq!(ipc.kind, "rate_limited");
+        assert_eq!(ipc.retry_after, Some(3));
+        assert_eq!(ipc.error_id, None);
+
+        let error = Error::RequestFailed(ClientFailed {
+            reason: "connection refused".to_owned(),
+        });
+        let ipc = IpcError::from(&error);
+        assert_eq!(ipc.kind, "network");
+        assert_eq!(ipc.status_code, None);
+
+        let error = Error::Native(NativeError::AttachmentTooLarge {
+            name: "a.iso".to_owned(),
+            size: 2,
+            limit: 1,
+        });
+        let ipc = IpcError::from(&error);
+        assert_eq!(ipc.kind, "attachment_too_large");
+        assert_eq!(ipc.key, "error-attachment-too-large");
+        assert_eq!(ipc.params["limit"], 1);
+        assert_eq!(
+            ipc.message,
+            "a.iso has 2 bytes, server accepts files of at most 1 bytes"
+        );
+    }
+
+    /// Error of every variant whose kind is local
+    fn local_errors() -> Vec<Error> {
+        let native = [
+            NativeError::ServerNotSelected,
+            NativeError::UnexpectedResponse,
+            NativeError::FetchTeams,
+            NativeError::FetchTeamMembers,
+            NativeError::FetchChannels,
+            NativeError::FetchPosts,
+            NativeError::FetchChannelMembers,
+            NativeError::FetchChannelStats,
+            NativeError::FetchUser,
+            NativeError::FetchStatuses,
+            NativeError::SetStatus,
+            NativeError::AutocompleteUsers,
+            NativeError::FetchEmoji,
+            NativeError::FetchPreferences,
+            NativeError::SavePreferences,
+            NativeError::FetchCategories,
+            NativeError::SaveCategories,
+            NativeError::CreatePost,
+            NativeError::FetchLinkPreview,
+            NativeError::FetchQuota,
+            NativeError::AttachmentsDisabled,
+            NativeError::AttachmentTooLarge {
+                name: "a.iso".to_owned(),
+                size: 2,
+                limit: 1,
+            },
+            NativeError::FileTypeNotAllowed {
+                name: "a.exe".to_owned(),
+                allowed: "png".to_owned(),
+            },
+            NativeError::StorageQuotaExceeded {
+                size: 2,
+                remaining: 1,
+            },
+            NativeError::SnippetPassphrase,
+            NativeError::SnippetEncrypt,
+            NativeError::SnippetDecrypt,
+            NativeError::NotSecureSnippet,
+            NativeError::AppDataPassphrase,
+            NativeError::AppDataEncrypt,
+            NativeError::AppDataDecrypt,
+            NativeError::NotAppDataExport,
+            NativeError::DownloadFile,
+            NativeError::PinPost,
+            NativeError::UpdateChannel,
+            NativeError::CreateChannel,
+            NativeError::ChannelMembership,
+            NativeError::UpdateNotifyProps,
+            NativeError::InvalidChannelName,
+            NativeError::MarkThreadUnread,
+            NativeError::ViewChannel,
+            NativeError::SearchPosts,
+            NativeError::NotDirectChannel,
+            NativeError::PerformLogin,
+            NativeError::InvalidLogin,
+            NativeError::InvalidPassword,
+            NativeError::MissingToken,
+            NativeError::InvalidToken,
+            NativeError::SsoFailed,
+            NativeError::SsoTimeout,
+            NativeError::Logout,
+            NativeError::ProbeServer,
+            NativeError::InvalidServerUrl,
+            NativeError::InvalidServerName,
+            NativeError::DuplicateServer,
+            NativeError::UnknownTeam,
+            NativeError::InvalidPermalink,
+            NativeError::ClipboardImage,
+            NativeError::UploadFile,
+            NativeError::FetchCallState,
+            NativeError::CallsDisabled,
+            NativeError::InvalidScheduleTime,
+            NativeError::UnknownServer,
+            NativeError::NotLoggedIn,
+            NativeError::InvalidProxy,
+            NativeError::InvalidCertificate,
+            NativeError::AutoStart,
+            NativeError::InvalidShortcut,
+            NativeError::WebSocketClosed,
+            NativeError::Superseded,
+        ];
+        let storage = [
+            StorageError::Closed,
+            StorageError::Keyring("locked".to_owned()),
+            StorageError::UnsupportedSchema {
+                found: 9,
+                supported: 3,
+            },
+            StorageError::AlreadyRunning { pid: 1 },
+            StorageError::Damaged,
+            StorageError::WrongPassword,
+            StorageError::ConfigDir("no home".to_owned()),
+            StorageError::Io(std::io::ErrorKind::Other.into()),
+        ];
+        let mut errors: Vec<Error> = native.into_iter().map(Error::from).collect();
+        errors.extend(storage.into_iter().map(Error::from));
+        errors.extend([
+            Error::RequestFailed(ClientFailed {
+                reason: "refused".to_owned(),
+            }),
+            Error::WebSocket(tokio_tungstenite::tungstenite::Error::ConnectionClosed),
+            Error::Io(std::io::ErrorKind::Other.into()),
+            Error::Url(url::ParseError::EmptyHost),
+            Error::Json(serde_json::from_str::<u8>("x").unwrap_err()),
+            Error::PoisonError("poisoned".to_owned()),
+        ]);
+        errors
+    }
+
+    #[test]
+    fn local_kinds_are_listed() {
+        let kinds: Vec<&str> = local_errors().iter().map(Error::kind).collect();
+        for kind in &kinds {
+            assert!(LOCAL_KINDS.contains(kind), "{kind} isn't in LOCAL_KINDS");
+        }
+        for kind in LOCAL_KINDS {
+            assert!(kinds.contains(kind), "{kind} has no local error");
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Write};
+
+use chrono::{TimeZone, Utc};
+use models::*;
+use serde::{Deserialize, Serialize};
+
+use crate::markdown;
+
+/// Channel history is walked at most this far back, archival of larger
+/// channels is job for server side compliance export
+pub const MAX_EXPORT_POSTS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelExportFormat {
+    Json,
+    Csv,
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThreadExportFormat {
+    Markdown,
+    Html,
+}
+
+/// Creation times of exported posts in milliseconds, both ends inclusive,
+/// missing end is unbounded
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct ExportRange {
+    pub since: Option<Timestamp>,
+    pub until: Option<Timestamp>,
+}
+
+impl ExportRange {
+    pub fn contains(&self, created_at: Timestamp) -> bool {
+        !self.is_before(created_at) && self.until.map_or(true, |until| created_at <= until)
+    }
+
+    /// Post is older than range, so are all posts on later pages
+    pub fn is_before(&self, created_at: Timestamp) -> bool {
+        self.since.is_some_and(|since| created_at < since)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExportedChannel {
+    pub path: String,
+    pub posts: usize,
+    /// Files saved into attachments folder
+    pub attachments: usize,
+    /// Files which couldn't be downloaded, export is written without them
+    pub failed_attachments: usize,
+}
+
+/// Post as written to JSON export
+#[derive(Serialize)]
+struct ExportedPost<'a> {
+    id: &'a str,
+    create_at: Timestamp,
+    edit_at: Timestamp,
+    user_id: Option<&'a str>,
+    author: &'a str,
+    /// Post which thread this reply belongs to
+    root_id: Option<&'a str>,
+    message: &'a str,
+    files: Vec<&'a str>,
+}
+
+/// Display name of post author, user id when name isn't known
+pub fn author<'a>(post: &'a Post, authors: &'a HashMap<String, String>) -> &'a str {
+    post.user_id
+        .as_ref()
+        .map(|id| {
+            authors
+                .get(id.as_str())
+                .map(String::as_str)
+                .unwrap_or(id.as_str())
+        })
+        .unwrap_or("unknown")
+}
+
+/// `created_at` in `timezone` down to minutes
+pub fn local_time<Tz>(created_at: Timestamp, timezone: &Tz) -> String
+where
+    Tz: TimeZone,
+    Tz::Offset: Display,
+{
+    Utc.timestamp_millis_opt(created_at as i64)
+        .single()
+        .map(|date| {
+            date.with_timezone(timezone)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+/// Files attached to post which weren't deleted
+pub fn attached_files(post: &Post) -> impl Iterator<Item = &MetaFile> {
+    post.metadata
+        .iter()
+        .flat_map(|metadata| &metadata.files)
+        .filter(|file| file.delete_at == 0)
+}
+
+/// Name attachment is saved under, id keeps files of the same name apart
+/// and path separators are replaced so it stays in export folder
+pub fn attachment_file_name(file: &MetaFile) -> String {
+    let name: String = file
+        .name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    format!("{}-{}", file.id, name.trim_start_matches('.'))
+}
+
+/// Render channel `posts`, oldest first, in `format`. Dates of CSV and text
+/// are shown in `timezone`, JSON keeps timestamps.
+pub fn render_channel<Tz>(
+    format: ChannelExportFormat,
+    channel_name: &str,
+    posts: &[Post],
+    authors: &HashMap<String, String>,
+    timezone: &Tz,
+) -> Result<String, serde_json::Error>
+where
+    Tz: TimeZone,
+    Tz::Offset: Display,
+{
+    match format {
+        ChannelExportFormat::Json => {
+            let posts: Vec<ExportedPost> = posts
+                .iter()
+                .map(|post| ExportedPost {
+                    id: post.id.as_str(),
+                    create_at: post.create_at,
+                    edit_at: post.edit_at,
+                    user_id: post.user_id.as_ref().map(|id| id.as_str()),
+                    author: author(post, authors),
+                    root_id: root_id(post),
+                    message: post.message.as_str(),
+                    files: attached_files(post)
+                        .map(|file| file.name.as_str())
+                        .collect(),
+                })
+                .collect();
+            serde_json::to_string_pretty(&serde_json::json!({
+                "channel": channel_name,
+                "posts": posts,
+            }))
+        }
+        ChannelExportFormat::Csv => {
+            let mut out = String::from("id,date,author,root_id,message,files\r\n");
+            for post in posts {
+                let files: Vec<&str> = attached_files(post)
+                    .map(|file| file.name.as_str())
+                    .collect();
+                let fields = [
+                    post.id.as_str(),
+                    &local_time(post.create_at, timezone),
+                    author(post, authors),
+                    root_id(post).unwrap_or_default(),
+                    post.message.as_str(),
+                    &files.join("; "),
+                ];
+                let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                out.push_str(&row.join(","));
+                out.push_str("\r\n");
+            }
+            Ok(out)
+        }
+        ChannelExportFormat::Text => {
+            let mut out = format!("# {channel_name}\n");
+            for post in posts {
+                // Replies are indented under thread they belong to
+                let indent = if root_id(post).is_some() { "    " } else { "" };
+                // Writing into String can't fail
+                let _ = write!(
+                    out,
+                    "\n{indent}[{}] {}:",
+                    local_time(post.create_at, timezone),
+                    author(post, authors)
+                );
+                for line in post.message.lines() {
+                    let _ = write!(out, "\n{indent}  {line}");
+                }
+                for file in attached_files(post) {
+                    let _ = write!(out, "\n{indent}  [attachment: {}]", file.name);
+                }
+                out.push('\n');
+            }
+            Ok(out)
+        }
+    }
+}
+
+/// Render root post and replies of `thread`, oldest first, for pasting into
+/// tickets or documents. Every post is quoted under its author and time
+/// in `timezone`.
+pub fn render_thread<Tz>(
+    format: ThreadExportFormat,
+    thread: &PostThread,
+    authors: &HashMap<String, String>,
+    timezone: &Tz,
+) -> String
+where
+    Tz: TimeZone,
+    Tz::Offset: Display,
+{
+    let mut posts: Vec<&Post> = thread
+        .posts
+        .values()
+        .filter(|post| post.delete_at == 0)
+        .collect();
+    posts.sort_by_key(|post| post.create_at);
+    let mut out = String::new();
+    match format {
+        ThreadExportFormat::Markdown => {
+            for (index, post) in posts.into_iter().enumerate() {
+                if index > 0 {
+                    out.push('\n');
+                }
+                // Writing into String can't fail
+                let _ = writeln!(
+                    out,
+                    "**{}** — {}\n",
+                    author(post, authors),
+                    local_time(post.create_at, timezone)
+                );
+                for line in post.message.lines() {
+                    let _ = writeln!(out, "> {line}");
+                }
+                for file in attached_files(post) {
+                    let _ = writeln!(out, ">\n> [attachment: {}]", file.name);
+                }
+            }
+        }
+        ThreadExportFormat::Html => {
+            out.push_str("<div class=\"thread\">\n");
+            for post in posts {
+                let created_at = Utc
+                    .timestamp_millis_opt(post.create_at as i64)
+                    .single()
+                    .map(|date| date.to_rfc3339())
+                    .unwrap_or_default();
+                let blocks = match &post.message_ast {
+                    Some(blocks) => markdown::to_html(blocks),
+                    None => markdown::to_html(&markdown::parse(&post.message)),
+                };
+                let _ = write!(
+                    out,
+                    "<div class=\"post\">\n<p><strong>{}</strong> <time datetime=\"{created_at}\">{}</time></p>\n<blockquote>\n{blocks}",
+                    markdown::escape_html(author(post, authors)),
+                    local_time(post.create_at, timezone)
+                );
+                for file in attached_files(post) {
+                    let _ = writeln!(
+                        out,
+                        "<p>[attachment: {}]</p>",
+                        markdown::escape_html(&file.name)
+                    );
+                }
+                out.push_str("</blockquote>\n</div>\n");
+            }
+            out.push_str("</div>\n");
+        }
+    }
+    out
+}
+
+/// Thread post belongs to, `None` for root posts
+fn root_id(post: &Post) -> Option<&str> {
+    Some(post.root_id.as_str()).filter(|id| !id.is_empty())
+}
+
+/// Quote CSV field when it holds separator, quote or line break. Field
+/// spreadsheets would run as formula gets `'` in front, chat content must
+/// not execute when export is opened.
+fn csv_field(field: &str) -> String {
+    let field = match field.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        true => format!("'{field}"),
+        false => field.to_owned(),
+    };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use chrono::FixedOffset;
+
+    use super::*;
+    use crate::test_support;
+
+    fn post(id: &str, root_id: &str, create_at: Timestamp, message: &str) -> Post {
+        Post {
+            user_id: Some(UserId::new("u1".to_owned())),
+            root_id: root_id.to_owned(),
+            message: Message::new(message.to_owned()),
+            ..test_support::post(id, create_at)
+        }
+    }
+
+    #[test]
+    fn range_bounds() {
+        let range = ExportRange {
+            since: Some(10),
+            until: Some(20),
+        };
+        assert!(range.contains(10) && range.contains(20));
+        assert!(!range.contains(9) && !range.contains(21));
+        assert!(range.is_before(9) && !range.is_before(21));
+        assert!(ExportRange::default().contains(0));
+    }
+
+    #[test]
+    fn renders_formats() {
+        let posts = [
+            post("p1", "", 1_700_000_000_000, "Deploy today, 5pm"),
+            post("p2", "p1", 1_700_000_060_000, "Say \"when\"\nready"),
+        ];
+        let authors = HashMap::from([("u1".to_owned(), "maria.k".to_owned())]);
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let render = |format| render_channel(format, "Town Square", &posts, &authors, &utc);
+
+        assert_eq!(
+            render(ChannelExportFormat::Csv).unwrap(),
+            "id,date,author,root_id,message,files\r\n\
+             p1,2023-11-14 22:13,maria.k,,\"Deploy today, 5pm\",\r\n\
+             p2,2023-11-14 22:14,maria.k,p1,\"Say \"\"when\"\"\nready\",\r\n"
+        );
+        assert_eq!(
+            render(ChannelExportFormat::Text).unwrap(),
+            "# Town Square\n\
+             \n[2023-11-14 22:13] maria.k:\n  Deploy today, 5pm\n\
+             \n    [2023-11-14 22:14] maria.k:\n      Say \"when\"\n      ready\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&render(ChannelExportFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["posts"][1]["root_id"], "p1");
+        assert_eq!(json["posts"][0]["root_id"], serde_json::Value::Null);
+        assert_eq!(json["posts"][0]["author"], "maria.k");
+    }
+
+    #[test]
+    fn csv_cells_never_run_as_formula() {
+        assert_eq!(
+            csv_field("=HYPERLINK(\"http://x\")"),
+            "\"'=HYPERLINK(\"\"http://x\"\")\""
+        );
+        assert_eq!(csv_field("+1 from me"), "'+1 from me");
+        assert_eq!(csv_field("-2"), "'-2");
+        assert_eq!(csv_field("@here deploy"), "'@here deploy");
+        assert_eq!(csv_field("plain = text"), "plain = text");
+    }
+
+    #[test]
+    fn renders_thread() {
+        let mut deleted = post("p3", "p1", 1_700_000_120_000, "oops");
+        deleted.delete_at = 1;
+        let thread = PostThread {
+            order: vec![PostId::new("p2".to_owned()), PostId::new("p1".to_owned())],
+            posts: [
+                post("p2", "p1", 1_700_000_060_000, "Done <3"),
+                post("p1", "", 1_700_000_000_000, "Deploy **today**\nplease"),
+                deleted,
+            ]
+            .into_iter()
+            .map(|post| (post.id.to_string(), post))
+            .collect(),
+            ..PostThread::default()
+        };
+        let authors = HashMap::from([("u1".to_owned(), "maria.k".to_owned())]);
+        let utc = FixedOffset::east_opt(0).unwrap();
+
+        assert_eq!(
+            render_thread(ThreadExportFormat::Markdown, &thread, &authors, &utc),
+            "**maria.k** — 2023-11-14 22:13\n\n> Deploy **today**\n> please\n\
+             \n**maria.k** — 2023-11-14 22:14\n\n> Done <3\n"
+        );
+        let html = render_thread(ThreadExportFormat::Html, &thread, &authors, &utc);
+        assert!(html.starts_with("<div class=\"thread\">\n<div class=\"post\">\n<p><strong>maria.k</strong> <time datetime=\"2023-11-14T22:13:20+00:00\">2023-11-14 22:13</time></p>"));
+        assert!(html.contains("<p>Deploy <strong>today</strong><br>\nplease</p>"));
+        assert!(html.contains("<p>Done &lt;3</p>"));
+        assert!(!html.contains("oops"));
+    }
+}
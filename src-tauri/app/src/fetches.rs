@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+use crate::errors::{Error, NativeError};
+
+/// Fetches in flight keyed by what they fetch. Starting new fetch of the same
+/// key supersedes older one, its request is aborted and its result is never
+/// stored, so slow response can't overwrite newer data.
+#[derive(Clone, Default)]
+pub struct Fetches {
+    last: u64,
+    latest: HashMap<String, (u64, Arc<Notify>)>,
+}
+
+pub struct FetchTicket {
+    key: String,
+    pub generation: u64,
+    superseded: Arc<Notify>,
+}
+
+impl Fetches {
+    pub fn begin(&mut self, key: String) -> FetchTicket {
+        self.last += 1;
+        let superseded = Arc::new(Notify::new());
+        if let Some((_, older)) = self
+            .latest
+            .insert(key.clone(), (self.last, superseded.clone()))
+        {
+            // Stores permit when older fetch isn't awaiting yet
+            older.notify_one();
+        }
+        FetchTicket {
+            key,
+            generation: self.last,
+            superseded,
+        }
+    }
+
+    pub fn is_latest(&self, ticket: &FetchTicket) -> bool {
+        self.latest
+            .get(&ticket.key)
+            .is_some_and(|(generation, _)| *generation == ticket.generation)
+    }
+
+    /// Forget finished fetch, returns `false` when it was superseded and its
+    /// result should be dropped
+    pub fn finish(&mut self, ticket: &FetchTicket) -> bool {
+        let latest = self.is_latest(ticket);
+        if latest {
+            self.latest.remove(&ticket.key);
+        }
+        latest
+    }
+}
+
+impl FetchTicket {
+    /// Drive `fetch` until it completes or newer fetch of the same key
+    /// begins, dropping the future aborts request in flight
+    pub async fn run<T, F>(&self, fetch: F) -> Result<T, Error>
+    where
+        F: Future<Output = Result<T, Error>>,
+    {
+        tokio::select! {
+            result = fetch => result,
+            _ = self.superseded.notified() => Err(NativeError::Superseded)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[tokio::test]
+    async fn newer_fetch_aborts_older() {
+        let mut fetches = Fetches::default();
+        let first = fetches.begin("town-square".to_owned());
+        let other = fetches.begin("off-topic".to_owned());
+        let second = fetches.begin("town-square".to_owned());
+
+        let pending = first.run(std::future::pending::<Result<(), Error>>());
+        assert!(matches!(
+            pending.await,
+            Err(Error::Native(NativeError::Superseded))
+        ));
+        assert!(!fetches.finish(&first));
+        assert!(second.run(async { Ok(()) }).await.is_ok());
+        assert!(fetches.finish(&second));
+        assert!(fetches.is_latest(&other));
+        assert!(second.generation > first.generation);
+    }
+}
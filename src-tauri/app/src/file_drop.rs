@@ -0,0 +1,224 @@
+use std::path::{Path, PathBuf};
+
+use futures::Stream;
+use models::*;
+use reqwest::Client;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tokio::io::AsyncReadExt;
+use tokio::sync::Mutex;
+
+use crate::api::upload_stream;
+use crate::attachments::{self, Attachment};
+use crate::commands::{attachment_policy, current_server_url};
+use crate::errors::{Error, IpcError, NativeError};
+use crate::states::{ServerState, UserState};
+
+/// Bytes read from disk at once, progress is reported after each chunk
+const CHUNK_SIZE: usize = 256 * 1024;
+
+pub const UPLOAD_PROGRESS_EVENT: &str = "upload-progress";
+pub const UPLOAD_FINISHED_EVENT: &str = "upload-finished";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UploadProgress {
+    pub path: PathBuf,
+    pub channel_id: ChannelId,
+    pub sent: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadFinished {
+    pub path: PathBuf,
+    pub channel_id: ChannelId,
+    /// File to attach to post, `None` when upload failed
+    pub file: Option<MetaFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<IpcError>,
+}
+
+/// Upload files dropped on window to channel open in it. Files are checked
+/// against upload policy together and then sent one after another.
+pub fn dropped(app: &AppHandle, paths: Vec<PathBuf>) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let Some(channel_id) = app
+            .state::<Mutex<UserState>>()
+            .lock()
+            .await
+            .open_channels
+            .last()
+            .cloned()
+        else {
+            tracing::info!("Ignoring {} dropped files, no channel is open", paths.len());
+            return;
+        };
+        let finished = |path: &Path, result: Result<MetaFile, &Error>| {
+            let (file, error) = match result {
+                Ok(file) => (Some(file), None),
+                Err(e) => {
+                    tracing::warn!("Failed to upload {}: {e}", path.display());
+                    (None, Some(IpcError::from(e)))
+                }
+            };
+            let finished = UploadFinished {
+                path: path.to_owned(),
+                channel_id: channel_id.clone(),
+                file,
+                error,
+            };
+            app.emit_all(UPLOAD_FINISHED_EVENT, finished).ok();
+        };
+        let attachments = match check(&app, &paths).await {
+            Ok(attachments) => attachments,
+            Err(e) => {
+                for path in &paths {
+                    finished(path, Err(&e));
+                }
+                return;
+            }
+        };
+        for (path, attachment) in paths.iter().zip(attachments) {
+            match upload(&app, &channel_id, path, attachment).await {
+                Ok(file) => finished(path, Ok(file)),
+                Err(e) => finished(path, Err(&e)),
+            }
+        }
+    });
+}
+
+/// Names and sizes of `paths` when server accepts all of them
+async fn check(app: &AppHandle, paths: &[PathBuf]) -> Result<Vec<Attachment>, Error> {
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let metadata = tokio::fs::metadata(path).await?;
+        if !metadata.is_file() {
+            return Err(NativeError::UploadFile)?;
+        }
+        files.push(Attachment {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string()),
+            size: metadata.len(),
+        });
+    }
+    let token = app.state::<Mutex<UserState>>().lock().await.token.clone();
+    let server_url = current_server_url(&app.state::<Mutex<ServerState>>()).await?;
+    let policy = attachment_policy(&app.state::<Client>(), &server_url, token.as_ref()).await?;
+    attachments::check(&policy, &files)?;
+    Ok(files)
+}
+
+async fn upload(
+    app: &AppHandle,
+    channel_id: &ChannelId,
+    path: &Path,
+    attachment: Attachment,
+) -> Result<MetaFile, Error> {
+    let file = tokio::fs::File::open(path).await?;
+    let progress = {
+        let app = app.clone();
+        let path = path.to_owned();
+        let channel_id = channel_id.clone();
+        move |sent, total| {
+            let progress = UploadProgress {
+                path: path.clone(),
+                channel_id: channel_id.clone(),
+                sent,
+                total,
+            };
+            app.emit_all(UPLOAD_PROGRESS_EVENT, progress).ok();
+        }
+    };
+    let body = reqwest::Body::wrap_stream(chunks(file, attachment.size, progress));
+    let token = app.state::<Mutex<UserState>>().lock().await.token.clone();
+    let server_url = current_server_url(&app.state::<Mutex<ServerState>>()).await?;
+    upload_stream(
+        &app.state::<Client>(),
+        &server_url,
+        token.as_ref(),
+        channel_id,
+        &attachment.name,
+        attachment.size,
+        body,
+        path,
+    )
+    .await
+}
+
+/// Content of `file` in chunks of [`CHUNK_SIZE`], `progress` gets bytes
+/// read so far and `total` after each of them. File is not read past
+/// `total`, so body matches length announced to server even when file
+/// grows meanwhile.
+fn chunks(
+    file: tokio::fs::File,
+    total: u64,
+    progress: impl Fn(u64, u64) + Send + Sync + 'static,
+) -> impl Stream<Item = std::io::Result<Vec<u8>>> {
+    let progress = std::sync::Arc::new(progress);
+    futures::stream::try_unfold((file, 0u64), move |(mut file, sent)| {
+        let left = total.saturating_sub(sent);
+        let progress = progress.clone();
+        async move {
+            if left == 0 {
+                return Ok(None);
+            }
+            let mut chunk = vec![0; CHUNK_SIZE.min(left as usize)];
+            let read = file.read(&mut chunk).await?;
+            if read == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            chunk.truncate(read);
+            let sent = sent + read as u64;
+            progress(sent, total);
+            Ok(Some((chunk, (file, sent))))
+        }
+    })
+}
+
+#[cfg(test)]
+mod check {
+    use std::sync::{Arc, Mutex};
+
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_file_in_chunks() {
+        let dir = tempdir::TempDir::new("file_drop").unwrap();
+        let path = dir.path().join("report.bin");
+        let content: Vec<u8> = (0..CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let progress = {
+            let reported = reported.clone();
+            move |sent, total| reported.lock().unwrap().push((sent, total))
+        };
+        let file = tokio::fs::File::open(&path).await.unwrap();
+        let read: Vec<Vec<u8>> = chunks(file, content.len() as u64, progress)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(read.len(), 3);
+        assert_eq!(read.concat(), content);
+        let total = content.len() as u64;
+        assert_eq!(
+            *reported.lock().unwrap(),
+            [
+                (CHUNK_SIZE as u64, total),
+                (CHUNK_SIZE as u64 * 2, total),
+                (total, total)
+            ]
+        );
+
+        // File shrunk after its size was taken
+        let file = tokio::fs::File::open(&path).await.unwrap();
+        let result: std::io::Result<Vec<Vec<u8>>> =
+            chunks(file, total + 1, |_, _| {}).try_collect().await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use serde::Serialize;
+use url::Url;
+
+/// Link found in channel header, `text` is the URL itself for bare links
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HeaderLink {
+    pub text: String,
+    pub url: Url,
+}
+
+/// Links of channel header Markdown in order of appearance.
+///
+/// Recognizes `[text](url)`, `<url>` and bare URLs, code spans are skipped.
+/// Only web and mail links are returned so header can't smuggle e.g.
+/// `javascript:` URLs into UI.
+pub fn header_links(header: &str) -> Vec<HeaderLink> {
+    let mut links: Vec<HeaderLink> = Vec::new();
+    let mut push = |text: &str, url: &str| {
+        let Ok(url) = Url::parse(url.trim()) else {
+            return;
+        };
+        if !matches!(url.scheme(), "http" | "https" | "mailto") {
+            return;
+        }
+        if links.iter().any(|link| link.url == url) {
+            return;
+        }
+        let text = text.trim();
+        links.push(HeaderLink {
+            text: if text.is_empty() {
+                url.to_string()
+            } else {
+                text.to_owned()
+            },
+            url,
+        });
+    };
+
+    let mut rest = header;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '`' => {
+                let fence = rest.len() - rest.trim_start_matches('`').len();
+                let after = &rest[fence..];
+                rest = match after.find(&rest[..fence]) {
+                    Some(end) => &after[end + fence..],
+                    None => after,
+                };
+                continue;
+            }
+            '[' => {
+                if let Some((text, url, len)) = markdown_link(rest) {
+                    push(text, url);
+                    rest = &rest[len..];
+                    continue;
+                }
+            }
+            '<' => {
+                if let Some(end) = rest.find('>') {
+                    let url = &rest[1..end];
+                    if !url.contains(char::is_whitespace) && url.contains(':') {
+                        push(url, url);
+                        rest = &rest[end + 1..];
+                        continue;
+                    }
+                }
+            }
+            _ if starts_bare_url(rest) => {
+                let len = bare_url_len(rest);
+                push(&rest[..len], &rest[..len]);
+                rest = &rest[len..];
+                continue;
+            }
+            _ => {}
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    links
+}
+
+/// `[text](url "title")` at start of `s`, returns text, URL and length of
+/// whole link
+fn markdown_link(s: &str) -> Option<(&str, &str, usize)> {
+    let mut depth = 0;
+    let close = s.char_indices().find_map(|(i, c)| {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ => {}
+        }
+        (depth == 0).then_some(i)
+    })?;
+    let target = s[close + 1..].strip_prefix('(')?;
+    let end = target.find(')')?;
+    let url = target[..end].split_whitespace().next()?;
+    let url = url
+        .strip_prefix('<')
+        .and_then(|url| url.strip_suffix('>'))
+        .unwrap_or(url);
+    Some((&s[1..close], url, close + 2 + end + 1))
+}
+
+fn starts_bare_url(s: &str) -> bool {
+    ["http://", "https://"]
+        .iter()
+        .any(|scheme| s.len() > scheme.len() && s[..scheme.len()].eq_ignore_ascii_case(scheme))
+}
+
+/// Trailing punctuation belongs to sentence rather than URL, closing
+/// parenthesis only when URL itself doesn't open one
+fn bare_url_len(s: &str) -> usize {
+    let end = s
+        .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '`'))
+        .unwrap_or(s.len());
+    let mut url = &s[..end];
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '*', '_', '\'']);
+        let trimmed = match trimmed.strip_suffix(')') {
+            Some(inner) if inner.matches('(').count() < trimmed.matches(')').count() => inner,
+            _ => trimmed,
+        };
+        if trimmed.len() == url.len() {
+            return url.len();
+        }
+        url = trimmed;
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn links(header: &str) -> Vec<(String, String)> {
+        header_links(header)
+            .into_iter()
+            .map(|link| (link.text, link.url.to_string()))
+            .collect()
+    }
+
+    fn link(text: &str, url: &str) -> (String, String) {
+        (text.to_owned(), url.to_owned())
+    }
+
+    #[test]
+    fn markdown_links() {
+        let header = concat!(
+            "[Runbook](https://wiki.example.com/rb \"Runbook\") | ",
+            "[**Board**](<https://b.example.com>)",
+        );
+        assert_eq!(
+            links(header),
+            [
+                link("Runbook", "https://wiki.example.com/rb"),
+                link("**Board**", "https://b.example.com/"),
+            ]
+        );
+    }
+
+    #[test]
+    fn bare_and_angle_links() {
+        assert_eq!(
+            links("Docs: https://docs.example.com/a_(b). On call <mailto:ops@example.com>"),
+            [
+                link(
+                    "https://docs.example.com/a_(b)",
+                    "https://docs.example.com/a_(b)"
+                ),
+                link("mailto:ops@example.com", "mailto:ops@example.com"),
+            ]
+        );
+        assert_eq!(
+            links("(see https://example.com/x)"),
+            [link("https://example.com/x", "https://example.com/x")]
+        );
+    }
+
+    #[test]
+    fn skips_code_unsafe_and_duplicates() {
+        let header = concat!(
+            "`https://in.code` [x](javascript:alert(1)) ",
+            "https://a.example.com [A](https://a.example.com)",
+        );
+        assert_eq!(
+            links(header),
+            [link("https://a.example.com", "https://a.example.com/")]
+        );
+        assert!(links("no links [here] (either)").is_empty());
+    }
+}
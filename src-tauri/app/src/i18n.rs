@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Locale every other one falls back to
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Catalogs by locale, see `locales/en.txt` for their format
+const CATALOGS: &[(&str, &str)] = &[
+    (DEFAULT_LOCALE, include_str!("locales/en.txt")),
+    ("de", include_str!("locales/de.txt")),
+];
+
+/// Locale of catalog messages are looked up in first
+static CURRENT: RwLock<&'static str> = RwLock::new(DEFAULT_LOCALE);
+
+/// Values of `{ $param }` placeholders by name
+pub type Params = BTreeMap<&'static str, Value>;
+
+/// Catalog of current locale merged over the default one, for frontend to
+/// render keys sent with errors and events
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Translations {
+    pub locale: &'static str,
+    pub available: Vec<&'static str>,
+    pub messages: BTreeMap<&'static str, &'static str>,
+}
+
+/// Locale as settings keep it, `pt-BR` rather than `pt_br`, `None` when
+/// blank
+pub fn normalized_locale(locale: Option<String>) -> Option<String> {
+    let locale = locale?;
+    let mut parts = locale.trim().split(['-', '_']);
+    let language = parts.next().filter(|language| !language.is_empty())?;
+    let mut normalized = language.to_ascii_lowercase();
+    if let Some(region) = parts.next().filter(|region| !region.is_empty()) {
+        normalized.push('-');
+        normalized.push_str(&region.to_ascii_uppercase());
+    }
+    Some(normalized)
+}
+
+/// Language of OS like `en_US`, from environment as libc reads it
+pub fn system_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX")
+        .and_then(|value| {
+            let language = value.split(['.', '@']).next().unwrap_or_default();
+            crate::spellcheck::normalized_languages(&[language.to_owned()]).pop()
+        })
+}
+
+/// Catalog serving `locale`, region specific one when there is such, then
+/// the one of language
+fn supported(locale: &str) -> Option<&'static str> {
+    let locale = normalized_locale(Some(locale.to_owned()))?;
+    let language = locale.split('-').next().unwrap_or_default();
+    let tag = [locale.as_str(), language].into_iter().find_map(|wanted| {
+        CATALOGS
+            .iter()
+            .map(|(tag, _)| *tag)
+            .find(|tag| tag.eq_ignore_ascii_case(wanted))
+    });
+    tag
+}
+
+/// Switch messages to configured locale, OS language when there is none,
+/// default locale when neither is translated
+pub fn apply(configured: Option<&str>) {
+    let locale = configured
+        .map(str::to_owned)
+        .or_else(system_language)
+        .and_then(|locale| supported(&locale))
+        .unwrap_or(DEFAULT_LOCALE);
+    *CURRENT.write().unwrap() = locale;
+    tracing::info!("Messages are in locale {locale}");
+}
+
+pub fn current() -> &'static str {
+    *CURRENT.read().unwrap()
+}
+
+fn catalog(locale: &str) -> &'static str {
+    CATALOGS
+        .iter()
+        .find(|(tag, _)| *tag == locale)
+        .map_or("", |(_, source)| source)
+}
+
+/// `(key, text)` entries of catalog, comments and blank lines skipped
+fn entries(source: &'static str) -> impl Iterator<Item = (&'static str, &'static str)> {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, text)| (key.trim(), text.trim()))
+}
+
+fn lookup(locale: &str, key: &str) -> Option<&'static str> {
+    entries(catalog(locale)).find_map(|(known, text)| (known == key).then_some(text))
+}
+
+/// Replace `{ $param }` placeholders, unknown ones are left as they are
+fn format(text: &str, params: &Params) -> String {
+    let mut formatted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        formatted.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        let placeholder = &rest[..=end];
+        let name = placeholder[1..placeholder.len() - 1].trim();
+        match name.strip_prefix('$').and_then(|name| params.get(name)) {
+            Some(Value::String(value)) => formatted.push_str(value),
+            Some(value) => formatted.push_str(&value.to_string()),
+            None => formatted.push_str(placeholder),
+        }
+        rest = &rest[end + 1..];
+    }
+    formatted.push_str(rest);
+    formatted
+}
+
+/// Message of `key` in current locale, `None` when neither it nor default
+/// locale has it
+pub fn translate(key: &str, params: &Params) -> Option<String> {
+    let locale = current();
+    lookup(locale, key)
+        .or_else(|| lookup(DEFAULT_LOCALE, key))
+        .map(|text| format(text, params))
+}
+
+/// Message of `key` which is always in the catalog of default locale
+pub fn text(key: &str, params: &Params) -> String {
+    translate(key, params).unwrap_or_else(|| key.to_owned())
+}
+
+pub fn translations() -> Translations {
+    let locale = current();
+    let mut messages: BTreeMap<&'static str, &'static str> =
+        entries(catalog(DEFAULT_LOCALE)).collect();
+    messages.extend(entries(catalog(locale)));
+    Translations {
+        locale,
+        available: CATALOGS.iter().map(|(tag, _)| *tag).collect(),
+        messages,
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn formats_messages() {
+        let params = Params::from([("name", Value::from("a.iso")), ("size", Value::from(2048))]);
+        assert_eq!(
+            format("{ $name } has {$size} bytes of { $limit }", &params),
+            "a.iso has 2048 bytes of { $limit }"
+        );
+        assert_eq!(format("no { placeholder", &params), "no { placeholder");
+
+        assert_eq!(supported("de_AT"), Some("de"));
+        assert_eq!(supported("EN-us"), Some("en"));
+        assert_eq!(supported("fr"), None);
+        assert_eq!(
+            normalized_locale(Some(" pt_br ".to_owned())).as_deref(),
+            Some("pt-BR")
+        );
+        assert_eq!(normalized_locale(Some(" ".to_owned())), None);
+
+        let server = Params::from([("server", Value::from("mm.example.com"))]);
+        assert_eq!(
+            lookup("de", "notification-reminder-title").map(|text| format(text, &server)),
+            Some("Erinnerung: mm.example.com".to_owned())
+        );
+        assert_eq!(lookup("de", "error-no-such-kind"), None);
+    }
+
+    #[test]
+    fn german_covers_local_errors() {
+        for kind in crate::errors::LOCAL_KINDS {
+            let key = format!("error-{}", kind.replace('_', "-"));
+            assert!(lookup("de", &key).is_some(), "{key} has no German text");
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use models::*;
+use tokio::sync::Mutex;
+
+/// Previews kept in memory, least recently used are dropped first
+const MAX_ENTRIES: usize = 256;
+
+/// Link previews by server and URL. Pages without anything to show are
+/// remembered too, so they aren't requested again on every render.
+#[derive(Default)]
+pub struct LinkPreviews {
+    /// Most recently used last
+    entries: Mutex<Vec<(String, Option<OpenGraph>)>>,
+}
+
+impl LinkPreviews {
+    /// `None` means link wasn't previewed yet
+    pub async fn get(&self, server: &ServerUrl, url: &str) -> Option<Option<OpenGraph>> {
+        let key = key(server, url);
+        let mut entries = self.entries.lock().await;
+        let index = entries.iter().position(|(cached, _)| *cached == key)?;
+        let entry = entries.remove(index);
+        let preview = entry.1.clone();
+        entries.push(entry);
+        Some(preview)
+    }
+
+    pub async fn insert(&self, server: &ServerUrl, url: &str, preview: Option<OpenGraph>) {
+        let key = key(server, url);
+        let mut entries = self.entries.lock().await;
+        entries.retain(|(cached, _)| *cached != key);
+        if entries.len() >= MAX_ENTRIES {
+            entries.remove(0);
+        }
+        entries.push((key, preview));
+    }
+}
+
+fn key(server: &ServerUrl, url: &str) -> String {
+    format!("{} {url}", server.as_str())
+}
+
+/// Preview server already attached to post, saves request for links of
+/// posts fetched with metadata
+pub fn from_embeds(url: &str, embeds: &[MetaEmbed]) -> Option<OpenGraph> {
+    // Server keeps URLs normalized
+    let url = url::Url::parse(url).ok()?;
+    let url = url.as_str();
+    embeds
+        .iter()
+        .filter(|embed| embed.embed_type.as_str() == "opengraph")
+        .find(|embed| {
+            embed
+                .url
+                .as_ref()
+                .is_some_and(|embed| embed.as_str() == url)
+        })
+        .and_then(|embed| serde_json::from_value(embed.data.clone()).ok())
+}
+
+/// Preview with nothing to render, e.g. of page without OpenGraph tags
+pub fn is_empty(preview: &OpenGraph) -> bool {
+    preview.title.is_empty()
+        && preview.description.is_empty()
+        && preview.images.as_ref().map_or(true, Vec::is_empty)
+}
+
+#[cfg(test)]
+mod check {
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn drops_least_recently_used() {
+        let previews = LinkPreviews::default();
+        let server = ServerUrl::parse("https://mm.example.com").unwrap();
+        for n in 0..MAX_ENTRIES {
+            previews
+                .insert(&server, &format!("https://example.com/{n}"), None)
+                .await;
+        }
+        assert!(previews
+            .get(&server, "https://example.com/0")
+            .await
+            .is_some());
+        previews
+            .insert(&server, "https://example.com/new", None)
+            .await;
+        assert!(previews
+            .get(&server, "https://example.com/0")
+            .await
+            .is_some());
+        assert!(previews
+            .get(&server, "https://example.com/1")
+            .await
+            .is_none());
+    }
+
+    #[test]
+    fn uses_post_metadata() {
+        let embeds: Vec<MetaEmbed> = serde_json::from_value(json!([{
+            "type": "opengraph",
+            "url": "https://example.com/article",
+            "data": {
+                "title": "Article",
+                "description": "About things",
+                "images": [{ "secure_url": "https://example.com/a.png", "width": 64 }],
+            },
+        }]))
+        .unwrap();
+        let preview = from_embeds("https://example.com/article", &embeds).unwrap();
+        assert_eq!(preview.title, "Article");
+        assert_eq!(preview.images.unwrap()[0].width, 64);
+        assert!(from_embeds("https://example.com/other", &embeds).is_none());
+        assert!(is_empty(&OpenGraph::default()));
+    }
+}
@@ -0,0 +1,254 @@
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::{self, MakeWriter};
+use tracing_subscriber::prelude::*;
+
+const LOG_PREFIX: &str = "worryless";
+const LOG_SUFFIX: &str = "log";
+/// Days of logs kept around
+const MAX_LOG_FILES: usize = 7;
+/// Values following these keys never reach log, matched case-insensitively
+/// anywhere in key, e.g. `access_token` or `MMAUTHTOKEN`
+const SECRET_KEYS: [&str; 6] = [
+    "token",
+    "password",
+    "passwd",
+    "secret",
+    "authorization",
+    "cookie",
+];
+const REDACTED: &str = "[redacted]";
+
+/// Daily rotated JSON logs next to vault
+pub fn log_dir() -> PathBuf {
+    directories::BaseDirs::new()
+        .map(|dirs| dirs.config_dir().join("worryless"))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("logs")
+}
+
+/// Log human readable lines to stdout and JSON lines to file, both with
+/// secrets scrubbed.
+///
+/// File is written without buffering so nothing is lost when application
+/// crashes, panics are logged before process goes down.
+pub fn init() {
+    let file = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_PREFIX)
+        .filename_suffix(LOG_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(log_dir());
+    let (file_layer, error) = match file {
+        Ok(appender) => {
+            let layer = fmt::layer()
+                .json()
+                .with_writer(Scrubbing(appender))
+                .with_filter(LevelFilter::INFO);
+            (Some(layer), None)
+        }
+        Err(e) => (None, Some(e)),
+    };
+    tracing_subscriber::registry()
+        // Color codes would hide keys from scrubbing, request and response
+        // bodies logged at trace level stay out of terminal
+        .with(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(Scrubbing(io::stdout))
+                .with_filter(LevelFilter::INFO),
+        )
+        .with(file_layer)
+        .init();
+    if let Some(e) = error {
+        tracing::warn!("Unable to write logs to {}: {e}", log_dir().display());
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        tracing::error!("{info}");
+        default_hook(info);
+    }));
+}
+
+/// Up to `limit` newest lines of logs, oldest first
+pub fn recent(limit: usize) -> io::Result<Vec<String>> {
+    let entries = match std::fs::read_dir(log_dir()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(LOG_PREFIX) && name.ends_with(LOG_SUFFIX))
+        })
+        .collect();
+    // Date in name sorts files from oldest to newest
+    files.sort();
+    let mut lines = Vec::new();
+    for path in files.iter().rev() {
+        let content = std::fs::read_to_string(path)?;
+        let mut newer = std::mem::take(&mut lines);
+        lines = content.lines().map(str::to_owned).collect();
+        lines.append(&mut newer);
+        if lines.len() >= limit {
+            break;
+        }
+    }
+    let skip = lines.len().saturating_sub(limit);
+    Ok(lines.split_off(skip))
+}
+
+/// `line` with values of secret keys and bearer tokens replaced
+pub fn scrub(line: &str) -> Cow<'_, str> {
+    let lower = line.to_ascii_lowercase();
+    let mut ranges = Vec::new();
+    for key in SECRET_KEYS {
+        for (start, _) in lower.match_indices(key) {
+            if let Some(range) = value_after(&lower, start + key.len(), true) {
+                ranges.push(range);
+            }
+        }
+    }
+    for (start, _) in lower.match_indices("bearer ") {
+        if let Some(range) = value_after(&lower, start + "bearer ".len(), false) {
+            ranges.push(range);
+        }
+    }
+    if ranges.is_empty() {
+        return Cow::Borrowed(line);
+    }
+    ranges.sort();
+    let mut scrubbed = String::with_capacity(line.len());
+    let mut copied = 0;
+    for (start, end) in ranges {
+        if end <= copied {
+            continue;
+        }
+        if start >= copied {
+            scrubbed.push_str(&line[copied..start]);
+            scrubbed.push_str(REDACTED);
+        }
+        copied = end;
+    }
+    scrubbed.push_str(&line[copied..]);
+    Cow::Owned(scrubbed)
+}
+
+/// Byte range of value starting at `at`. Key has to be followed by `:` or
+/// `=`, otherwise it's only a word like "tokens".
+fn value_after(lower: &str, at: usize, keyed: bool) -> Option<(usize, usize)> {
+    let bytes = lower.as_bytes();
+    let mut start = at;
+    let mut separated = !keyed;
+    while let Some(&c) = bytes.get(start) {
+        match c {
+            b':' | b'=' => separated = true,
+            b'"' | b'\'' | b'\\' | b' ' => {}
+            _ => break,
+        }
+        start += 1;
+    }
+    if !separated {
+        return None;
+    }
+    if lower[start..].starts_with("some(") {
+        start += "some(".len();
+        while matches!(bytes.get(start), Some(b'"' | b'\'' | b'\\')) {
+            start += 1;
+        }
+    }
+    let value_end = |from: usize| {
+        lower[from..]
+            .find(|c: char| c.is_whitespace() || "\"'\\,;&)}".contains(c))
+            .map_or(lower.len(), |end| from + end)
+    };
+    let mut end = value_end(start);
+    if end == start || lower[start..end] == *"none" {
+        return None;
+    }
+    // Authorization header value, scheme followed by credentials
+    if matches!(&lower[start..end], "bearer" | "basic") && bytes.get(end) == Some(&b' ') {
+        end = value_end(end + 1);
+    }
+    Some((start, end))
+}
+
+struct Scrubbing<M>(M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Scrubbing<M> {
+    type Writer = ScrubbingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        ScrubbingWriter(self.0.make_writer())
+    }
+}
+
+/// Formatter writes every event at once, so secrets are never split
+/// between writes
+struct ScrubbingWriter<W>(W);
+
+impl<W: Write> Write for ScrubbingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.0.write_all(scrub(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn scrubs_secrets() {
+        assert_eq!(
+            scrub(r#"login {"login_id":"me","password":"hunter2"}"#),
+            r#"login {"login_id":"me","password":"[redacted]"}"#
+        );
+        assert_eq!(
+            scrub("Authorization: Bearer abc.def, retrying"),
+            "Authorization: [redacted], retrying"
+        );
+        assert_eq!(
+            scrub("Cookie: MMAUTHTOKEN=abc; MMUSERID=u1"),
+            "Cookie: [redacted]; MMUSERID=u1"
+        );
+        assert_eq!(
+            scrub(r#"{"message":"saved Credentials { token: Some(\"abc\") }"}"#),
+            r#"{"message":"saved Credentials { token: Some(\"[redacted]\") }"}"#
+        );
+        assert_eq!(
+            scrub("3 tokens refreshed, token: None"),
+            "3 tokens refreshed, token: None"
+        );
+        assert!(matches!(scrub("nothing secret"), Cow::Borrowed(_)));
+
+        let credentials = models::ServerCredentials {
+            url: models::ServerUrl::parse("https://mm.example.com").unwrap(),
+            access_token: models::AccessToken::try_from("hs8das8dg8asgd").unwrap(),
+        };
+        let logged = format!("saved {credentials:?}");
+        assert!(!scrub(&logged).contains("hs8das8dg8asgd"));
+        assert_eq!(
+            scrub(&format!("{:?}", credentials.access_token)),
+            "AccessToken(***)"
+        );
+        let login = models::Credentials {
+            login: models::Login::try_from("me").unwrap(),
+            password: models::Pass::try_from("hunter2").unwrap(),
+        };
+        assert!(!format!("{login:?}").contains("hunter2"));
+    }
+}
@@ -2,24 +2,86 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use reqwest::Client;
-use tokio::sync::Mutex;
+use tauri::{FileDropEvent, Manager, RunEvent, WindowEvent};
+use tokio::sync::{Mutex, RwLock};
 
 use crate::commands::*;
 use crate::errors::*;
 use crate::states::{ServerState, UserState};
 
 mod api;
+mod app_data;
+mod attachments;
+mod audit;
+mod autocomplete;
+mod autostart;
+mod bandwidth;
+mod capabilities;
+mod channel_names;
+mod channels;
+mod clipboard;
 mod commands;
+mod composer;
+mod connection;
+mod diagnostics;
+mod digest;
+mod dnd;
+mod emoji;
+mod envelope;
 pub mod errors;
+mod export;
+mod fetches;
+mod file_drop;
+mod header_links;
+mod i18n;
+mod link_preview;
+mod logging;
+mod markdown;
+mod mentions;
+mod navigation;
+mod notifications;
+mod outbox;
+mod patch;
+mod permalinks;
+mod post_store;
+mod post_stream;
+mod post_types;
+mod preferences;
+mod reminders;
+mod repo_lock;
+mod saved_posts;
+mod scheduled_posts;
+mod scheduler;
+mod secrets;
+mod servers;
+mod sessions;
+mod settings;
+mod shortcut;
+mod shutdown;
+mod single_instance;
+mod snippets;
+mod spellcheck;
+mod sso;
 mod states;
+mod status;
 pub mod storage;
+mod storage_handle;
+mod switcher;
+#[cfg(test)]
+mod test_support;
+mod threads;
+mod timeline;
+mod unread;
+mod vault_key;
+mod websocket;
+mod working_hours;
 
 impl serde::Serialize for Error {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::ser::Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        serde::Serialize::serialize(&IpcError::from(self), serializer)
     }
 }
 
@@ -31,30 +93,294 @@ impl<T> From<std::sync::PoisonError<T>> for Error {
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    logging::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let listener = match single_instance::acquire(&args) {
+        Ok(single_instance::Instance::Primary(listener)) => Some(listener),
+        Ok(single_instance::Instance::Forwarded) => {
+            tracing::info!("Application is already running, invocation was forwarded to it");
+            return;
+        }
+        Ok(single_instance::Instance::Unreachable(e)) => {
+            tracing::warn!("Application is already running but didn't take invocation: {e}");
+            return;
+        }
+        Err(e) => {
+            tracing::warn!("Failed to check for running instance: {e}");
+            None
+        }
+    };
+    let launched_at_login = autostart::launched_at_login(&args);
+    // Read synchronously during setup, everything later goes through handle
+    let storage = storage::Storage::new();
     tauri::Builder::default()
         .manage(Client::new())
         .manage(Mutex::new(UserState::default()))
         .manage(Mutex::new(ServerState::default()))
-        .manage(storage::Storage::new())
+        .manage(storage_handle::StorageHandle::new(storage.clone()))
+        .manage(outbox::Outbox::default())
+        .manage(emoji::EmojiCache::default())
+        .manage(channel_names::UserCache::default())
+        .manage(link_preview::LinkPreviews::default())
+        .manage(Mutex::new(navigation::NavigationHistory::default()))
+        .manage(websocket::WebSocket::default())
+        .manage(patch::Snapshots::default())
+        .manage(sessions::Sessions::default())
+        .manage(status::StatusManager::default())
+        .manage(dnd::DndManager::default())
+        .manage(unread::UnreadTracker::default())
+        .manage(notifications::PendingNavigation::default())
+        .manage(single_instance::DeepLinks::new(single_instance::deep_link(
+            &args,
+        )))
+        .setup(move |app| {
+            if let Some(listener) = listener {
+                single_instance::spawn(app.handle(), listener);
+            }
+            let density = storage.post_density().unwrap_or_else(|e| {
+                tracing::warn!("Failed to load post density: {e}");
+                Default::default()
+            });
+            app.manage(RwLock::new(density));
+            let secret_guard = storage.secret_guard().unwrap_or_else(|e| {
+                tracing::warn!("Failed to load secret guard setting: {e}");
+                false
+            });
+            app.manage(secrets::SecretGuard::new(secret_guard));
+            match storage.servers() {
+                Ok(Some(servers)) => {
+                    let mut state = app
+                        .state::<Mutex<states::ServerState>>()
+                        .inner()
+                        .blocking_lock();
+                    state.current = servers.first().cloned();
+                    state.servers = servers;
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to load servers: {e}"),
+            }
+            match storage.strict_schema() {
+                Ok(enabled) => api::schema::configure(enabled),
+                Err(e) => tracing::warn!("Failed to load strict schema setting: {e}"),
+            }
+            match storage.audit_log_enabled() {
+                Ok(enabled) => audit::configure(enabled),
+                Err(e) => tracing::warn!("Failed to load audit log setting: {e}"),
+            }
+            match storage.request_signing() {
+                Ok(signers) => api::signing::configure(signers),
+                Err(e) => tracing::warn!("Failed to load request signing: {e}"),
+            }
+            let settings = storage.settings().unwrap_or_else(|e| {
+                tracing::warn!("Failed to load settings: {e}");
+                Default::default()
+            });
+            if let Err(e) = api::network::configure(&settings.network) {
+                tracing::warn!("Failed to apply network settings: {e}");
+            }
+            if settings.startup.launch_at_login {
+                // Executable may have been moved or updated since
+                autostart::set_enabled(true).ok();
+                if launched_at_login && settings.startup.start_minimized {
+                    if let Some(window) = app.get_window("main") {
+                        window.minimize().ok();
+                    }
+                }
+            }
+            if let Err(e) = shortcut::apply(&app.handle(), &settings.shortcut) {
+                tracing::warn!("Failed to register global shortcut: {e}");
+            }
+            app.manage(scheduler::Scheduler::new(settings.sync_intervals));
+            app.manage(settings::SettingsState::new(settings));
+            outbox::spawn(app.handle());
+            scheduled_posts::spawn(app.handle());
+            reminders::spawn(app.handle());
+            connection::spawn(app.handle());
+            bandwidth::spawn(app.handle());
+            audit::spawn(app.handle());
+            websocket::spawn(app.handle());
+            sessions::spawn(app.handle());
+            scheduler::spawn(app.handle());
+            status::spawn(app.handle());
+            settings::spawn(app.handle());
+            Ok(())
+        })
+        .on_window_event(|event| match event.event() {
+            WindowEvent::CloseRequested { api, .. } => {
+                api.prevent_close();
+                shutdown::begin(&event.window().app_handle());
+            }
+            WindowEvent::FileDrop(FileDropEvent::Dropped(paths)) => {
+                file_drop::dropped(&event.window().app_handle(), paths.clone());
+            }
+            WindowEvent::Focused(focused) => {
+                if let Some(status) = event.window().try_state::<status::StatusManager>() {
+                    status.set_focused(*focused);
+                }
+                if let Some(scheduler) = event.window().try_state::<scheduler::Scheduler>() {
+                    scheduler.set_focused(*focused);
+                }
+            }
+            _ => {}
+        })
         .on_page_load(|window, _load_payload| {
-            window.open_devtools();
-            // window.close_devtools();
-            //
+            let requested = window
+                .try_state::<settings::SettingsState>()
+                .is_some_and(|settings| settings.get().startup.open_devtools);
+            if cfg!(debug_assertions) || requested {
+                window.open_devtools();
+            }
         })
         .invoke_handler(tauri::generate_handler![
             login,
+            login_with_token,
+            login_with_sso,
             logout,
+            forget_server_account,
             add_server,
+            remove_server,
+            rename_server,
+            probe_server,
             get_current_server,
             get_all_servers,
+            bootstrap,
             my_teams,
             my_team_members,
             my_channels,
+            my_team_channels,
+            change_team,
             change_server,
             post_threads,
             channel_posts,
+            stream_channel_posts,
+            switch_channel_timeline,
+            post_render_hint,
+            sync_channels,
+            sync_channel_posts,
+            reset_state_patches,
+            post_density,
+            set_post_density,
+            channel_members,
+            all_channel_members,
+            list_users,
+            list_teams,
+            channel_stats,
+            update_channel,
+            update_channel_notify_props,
+            set_channel_muted,
+            should_notify,
+            show_notification,
+            create_channel,
+            add_channel_member,
+            remove_channel_member,
+            leave_channel,
+            get_channel_header_links,
+            suggest_link_markdown,
+            get_link_metadata,
+            check_attachments,
+            upload_clipboard_image,
+            autocomplete_users,
+            export_pinned_digest,
+            export_channel,
+            export_thread,
+            get_pinned_posts,
+            set_post_pinned,
+            get_saved_posts,
+            set_post_saved,
+            list_custom_emoji,
+            search_emoji,
+            get_custom_emoji_image,
+            resolve_message_emoji,
+            dm_recipient_local_time,
+            load_cached_posts,
+            mark_thread_unread,
+            set_last_viewed,
+            get_scroll_anchor,
+            my_mentions,
+            create_post,
+            reply_in_thread,
+            thread_updates,
+            pending_posts,
+            schedule_post,
+            list_scheduled_posts,
+            cancel_scheduled_post,
+            add_reminder,
+            list_reminders,
+            snooze_reminder,
+            delete_reminder,
+            sync_preferences,
+            get_preferences,
+            update_preferences,
+            sidebar_categories,
+            update_sidebar_categories,
+            reorder_sidebar_categories,
+            send_secure_snippet,
+            decrypt_snippet,
+            get_post_permalink,
+            forward_post,
+            resolve_permalink,
+            get_call_state,
+            open_call,
+            secret_guard,
+            set_secret_guard,
+            add_to_watch_later,
+            remove_from_watch_later,
+            watch_later,
+            set_request_signing,
+            request_signing,
+            strict_schema,
+            set_strict_schema,
+            get_network_settings,
+            configure_network,
+            get_user_status,
+            get_user_statuses,
+            set_user_status,
+            report_user_activity,
+            get_dnd,
+            set_dnd,
+            get_connection_state,
+            start_sync_scheduler,
+            stop_sync_scheduler,
+            sync_intervals,
+            set_sync_intervals,
+            get_settings,
+            update_settings,
+            set_auto_start,
+            set_memory_limits,
+            set_spellcheck,
+            set_locale,
+            get_translations,
+            set_global_shortcut,
+            toggle_devtools,
+            get_recent_logs,
+            open_log_dir,
+            export_diagnostics,
+            export_app_data,
+            storage_doctor,
+            import_app_data,
+            get_bandwidth_usage,
+            get_rate_limit_status,
+            get_audit_log,
+            clear_audit_log,
+            audit_log_enabled,
+            set_audit_log_enabled,
+            vault_account_binding,
+            set_vault_account_binding,
+            record_navigation,
+            get_navigation_history,
+            navigate_back,
+            navigate_forward,
+            quick_switch,
+            send_websocket_action,
+            send_typing,
+            take_deep_link,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::ExitRequested { api, .. } = event {
+                api.prevent_exit();
+                shutdown::begin(app);
+            }
+        });
 }
@@ -0,0 +1,551 @@
+use models::*;
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, Options, Parser, Tag};
+use url::Url;
+
+/// Parse messages of all posts in thread, so frontend only renders the tree
+/// and doesn't need Markdown parser of its own
+pub fn annotate(thread: &mut PostThread) {
+    for post in thread.posts.values_mut() {
+        post.message_ast = Some(parse(&post.message));
+    }
+}
+
+/// Parsed tree of Mattermost-flavored Markdown. Mattermost treats single
+/// newline as line break and recognizes mentions, channel links and emoji
+/// in plain text on top of CommonMark with tables and strikethrough.
+pub fn parse(message: &str) -> Vec<MarkdownBlock> {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let mut stack: Vec<(Tag, Vec<Node>)> = Vec::new();
+    let mut root = Vec::new();
+    // Parser splits text at potential delimiters, e.g. `~`, so adjacent
+    // pieces are joined before looking for mentions
+    let mut text = String::new();
+    for event in Parser::new_ext(message, options) {
+        if let Event::Text(piece) = &event {
+            text.push_str(piece);
+            continue;
+        }
+        if !text.is_empty() {
+            let verbatim = stack
+                .iter()
+                .any(|(tag, _)| matches!(tag, Tag::CodeBlock(_) | Tag::Link { .. }));
+            let parent = stack.last_mut().map_or(&mut root, |(_, children)| children);
+            let text = std::mem::take(&mut text);
+            if verbatim {
+                parent.push(Node::Inline(MarkdownInline::Text { text }));
+            } else {
+                parent.extend(split_text(&text).into_iter().map(Node::Inline));
+            }
+        }
+        let node = match event {
+            Event::Start(tag) => {
+                stack.push((tag, Vec::new()));
+                continue;
+            }
+            Event::End(_) => {
+                let (tag, children) = stack.pop().expect("Parser balances tags");
+                close(tag, children)
+            }
+            Event::Code(code) => Node::Inline(MarkdownInline::Code {
+                code: code.into_string(),
+            }),
+            Event::Html(html) | Event::InlineHtml(html) => Node::Inline(MarkdownInline::Text {
+                text: html.into_string(),
+            }),
+            Event::SoftBreak | Event::HardBreak => Node::Inline(MarkdownInline::LineBreak),
+            Event::Rule => Node::Block(MarkdownBlock::ThematicBreak),
+            _ => continue,
+        };
+        stack
+            .last_mut()
+            .map_or(&mut root, |(_, children)| children)
+            .push(node);
+    }
+    blocks(root)
+}
+
+/// Parsed element waiting for its parent to close
+enum Node {
+    Block(MarkdownBlock),
+    Inline(MarkdownInline),
+    Item(Vec<MarkdownBlock>),
+    Row(Vec<Vec<MarkdownInline>>),
+    Cell(Vec<MarkdownInline>),
+}
+
+fn close(tag: Tag, children: Vec<Node>) -> Node {
+    match tag {
+        Tag::Paragraph => Node::Block(MarkdownBlock::Paragraph {
+            inlines: inlines(children),
+        }),
+        Tag::Heading { level, .. } => Node::Block(MarkdownBlock::Heading {
+            level: level as u8,
+            inlines: inlines(children),
+        }),
+        Tag::CodeBlock(kind) => Node::Block(MarkdownBlock::CodeBlock {
+            language: match kind {
+                CodeBlockKind::Fenced(info) => info.split_whitespace().next().map(str::to_owned),
+                CodeBlockKind::Indented => None,
+            },
+            code: plain_text(&inlines(children)),
+        }),
+        Tag::BlockQuote(_) => Node::Block(MarkdownBlock::BlockQuote {
+            blocks: blocks(children),
+        }),
+        Tag::List(start) => Node::Block(MarkdownBlock::List {
+            start,
+            items: children
+                .into_iter()
+                .filter_map(|child| match child {
+                    Node::Item(item) => Some(item),
+                    _ => None,
+                })
+                .collect(),
+        }),
+        Tag::Item => Node::Item(blocks(children)),
+        Tag::Table(alignments) => {
+            let mut rows = children.into_iter().filter_map(|child| match child {
+                Node::Row(cells) => Some(cells),
+                _ => None,
+            });
+            Node::Block(MarkdownBlock::Table {
+                alignments: alignments
+                    .into_iter()
+                    .map(|alignment| match alignment {
+                        Alignment::None => TableAlignment::None,
+                        Alignment::Left => TableAlignment::Left,
+                        Alignment::Center => TableAlignment::Center,
+                        Alignment::Right => TableAlignment::Right,
+                    })
+                    .collect(),
+                header: rows.next().unwrap_or_default(),
+                rows: rows.collect(),
+            })
+        }
+        Tag::TableHead | Tag::TableRow => Node::Row(
+            children
+                .into_iter()
+                .filter_map(|child| match child {
+                    Node::Cell(cell) => Some(cell),
+                    _ => None,
+                })
+                .collect(),
+        ),
+        Tag::TableCell => Node::Cell(inlines(children)),
+        Tag::Emphasis => Node::Inline(MarkdownInline::Emphasis {
+            inlines: inlines(children),
+        }),
+        Tag::Strong => Node::Inline(MarkdownInline::Strong {
+            inlines: inlines(children),
+        }),
+        Tag::Strikethrough => Node::Inline(MarkdownInline::Strikethrough {
+            inlines: inlines(children),
+        }),
+        Tag::Link { dest_url, .. } => match safe_url(&dest_url) {
+            Some(url) => Node::Inline(MarkdownInline::Link {
+                url,
+                inlines: inlines(children),
+            }),
+            // Text of rejected link stays, e.g. for `javascript:` URLs
+            None => Node::Inline(MarkdownInline::Text {
+                text: plain_text(&inlines(children)),
+            }),
+        },
+        Tag::Image { dest_url, .. } => {
+            let alt = plain_text(&inlines(children));
+            match safe_url(&dest_url) {
+                Some(url) => Node::Inline(MarkdownInline::Image { url, alt }),
+                None => Node::Inline(MarkdownInline::Text { text: alt }),
+            }
+        }
+        // Not enabled, content is kept as it is
+        _ => Node::Block(MarkdownBlock::Paragraph {
+            inlines: inlines(children),
+        }),
+    }
+}
+
+/// Blocks of container, inlines directly inside it (e.g. items of tight
+/// list) are wrapped in paragraph
+fn blocks(children: Vec<Node>) -> Vec<MarkdownBlock> {
+    let mut blocks = Vec::new();
+    let mut loose = Vec::new();
+    for child in children {
+        match child {
+            Node::Inline(inline) => loose.push(inline),
+            Node::Block(block) => {
+                if !loose.is_empty() {
+                    blocks.push(MarkdownBlock::Paragraph {
+                        inlines: std::mem::take(&mut loose),
+                    });
+                }
+                blocks.push(block);
+            }
+            _ => {}
+        }
+    }
+    if !loose.is_empty() {
+        blocks.push(MarkdownBlock::Paragraph { inlines: loose });
+    }
+    blocks
+}
+
+fn inlines(children: Vec<Node>) -> Vec<MarkdownInline> {
+    children
+        .into_iter()
+        .filter_map(|child| match child {
+            Node::Inline(inline) => Some(inline),
+            _ => None,
+        })
+        .collect()
+}
+
+fn plain_text(inlines: &[MarkdownInline]) -> String {
+    inlines
+        .iter()
+        .map(|inline| match inline {
+            MarkdownInline::Text { text } => text.clone(),
+            MarkdownInline::Code { code } => code.clone(),
+            MarkdownInline::Emphasis { inlines }
+            | MarkdownInline::Strong { inlines }
+            | MarkdownInline::Strikethrough { inlines }
+            | MarkdownInline::Link { inlines, .. } => plain_text(inlines),
+            MarkdownInline::Image { alt, .. } => alt.clone(),
+            MarkdownInline::Mention { username } => format!("@{username}"),
+            MarkdownInline::ChannelLink { name } => format!("~{name}"),
+            MarkdownInline::Emoji { name } => format!(":{name}:"),
+            MarkdownInline::LineBreak => "\n".to_owned(),
+        })
+        .collect()
+}
+
+/// Same schemes as links of channel header, anything else could run code in
+/// webview
+fn safe_url(url: &str) -> Option<String> {
+    let parsed = Url::parse(url).ok()?;
+    matches!(parsed.scheme(), "http" | "https" | "mailto").then(|| url.to_owned())
+}
+
+/// Split plain text into text, mentions, channel links and emoji
+fn split_text(text: &str) -> Vec<MarkdownInline> {
+    let mut inlines = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text;
+    let mut previous: Option<char> = None;
+    while let Some(c) = rest.chars().next() {
+        let boundary = previous.map_or(true, |previous| !is_word(previous));
+        let found = match c {
+            '@' if boundary => name(&rest[1..], is_username).map(|username| {
+                let username = username.trim_end_matches('.');
+                (
+                    1 + username.len(),
+                    MarkdownInline::Mention {
+                        username: username.to_owned(),
+                    },
+                )
+            }),
+            '~' if boundary => name(&rest[1..], is_channel_name).map(|name| {
+                (
+                    1 + name.len(),
+                    MarkdownInline::ChannelLink {
+                        name: name.to_owned(),
+                    },
+                )
+            }),
+            ':' if boundary => name(&rest[1..], is_emoji_name)
+                .filter(|name| rest[1 + name.len()..].starts_with(':'))
+                .map(|name| {
+                    (
+                        2 + name.len(),
+                        MarkdownInline::Emoji {
+                            name: name.to_owned(),
+                        },
+                    )
+                }),
+            _ => None,
+        };
+        match found {
+            Some((length, inline)) if length > 1 => {
+                if !plain.is_empty() {
+                    inlines.push(MarkdownInline::Text {
+                        text: std::mem::take(&mut plain),
+                    });
+                }
+                inlines.push(inline);
+                previous = rest[..length].chars().last();
+                rest = &rest[length..];
+            }
+            _ => {
+                plain.push(c);
+                previous = Some(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    if !plain.is_empty() {
+        inlines.push(MarkdownInline::Text { text: plain });
+    }
+    inlines
+}
+
+/// HTML fragment of parsed message, e.g. for export. Unicode is used for
+/// known emoji, custom ones stay as `:name:`.
+pub fn to_html(blocks: &[MarkdownBlock]) -> String {
+    let mut out = String::new();
+    for block in blocks {
+        push_block(&mut out, block);
+    }
+    out
+}
+
+fn push_block(out: &mut String, block: &MarkdownBlock) {
+    match block {
+        MarkdownBlock::Paragraph { inlines } => {
+            out.push_str("<p>");
+            push_inlines(out, inlines);
+            out.push_str("</p>\n");
+        }
+        MarkdownBlock::Heading { level, inlines } => {
+            let level = level.clamp(&1, &6);
+            out.push_str(&format!("<h{level}>"));
+            push_inlines(out, inlines);
+            out.push_str(&format!("</h{level}>\n"));
+        }
+        MarkdownBlock::CodeBlock { language, code } => {
+            match language {
+                Some(language) => out.push_str(&format!(
+                    "<pre><code class=\"language-{}\">",
+                    escape_html(language)
+                )),
+                None => out.push_str("<pre><code>"),
+            }
+            out.push_str(&escape_html(code));
+            out.push_str("</code></pre>\n");
+        }
+        MarkdownBlock::BlockQuote { blocks } => {
+            out.push_str("<blockquote>\n");
+            out.push_str(&to_html(blocks));
+            out.push_str("</blockquote>\n");
+        }
+        MarkdownBlock::List { start, items } => {
+            let tag = match start {
+                Some(1) => {
+                    out.push_str("<ol>\n");
+                    "ol"
+                }
+                Some(start) => {
+                    out.push_str(&format!("<ol start=\"{start}\">\n"));
+                    "ol"
+                }
+                None => {
+                    out.push_str("<ul>\n");
+                    "ul"
+                }
+            };
+            for item in items {
+                out.push_str("<li>");
+                out.push_str(&to_html(item));
+                out.push_str("</li>\n");
+            }
+            out.push_str(&format!("</{tag}>\n"));
+        }
+        MarkdownBlock::Table {
+            alignments,
+            header,
+            rows,
+        } => {
+            let cell = |out: &mut String, tag: &str, index: usize, inlines: &[MarkdownInline]| {
+                let align = match alignments.get(index) {
+                    Some(TableAlignment::Left) => " style=\"text-align: left\"",
+                    Some(TableAlignment::Center) => " style=\"text-align: center\"",
+                    Some(TableAlignment::Right) => " style=\"text-align: right\"",
+                    _ => "",
+                };
+                out.push_str(&format!("<{tag}{align}>"));
+                push_inlines(out, inlines);
+                out.push_str(&format!("</{tag}>"));
+            };
+            out.push_str("<table>\n<thead><tr>");
+            for (index, inlines) in header.iter().enumerate() {
+                cell(out, "th", index, inlines);
+            }
+            out.push_str("</tr></thead>\n<tbody>\n");
+            for row in rows {
+                out.push_str("<tr>");
+                for (index, inlines) in row.iter().enumerate() {
+                    cell(out, "td", index, inlines);
+                }
+                out.push_str("</tr>\n");
+            }
+            out.push_str("</tbody>\n</table>\n");
+        }
+        MarkdownBlock::ThematicBreak => out.push_str("<hr>\n"),
+    }
+}
+
+fn push_inlines(out: &mut String, inlines: &[MarkdownInline]) {
+    for inline in inlines {
+        match inline {
+            MarkdownInline::Text { text } => out.push_str(&escape_html(text)),
+            MarkdownInline::Code { code } => {
+                out.push_str(&format!("<code>{}</code>", escape_html(code)));
+            }
+            MarkdownInline::Emphasis { inlines } => wrap(out, "em", inlines),
+            MarkdownInline::Strong { inlines } => wrap(out, "strong", inlines),
+            MarkdownInline::Strikethrough { inlines } => wrap(out, "del", inlines),
+            MarkdownInline::Link { url, inlines } => {
+                out.push_str(&format!("<a href=\"{}\">", escape_html(url)));
+                push_inlines(out, inlines);
+                out.push_str("</a>");
+            }
+            MarkdownInline::Image { url, alt } => out.push_str(&format!(
+                "<img src=\"{}\" alt=\"{}\">",
+                escape_html(url),
+                escape_html(alt)
+            )),
+            MarkdownInline::Mention { username } => out.push_str(&format!(
+                "<span class=\"mention\">@{}</span>",
+                escape_html(username)
+            )),
+            MarkdownInline::ChannelLink { name } => out.push_str(&format!(
+                "<span class=\"channel-link\">~{}</span>",
+                escape_html(name)
+            )),
+            MarkdownInline::Emoji { name } => match emojis::get_by_shortcode(name) {
+                Some(emoji) => out.push_str(emoji.as_str()),
+                None => out.push_str(&format!(":{}:", escape_html(name))),
+            },
+            MarkdownInline::LineBreak => out.push_str("<br>\n"),
+        }
+    }
+}
+
+fn wrap(out: &mut String, tag: &str, inlines: &[MarkdownInline]) {
+    out.push_str(&format!("<{tag}>"));
+    push_inlines(out, inlines);
+    out.push_str(&format!("</{tag}>"));
+}
+
+/// Text safe to put into HTML element or quoted attribute
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Longest non-empty prefix of `text` made of `allowed` characters
+fn name(text: &str, allowed: fn(char) -> bool) -> Option<&str> {
+    let end = text.find(|c| !allowed(c)).unwrap_or(text.len());
+    (end > 0).then(|| &text[..end])
+}
+
+/// Mention must not start in the middle of word, e.g. in email address
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '@' | '~' | ':')
+}
+
+fn is_username(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')
+}
+
+fn is_channel_name(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-')
+}
+
+fn is_emoji_name(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-')
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn text(text: &str) -> MarkdownInline {
+        MarkdownInline::Text {
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn mentions_channels_and_emoji() {
+        assert_eq!(
+            parse("Thanks @john.doe, see ~town-square :+1:\nmail me@example.com at 10:30:00"),
+            vec![MarkdownBlock::Paragraph {
+                inlines: vec![
+                    text("Thanks "),
+                    MarkdownInline::Mention {
+                        username: "john.doe".to_owned()
+                    },
+                    text(", see "),
+                    MarkdownInline::ChannelLink {
+                        name: "town-square".to_owned()
+                    },
+                    text(" "),
+                    MarkdownInline::Emoji {
+                        name: "+1".to_owned()
+                    },
+                    MarkdownInline::LineBreak,
+                    text("mail me@example.com at 10:30:00"),
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn renders_html() {
+        let message = "Ship it @john.doe :+1: <b>now</b>\n\n1. `a<b`\n\n| x |\n|--:|\n| *y* |";
+        assert_eq!(
+            to_html(&parse(message)),
+            "<p>Ship it <span class=\"mention\">@john.doe</span> 👍 &lt;b&gt;now&lt;/b&gt;</p>\n\
+             <ol>\n<li><p><code>a&lt;b</code></p>\n</li>\n</ol>\n\
+             <table>\n<thead><tr><th style=\"text-align: right\">x</th></tr></thead>\n\
+             <tbody>\n<tr><td style=\"text-align: right\"><em>y</em></td></tr>\n</tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn code_and_tables() {
+        let message = "```rust\nlet a = @b;\n```\n\n| Name | Value |\n|:-----|------:|\n| `x` | **1** |\n\n- one\n- [two](javascript:alert(1))";
+        assert_eq!(
+            parse(message),
+            vec![
+                MarkdownBlock::CodeBlock {
+                    language: Some("rust".to_owned()),
+                    code: "let a = @b;\n".to_owned(),
+                },
+                MarkdownBlock::Table {
+                    alignments: vec![TableAlignment::Left, TableAlignment::Right],
+                    header: vec![vec![text("Name")], vec![text("Value")]],
+                    rows: vec![vec![
+                        vec![MarkdownInline::Code {
+                            code: "x".to_owned()
+                        }],
+                        vec![MarkdownInline::Strong {
+                            inlines: vec![text("1")]
+                        }],
+                    ]],
+                },
+                MarkdownBlock::List {
+                    start: None,
+                    items: vec![
+                        vec![MarkdownBlock::Paragraph {
+                            inlines: vec![text("one")]
+                        }],
+                        vec![MarkdownBlock::Paragraph {
+                            inlines: vec![text("two")]
+                        }],
+                    ],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use models::*;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Mutex;
+
+use crate::states::UserState;
+
+/// Payload is [`MentionsChanged`]
+pub const MENTIONS_EVENT: &str = "mentions-changed";
+
+/// Posts found by last search for mentions plus ones mentioning user which
+/// came over WebSocket since
+#[derive(Clone, Default)]
+pub(crate) struct Mentions {
+    posts: Option<PostThread>,
+    /// Mentions which came after panel was last shown
+    unseen: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentMentions {
+    /// Newest first
+    pub posts: PostThread,
+    pub unseen: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MentionsChanged {
+    pub post_id: PostId,
+    pub unseen: usize,
+}
+
+impl Mentions {
+    /// Cached mentions, they are marked seen
+    pub(crate) fn take_cached(&mut self) -> Option<RecentMentions> {
+        let posts = self.posts.clone()?;
+        let unseen = std::mem::take(&mut self.unseen);
+        Some(RecentMentions { posts, unseen })
+    }
+
+    /// Replace cached mentions with fresh search results, results of
+    /// several teams are merged
+    pub(crate) fn replace(&mut self, results: Vec<PostThread>) -> RecentMentions {
+        let mut posts = PostThread::default();
+        for result in results {
+            for id in result.order {
+                if !posts.posts.contains_key(id.as_str()) {
+                    posts.order.push(id);
+                }
+            }
+            posts.posts.extend(result.posts);
+        }
+        let created_at = |id: &PostId| {
+            posts
+                .posts
+                .get(id.as_str())
+                .map_or(0, |post| post.create_at)
+        };
+        let mut order = std::mem::take(&mut posts.order);
+        order.sort_by_key(|id| std::cmp::Reverse(created_at(id)));
+        posts.order = order;
+        self.posts = Some(posts.clone());
+        self.unseen = 0;
+        RecentMentions { posts, unseen: 0 }
+    }
+
+    /// Add post mentioning user, returns count of unseen mentions. Edit of
+    /// known post replaces it without being counted again. Post is only
+    /// counted until first search, which finds it along with the rest.
+    pub(crate) fn add(&mut self, post: Post) -> usize {
+        let Some(posts) = &mut self.posts else {
+            self.unseen += 1;
+            return self.unseen;
+        };
+        if !posts.posts.contains_key(post.id.as_str()) {
+            posts.order.insert(0, post.id.clone());
+            self.unseen += 1;
+        }
+        posts.posts.insert(post.id.to_string(), post);
+        self.unseen
+    }
+}
+
+/// Search terms matching posts which mention `user`: `@username`, first
+/// name when user enabled it and custom mention keys
+pub fn search_terms(user: &UserResponse) -> String {
+    let props = &user.notify_props;
+    let first_name = Some(user.first_name.as_str())
+        .filter(|name| props.first_name == "true" && !name.is_empty());
+    let mut terms = vec![format!("@{}", user.username)];
+    for key in first_name.into_iter().chain(props.mention_keys.split(',')) {
+        let key = key.trim();
+        if key.is_empty() {
+            continue;
+        }
+        let term = if key.contains(char::is_whitespace) {
+            format!("\"{key}\"")
+        } else {
+            key.to_owned()
+        };
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+    terms.join(" ")
+}
+
+/// Whether `posted` event says its post mentions `user_id`. Server sends
+/// mentioned user ids as JSON in a string.
+pub(crate) fn mentions_user(event: &serde_json::Value, user_id: &UserId) -> bool {
+    if event.get("event").and_then(serde_json::Value::as_str) != Some("posted") {
+        return false;
+    }
+    event
+        .get("data")
+        .and_then(|data| data.get("mentions"))
+        .and_then(serde_json::Value::as_str)
+        .and_then(|mentions| serde_json::from_str::<Vec<String>>(mentions).ok())
+        .is_some_and(|mentions| mentions.iter().any(|id| id == user_id.as_str()))
+}
+
+/// Count post of WebSocket `event` when it mentions current user and let
+/// window know as [`MENTIONS_EVENT`]
+pub(crate) async fn receive(app: &AppHandle, event: &serde_json::Value, post: &Post) {
+    let user_state_mutex = app.state::<Mutex<UserState>>();
+    let unseen = {
+        let mut user_state = user_state_mutex.lock().await;
+        match &user_state.id {
+            Some(user_id) if mentions_user(event, user_id) => user_state.mentions.add(post.clone()),
+            _ => return,
+        }
+    };
+    let changed = MentionsChanged {
+        post_id: post.id.clone(),
+        unseen,
+    };
+    app.emit_all(MENTIONS_EVENT, changed).ok();
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn terms_of_user() {
+        let mut user: UserResponse = serde_json::from_value(serde_json::json!({
+            "id": "u1",
+            "username": "maria.k",
+            "first_name": "Maria",
+            "roles": "system_user",
+            "notify_props": { "mention_keys": "maria.k, deploy bot,,oncall", "first_name": "true" },
+        }))
+        .unwrap();
+        assert_eq!(
+            search_terms(&user),
+            "@maria.k Maria maria.k \"deploy bot\" oncall"
+        );
+        user.notify_props = UserNotifyProps::default();
+        assert_eq!(search_terms(&user), "@maria.k");
+    }
+
+    #[test]
+    fn counts_mentions_from_events() {
+        let me = UserId::new("u1".to_owned());
+        let event = |event: &str, mentions: &str| serde_json::json!({ "event": event, "data": { "mentions": mentions } });
+        assert!(mentions_user(&event("posted", "[\"u2\",\"u1\"]"), &me));
+        assert!(!mentions_user(&event("posted", "[\"u2\"]"), &me));
+        assert!(!mentions_user(&event("post_edited", "[\"u1\"]"), &me));
+        assert!(!mentions_user(
+            &serde_json::json!({ "event": "posted", "data": {} }),
+            &me
+        ));
+
+        let post = Post {
+            message: Message::new("@maria.k".to_owned()),
+            ..test_support::post("p1", 1)
+        };
+        let mut mentions = Mentions::default();
+        assert_eq!(mentions.add(post.clone()), 1);
+        // Single post isn't all mentions, search has to run first
+        assert!(mentions.take_cached().is_none());
+
+        mentions.replace(Vec::new());
+        assert_eq!(mentions.add(post.clone()), 1);
+        assert_eq!(mentions.add(post), 1);
+        let cached = mentions.take_cached().unwrap();
+        assert_eq!(cached.unseen, 1);
+        assert_eq!(cached.posts.order.len(), 1);
+        assert_eq!(mentions.take_cached().unwrap().unseen, 0);
+    }
+}
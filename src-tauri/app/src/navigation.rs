@@ -0,0 +1,139 @@
+use models::*;
+use serde::Serialize;
+
+/// Oldest entries are dropped beyond this
+const MAX_ENTRIES: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NavigationEntry {
+    pub server: ServerUrl,
+    pub team_id: Option<TeamId>,
+    pub channel_id: ChannelId,
+    pub visited_at: Timestamp,
+}
+
+impl NavigationEntry {
+    fn is_same_place(&self, other: &Self) -> bool {
+        self.server == other.server && self.channel_id == other.channel_id
+    }
+}
+
+/// Browser-like history of visited conversations.
+///
+/// Visiting a conversation while somewhere in the middle of history drops
+/// entries after current one, the same way browsers drop forward history.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NavigationHistory {
+    entries: Vec<NavigationEntry>,
+    /// Index of current entry, meaningless when there are no entries
+    current: usize,
+}
+
+impl NavigationHistory {
+    pub fn visit(&mut self, entry: NavigationEntry) {
+        if let Some(current) = self.entries.get_mut(self.current) {
+            if current.is_same_place(&entry) {
+                current.visited_at = entry.visited_at;
+                return;
+            }
+            self.entries.truncate(self.current + 1);
+        }
+        self.entries.push(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.current = self.entries.len() - 1;
+    }
+
+    pub fn back(&mut self) -> Option<&NavigationEntry> {
+        let previous = self.current.checked_sub(1)?;
+        self.current = previous;
+        self.entries.get(previous)
+    }
+
+    pub fn forward(&mut self) -> Option<&NavigationEntry> {
+        let next = self.current + 1;
+        let entry = self.entries.get(next)?;
+        self.current = next;
+        Some(entry)
+    }
+
+    pub fn snapshot(&self) -> NavigationSnapshot {
+        NavigationSnapshot {
+            entries: self.entries.clone(),
+            current: (!self.entries.is_empty()).then_some(self.current),
+            can_go_back: self.current > 0,
+            can_go_forward: self.current + 1 < self.entries.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NavigationSnapshot {
+    pub entries: Vec<NavigationEntry>,
+    pub current: Option<usize>,
+    pub can_go_back: bool,
+    pub can_go_forward: bool,
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn entry(channel: &str, visited_at: Timestamp) -> NavigationEntry {
+        NavigationEntry {
+            server: ServerUrl::parse("https://mm.example.com").unwrap(),
+            team_id: None,
+            channel_id: ChannelId::new(channel.to_owned()),
+            visited_at,
+        }
+    }
+
+    fn channel(entry: Option<&NavigationEntry>) -> Option<&str> {
+        entry.map(|entry| entry.channel_id.as_str())
+    }
+
+    #[test]
+    fn back_and_forward() {
+        let mut history = NavigationHistory::default();
+        assert!(history.back().is_none());
+        history.visit(entry("a", 1));
+        history.visit(entry("b", 2));
+        history.visit(entry("b", 3));
+        history.visit(entry("c", 4));
+
+        assert_eq!(channel(history.back()), Some("b"));
+        assert_eq!(channel(history.back()), Some("a"));
+        assert!(history.back().is_none());
+        assert_eq!(channel(history.forward()), Some("b"));
+
+        let snapshot = history.snapshot();
+        assert_eq!(snapshot.entries.len(), 3);
+        assert_eq!(snapshot.entries[1].visited_at, 3);
+        assert!(snapshot.can_go_back && snapshot.can_go_forward);
+    }
+
+    #[test]
+    fn visit_drops_forward_history() {
+        let mut history = NavigationHistory::default();
+        history.visit(entry("a", 1));
+        history.visit(entry("b", 2));
+        history.back();
+        history.visit(entry("c", 3));
+
+        assert!(history.forward().is_none());
+        assert_eq!(channel(history.back()), Some("a"));
+    }
+
+    #[test]
+    fn bounded() {
+        let mut history = NavigationHistory::default();
+        for n in 0..MAX_ENTRIES as u64 + 10 {
+            history.visit(entry(&n.to_string(), n));
+        }
+        let snapshot = history.snapshot();
+        assert_eq!(snapshot.entries.len(), MAX_ENTRIES);
+        assert_eq!(snapshot.entries[0].channel_id.as_str(), "10");
+        assert_eq!(snapshot.current, Some(MAX_ENTRIES - 1));
+    }
+}
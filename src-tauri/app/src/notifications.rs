@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use models::*;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, UserAttentionType};
+
+use crate::settings::SettingsState;
+
+/// Payload is [`NavigationTarget`] of clicked notification
+pub const NOTIFICATION_CLICKED_EVENT: &str = "notification-clicked";
+
+/// Notifications still on screen whose click is remembered, the oldest are
+/// forgotten beyond this
+const MAX_PENDING: usize = 100;
+
+/// Conversation notification leads to
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NavigationTarget {
+    pub server: ServerUrl,
+    /// `None` for direct and group messages and when team isn't known
+    pub team_id: Option<TeamId>,
+    pub channel_id: ChannelId,
+    pub post_id: Option<PostId>,
+}
+
+/// Targets of shown notifications by notification id, taken once the
+/// notification is clicked
+#[derive(Default)]
+pub struct PendingNavigation(Mutex<Pending>);
+
+#[derive(Default)]
+struct Pending {
+    next_id: u32,
+    targets: VecDeque<(u32, NavigationTarget)>,
+}
+
+impl PendingNavigation {
+    fn register(&self, target: NavigationTarget) -> u32 {
+        let mut pending = self.0.lock().unwrap();
+        let id = pending.next_id;
+        pending.next_id = pending.next_id.wrapping_add(1);
+        pending.targets.push_back((id, target));
+        while pending.targets.len() > MAX_PENDING {
+            pending.targets.pop_front();
+        }
+        id
+    }
+
+    fn take(&self, id: u32) -> Option<NavigationTarget> {
+        let mut pending = self.0.lock().unwrap();
+        let index = pending.targets.iter().position(|(known, _)| *known == id)?;
+        pending.targets.remove(index).map(|(_, target)| target)
+    }
+
+    /// Notification went away without click
+    fn forget(&self, id: u32) {
+        self.take(id);
+    }
+}
+
+/// Show desktop notification, clicking it brings window up and sends
+/// [`NOTIFICATION_CLICKED_EVENT`] with `target`. Notification settings
+/// decide whether it plays sound, shows `body` and flashes window.
+pub fn show(app: &AppHandle, title: String, body: String, target: NavigationTarget) {
+    let settings = app.state::<SettingsState>().get().notifications;
+    let body = match settings.show_preview {
+        true => body,
+        false => crate::i18n::text("notification-hidden-preview", &Default::default()),
+    };
+    if settings.flash_window {
+        flash(app);
+    }
+    let sound = settings.sound;
+    let id = app.state::<PendingNavigation>().register(target);
+    let app = app.clone();
+    // Platforms report click by blocking until notification is gone
+    let spawned = std::thread::Builder::new()
+        .name("notification".to_owned())
+        .spawn(move || {
+            let handle = app.clone();
+            let shown = platform::show(&app, &title, &body, sound, move |clicked| {
+                if clicked {
+                    open(&handle, id);
+                } else {
+                    handle.state::<PendingNavigation>().forget(id);
+                }
+            });
+            if let Err(e) = shown {
+                app.state::<PendingNavigation>().forget(id);
+                tracing::warn!("Failed to show notification: {e}");
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::warn!("Failed to start notification thread: {e}");
+    }
+}
+
+/// Flash taskbar entry of main window until it's focused
+fn flash(app: &AppHandle) {
+    let Some(window) = app.get_window("main") else {
+        return;
+    };
+    if !window.is_focused().unwrap_or(true) {
+        window
+            .request_user_attention(Some(UserAttentionType::Informational))
+            .ok();
+    }
+}
+
+fn open(app: &AppHandle, id: u32) {
+    let Some(target) = app.state::<PendingNavigation>().take(id) else {
+        return;
+    };
+    if let Some(window) = app.get_window("main") {
+        window.unminimize().ok();
+        window.show().ok();
+        window.set_focus().ok();
+    }
+    tracing::info!(
+        "Notification clicked, opening channel {}",
+        target.channel_id
+    );
+    app.emit_all(NOTIFICATION_CLICKED_EVENT, target).ok();
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use tauri::AppHandle;
+
+    /// Action freedesktop servers invoke on click of notification body
+    const DEFAULT_ACTION: &str = "default";
+    /// Sound of freedesktop sound naming spec
+    const SOUND: &str = "message-new-instant";
+
+    pub(super) fn show(
+        _app: &AppHandle,
+        title: &str,
+        body: &str,
+        sound: bool,
+        done: impl FnOnce(bool),
+    ) -> Result<(), String> {
+        let mut notification = notify_rust::Notification::new();
+        notification.summary(title).body(body).auto_icon().action(
+            DEFAULT_ACTION,
+            &crate::i18n::text("notification-open", &Default::default()),
+        );
+        match sound {
+            true => notification.sound_name(SOUND),
+            false => notification.hint(notify_rust::Hint::SuppressSound(true)),
+        };
+        let handle = notification.show().map_err(|e| e.to_string())?;
+        handle.wait_for_action(|action| done(action == DEFAULT_ACTION));
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::sync::Once;
+
+    use mac_notification_sys::{Notification, NotificationResponse};
+    use tauri::AppHandle;
+
+    static SET_APPLICATION: Once = Once::new();
+
+    pub(super) fn show(
+        app: &AppHandle,
+        title: &str,
+        body: &str,
+        sound: bool,
+        done: impl FnOnce(bool),
+    ) -> Result<(), String> {
+        SET_APPLICATION.call_once(|| {
+            let identifier = &app.config().tauri.bundle.identifier;
+            if let Err(e) = mac_notification_sys::set_application(identifier) {
+                tracing::warn!("Failed to set application of notifications: {e}");
+            }
+        });
+        let mut notification = Notification::default();
+        notification.title(title).message(body).wait_for_click(true);
+        if sound {
+            notification.default_sound();
+        }
+        let response = notification.send().map_err(|e| e.to_string())?;
+        done(matches!(response, NotificationResponse::Click));
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::sync::Mutex;
+
+    use tauri::AppHandle;
+    use tauri_winrt_notification::{Sound, Toast};
+
+    /// Toast reports only activation, target of dismissed one stays pending
+    /// until newer notifications push it out
+    pub(super) fn show(
+        app: &AppHandle,
+        title: &str,
+        body: &str,
+        sound: bool,
+        done: impl FnOnce(bool) + Send + 'static,
+    ) -> Result<(), String> {
+        let done = Mutex::new(Some(done));
+        Toast::new(&app.config().tauri.bundle.identifier)
+            .title(title)
+            .text1(body)
+            .sound(sound.then_some(Sound::Default))
+            .on_activated(move || {
+                if let Some(done) = done.lock().unwrap().take() {
+                    done(true);
+                }
+                Ok(())
+            })
+            .show()
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use tauri::AppHandle;
+
+    pub(super) fn show(
+        app: &AppHandle,
+        title: &str,
+        body: &str,
+        _sound: bool,
+        _done: impl FnOnce(bool),
+    ) -> Result<(), String> {
+        tauri::api::notification::Notification::new(&app.config().tauri.bundle.identifier)
+            .title(title)
+            .body(body)
+            .show()
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn target(channel: &str) -> NavigationTarget {
+        NavigationTarget {
+            server: ServerUrl::parse("https://mm.example.com").unwrap(),
+            team_id: None,
+            channel_id: ChannelId::new(channel.to_owned()),
+            post_id: None,
+        }
+    }
+
+    #[test]
+    fn takes_target_once() {
+        let pending = PendingNavigation::default();
+        let first = pending.register(target("c1"));
+        let second = pending.register(target("c2"));
+        assert_eq!(pending.take(second), Some(target("c2")));
+        assert_eq!(pending.take(second), None);
+        pending.forget(first);
+        assert_eq!(pending.take(first), None);
+
+        let oldest = pending.register(target("old"));
+        for _ in 0..MAX_PENDING {
+            pending.register(target("new"));
+        }
+        assert_eq!(pending.take(oldest), None);
+    }
+}
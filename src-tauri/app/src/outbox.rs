@@ -0,0 +1,351 @@
+use std::time::Duration;
+
+use models::*;
+use reqwest::Client;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Mutex;
+use url::Url;
+
+use crate::api::call_event::*;
+use crate::api::handle_request;
+use crate::errors::{Error, NativeError};
+use crate::shutdown;
+use crate::states::{ServerState, UserState};
+use crate::storage_handle::StorageHandle;
+
+/// How often queued posts are retried
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+/// Longest wait between retries while server keeps failing
+const MAX_FLUSH_DELAY: Duration = Duration::from_secs(300);
+
+pub const OUTBOX_SENT_EVENT: &str = "outbox-sent";
+pub const OUTBOX_FAILED_EVENT: &str = "outbox-failed";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxSent {
+    pub pending_post_id: PostId,
+    pub post: Post,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxFailed {
+    pub pending_post_id: PostId,
+    pub reason: String,
+}
+
+/// Whether server refused post for good, so retrying it can't help. Busy or
+/// failing server, expired session and answers of proxies in front of server
+/// leave post queued.
+fn is_rejected(error: &Error) -> bool {
+    match error {
+        Error::PermissionDenied(_) | Error::NotFound(_) => true,
+        Error::ApiError(e) => {
+            matches!(e.status_code, 400..=499) && !matches!(e.status_code, 401 | 408 | 429)
+        }
+        _ => false,
+    }
+}
+
+/// Delay before next periodic retry, doubled after each flush server didn't
+/// accept
+#[derive(Debug, Default)]
+struct Backoff {
+    failures: u32,
+    /// Seconds rate limited server asked to wait
+    retry_after: Option<u64>,
+}
+
+impl Backoff {
+    fn delay(&self) -> Duration {
+        match self.retry_after {
+            Some(seconds) => Duration::from_secs(seconds).max(FLUSH_INTERVAL),
+            None => FLUSH_INTERVAL
+                .saturating_mul(1 << self.failures.min(5))
+                .min(MAX_FLUSH_DELAY),
+        }
+    }
+
+    fn failed(&mut self, error: &Error) {
+        self.failures = self.failures.saturating_add(1);
+        self.retry_after = match error {
+            Error::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        };
+    }
+}
+
+/// Queue of posts composed while server was unreachable.
+///
+/// Items are persisted in vault so they survive application restart. `lock`
+/// guards read-modify-write of stored queue, `flushing` is held while queue
+/// is sent so no post is sent by two flushes.
+#[derive(Default)]
+pub struct Outbox {
+    lock: Mutex<()>,
+    flushing: Mutex<()>,
+    backoff: Mutex<Backoff>,
+}
+
+impl Outbox {
+    pub async fn enqueue(&self, storage: &StorageHandle, item: OutboxItem) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        storage
+            .run(move |storage| {
+                let mut items = storage.outbox()?;
+                items.push(item);
+                storage.store_outbox(&items)
+            })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn items(&self, storage: &StorageHandle) -> Result<Vec<OutboxItem>, Error> {
+        Ok(storage.run(|storage| storage.outbox()).await?)
+    }
+
+    async fn remove(&self, storage: &StorageHandle, done: PostId) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        storage
+            .run(move |storage| {
+                let mut items = storage.outbox()?;
+                items.retain(|item| item.post.pending_post_id != done);
+                storage.store_outbox(&items)
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Send queued posts of currently selected server, does nothing when
+    /// another flush is running.
+    ///
+    /// Stops at first failure which isn't [rejection](is_rejected) since
+    /// server is most likely still unreachable or busy, periodic retries then
+    /// back off. Posts rejected by server are dropped and reported with
+    /// `outbox-failed` event.
+    pub async fn flush(&self, app: &AppHandle) -> Result<(), Error> {
+        let Some(server_url) = app
+            .state::<Mutex<ServerState>>()
+            .lock()
+            .await
+            .current
+            .as_ref()
+            .map(|server| server.url.clone())
+        else {
+            return Ok(());
+        };
+        let Some(token) = app.state::<Mutex<UserState>>().lock().await.token.clone() else {
+            return Ok(());
+        };
+        let storage = app.state::<StorageHandle>();
+        let client = app.state::<Client>();
+        self.send(&storage, &client, &server_url, &token, |outcome| {
+            let emitted = match outcome {
+                Ok(sent) => app.emit_all(OUTBOX_SENT_EVENT, sent),
+                Err(failed) => app.emit_all(OUTBOX_FAILED_EVENT, failed),
+            };
+            emitted.ok();
+        })
+        .await
+    }
+
+    /// Send queued posts of `server_url`, each is removed from queue as soon
+    /// as server answers it so interrupted flush doesn't send it again
+    async fn send(
+        &self,
+        storage: &StorageHandle,
+        client: &Client,
+        server_url: &Url,
+        token: &AccessToken,
+        report: impl Fn(Result<OutboxSent, OutboxFailed>),
+    ) -> Result<(), Error> {
+        let Ok(_flushing) = self.flushing.try_lock() else {
+            tracing::debug!("Outbox is already being flushed");
+            return Ok(());
+        };
+        let items = self.items(storage).await?;
+        let mut failure = None;
+        for item in items.iter().filter(|item| *item.server == *server_url) {
+            let pending_post_id = item.post.pending_post_id.clone();
+            let outcome = match handle_request(
+                client,
+                server_url,
+                &ApiEvent::CreatePost(item.post.clone()),
+                Some(token),
+            )
+            .await
+            {
+                Ok(Response::Post(post)) => {
+                    tracing::info!("Queued post {pending_post_id} delivered");
+                    Ok(OutboxSent {
+                        pending_post_id: pending_post_id.clone(),
+                        post,
+                    })
+                }
+                Err(e) if !is_rejected(&e) => {
+                    tracing::debug!("Server didn't accept queued posts: {e}");
+                    failure = Some(e);
+                    break;
+                }
+                other => {
+                    let reason = match other {
+                        Err(e) => e.to_string(),
+                        Ok(_) => NativeError::UnexpectedResponse.to_string(),
+                    };
+                    tracing::warn!("Queued post {pending_post_id} rejected: {reason}");
+                    Err(OutboxFailed {
+                        pending_post_id: pending_post_id.clone(),
+                        reason,
+                    })
+                }
+            };
+            self.remove(storage, pending_post_id).await?;
+            report(outcome);
+        }
+        match &failure {
+            Some(e) => self.backoff.lock().await.failed(e),
+            None => *self.backoff.lock().await = Backoff::default(),
+        }
+        Ok(())
+    }
+
+    /// Wait before next periodic flush
+    async fn retry_delay(&self) -> Duration {
+        self.backoff.lock().await.delay()
+    }
+}
+
+/// Periodically retry sending queued posts
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let outbox = app.state::<Outbox>();
+        loop {
+            tokio::time::sleep(outbox.retry_delay().await).await;
+            if shutdown::is_shutting_down() {
+                break;
+            }
+            if let Err(e) = outbox.flush(&app).await {
+                tracing::warn!("Failed to flush outbox: {e}");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod check {
+    use tempdir::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    use super::*;
+    use crate::api::mock::MockMattermost;
+    use crate::storage::Storage;
+    use crate::test_support;
+
+    fn api(status_code: i16) -> Error {
+        Error::ApiError(ServerApiError {
+            id: String::new(),
+            message: String::new(),
+            request_id: None,
+            status_code,
+        })
+    }
+
+    #[test]
+    fn drops_only_rejected_posts() {
+        assert!(is_rejected(&api(400)));
+        assert!(is_rejected(&Error::NotFound(ServerApiError {
+            id: String::new(),
+            message: String::new(),
+            request_id: None,
+            status_code: 404,
+        })));
+        assert!(!is_rejected(&api(401)));
+        assert!(!is_rejected(&api(429)));
+        assert!(!is_rejected(&api(503)));
+        assert!(!is_rejected(&NativeError::CreatePost.into()));
+        assert!(!is_rejected(&Error::RateLimited {
+            retry_after: Some(1),
+            error: ServerApiError {
+                id: String::new(),
+                message: String::new(),
+                request_id: None,
+                status_code: 429,
+            },
+        }));
+    }
+
+    #[test]
+    fn backs_off_until_flushed() {
+        let mut backoff = Backoff::default();
+        assert_eq!(backoff.delay(), FLUSH_INTERVAL);
+        for _ in 0..10 {
+            backoff.failed(&api(503));
+        }
+        assert_eq!(backoff.delay(), MAX_FLUSH_DELAY);
+        backoff.failed(&Error::RateLimited {
+            retry_after: Some(60),
+            error: ServerApiError {
+                id: String::new(),
+                message: String::new(),
+                request_id: None,
+                status_code: 429,
+            },
+        });
+        assert_eq!(backoff.delay(), Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn sends_each_post_once() {
+        let mock = MockMattermost::start().await;
+        let created = serde_json::to_string(&test_support::post("p1", 1)).unwrap();
+        // Slow enough for second flush to start while first one is sending
+        Mock::given(method("POST"))
+            .and(path("/api/v4/posts"))
+            .respond_with(
+                ResponseTemplate::new(201)
+                    .set_body_raw(created, "application/json")
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .mount(&mock.server)
+            .await;
+        let root = TempDir::new("outbox").unwrap();
+        let storage = StorageHandle::new(Storage::open_with_root(root.path().to_owned()));
+        let outbox = Outbox::default();
+        for id in ["u1:1", "u1:2"] {
+            let item = OutboxItem {
+                server: mock.url().into(),
+                post: CreatePostRequest {
+                    channel_id: ChannelId::new("town-square".to_owned()),
+                    message: Message::new("hi".to_owned()),
+                    root_id: None,
+                    pending_post_id: PostId::new(id.to_owned()),
+                    props: None,
+                },
+                queued_at: 1,
+            };
+            outbox.enqueue(&storage, item).await.unwrap();
+        }
+
+        let (client, url, token) = (Client::new(), mock.url(), MockMattermost::token());
+        let sent = std::sync::Mutex::new(Vec::new());
+        let report = |outcome: Result<OutboxSent, OutboxFailed>| {
+            sent.lock().unwrap().push(outcome.unwrap().pending_post_id);
+        };
+        let (first, second) = tokio::join!(
+            outbox.send(&storage, &client, &url, &token, report),
+            outbox.send(&storage, &client, &url, &token, report),
+        );
+        first.unwrap();
+        second.unwrap();
+
+        let requests = mock.server.received_requests().await.unwrap();
+        let posted = requests
+            .iter()
+            .filter(|request| request.url.path() == "/api/v4/posts")
+            .count();
+        assert_eq!(posted, 2);
+        assert_eq!(sent.into_inner().unwrap().len(), 2);
+        assert!(outbox.items(&storage).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Mutex;
+
+use crate::errors::Error;
+
+pub const STATE_PATCH_EVENT: &str = "state-patch";
+
+/// RFC 6902 operation, only the ones needed to describe difference of two
+/// documents are produced
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatePatch<'a> {
+    pub topic: &'a str,
+    pub patch: Vec<PatchOp>,
+}
+
+/// Last value emitted to frontend per topic.
+///
+/// First publish of topic replaces whole document (`""` path), subsequent
+/// ones send only differences against previous value.
+#[derive(Default)]
+pub struct Snapshots(Mutex<HashMap<String, Value>>);
+
+impl Snapshots {
+    pub async fn publish<T: Serialize>(
+        &self,
+        app: &AppHandle,
+        topic: &str,
+        data: &T,
+    ) -> Result<(), Error> {
+        let value = serde_json::to_value(data)?;
+        let mut snapshots = self.0.lock().await;
+        let patch = match snapshots.get(topic) {
+            Some(previous) => diff(previous, &value),
+            None => vec![PatchOp::Replace {
+                path: String::new(),
+                value: value.clone(),
+            }],
+        };
+        if patch.is_empty() {
+            return Ok(());
+        }
+        snapshots.insert(topic.to_owned(), value);
+        app.emit_all(STATE_PATCH_EVENT, StatePatch { topic, patch })
+            .ok();
+        Ok(())
+    }
+
+    /// Forget snapshots so next publish sends whole documents, e.g. after
+    /// frontend reload lost its state. All topics are reset for `None`.
+    pub async fn reset(&self, topic: Option<&str>) {
+        let mut snapshots = self.0.lock().await;
+        match topic {
+            Some(topic) => {
+                snapshots.remove(topic);
+            }
+            None => snapshots.clear(),
+        }
+    }
+}
+
+/// Operations transforming `old` into `new`
+pub fn diff(old: &Value, new: &Value) -> Vec<PatchOp> {
+    let mut patch = Vec::new();
+    diff_at(&mut String::new(), old, new, &mut patch);
+    patch
+}
+
+fn diff_at(path: &mut String, old: &Value, new: &Value, patch: &mut Vec<PatchOp>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let len = push_segment(path, key);
+                match new.get(key) {
+                    Some(new_value) => diff_at(path, old_value, new_value, patch),
+                    None => patch.push(PatchOp::Remove { path: path.clone() }),
+                }
+                path.truncate(len);
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    let len = push_segment(path, key);
+                    patch.push(PatchOp::Add {
+                        path: path.clone(),
+                        value: new_value.clone(),
+                    });
+                    path.truncate(len);
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => diff_arrays(path, old, new, patch),
+        (old, new) if old != new => patch.push(PatchOp::Replace {
+            path: path.clone(),
+            value: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Common head and tail are kept, so items added to or removed from either
+/// end of timeline produce only operations for those items
+fn diff_arrays(path: &mut String, old: &[Value], new: &[Value], patch: &mut Vec<PatchOp>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let common = old_middle.len().min(new_middle.len());
+    for (i, (old_value, new_value)) in old_middle.iter().zip(new_middle).enumerate() {
+        let len = push_segment(path, &(prefix + i).to_string());
+        diff_at(path, old_value, new_value, patch);
+        path.truncate(len);
+    }
+    // Removed from the end so earlier indexes stay valid
+    for i in (common..old_middle.len()).rev() {
+        let len = push_segment(path, &(prefix + i).to_string());
+        patch.push(PatchOp::Remove { path: path.clone() });
+        path.truncate(len);
+    }
+    for (i, value) in new_middle.iter().enumerate().skip(common) {
+        let len = push_segment(path, &(prefix + i).to_string());
+        patch.push(PatchOp::Add {
+            path: path.clone(),
+            value: value.clone(),
+        });
+        path.truncate(len);
+    }
+}
+
+/// Append JSON Pointer segment, returns length of path before it
+fn push_segment(path: &mut String, segment: &str) -> usize {
+    let len = path.len();
+    path.push('/');
+    path.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+    len
+}
+
+#[cfg(test)]
+mod check {
+    use serde_json::json;
+
+    use super::*;
+
+    fn apply(mut doc: Value, patch: &[PatchOp]) -> Value {
+        fn parent<'a>(doc: &'a mut Value, path: &str) -> (&'a mut Value, String) {
+            let (parent, last) = path.rsplit_once('/').unwrap();
+            let key = last.replace("~1", "/").replace("~0", "~");
+            (doc.pointer_mut(parent).unwrap(), key)
+        }
+        for op in patch {
+            match op {
+                PatchOp::Replace { path, value } if path.is_empty() => doc = value.clone(),
+                PatchOp::Replace { path, value } => *doc.pointer_mut(path).unwrap() = value.clone(),
+                PatchOp::Add { path, value } => match parent(&mut doc, path) {
+                    (Value::Array(items), index) => {
+                        items.insert(index.parse().unwrap(), value.clone())
+                    }
+                    (Value::Object(map), key) => {
+                        map.insert(key, value.clone());
+                    }
+                    _ => panic!("add into scalar"),
+                },
+                PatchOp::Remove { path } => match parent(&mut doc, path) {
+                    (Value::Array(items), index) => {
+                        items.remove(index.parse().unwrap());
+                    }
+                    (Value::Object(map), key) => {
+                        map.remove(&key);
+                    }
+                    _ => panic!("remove from scalar"),
+                },
+            }
+        }
+        doc
+    }
+
+    fn roundtrip(old: Value, new: Value) -> Vec<PatchOp> {
+        let patch = diff(&old, &new);
+        assert_eq!(apply(old, &patch), new);
+        patch
+    }
+
+    #[test]
+    fn objects() {
+        let patch = roundtrip(
+            json!({ "a": 1, "b": { "c": "x", "d/e": true }, "gone": null }),
+            json!({ "a": 1, "b": { "c": "y", "d/e": true }, "new~": [] }),
+        );
+        assert_eq!(
+            patch,
+            [
+                PatchOp::Replace {
+                    path: "/b/c".to_owned(),
+                    value: json!("y"),
+                },
+                PatchOp::Remove {
+                    path: "/gone".to_owned(),
+                },
+                PatchOp::Add {
+                    path: "/new~0".to_owned(),
+                    value: json!([]),
+                },
+            ]
+        );
+        assert!(diff(&json!({ "a": [1] }), &json!({ "a": [1] })).is_empty());
+    }
+
+    #[test]
+    fn timeline_grows_at_head() {
+        let patch = roundtrip(json!(["c", "b", "a"]), json!(["e", "d", "c", "b", "a"]));
+        assert_eq!(
+            patch,
+            [
+                PatchOp::Add {
+                    path: "/0".to_owned(),
+                    value: json!("e"),
+                },
+                PatchOp::Add {
+                    path: "/1".to_owned(),
+                    value: json!("d"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        roundtrip(json!([1, 2, 3, 4, 5]), json!([1, 5]));
+        roundtrip(json!([1, 2, 3]), json!([1, { "x": 2 }, 7, 8, 3]));
+        roundtrip(json!([{ "id": 1, "n": 1 }]), json!([{ "id": 1, "n": 2 }]));
+        roundtrip(json!([1, 2]), json!({ "now": "object" }));
+    }
+}
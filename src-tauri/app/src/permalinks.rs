@@ -0,0 +1,135 @@
+use models::*;
+use serde::Serialize;
+use url::Url;
+
+/// Post ids are 26 lowercase letters and digits
+const POST_ID_LENGTH: usize = 26;
+
+/// Post a permalink points to, with its channel which user is member of
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedPermalink {
+    pub post: Post,
+    pub channel: Channel,
+    /// User wasn't member of public channel and joined it to open the post
+    pub joined: bool,
+}
+
+/// Server URL with trailing slash, so relative paths are appended to it
+/// instead of replacing its last segment
+fn base(server: &Url) -> Url {
+    let mut base = server.clone();
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+    base
+}
+
+/// `https://server/{team}/pl/{post_id}`, link web and desktop apps open
+/// post from
+pub fn build(server: &Url, team_name: &str, post_id: &PostId) -> Option<Url> {
+    base(server).join(&format!("{team_name}/pl/{post_id}")).ok()
+}
+
+/// Message of forwarded post, server embeds post `link` points to below
+/// comment
+pub fn forward_message(comment: Option<&str>, link: &Url) -> String {
+    match comment.map(str::trim).filter(|comment| !comment.is_empty()) {
+        Some(comment) => format!("{comment}\n{link}"),
+        None => link.to_string(),
+    }
+}
+
+/// `https://server/{team}/channels/{channel}`, channel in web app
+pub fn channel(server: &Url, team_name: &str, channel_name: &str) -> Option<Url> {
+    base(server)
+        .join(&format!("{team_name}/channels/{channel_name}"))
+        .ok()
+}
+
+/// Team name and post id of permalink to post on `server`. `mattermost://`
+/// links of desktop apps are accepted as well.
+pub fn parse(server: &Url, link: &str) -> Option<(String, PostId)> {
+    let link = link.trim();
+    // Scheme of server isn't part of deep link, only its host. URL of custom
+    // scheme can't be switched to `https` in place.
+    let link = match link.strip_prefix("mattermost://") {
+        Some(rest) => Url::parse(&format!("{}://{rest}", server.scheme())),
+        None => Url::parse(link),
+    }
+    .ok()?;
+    let base = base(server);
+    if link.origin() != base.origin() {
+        return None;
+    }
+    let path = link.path().strip_prefix(base.path())?;
+    let mut segments = path.trim_end_matches('/').split('/');
+    let (Some(team), Some("pl"), Some(post_id), None) = (
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+    ) else {
+        return None;
+    };
+    let valid = post_id.len() == POST_ID_LENGTH
+        && post_id
+            .bytes()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+    (!team.is_empty() && valid).then(|| (team.to_owned(), PostId::new(post_id.to_owned())))
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    const POST: &str = "4xp9fdt3pjgdpbnpr6kbcy1hzw";
+
+    #[test]
+    fn round_trip() {
+        let server = Url::parse("https://mm.example.com/chat").unwrap();
+        let link = build(&server, "dev", &PostId::new(POST.to_owned())).unwrap();
+        assert_eq!(
+            link.as_str(),
+            format!("https://mm.example.com/chat/dev/pl/{POST}")
+        );
+        let (team, post_id) = parse(&server, link.as_str()).unwrap();
+        assert_eq!(team, "dev");
+        assert_eq!(post_id.as_str(), POST);
+
+        let link = channel(&server, "dev", "town-square").unwrap();
+        assert_eq!(
+            link.as_str(),
+            "https://mm.example.com/chat/dev/channels/town-square"
+        );
+    }
+
+    #[test]
+    fn forwarded_message() {
+        let server = Url::parse("https://mm.example.com/").unwrap();
+        let link = build(&server, "dev", &PostId::new(POST.to_owned())).unwrap();
+        assert_eq!(
+            forward_message(Some(" FYI \n"), &link),
+            format!("FYI\nhttps://mm.example.com/dev/pl/{POST}")
+        );
+        assert_eq!(forward_message(Some("  "), &link), link.as_str());
+        assert_eq!(forward_message(None, &link), link.as_str());
+    }
+
+    #[test]
+    fn rejects_other_links() {
+        let server = Url::parse("https://mm.example.com/").unwrap();
+        assert!(parse(
+            &server,
+            &format!("mattermost://mm.example.com/dev/pl/{POST}")
+        )
+        .is_some());
+        assert!(parse(&server, &format!("https://other.example.com/dev/pl/{POST}")).is_none());
+        assert!(parse(&server, "https://mm.example.com/dev/pl/short").is_none());
+        assert!(parse(
+            &server,
+            &format!("https://mm.example.com/dev/channels/{POST}")
+        )
+        .is_none());
+        assert!(parse(&server, "not a link").is_none());
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::VecDeque;
+
+use models::*;
+
+use crate::storage_handle::StorageHandle;
+use crate::threads;
+
+/// Posts of one channel, oldest first
+#[derive(Clone)]
+struct ChannelPosts {
+    server: ServerUrl,
+    channel_id: ChannelId,
+    posts: VecDeque<Post>,
+}
+
+/// Posts pushed out of memory, they are merged into disk cache by
+/// [`persist`]
+#[derive(Debug)]
+pub(crate) struct Evicted {
+    pub(crate) server: ServerUrl,
+    pub(crate) channel_id: ChannelId,
+    pub(crate) posts: PostThread,
+}
+
+/// Posts of recently used channels kept in memory within
+/// [`MemorySettings`]. Each channel is a ring buffer dropping its oldest
+/// posts, whole channels are dropped least recently used first.
+#[derive(Clone, Default)]
+pub(crate) struct PostStore {
+    /// Least recently used first
+    channels: VecDeque<ChannelPosts>,
+}
+
+impl PostStore {
+    /// Add or replace posts of channel and mark it most recently used,
+    /// returns what no longer fits in `limits`
+    pub(crate) fn insert(
+        &mut self,
+        server: &ServerUrl,
+        channel_id: &ChannelId,
+        posts: impl IntoIterator<Item = Post>,
+        limits: MemorySettings,
+    ) -> Vec<Evicted> {
+        let mut channel = self
+            .position(server, channel_id)
+            .and_then(|index| self.channels.remove(index))
+            .unwrap_or_else(|| ChannelPosts {
+                server: server.clone(),
+                channel_id: channel_id.clone(),
+                posts: VecDeque::new(),
+            });
+        for post in posts {
+            channel.upsert(post);
+        }
+        self.channels.push_back(channel);
+        self.trim(limits)
+    }
+
+    /// Update post of channel which is kept already, e.g. from WebSocket
+    /// event. Posts of other channels are ignored, a single post wouldn't
+    /// stand for their timeline.
+    pub(crate) fn update(
+        &mut self,
+        server: &ServerUrl,
+        post: Post,
+        limits: MemorySettings,
+    ) -> Vec<Evicted> {
+        let Some(index) = self.position(server, &post.channel_id) else {
+            return Vec::new();
+        };
+        self.channels[index].upsert(post);
+        self.trim(limits)
+    }
+
+    /// Posts of channel newest first, deleted ones left out. Channel becomes
+    /// the most recently used one.
+    pub(crate) fn get(&mut self, server: &ServerUrl, channel_id: &ChannelId) -> Option<PostThread> {
+        let index = self.position(server, channel_id)?;
+        let channel = self.channels.remove(index)?;
+        let thread = channel.thread();
+        self.channels.push_back(channel);
+        Some(thread)
+    }
+
+    /// Drop posts beyond `limits`, oldest posts of each channel and least
+    /// recently used channels go first
+    pub(crate) fn trim(&mut self, limits: MemorySettings) -> Vec<Evicted> {
+        let mut evicted = Vec::new();
+        while self.channels.len() > limits.channels {
+            if let Some(mut channel) = self.channels.pop_front() {
+                evicted.push(channel.evict(channel.posts.len()));
+            }
+        }
+        for channel in &mut self.channels {
+            let over = channel.posts.len().saturating_sub(limits.posts_per_channel);
+            if over > 0 {
+                evicted.push(channel.evict(over));
+            }
+        }
+        evicted
+    }
+
+    fn position(&self, server: &ServerUrl, channel_id: &ChannelId) -> Option<usize> {
+        self.channels
+            .iter()
+            .position(|channel| &channel.server == server && &channel.channel_id == channel_id)
+    }
+}
+
+impl ChannelPosts {
+    /// Replace post with the same id or put new one in place by creation
+    /// time. Deleted posts are kept, so deletion reaches disk cache.
+    fn upsert(&mut self, post: Post) {
+        if let Some(known) = self.posts.iter_mut().find(|known| known.id == post.id) {
+            *known = post;
+            return;
+        }
+        let index = self
+            .posts
+            .partition_point(|known| known.create_at <= post.create_at);
+        self.posts.insert(index, post);
+    }
+
+    /// Remove `count` oldest posts
+    fn evict(&mut self, count: usize) -> Evicted {
+        let posts: Vec<Post> = self.posts.drain(..count.min(self.posts.len())).collect();
+        Evicted {
+            server: self.server.clone(),
+            channel_id: self.channel_id.clone(),
+            posts: newest_first(posts.iter()),
+        }
+    }
+
+    fn thread(&self) -> PostThread {
+        newest_first(self.posts.iter().filter(|post| post.delete_at == 0))
+    }
+}
+
+/// Thread of oldest first `posts`
+fn newest_first<'a>(posts: impl DoubleEndedIterator<Item = &'a Post>) -> PostThread {
+    let posts: Vec<&Post> = posts.rev().collect();
+    PostThread {
+        order: posts.iter().map(|post| post.id.clone()).collect(),
+        posts: posts
+            .into_iter()
+            .map(|post| (post.id.to_string(), post.clone()))
+            .collect(),
+        ..PostThread::default()
+    }
+}
+
+/// Merge evicted posts into disk cache of their channels on storage
+/// thread. Cache keeps `retain` newest posts there and every evicted one.
+pub(crate) fn persist(storage: &StorageHandle, evicted: Vec<Evicted>, retain: usize) {
+    if evicted.is_empty() {
+        return;
+    }
+    storage.spawn(move |storage| {
+        for Evicted {
+            server,
+            channel_id,
+            posts,
+        } in evicted
+        {
+            let stored = storage
+                .cached_posts(&server, &channel_id)
+                .and_then(|cached| {
+                    let mut cached = cached.unwrap_or_default();
+                    merge_evicted(&mut cached, posts, retain);
+                    storage.store_cached_posts(&server, &channel_id, &cached)
+                });
+            if let Err(e) = stored {
+                tracing::warn!("Failed to move posts of channel {channel_id} to disk: {e}");
+            }
+        }
+    });
+}
+
+/// Merge `evicted` posts into newest-first `cached` ones. Evicted posts
+/// are usually older than cached ones, trimming to `retain` would drop
+/// them before they ever reach disk.
+fn merge_evicted(cached: &mut PostThread, evicted: PostThread, retain: usize) {
+    let ids = evicted.order.clone();
+    threads::merge_since(cached, evicted, usize::MAX);
+    let oldest_evicted = cached
+        .order
+        .iter()
+        .rposition(|id| ids.contains(id))
+        .map_or(0, |index| index + 1);
+    threads::retain_newest(cached, retain.max(oldest_evicted));
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+    use crate::test_support;
+
+    fn post(channel: &str, id: &str, create_at: Timestamp) -> Post {
+        Post {
+            channel_id: ChannelId::new(channel.to_owned()),
+            ..test_support::post(id, create_at)
+        }
+    }
+
+    fn order(thread: &PostThread) -> Vec<&str> {
+        thread.order.iter().map(|id| id.as_str()).collect()
+    }
+
+    #[test]
+    fn bounds_posts_and_channels() {
+        let server = ServerUrl::parse("https://mm.example.com").unwrap();
+        let limits = MemorySettings {
+            posts_per_channel: 3,
+            channels: 2,
+        };
+        let (a, b, c) = (
+            ChannelId::new("a".to_owned()),
+            ChannelId::new("b".to_owned()),
+            ChannelId::new("c".to_owned()),
+        );
+        let mut store = PostStore::default();
+
+        let posts = (1..=4).map(|i| post("a", &format!("a{i}"), i));
+        let evicted = store.insert(&server, &a, posts, limits);
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(order(&evicted[0].posts), ["a1"]);
+        assert_eq!(order(&store.get(&server, &a).unwrap()), ["a4", "a3", "a2"]);
+
+        // Edit replaces post, deletion hides it
+        let mut edited = post("a", "a3", 3);
+        edited.message = Message::new("edited".to_owned());
+        assert!(store.update(&server, edited, limits).is_empty());
+        let mut deleted = post("a", "a2", 2);
+        deleted.delete_at = 5;
+        assert!(store.update(&server, deleted, limits).is_empty());
+        let kept = store.get(&server, &a).unwrap();
+        assert_eq!(order(&kept), ["a4", "a3"]);
+        assert_eq!(kept.posts["a3"].message.as_str(), "edited");
+
+        // Channel which isn't kept doesn't start from WebSocket post
+        assert!(store.update(&server, post("b", "b1", 1), limits).is_empty());
+        assert!(store.get(&server, &b).is_none());
+
+        store.insert(&server, &b, [post("b", "b1", 1)], limits);
+        store.get(&server, &a);
+        let evicted = store.insert(&server, &c, [post("c", "c1", 1)], limits);
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].channel_id, b);
+        assert!(store.get(&server, &b).is_none());
+        assert!(store.get(&server, &a).is_some());
+    }
+
+    #[test]
+    fn evicted_posts_reach_disk_cache() {
+        let server = ServerUrl::parse("https://mm.example.com").unwrap();
+        let a = ChannelId::new("a".to_owned());
+        let mut cached = newest_first([post("a", "a4", 4), post("a", "a5", 5)].iter());
+        let mut store = PostStore::default();
+        let limits = MemorySettings {
+            posts_per_channel: 2,
+            channels: 1,
+        };
+        let posts = (1..=5).map(|i| post("a", &format!("a{i}"), i));
+        let evicted = store.insert(&server, &a, posts, limits).remove(0);
+        assert_eq!(order(&evicted.posts), ["a3", "a2", "a1"]);
+
+        merge_evicted(&mut cached, evicted.posts, 2);
+        assert_eq!(order(&cached), ["a5", "a4", "a3", "a2", "a1"]);
+
+        // Posts cached before are still trimmed to `retain`
+        let mut cached = newest_first(
+            (1..=5)
+                .map(|i| post("a", &format!("a{i}"), i))
+                .collect::<Vec<_>>()
+                .iter(),
+        );
+        merge_evicted(&mut cached, newest_first([post("a", "a4", 4)].iter()), 3);
+        assert_eq!(order(&cached), ["a5", "a4", "a3"]);
+    }
+}
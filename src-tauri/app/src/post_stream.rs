@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use models::*;
+use serde::Serialize;
+
+pub const CHANNEL_POSTS_CHUNK_EVENT: &str = "channel-posts-chunk";
+pub const CHANNEL_POSTS_DONE_EVENT: &str = "channel-posts-done";
+pub const DEFAULT_CHUNK_SIZE: usize = 50;
+
+/// Part of page of channel posts, shaped like [`PostThread`] so it can be
+/// merged the same way. Chunks of page are emitted newest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct PostsChunk {
+    pub channel_id: ChannelId,
+    pub page: u32,
+    pub index: usize,
+    pub order: Vec<PostId>,
+    pub posts: HashMap<String, Post>,
+}
+
+/// Emitted after last chunk of page, carries paging fields of the thread
+#[derive(Debug, Clone, Serialize)]
+pub struct PostsDone {
+    pub channel_id: ChannelId,
+    pub page: u32,
+    pub chunks: usize,
+    pub total_posts: usize,
+    pub next_post_id: Option<PostId>,
+    pub prev_post_id: Option<PostId>,
+    pub has_next: bool,
+}
+
+/// Split page of posts into chunks of at most `size` posts of `order`.
+///
+/// Posts which aren't part of `order`, e.g. roots of threads replies on
+/// page belong to, go with the last chunk. There's always at least one
+/// chunk so frontend sees empty page the same way as any other.
+pub fn split(
+    channel_id: &ChannelId,
+    page: u32,
+    mut thread: PostThread,
+    size: usize,
+) -> (Vec<PostsChunk>, PostsDone) {
+    let total_posts = thread.posts.len();
+    let mut chunks: Vec<PostsChunk> = thread
+        .order
+        .chunks(size.max(1))
+        .enumerate()
+        .map(|(index, order)| PostsChunk {
+            channel_id: channel_id.clone(),
+            page,
+            index,
+            order: order.to_vec(),
+            posts: order
+                .iter()
+                .filter_map(|id| thread.posts.remove_entry(id.as_str()))
+                .collect(),
+        })
+        .collect();
+    if chunks.is_empty() {
+        chunks.push(PostsChunk {
+            channel_id: channel_id.clone(),
+            page,
+            index: 0,
+            order: Vec::new(),
+            posts: HashMap::new(),
+        });
+    }
+    if let Some(last) = chunks.last_mut() {
+        last.posts.extend(thread.posts);
+    }
+    let done = PostsDone {
+        channel_id: channel_id.clone(),
+        page,
+        chunks: chunks.len(),
+        total_posts,
+        next_post_id: thread.next_post_id,
+        prev_post_id: thread.prev_post_id,
+        has_next: thread.has_next,
+    };
+    (chunks, done)
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn splits_in_order() {
+        let channel_id = ChannelId::new("town-square".to_owned());
+        let thread = PostThread {
+            order: ["e", "d", "c", "b", "a"]
+                .map(|id| PostId::new(id.to_owned()))
+                .to_vec(),
+            posts: ["a", "b", "c", "d", "e", "root"]
+                .map(|id| (id.to_owned(), test_support::post(id, 0)))
+                .into_iter()
+                .collect(),
+            prev_post_id: Some(PostId::new("z".to_owned())),
+            ..PostThread::default()
+        };
+        let (chunks, done) = split(&channel_id, 1, thread, 2);
+        let orders: Vec<Vec<&str>> = chunks
+            .iter()
+            .map(|chunk| chunk.order.iter().map(|id| id.as_str()).collect())
+            .collect();
+        assert_eq!(orders, [vec!["e", "d"], vec!["c", "b"], vec!["a"]]);
+        assert_eq!(chunks[0].posts.len(), 2);
+        assert!(chunks[2].posts.contains_key("root"));
+        assert_eq!(done.chunks, 3);
+        assert_eq!(done.total_posts, 6);
+        assert_eq!(done.prev_post_id.as_ref().map(|id| id.as_str()), Some("z"));
+    }
+
+    #[test]
+    fn empty_page() {
+        let channel_id = ChannelId::new("town-square".to_owned());
+        let (chunks, done) = split(&channel_id, 0, PostThread::default(), 50);
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].order.is_empty());
+        assert_eq!(done.chunks, 1);
+    }
+}
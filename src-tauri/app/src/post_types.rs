@@ -0,0 +1,192 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// How to render post of custom type added by plugin, frontend has no
+/// renderer of its own for them and such posts would be blank otherwise
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RenderHint {
+    /// Event of known integration, e.g. updated Jira issue
+    Card {
+        source: &'static str,
+        title: String,
+        url: Option<String>,
+        text: Option<String>,
+        fields: Vec<HintField>,
+    },
+    /// Unknown type, props are shown as they are
+    RawProps {
+        post_type: String,
+        fields: Vec<HintField>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HintField {
+    pub label: String,
+    pub value: String,
+}
+
+/// Known integrations by prefix of post type
+const REGISTRY: &[(&str, &str)] = &[
+    ("custom_jira", "Jira"),
+    ("custom_github", "GitHub"),
+    ("custom_git_", "GitHub"),
+    ("custom_gitlab", "GitLab"),
+    ("custom_zoom", "Zoom"),
+    ("custom_calls", "Calls"),
+    ("custom_todo", "Todo"),
+];
+
+/// Props set by server or webapp, they say nothing about content
+const INTERNAL_PROPS: &[&str] = &[
+    "from_bot",
+    "from_plugin",
+    "from_webhook",
+    "override_icon_url",
+    "override_username",
+    "disable_group_highlight",
+    "channel_mentions",
+];
+
+/// Rendering hint of post, `None` for regular and system posts which
+/// frontend renders itself
+pub fn hint(post_type: &str, props: &Value) -> Option<RenderHint> {
+    if !post_type.starts_with("custom_") {
+        return None;
+    }
+    let card = REGISTRY
+        .iter()
+        .find(|(prefix, _)| post_type.starts_with(prefix))
+        .and_then(|(_, source)| card(source, props));
+    Some(card.unwrap_or_else(|| RenderHint::RawProps {
+        post_type: post_type.to_owned(),
+        fields: raw_fields(props),
+    }))
+}
+
+/// Integrations mostly post Slack-like attachments, first one makes the card
+fn card(source: &'static str, props: &Value) -> Option<RenderHint> {
+    let text = |value: &Value, key: &str| {
+        value
+            .get(key)
+            .and_then(Value::as_str)
+            .filter(|text| !text.is_empty())
+            .map(str::to_owned)
+    };
+    let attachment = props
+        .get("attachments")
+        .and_then(Value::as_array)
+        .and_then(|attachments| attachments.first())
+        .unwrap_or(props);
+    let title = text(attachment, "title")
+        .or_else(|| text(attachment, "pretext"))
+        .or_else(|| text(attachment, "fallback"))?;
+    let fields = attachment
+        .get("fields")
+        .and_then(Value::as_array)
+        .map(|fields| {
+            fields
+                .iter()
+                .filter_map(|field| {
+                    Some(HintField {
+                        label: text(field, "title")?,
+                        value: display(field.get("value")?),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(RenderHint::Card {
+        source,
+        title,
+        url: text(attachment, "title_link").or_else(|| text(attachment, "url")),
+        text: text(attachment, "text"),
+        fields,
+    })
+}
+
+fn raw_fields(props: &Value) -> Vec<HintField> {
+    let Some(props) = props.as_object() else {
+        return Vec::new();
+    };
+    props
+        .iter()
+        .filter(|(key, _)| !INTERNAL_PROPS.contains(&key.as_str()))
+        .map(|(key, value)| HintField {
+            label: key.clone(),
+            value: display(value),
+        })
+        .collect()
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn known_integration() {
+        let props = json!({
+            "from_plugin": "true",
+            "attachments": [{
+                "title": "MM-123 Crash on start",
+                "title_link": "https://jira.example.com/browse/MM-123",
+                "fields": [
+                    { "title": "Status", "value": "In Progress", "short": true },
+                    { "title": "Priority", "value": 2 },
+                ],
+            }],
+        });
+        assert_eq!(
+            hint("custom_jira_issue_updated", &props),
+            Some(RenderHint::Card {
+                source: "Jira",
+                title: "MM-123 Crash on start".to_owned(),
+                url: Some("https://jira.example.com/browse/MM-123".to_owned()),
+                text: None,
+                fields: vec![
+                    HintField {
+                        label: "Status".to_owned(),
+                        value: "In Progress".to_owned(),
+                    },
+                    HintField {
+                        label: "Priority".to_owned(),
+                        value: "2".to_owned(),
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_falls_back_to_props() {
+        let props = json!({ "from_bot": "true", "votes": { "yes": 3 } });
+        assert_eq!(
+            hint("custom_poll", &props),
+            Some(RenderHint::RawProps {
+                post_type: "custom_poll".to_owned(),
+                fields: vec![HintField {
+                    label: "votes".to_owned(),
+                    value: r#"{"yes":3}"#.to_owned(),
+                }],
+            })
+        );
+        // Known integration without anything to show as card
+        assert!(matches!(
+            hint("custom_github_push", &json!({})),
+            Some(RenderHint::RawProps { .. })
+        ));
+        assert_eq!(hint("", &props), None);
+        assert_eq!(hint("system_join_channel", &props), None);
+    }
+}
@@ -0,0 +1,189 @@
+use models::*;
+
+const FAVORITE_CHANNEL: &str = "favorite_channel";
+const DISPLAY_SETTINGS: &str = "display_settings";
+const THEME: &str = "theme";
+
+/// Decode preferences stored on server, unknown categories and malformed
+/// values are skipped
+pub fn decode(list: &[Preference], members: &[ChannelMember]) -> Preferences {
+    let mut preferences = Preferences::default();
+    for preference in list {
+        let value = preference.value.as_str();
+        match (preference.category.as_str(), preference.name.as_str()) {
+            (FAVORITE_CHANNEL, channel_id) if value == "true" => preferences
+                .favorite_channels
+                .push(ChannelId::new(channel_id.to_owned())),
+            (DISPLAY_SETTINGS, "use_military_time") => {
+                preferences.display.use_military_time = value == "true"
+            }
+            (DISPLAY_SETTINGS, "message_display") => {
+                value.clone_into(&mut preferences.display.message_display)
+            }
+            (DISPLAY_SETTINGS, "collapse_previews") => {
+                preferences.display.collapse_previews = value == "true"
+            }
+            (DISPLAY_SETTINGS, "colorize_usernames") => {
+                preferences.display.colorize_usernames = value == "true"
+            }
+            (DISPLAY_SETTINGS, "name_format") => {
+                value.clone_into(&mut preferences.display.name_format)
+            }
+            // Team specific themes are stored under team id, only the
+            // default one is used
+            (THEME, "") => match serde_json::from_str(value) {
+                Ok(theme) => preferences.theme = Some(theme),
+                Err(e) => tracing::warn!("Unsupported theme preference: {e}"),
+            },
+            _ => {}
+        }
+    }
+    preferences.muted_channels = members
+        .iter()
+        .filter(|member| member.notify_props.is_muted())
+        .map(|member| member.channel_id.clone())
+        .collect();
+    preferences
+}
+
+/// Preferences which have to be saved on server to get from `current` to
+/// `updated`. Muted channels are not preferences and are ignored.
+pub fn changes(user_id: &UserId, current: &Preferences, updated: &Preferences) -> Vec<Preference> {
+    let preference = |category: &str, name: &str, value: String| Preference {
+        user_id: user_id.clone(),
+        category: category.to_owned(),
+        name: name.to_owned(),
+        value,
+    };
+    let mut changes = Vec::new();
+
+    for channel_id in &updated.favorite_channels {
+        if !current.favorite_channels.contains(channel_id) {
+            changes.push(preference(FAVORITE_CHANNEL, channel_id, "true".to_owned()));
+        }
+    }
+    for channel_id in &current.favorite_channels {
+        if !updated.favorite_channels.contains(channel_id) {
+            changes.push(preference(FAVORITE_CHANNEL, channel_id, "false".to_owned()));
+        }
+    }
+
+    let (old, new) = (&current.display, &updated.display);
+    if old.use_military_time != new.use_military_time {
+        changes.push(preference(
+            DISPLAY_SETTINGS,
+            "use_military_time",
+            new.use_military_time.to_string(),
+        ));
+    }
+    if old.message_display != new.message_display {
+        changes.push(preference(
+            DISPLAY_SETTINGS,
+            "message_display",
+            new.message_display.clone(),
+        ));
+    }
+    if old.collapse_previews != new.collapse_previews {
+        changes.push(preference(
+            DISPLAY_SETTINGS,
+            "collapse_previews",
+            new.collapse_previews.to_string(),
+        ));
+    }
+    if old.colorize_usernames != new.colorize_usernames {
+        changes.push(preference(
+            DISPLAY_SETTINGS,
+            "colorize_usernames",
+            new.colorize_usernames.to_string(),
+        ));
+    }
+    if old.name_format != new.name_format {
+        changes.push(preference(
+            DISPLAY_SETTINGS,
+            "name_format",
+            new.name_format.clone(),
+        ));
+    }
+
+    if current.theme != updated.theme {
+        if let Some(theme) = &updated.theme {
+            // Theme has only string keys so it always serializes
+            let theme = serde_json::to_string(theme).unwrap_or_default();
+            changes.push(preference(THEME, "", theme));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn preference(category: &str, name: &str, value: &str) -> Preference {
+        Preference {
+            user_id: UserId::new("me".to_owned()),
+            category: category.to_owned(),
+            name: name.to_owned(),
+            value: value.to_owned(),
+        }
+    }
+
+    #[test]
+    fn decode_server_preferences() {
+        let list = [
+            preference("favorite_channel", "town-square", "true"),
+            preference("favorite_channel", "off-topic", "false"),
+            preference("display_settings", "use_military_time", "true"),
+            preference("display_settings", "message_display", "compact"),
+            preference("tutorial_step", "me", "999"),
+            preference(
+                "theme",
+                "",
+                &serde_json::json!({
+                    "type": "Onyx",
+                    "sidebarBg": "#1f1f1f",
+                    "sidebarText": "#ffffff",
+                    "centerChannelBg": "#2f3e4e",
+                    "centerChannelColor": "#dddddd",
+                    "linkColor": "#a4ffeb",
+                    "buttonBg": "#4cbba4",
+                    "buttonColor": "#ffffff",
+                    "codeTheme": "monokai",
+                })
+                .to_string(),
+            ),
+        ];
+        let preferences = decode(&list, &[]);
+        assert_eq!(
+            preferences.favorite_channels,
+            [ChannelId::new("town-square".to_owned())]
+        );
+        assert!(preferences.display.use_military_time);
+        assert_eq!(preferences.display.message_display, "compact");
+        let theme = preferences.theme.unwrap();
+        assert_eq!(theme.name.as_deref(), Some("Onyx"));
+        assert_eq!(theme.other["codeTheme"], "monokai");
+    }
+
+    #[test]
+    fn only_changes_are_saved() {
+        let me = UserId::new("me".to_owned());
+        let current = Preferences {
+            favorite_channels: vec![ChannelId::new("town-square".to_owned())],
+            ..Preferences::default()
+        };
+        let mut updated = current.clone();
+        updated.favorite_channels = vec![ChannelId::new("off-topic".to_owned())];
+        updated.display.use_military_time = true;
+
+        assert_eq!(
+            changes(&me, &current, &updated),
+            [
+                preference("favorite_channel", "off-topic", "true"),
+                preference("favorite_channel", "town-square", "false"),
+                preference("display_settings", "use_military_time", "true"),
+            ]
+        );
+        assert!(changes(&me, &current, &current).is_empty());
+    }
+}
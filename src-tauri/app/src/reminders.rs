@@ -0,0 +1,182 @@
+use std::time::Duration;
+
+use models::*;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::commands::now_millis;
+use crate::dnd::DndManager;
+use crate::errors::Error;
+use crate::notifications::{self, NavigationTarget};
+use crate::settings::SettingsState;
+use crate::storage_handle::StorageHandle;
+use crate::{i18n, shutdown};
+
+/// How often reminders are checked, notification comes at most this late
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Characters of post message shown in notification
+const PREVIEW_LENGTH: usize = 120;
+
+/// Payload is [`ReminderDue`], so window can open the post
+pub const REMINDER_DUE_EVENT: &str = "reminder-due";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReminderDue {
+    pub server: ServerUrl,
+    pub post_id: PostId,
+    pub channel_id: ChannelId,
+    pub preview: String,
+}
+
+/// First line of message shortened to [`PREVIEW_LENGTH`] characters
+pub fn preview(message: &str) -> String {
+    let line = message
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    if line.chars().count() <= PREVIEW_LENGTH {
+        return line.to_owned();
+    }
+    let mut short: String = line.chars().take(PREVIEW_LENGTH - 1).collect();
+    short.push('…');
+    short
+}
+
+/// Reminders not fired yet whose time has come by `now`
+fn due(reminders: &[Reminder], now: Timestamp) -> impl Iterator<Item = &Reminder> {
+    reminders
+        .iter()
+        .filter(move |reminder| !reminder.fired && reminder.remind_at <= now)
+}
+
+/// Mark reminder fired unless it was snoozed after it was found due at
+/// `due_at`
+fn mark_fired(reminder: &mut Reminder, due_at: Timestamp) {
+    if reminder.remind_at == due_at {
+        reminder.fired = true;
+    }
+}
+
+/// Show notification for every due reminder of any server and mark it
+/// fired. Reminders wait while do not disturb is on, with notifications
+/// turned off they only reach the window.
+async fn fire_due(app: &AppHandle) -> Result<(), Error> {
+    let now = now_millis();
+    if app.state::<DndManager>().suppresses_notifications(now) {
+        return Ok(());
+    }
+    let notify = app.state::<SettingsState>().get().notifications.enabled;
+    let storage = app.state::<StorageHandle>();
+    let reminders = storage.run(|storage| storage.reminders()).await?;
+    let due: Vec<Reminder> = due(&reminders, now).cloned().collect();
+    for reminder in &due {
+        let preview = preview(reminder.post.message.as_str());
+        tracing::info!("Reminder about post {} is due", reminder.post.id);
+        if notify {
+            show(app, reminder, preview.clone());
+        }
+        app.emit_all(
+            REMINDER_DUE_EVENT,
+            ReminderDue {
+                server: reminder.server.clone(),
+                post_id: reminder.post.id.clone(),
+                channel_id: reminder.post.channel_id.clone(),
+                preview,
+            },
+        )
+        .ok();
+        let (server, post_id, due_at) = (
+            reminder.server.clone(),
+            reminder.post.id.clone(),
+            reminder.remind_at,
+        );
+        storage
+            .run(move |storage| {
+                storage.update_reminders(&server, &[post_id], |reminder| {
+                    mark_fired(reminder, due_at);
+                    true
+                })
+            })
+            .await?;
+    }
+    Ok(())
+}
+
+fn show(app: &AppHandle, reminder: &Reminder, preview: String) {
+    notifications::show(
+        app,
+        i18n::text(
+            "notification-reminder-title",
+            &i18n::Params::from([(
+                "server",
+                reminder.server.host_str().unwrap_or_default().into(),
+            )]),
+        ),
+        preview,
+        NavigationTarget {
+            server: reminder.server.clone(),
+            team_id: None,
+            channel_id: reminder.post.channel_id.clone(),
+            post_id: Some(reminder.post.id.clone()),
+        },
+    );
+}
+
+/// Periodically fire reminders which are due, reminders due while
+/// application was closed fire right after start
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if shutdown::is_shutting_down() {
+                break;
+            }
+            if let Err(e) = fire_due(&app).await {
+                tracing::warn!("Failed to fire reminders: {e}");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn message_preview() {
+        assert_eq!(preview("\n  Deploy at 5  \nsecond line"), "Deploy at 5");
+        let long = "é".repeat(PREVIEW_LENGTH + 5);
+        let short = preview(&long);
+        assert_eq!(short.chars().count(), PREVIEW_LENGTH);
+        assert!(short.ends_with('…'));
+        assert_eq!(preview(""), "");
+    }
+
+    #[test]
+    fn snooze_outlives_firing() {
+        let post: Post = serde_json::from_str(include_str!("api/fixtures/posts.json"))
+            .map(|thread: PostThread| thread.posts.into_values().next().unwrap())
+            .unwrap();
+        let mut reminder = Reminder {
+            server: ServerUrl::parse("https://mm.example.com").unwrap(),
+            post,
+            remind_at: 2_000,
+            created_at: 1_000,
+            fired: false,
+        };
+        assert_eq!(due(&[reminder.clone()], 2_000).count(), 1);
+
+        // Snoozed while notification was shown
+        let mut snoozed = reminder.clone();
+        snoozed.remind_at = 5_000;
+        mark_fired(&mut snoozed, 2_000);
+        assert!(!snoozed.fired);
+        assert_eq!(due(&[snoozed], 2_000).count(), 0);
+
+        mark_fired(&mut reminder, 2_000);
+        assert!(reminder.fired);
+        assert_eq!(due(&[reminder], 9_000).count(), 0);
+    }
+}
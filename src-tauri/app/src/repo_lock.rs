@@ -0,0 +1,199 @@
+use std::io::ErrorKind;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, System};
+
+/// Process holding vault, written into lock file next to it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub pid: u32,
+    /// Seconds since epoch process was started at, tells apart process which
+    /// got PID of crashed owner
+    pub started_at: u64,
+}
+
+impl LockOwner {
+    /// This process, `None` if OS doesn't tell its start time
+    pub fn current() -> Option<Self> {
+        let pid = std::process::id();
+        started_at(pid).map(|started_at| Self { pid, started_at })
+    }
+
+    fn is_alive(&self) -> bool {
+        started_at(self.pid) == Some(self.started_at)
+    }
+}
+
+fn started_at(pid: u32) -> Option<u64> {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    if !system.refresh_process(pid) {
+        return None;
+    }
+    system.process(pid).map(|process| process.start_time())
+}
+
+/// Other running instance which holds lock at `path`.
+///
+/// Lock left by crashed instance or by this very process is removed so vault
+/// can be opened again. Lock is only ever published with its owner in it, one
+/// which doesn't parse is held by unknown process, unless it's older than OS
+/// boot. Its owner has PID 0 then.
+pub fn held_by_other(path: &Path) -> Option<LockOwner> {
+    let content = std::fs::read(path).ok()?;
+    match serde_json::from_slice::<LockOwner>(&content) {
+        Ok(owner) if owner.pid != std::process::id() && owner.is_alive() => {
+            return Some(owner);
+        }
+        Ok(owner) => tracing::info!("Removing stale vault lock of PID {}", owner.pid),
+        Err(_) if !before_boot(path) => {
+            tracing::warn!("Vault lock is unreadable, treating it as held");
+            return Some(LockOwner {
+                pid: 0,
+                started_at: 0,
+            });
+        }
+        Err(_) => tracing::info!("Removing unreadable vault lock left before boot"),
+    }
+    std::fs::remove_file(path).ok();
+    None
+}
+
+fn before_boot(path: &Path) -> bool {
+    let Ok(modified) = std::fs::metadata(path).and_then(|metadata| metadata.modified()) else {
+        return false;
+    };
+    let modified = modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    modified < System::boot_time()
+}
+
+/// Live owner older versions recorded in lock zbox keeps in vault directory,
+/// lock isn't changed
+pub fn recorded_owner(path: &Path) -> Option<LockOwner> {
+    let content = std::fs::read(path).ok()?;
+    serde_json::from_slice::<LockOwner>(&content)
+        .ok()
+        .filter(|owner| owner.pid != std::process::id() && owner.is_alive())
+}
+
+/// Take lock at `path` for this process, owner of lock is returned when
+/// other instance holds it.
+///
+/// Lock appears with owner already written, so other instance never reads it
+/// half written, and only one of instances starting together gets it.
+pub fn claim(path: &Path) -> std::io::Result<Option<LockOwner>> {
+    let Some(owner) = LockOwner::current() else {
+        return Ok(None);
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Lock of other instance can be removed as stale in between
+    for _ in 0..3 {
+        if let Some(holder) = held_by_other(path) {
+            return Ok(Some(holder));
+        }
+        match publish(path, &owner) {
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            result => return result.map(|_| None),
+        }
+    }
+    Err(ErrorKind::AlreadyExists.into())
+}
+
+/// Create lock with its content, failing when it exists
+fn publish(path: &Path, owner: &LockOwner) -> std::io::Result<()> {
+    let mut pending = path.as_os_str().to_owned();
+    pending.push(format!(".{}", owner.pid));
+    let pending = Path::new(&pending);
+    std::fs::write(pending, serde_json::to_vec(owner)?)?;
+    let linked = std::fs::hard_link(pending, path);
+    std::fs::remove_file(pending).ok();
+    linked
+}
+
+/// Remove lock at `path` if this process holds it
+pub fn release(path: &Path) {
+    let own = std::fs::read(path)
+        .ok()
+        .and_then(|content| serde_json::from_slice::<LockOwner>(&content).ok())
+        .is_some_and(|owner| owner.pid == std::process::id());
+    if own {
+        std::fs::remove_file(path).ok();
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn detects_live_owner() {
+        let dir = TempDir::new("repo_lock").unwrap();
+        let path = dir.path().join(".repo_lock");
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let owner = LockOwner {
+            pid: child.id(),
+            started_at: started_at(child.id()).unwrap(),
+        };
+        std::fs::write(&path, serde_json::to_vec(&owner).unwrap()).unwrap();
+        assert_eq!(held_by_other(&path), Some(owner));
+        assert_eq!(claim(&path).unwrap(), Some(owner));
+        assert_eq!(recorded_owner(&path), Some(owner));
+        release(&path);
+        assert!(path.exists());
+
+        // Same PID reused by another process
+        let reused = LockOwner {
+            started_at: owner.started_at - 1,
+            ..owner
+        };
+        std::fs::write(&path, serde_json::to_vec(&reused).unwrap()).unwrap();
+        assert_eq!(held_by_other(&path), None);
+        assert!(!path.exists());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        std::fs::write(&path, serde_json::to_vec(&owner).unwrap()).unwrap();
+        assert_eq!(held_by_other(&path), None);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn claims_lock() {
+        let dir = TempDir::new("repo_lock").unwrap();
+        let path = dir.path().join("secure").join("vault.lock");
+        assert_eq!(held_by_other(&path), None);
+
+        assert_eq!(claim(&path).unwrap(), None);
+        let written: LockOwner = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(Some(written), LockOwner::current());
+        assert_eq!(recorded_owner(&path), None);
+        // Lock of this very process is taken again
+        assert_eq!(claim(&path).unwrap(), None);
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+        release(&path);
+        assert!(!path.exists());
+
+        // Lock zbox or other instance is just creating isn't taken over
+        std::fs::write(&path, []).unwrap();
+        assert_eq!(claim(&path).unwrap().map(|owner| owner.pid), Some(0));
+        assert!(path.exists());
+        release(&path);
+        assert!(path.exists());
+    }
+}
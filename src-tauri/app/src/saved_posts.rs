@@ -0,0 +1,65 @@
+use models::*;
+
+/// Preference category of posts saved (flagged) by user, preference name is
+/// post id
+const FLAGGED_POST: &str = "flagged_post";
+
+pub fn flag(user_id: &UserId, post_id: &PostId) -> Preference {
+    Preference {
+        user_id: user_id.clone(),
+        category: FLAGGED_POST.to_owned(),
+        name: post_id.to_string(),
+        value: "true".to_owned(),
+    }
+}
+
+/// Posts in order given by server, deleted ones are left out. Server already
+/// sorts pinned and flagged posts newest first.
+pub fn panel(thread: PostThread, has_more: bool) -> PostsPanel {
+    let PostThread {
+        order, mut posts, ..
+    } = thread;
+    PostsPanel {
+        posts: order
+            .iter()
+            .filter_map(|id| posts.remove(id.as_str()))
+            .filter(|post| post.delete_at == 0)
+            .collect(),
+        has_more,
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+    use crate::test_support;
+
+    fn post(id: &str, delete_at: Timestamp) -> Post {
+        Post {
+            delete_at,
+            is_pinned: true,
+            ..test_support::post(id, 0)
+        }
+    }
+
+    #[test]
+    fn keeps_server_order() {
+        let posts = [post("a", 0), post("b", 0), post("c", 10)];
+        let thread = PostThread {
+            order: ["b", "c", "a", "missing"]
+                .map(|id| PostId::new(id.to_owned()))
+                .to_vec(),
+            posts: posts
+                .into_iter()
+                .map(|post| (post.id.to_string(), post))
+                .collect(),
+            next_post_id: None,
+            prev_post_id: None,
+            has_next: false,
+        };
+        let panel = panel(thread, false);
+        let ids: Vec<&str> = panel.posts.iter().map(|post| post.id.as_str()).collect();
+        assert_eq!(ids, ["b", "a"]);
+        assert!(panel.posts[0].is_pinned);
+    }
+}
@@ -0,0 +1,223 @@
+use std::time::Duration;
+
+use models::*;
+use reqwest::Client;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Mutex;
+
+use crate::api::call_event::*;
+use crate::api::handle_request;
+use crate::commands::now_millis;
+use crate::errors::{Error, NativeError};
+use crate::shutdown;
+use crate::states::{ServerState, UserState};
+use crate::storage_handle::StorageHandle;
+
+/// How often scheduled posts are checked, posts are sent at most this late
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+pub const SCHEDULED_POST_SENT_EVENT: &str = "scheduled-post-sent";
+pub const SCHEDULED_POST_FAILED_EVENT: &str = "scheduled-post-failed";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledPostSent {
+    pub pending_post_id: PostId,
+    pub post: Post,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledPostFailed {
+    pub pending_post_id: PostId,
+    pub reason: String,
+}
+
+/// Posts of `server` whose time has come by `now`, oldest first
+fn due<'a>(
+    items: &'a [ScheduledPost],
+    server: &ServerUrl,
+    now: Timestamp,
+) -> Vec<&'a ScheduledPost> {
+    let mut due: Vec<&ScheduledPost> = items
+        .iter()
+        .filter(|item| &item.server == server && item.send_at <= now)
+        .collect();
+    due.sort_by_key(|item| item.send_at);
+    due
+}
+
+/// Failures post waits out for next check: server is unreachable, busy or
+/// failing, or session expired until user logs in again. Mattermost answers
+/// invalid post with JSON error, answer it can't read comes from proxy in
+/// front of it.
+fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::RequestFailed(_) | Error::RateLimited { .. } => true,
+        Error::Native(NativeError::CreatePost | NativeError::InvalidToken) => true,
+        Error::ApiError(e) => matches!(e.status_code, 401 | 408 | 500..),
+        _ => false,
+    }
+}
+
+/// Send due posts of currently selected server. Posts whose time passed
+/// while application was closed are sent on first check after start.
+///
+/// [Transient](is_transient) failure leaves posts in place for next check,
+/// posts rejected by server are dropped and reported with
+/// `scheduled-post-failed` event.
+async fn send_due(app: &AppHandle) -> Result<(), Error> {
+    let storage = app.state::<StorageHandle>();
+    let items = storage.run(|storage| storage.scheduled_posts()).await?;
+    if items.is_empty() {
+        return Ok(());
+    }
+    let Some(server_url) = app
+        .state::<Mutex<ServerState>>()
+        .lock()
+        .await
+        .current
+        .as_ref()
+        .map(|server| server.url.clone())
+    else {
+        return Ok(());
+    };
+    let Some(token) = app.state::<Mutex<UserState>>().lock().await.token.clone() else {
+        return Ok(());
+    };
+    let server = ServerUrl::from(server_url.clone());
+    let client = app.state::<Client>();
+
+    let mut done = Vec::new();
+    for item in due(&items, &server, now_millis()) {
+        let pending_post_id = item.post.pending_post_id.clone();
+        match handle_request(
+            &client,
+            &server_url,
+            &ApiEvent::CreatePost(item.post.clone()),
+            Some(&token),
+        )
+        .await
+        {
+            Ok(Response::Post(post)) => {
+                tracing::info!("Scheduled post {pending_post_id} sent");
+                app.emit_all(
+                    SCHEDULED_POST_SENT_EVENT,
+                    ScheduledPostSent {
+                        pending_post_id: pending_post_id.clone(),
+                        post,
+                    },
+                )
+                .ok();
+            }
+            Err(e) if is_transient(&e) => {
+                tracing::debug!("Scheduled posts wait for server: {e}");
+                break;
+            }
+            other => {
+                let reason = match other {
+                    Err(e) => e.to_string(),
+                    Ok(_) => NativeError::UnexpectedResponse.to_string(),
+                };
+                tracing::warn!("Scheduled post {pending_post_id} rejected: {reason}");
+                app.emit_all(
+                    SCHEDULED_POST_FAILED_EVENT,
+                    ScheduledPostFailed {
+                        pending_post_id: pending_post_id.clone(),
+                        reason,
+                    },
+                )
+                .ok();
+            }
+        }
+        done.push(pending_post_id);
+    }
+    if !done.is_empty() {
+        storage
+            .run(move |storage| storage.remove_scheduled_posts(&server, &done))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Periodically send scheduled posts which are due
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if shutdown::is_shutting_down() {
+                break;
+            }
+            if let Err(e) = send_due(&app).await {
+                tracing::warn!("Failed to send scheduled posts: {e}");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn scheduled(server: &ServerUrl, id: &str, send_at: Timestamp) -> ScheduledPost {
+        ScheduledPost {
+            server: server.clone(),
+            post: CreatePostRequest {
+                channel_id: ChannelId::new("c1".to_owned()),
+                message: Message::new("later".to_owned()),
+                root_id: None,
+                pending_post_id: PostId::new(id.to_owned()),
+                props: None,
+            },
+            send_at,
+            scheduled_at: 0,
+        }
+    }
+
+    #[test]
+    fn due_posts() {
+        let server = ServerUrl::parse("https://mm.example.com").unwrap();
+        let other = ServerUrl::parse("https://other.example.com").unwrap();
+        let items = [
+            scheduled(&server, "late", 2_000),
+            scheduled(&server, "future", 9_000),
+            scheduled(&other, "other", 1_000),
+            scheduled(&server, "early", 1_000),
+        ];
+        let ids: Vec<&str> = due(&items, &server, 5_000)
+            .into_iter()
+            .map(|item| item.post.pending_post_id.as_str())
+            .collect();
+        assert_eq!(ids, ["early", "late"]);
+    }
+
+    #[test]
+    fn keeps_posts_on_transient_failures() {
+        let api = |status_code: i16| {
+            Error::ApiError(ServerApiError {
+                id: String::new(),
+                message: String::new(),
+                request_id: None,
+                status_code,
+            })
+        };
+        assert!(is_transient(&api(401)));
+        assert!(is_transient(&api(503)));
+        assert!(is_transient(&Error::RateLimited {
+            retry_after: Some(1),
+            error: ServerApiError {
+                id: String::new(),
+                message: String::new(),
+                request_id: None,
+                status_code: 429,
+            },
+        }));
+        assert!(is_transient(&NativeError::CreatePost.into()));
+        assert!(!is_transient(&api(400)));
+        assert!(!is_transient(&Error::NotFound(ServerApiError {
+            id: String::new(),
+            message: String::new(),
+            request_id: None,
+            status_code: 404,
+        })));
+    }
+}
@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use models::*;
+use reqwest::Client;
+use tauri::{AppHandle, Manager};
+use tokio::sync::{watch, Mutex};
+use tokio::time::Instant;
+use url::Url;
+
+use crate::api::call_event::*;
+use crate::api::{handle_request, rate_limit};
+use crate::commands::now_millis;
+use crate::errors::{Error, NativeError};
+use crate::patch::Snapshots;
+use crate::states::{ServerState, UserState};
+use crate::working_hours::dm_recipient;
+use crate::{dnd, sessions, shutdown, unread};
+
+/// Payload is [`sessions::ServerBadge`] of active server
+pub const SYNC_UNREADS_EVENT: &str = "sync-unreads";
+/// Payload is list of [`UserStatus`] of direct message partners, own status
+/// is kept in sync with do not disturb instead
+pub const SYNC_STATUSES_EVENT: &str = "sync-statuses";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Task {
+    Unreads,
+    Channels,
+    Statuses,
+}
+
+const TASKS: [Task; 3] = [Task::Unreads, Task::Channels, Task::Statuses];
+
+impl Task {
+    fn interval(self, intervals: &SyncIntervals) -> Duration {
+        Duration::from_secs(match self {
+            Self::Unreads => intervals.unreads,
+            Self::Channels => intervals.channels,
+            Self::Statuses => intervals.statuses,
+        })
+    }
+}
+
+/// Periodic refresh of active server. Runs only while it's started and
+/// window has focus, tasks which became due in the meantime run as soon as
+/// both are true again.
+pub struct Scheduler {
+    running: watch::Sender<bool>,
+    focused: watch::Sender<bool>,
+    intervals: watch::Sender<SyncIntervals>,
+}
+
+impl Scheduler {
+    pub fn new(intervals: SyncIntervals) -> Self {
+        Self {
+            running: watch::channel(true).0,
+            focused: watch::channel(true).0,
+            intervals: watch::channel(intervals.clamped()).0,
+        }
+    }
+
+    pub fn start(&self) {
+        self.running.send_replace(true);
+    }
+
+    pub fn stop(&self) {
+        self.running.send_replace(false);
+    }
+
+    pub fn is_running(&self) -> bool {
+        *self.running.borrow()
+    }
+
+    pub fn set_focused(&self, focused: bool) {
+        self.focused.send_if_modified(|current| {
+            let changed = *current != focused;
+            *current = focused;
+            changed
+        });
+    }
+
+    pub fn intervals(&self) -> SyncIntervals {
+        *self.intervals.borrow()
+    }
+
+    /// Returns intervals actually used, too short ones are raised to
+    /// [`SyncIntervals::MIN_SECS`]
+    pub fn set_intervals(&self, intervals: SyncIntervals) -> SyncIntervals {
+        let intervals = intervals.clamped();
+        self.intervals.send_replace(intervals);
+        intervals
+    }
+}
+
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let scheduler = app.state::<Scheduler>();
+        let mut running = scheduler.running.subscribe();
+        let mut focused = scheduler.focused.subscribe();
+        let mut intervals = scheduler.intervals.subscribe();
+        let mut last_run: [Option<Instant>; TASKS.len()] = [None; TASKS.len()];
+        loop {
+            if shutdown::is_shutting_down() {
+                break;
+            }
+            if !*running.borrow_and_update() || !*focused.borrow_and_update() {
+                tokio::select! {
+                    _ = running.changed() => {}
+                    _ = focused.changed() => {}
+                }
+                continue;
+            }
+            if let Some(delay) = throttled(&app).await {
+                tracing::info!("Background sync paused for {delay:?}, rate limit is low");
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = running.changed() => {}
+                }
+                continue;
+            }
+            let current = *intervals.borrow_and_update();
+            for (task, last_run) in TASKS.iter().zip(&mut last_run) {
+                let due = last_run.map_or(true, |at| at.elapsed() >= task.interval(&current));
+                if !due {
+                    continue;
+                }
+                if let Err(e) = run(&app, *task).await {
+                    tracing::warn!("Background sync of {task:?} failed: {e}");
+                }
+                *last_run = Some(Instant::now());
+            }
+            let next = TASKS
+                .iter()
+                .zip(&last_run)
+                .filter_map(|(task, at)| Some(*at.as_ref()? + task.interval(&current)))
+                .min()
+                .unwrap_or_else(Instant::now);
+            tokio::select! {
+                _ = tokio::time::sleep_until(next) => {}
+                _ = running.changed() => {}
+                _ = focused.changed() => {}
+                _ = intervals.changed() => {}
+            }
+        }
+    });
+}
+
+/// Servers other than active one have no WebSocket, their counts are
+/// fetched along with unreads of active server so badge sums them all
+async fn sync_other_unreads(app: &AppHandle, client: &Client, current: &Url) {
+    let sessions = app.state::<sessions::Sessions>();
+    for (server, session) in sessions.others(current).await {
+        match sessions::fetch_unreads(client, &server, &session).await {
+            Ok((channels, members)) => {
+                let badge = sessions::badge(server.clone().into(), &channels, &members);
+                sessions.update_unreads(&server, channels, members).await;
+                unread::update(app, &badge);
+            }
+            Err(e) => tracing::warn!("Failed to sync unreads of {server}: {e}"),
+        }
+    }
+}
+
+/// Remaining time of low request budget of active server
+async fn throttled(app: &AppHandle) -> Option<Duration> {
+    let state = app.state::<Mutex<ServerState>>();
+    let server = state.lock().await;
+    rate_limit::throttle(&server.current.as_ref()?.url)
+}
+
+async fn run(app: &AppHandle, task: Task) -> Result<(), Error> {
+    let user_state_mutex = app.state::<Mutex<UserState>>();
+    let (Some(token), Some(user_id)) = ({
+        let user_state = user_state_mutex.lock().await;
+        (user_state.token.clone(), user_state.id.clone())
+    }) else {
+        // Nothing to refresh until user logs in
+        return Ok(());
+    };
+    let server_url = app
+        .state::<Mutex<ServerState>>()
+        .lock()
+        .await
+        .current
+        .as_ref()
+        .ok_or(NativeError::ServerNotSelected)?
+        .url
+        .clone();
+    let client = app.state::<Client>();
+    match task {
+        Task::Unreads => {
+            let members =
+                sessions::all_channel_members(&client, &server_url, Some(&token), &user_id).await?;
+            let channels = {
+                let mut user_state = user_state_mutex.lock().await;
+                user_state.channel_members = Some(members.clone());
+                user_state.channels.clone().unwrap_or_default()
+            };
+            let badge = sessions::badge(server_url.clone().into(), &channels, &members);
+            app.state::<sessions::Sessions>()
+                .update_channel_members(&server_url, members)
+                .await;
+            unread::update(app, &badge);
+            app.emit_all(SYNC_UNREADS_EVENT, badge).ok();
+            sync_other_unreads(app, &client, &server_url).await;
+        }
+        Task::Channels => {
+            let Response::MyTeams(teams) =
+                handle_request(&client, &server_url, &ApiEvent::MyTeams, Some(&token)).await?
+            else {
+                return Err(NativeError::UnexpectedResponse)?;
+            };
+            let Response::MyChannels(channels) =
+                handle_request(&client, &server_url, &ApiEvent::MyChannels, Some(&token)).await?
+            else {
+                return Err(NativeError::UnexpectedResponse)?;
+            };
+            {
+                let mut user_state = user_state_mutex.lock().await;
+                user_state.teams = Some(teams.clone());
+                user_state.channels = Some(channels.clone());
+            }
+            let snapshots = app.state::<Snapshots>();
+            snapshots.publish(app, "teams", &teams).await?;
+            snapshots.publish(app, "channels", &channels).await?;
+        }
+        Task::Statuses => {
+            dnd::sync(app, &client, &server_url, &token, &user_id, now_millis()).await?;
+            let partners: Vec<UserId> = user_state_mutex
+                .lock()
+                .await
+                .channels
+                .iter()
+                .flatten()
+                .filter(|channel| channel.r#type.as_deref().map(String::as_str) == Some("D"))
+                .filter_map(|channel| dm_recipient(channel.name.as_ref()?, &user_id))
+                .collect();
+            if partners.is_empty() {
+                return Ok(());
+            }
+            let Response::Statuses(statuses) = handle_request(
+                &client,
+                &server_url,
+                &ApiEvent::UserStatuses(partners),
+                Some(&token),
+            )
+            .await?
+            else {
+                return Err(NativeError::UnexpectedResponse)?;
+            };
+            app.emit_all(SYNC_STATUSES_EVENT, statuses).ok();
+        }
+    }
+    Ok(())
+}
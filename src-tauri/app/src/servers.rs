@@ -0,0 +1,74 @@
+use url::Url;
+
+use crate::errors::NativeError;
+
+/// Turn address typed by user into server base URL.
+///
+/// `https` is assumed when scheme is missing. Query and fragment are
+/// dropped and path always ends with `/`, otherwise joining API paths would
+/// replace last segment of servers running under subpath.
+pub fn normalize_url(input: &str) -> Result<Url, NativeError> {
+    let input = input.trim();
+    let with_scheme = if input.contains("://") {
+        input.to_owned()
+    } else {
+        format!("https://{input}")
+    };
+    let mut url = Url::parse(&with_scheme).map_err(|e| {
+        tracing::warn!("Invalid url {input:?}: {e}");
+        NativeError::InvalidServerUrl
+    })?;
+    // URLs with credentials are rejected, it's also what e.g. `mailto:a@b`
+    // turns into once `https://` is prepended
+    if !matches!(url.scheme(), "http" | "https")
+        || url.host_str().is_none()
+        || !url.username().is_empty()
+        || url.password().is_some()
+    {
+        return Err(NativeError::InvalidServerUrl);
+    }
+    url.set_query(None);
+    url.set_fragment(None);
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    Ok(url)
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn normalized(input: &str) -> Option<String> {
+        normalize_url(input).ok().map(String::from)
+    }
+
+    #[test]
+    fn normalizes() {
+        assert_eq!(
+            normalized(" mm.Example.com "),
+            Some("https://mm.example.com/".to_owned())
+        );
+        assert_eq!(
+            normalized("http://localhost:8065"),
+            Some("http://localhost:8065/".to_owned())
+        );
+        assert_eq!(
+            normalized("https://example.com/chat?x=1#top"),
+            Some("https://example.com/chat/".to_owned())
+        );
+        assert_eq!(
+            normalized("HTTPS://example.com/chat/"),
+            Some("https://example.com/chat/".to_owned())
+        );
+    }
+
+    #[test]
+    fn rejects() {
+        assert!(normalized("").is_none());
+        assert!(normalized("ftp://example.com").is_none());
+        assert!(normalized("mailto:ops@example.com").is_none());
+        assert!(normalized("https://exa mple.com").is_none());
+    }
+}
@@ -0,0 +1,511 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures::StreamExt;
+use models::*;
+use reqwest::Client;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Mutex;
+use url::Url;
+
+use crate::api::call_event::*;
+use crate::api::handle_request;
+use crate::api::paging::{self, fetch_all_pages};
+use crate::errors::{Error, NativeError};
+use crate::states::{Server, ServerState, UserState};
+use crate::storage_handle::StorageHandle;
+use crate::{preferences, unread};
+
+pub const SERVER_BOOTSTRAPPED_EVENT: &str = "server-bootstrapped";
+pub const SERVER_BOOTSTRAP_FAILED_EVENT: &str = "server-bootstrap-failed";
+/// Payload is [`BootstrapProgress`]
+pub const BOOTSTRAP_PROGRESS_EVENT: &str = "bootstrap-progress";
+
+/// Requests of bootstrap, each reports progress once it's done
+const BOOTSTRAP_STEPS: [&str; 6] = [
+    "user",
+    "teams",
+    "team_members",
+    "channels",
+    "channel_members",
+    "preferences",
+];
+
+/// Servers bootstrapped at once, each of them runs a few requests in
+/// parallel on its own
+const MAX_PARALLEL_BOOTSTRAPS: usize = 3;
+
+/// Unread indicators of server shown in server list
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ServerBadge {
+    pub server: ServerUrl,
+    pub mentions: i64,
+    /// Channels with unread messages, muted ones are not counted
+    pub unread_channels: usize,
+    /// Same counts for channels of each team, direct and group messages
+    /// belong to no team and are left out
+    pub teams: Vec<TeamBadge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TeamBadge {
+    pub team_id: TeamId,
+    pub mentions: i64,
+    pub unread_channels: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BootstrapProgress {
+    pub server: ServerUrl,
+    /// Step which just finished, one of [`BOOTSTRAP_STEPS`]
+    pub step: &'static str,
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// Everything sidebar needs after login, fetched by one command
+#[derive(Debug, Clone, Serialize)]
+pub struct BootstrapOutput {
+    pub user_details: UserDetails,
+    pub teams: Vec<Team>,
+    pub team_members: Vec<TeamMember>,
+    pub channels: Vec<Channel>,
+    pub preferences: Preferences,
+    pub badge: ServerBadge,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BootstrapFailed {
+    pub server: ServerUrl,
+    pub reason: String,
+}
+
+/// Everything fetched for stored session of single server
+#[derive(Debug, Clone)]
+pub struct ServerSession {
+    pub token: AccessToken,
+    pub user_id: UserId,
+    pub user_details: UserDetails,
+    pub teams: Vec<Team>,
+    pub team_members: Vec<TeamMember>,
+    pub channels: Vec<Channel>,
+    pub channel_members: Vec<ChannelMember>,
+    pub preferences: Preferences,
+}
+
+impl ServerSession {
+    /// Make session the one of current server, per-user caches are reset
+    pub fn apply(&self, user_state: &mut UserState) {
+        *user_state = UserState {
+            id: Some(self.user_id.clone()),
+            token: Some(self.token.clone()),
+            user_details: Some(self.user_details.clone()),
+            teams: Some(self.teams.clone()),
+            team_members: Some(self.team_members.clone()),
+            channels: Some(self.channels.clone()),
+            channel_members: Some(self.channel_members.clone()),
+            preferences: Some(self.preferences.clone()),
+            ..UserState::default()
+        };
+    }
+}
+
+/// Sessions of all servers with stored credentials, so switching server
+/// doesn't have to fetch everything again. Keyed by server URL.
+#[derive(Default)]
+pub struct Sessions(Mutex<HashMap<String, ServerSession>>);
+
+impl Sessions {
+    pub async fn get(&self, server: &Url) -> Option<ServerSession> {
+        self.0.lock().await.get(server.as_str()).cloned()
+    }
+
+    pub async fn insert(&self, server: &Url, session: ServerSession) {
+        self.0
+            .lock()
+            .await
+            .insert(server.as_str().to_owned(), session);
+    }
+
+    /// Keep unread counts of session current, e.g. after periodic sync
+    pub async fn update_channel_members(&self, server: &Url, members: Vec<ChannelMember>) {
+        if let Some(session) = self.0.lock().await.get_mut(server.as_str()) {
+            session.channel_members = members;
+        }
+    }
+
+    /// Keep channels of session current along with unread counts
+    pub async fn update_unreads(
+        &self,
+        server: &Url,
+        channels: Vec<Channel>,
+        members: Vec<ChannelMember>,
+    ) {
+        if let Some(session) = self.0.lock().await.get_mut(server.as_str()) {
+            session.channels = channels;
+            session.channel_members = members;
+        }
+    }
+
+    /// Sessions of every server but `current`
+    pub async fn others(&self, current: &Url) -> Vec<(Url, ServerSession)> {
+        self.0
+            .lock()
+            .await
+            .iter()
+            .filter(|(server, _)| *server != current.as_str())
+            .filter_map(|(server, session)| Some((Url::parse(server).ok()?, session.clone())))
+            .collect()
+    }
+
+    pub async fn remove(&self, server: &Url) {
+        self.0.lock().await.remove(server.as_str());
+    }
+}
+
+pub fn badge(server: ServerUrl, channels: &[Channel], members: &[ChannelMember]) -> ServerBadge {
+    let mut badge = ServerBadge {
+        server,
+        mentions: 0,
+        unread_channels: 0,
+        teams: Vec::new(),
+    };
+    for member in members {
+        let channel = channels
+            .iter()
+            .find(|channel| channel.id.as_ref() == Some(&member.channel_id));
+        let team = channel
+            .and_then(|channel| channel.team_id.as_deref())
+            .filter(|team_id| !team_id.is_empty())
+            .map(|team_id| {
+                let index = match badge
+                    .teams
+                    .iter()
+                    .position(|team| team.team_id.as_str() == team_id)
+                {
+                    Some(index) => index,
+                    None => {
+                        badge.teams.push(TeamBadge {
+                            team_id: TeamId::from(team_id.to_owned()),
+                            mentions: 0,
+                            unread_channels: 0,
+                        });
+                        badge.teams.len() - 1
+                    }
+                };
+                &mut badge.teams[index]
+            });
+        let unread = !member.notify_props.is_muted()
+            && channel.is_some_and(|channel| channel.total_msg_count > member.msg_count);
+        if let Some(team) = team {
+            team.mentions += member.mention_count;
+            team.unread_channels += usize::from(unread);
+        }
+        badge.mentions += member.mention_count;
+        badge.unread_channels += usize::from(unread);
+    }
+    badge
+}
+
+/// Validate stored sessions of all servers and fetch their data in
+/// background, servers unknown to server list are added to it
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let storage = app.state::<StorageHandle>();
+        let credentials = match storage.run(|storage| storage.credentials()).await {
+            Ok(credentials) => credentials,
+            Err(e) => {
+                tracing::warn!("Failed to read stored sessions: {e}");
+                return;
+            }
+        };
+        let client = app.state::<Client>().inner().clone();
+        futures::stream::iter(credentials)
+            .map(|credentials| {
+                let client = client.clone();
+                async move {
+                    let result = bootstrap(&client, &credentials, &|_| {}).await;
+                    (credentials.url, result)
+                }
+            })
+            .buffer_unordered(MAX_PARALLEL_BOOTSTRAPS)
+            .for_each(|(server, result)| finish(&app, server, result))
+            .await;
+    });
+}
+
+async fn finish(app: &AppHandle, server: ServerUrl, result: Result<ServerSession, Error>) {
+    let session = match result {
+        Ok(session) => session,
+        Err(e) => {
+            tracing::warn!("Failed to bootstrap {}: {e}", server.as_str());
+            app.emit_all(
+                SERVER_BOOTSTRAP_FAILED_EVENT,
+                BootstrapFailed {
+                    server,
+                    reason: e.to_string(),
+                },
+            )
+            .ok();
+            return;
+        }
+    };
+    let url = server.clone().into_inner();
+    let is_current = {
+        let mut state = app.state::<Mutex<ServerState>>().inner().lock().await;
+        if !state.servers.iter().any(|known| known.url == url) {
+            state.servers.push(Server {
+                name: url.host_str().unwrap_or(url.as_str()).to_owned(),
+                url: url.clone(),
+                capabilities: None,
+            });
+        }
+        state
+            .current
+            .as_ref()
+            .is_some_and(|current| current.url == url)
+    };
+    if is_current {
+        let user_state = app.state::<Mutex<UserState>>();
+        let mut user_state = user_state.lock().await;
+        // User might have logged in while bootstrap was running
+        if user_state.token.is_none() {
+            session.apply(&mut user_state);
+        }
+    }
+    let badge = badge(server, &session.channels, &session.channel_members);
+    app.state::<Sessions>().insert(&url, session).await;
+    tracing::info!("Bootstrapped {url}: {badge:?}");
+    unread::update(app, &badge);
+    app.emit_all(SERVER_BOOTSTRAPPED_EVENT, badge).ok();
+}
+
+/// Fetch session of user behind `credentials`. Everything but user runs in
+/// parallel, `progress` gets step and count of finished steps as each of
+/// them completes.
+pub async fn bootstrap(
+    client: &Client,
+    credentials: &ServerCredentials,
+    progress: &(dyn Fn(BootstrapProgress) + Sync),
+) -> Result<ServerSession, Error> {
+    let server_url: &Url = &credentials.url;
+    let token = Some(&credentials.access_token);
+    let request =
+        |event: ApiEvent| async move { handle_request(client, server_url, &event, token).await };
+    let completed = AtomicUsize::new(0);
+    let done = |step: &'static str| {
+        progress(BootstrapProgress {
+            server: credentials.url.clone(),
+            step,
+            completed: completed.fetch_add(1, Ordering::Relaxed) + 1,
+            total: BOOTSTRAP_STEPS.len(),
+        });
+    };
+    let tracked = |step, event| {
+        let (request, done) = (&request, &done);
+        async move {
+            let response = request(event).await?;
+            done(step);
+            Ok::<_, Error>(response)
+        }
+    };
+
+    let Response::User(user) = tracked("user", ApiEvent::Me).await? else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let user_id = UserId::new(user.id.clone());
+    let (teams, team_members, channels, channel_members, preference_list) = futures::try_join!(
+        tracked("teams", ApiEvent::MyTeams),
+        tracked("team_members", ApiEvent::MyTeamMembers),
+        tracked("channels", ApiEvent::MyChannels),
+        async {
+            let members = all_channel_members(client, server_url, token, &user_id).await?;
+            done("channel_members");
+            Ok(members)
+        },
+        tracked("preferences", ApiEvent::Preferences(user_id.clone())),
+    )?;
+    let (
+        Response::MyTeams(teams),
+        Response::MyTeamMembers(team_members),
+        Response::MyChannels(channels),
+        Response::Preferences(preference_list),
+    ) = (teams, team_members, channels, preference_list)
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let preferences = preferences::decode(&preference_list, &channel_members);
+    Ok(ServerSession {
+        token: credentials.access_token.clone(),
+        user_details: UserDetails {
+            id: user.id,
+            username: user.username,
+        },
+        user_id,
+        teams,
+        team_members,
+        channels,
+        channel_members,
+        preferences,
+    })
+}
+
+/// Channels of session with their message counts and memberships with
+/// what user read of them, both fetched again
+pub async fn fetch_unreads(
+    client: &Client,
+    server_url: &Url,
+    session: &ServerSession,
+) -> Result<(Vec<Channel>, Vec<ChannelMember>), Error> {
+    let Response::MyChannels(channels) = handle_request(
+        client,
+        server_url,
+        &ApiEvent::MyChannels,
+        Some(&session.token),
+    )
+    .await?
+    else {
+        return Err(NativeError::UnexpectedResponse)?;
+    };
+    let members =
+        all_channel_members(client, server_url, Some(&session.token), &session.user_id).await?;
+    Ok((channels, members))
+}
+
+/// Memberships of user in all channels, fetched page by page
+pub async fn all_channel_members(
+    client: &Client,
+    server_url: &Url,
+    token: Option<&AccessToken>,
+    user_id: &UserId,
+) -> Result<Vec<ChannelMember>, Error> {
+    // Unread state is wrong for every membership left out, so no cap
+    let members = fetch_all_pages(paging::MAX_PER_PAGE, usize::MAX, |page, per_page| {
+        let event = ApiEvent::UserChannelMembers {
+            user_id: user_id.clone(),
+            page,
+            per_page,
+        };
+        async move {
+            match handle_request(client, server_url, &event, token).await? {
+                Response::ChannelMembers(members) => Ok(members),
+                _ => Err(NativeError::UnexpectedResponse)?,
+            }
+        }
+    })
+    .await?;
+    Ok(members.items)
+}
+
+#[cfg(test)]
+mod check {
+    use std::sync::Mutex as StdMutex;
+
+    use super::*;
+    use crate::api::mock::{MockMattermost, LOGIN};
+
+    fn channel(id: &str, team_id: &str, total_msg_count: i64) -> Channel {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "team_id": team_id,
+            "create_at": 0,
+            "update_at": 0,
+            "delete_at": 0,
+            "last_post_at": 0,
+            "total_msg_count": total_msg_count,
+            "extra_update_at": 0,
+        }))
+        .unwrap()
+    }
+
+    fn member(channel_id: &str, msg_count: i64, mention_count: i64, muted: bool) -> ChannelMember {
+        serde_json::from_value(serde_json::json!({
+            "channel_id": channel_id,
+            "user_id": "me",
+            "roles": "channel_user",
+            "last_viewed_at": 0,
+            "msg_count": msg_count,
+            "mention_count": mention_count,
+            "notify_props": { "mark_unread": if muted { "mention" } else { "all" } },
+            "last_update_at": 0,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn counts_unread() {
+        let server = ServerUrl::parse("https://mm.example.com").unwrap();
+        let channels = [
+            channel("a", "t1", 10),
+            channel("b", "t1", 5),
+            channel("c", "t2", 7),
+            channel("dm", "", 4),
+        ];
+        let members = [
+            member("a", 8, 1, false),
+            member("b", 5, 0, false),
+            member("c", 2, 2, true),
+            member("dm", 3, 1, false),
+        ];
+        assert_eq!(
+            badge(server.clone(), &channels, &members),
+            ServerBadge {
+                server,
+                mentions: 4,
+                unread_channels: 2,
+                teams: vec![
+                    TeamBadge {
+                        team_id: TeamId::from("t1".to_owned()),
+                        mentions: 1,
+                        unread_channels: 1,
+                    },
+                    TeamBadge {
+                        team_id: TeamId::from("t2".to_owned()),
+                        mentions: 2,
+                        unread_channels: 0,
+                    },
+                ],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn bootstraps_with_progress() {
+        let mock = MockMattermost::start().await;
+        mock.respond("/api/v4/users/me", 200, LOGIN).await;
+        mock.respond("/api/v4/users/me/teams/members", 200, "[]")
+            .await;
+        let user = "/api/v4/users/u1x9k3m4ajfzbp8c6wrtqhy5de";
+        mock.respond(&format!("{user}/channel_members"), 200, "[]")
+            .await;
+        mock.respond(&format!("{user}/preferences"), 200, "[]")
+            .await;
+        let credentials = ServerCredentials {
+            url: mock.url().into(),
+            access_token: MockMattermost::token(),
+        };
+        let steps = StdMutex::new(Vec::new());
+        let progress = |progress: BootstrapProgress| {
+            assert_eq!(progress.total, BOOTSTRAP_STEPS.len());
+            steps
+                .lock()
+                .unwrap()
+                .push((progress.completed, progress.step));
+        };
+
+        let session = bootstrap(&Client::new(), &credentials, &progress)
+            .await
+            .unwrap();
+        assert!(!session.teams.is_empty());
+        assert!(!session.channels.is_empty());
+        let steps = steps.into_inner().unwrap();
+        assert_eq!(steps[0], (1, "user"));
+        let mut done: Vec<&str> = steps.iter().map(|(_, step)| *step).collect();
+        done.sort_unstable();
+        let mut expected = BOOTSTRAP_STEPS;
+        expected.sort_unstable();
+        assert_eq!(done, expected);
+        assert_eq!(steps.last().unwrap().0, BOOTSTRAP_STEPS.len());
+    }
+}
@@ -0,0 +1,209 @@
+use models::{
+    NetworkSettings, NotificationSettings, Settings, ShortcutSettings, SpellcheckSettings,
+};
+use tauri::{AppHandle, Manager};
+use tokio::sync::{watch, Mutex};
+
+use crate::api::network;
+use crate::errors::Error;
+use crate::scheduler::Scheduler;
+use crate::storage_handle::StorageHandle;
+use crate::{autostart, i18n, shortcut, shutdown, spellcheck, unread};
+
+pub const SETTINGS_CHANGED_EVENT: &str = "settings-changed";
+
+/// Settings in effect, subsystems [`subscribe`](SettingsState::subscribe) to
+/// follow changes
+pub struct SettingsState {
+    current: watch::Sender<Settings>,
+    /// Held through [`modify`] so concurrent changes don't overwrite each
+    /// other
+    changing: Mutex<()>,
+}
+
+impl Default for SettingsState {
+    fn default() -> Self {
+        Self::new(Settings::default())
+    }
+}
+
+impl SettingsState {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            current: watch::Sender::new(normalized(settings)),
+            changing: Mutex::new(()),
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.current.borrow().clone()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Settings> {
+        self.current.subscribe()
+    }
+}
+
+/// Trim user input and raise values server or OS wouldn't accept
+pub fn normalized(settings: Settings) -> Settings {
+    let network = settings.network;
+    Settings {
+        sync_intervals: settings.sync_intervals.clamped(),
+        memory: settings.memory.clamped(),
+        notifications: NotificationSettings {
+            dnd_schedule: settings.notifications.dnd_schedule.clamped(),
+            ..settings.notifications
+        },
+        network: NetworkSettings {
+            proxy: network
+                .proxy
+                .map(|proxy| proxy.trim().to_owned())
+                .filter(|proxy| !proxy.is_empty()),
+            ca_certificate: network.ca_certificate.filter(|pem| !pem.trim().is_empty()),
+            insecure_hosts: network
+                .insecure_hosts
+                .iter()
+                .map(|host| host.trim().to_ascii_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect(),
+        },
+        spellcheck: SpellcheckSettings {
+            languages: spellcheck::normalized_languages(&settings.spellcheck.languages),
+            ..settings.spellcheck
+        },
+        shortcut: ShortcutSettings {
+            accelerator: shortcut::normalized_accelerator(settings.shortcut.accelerator),
+            ..settings.shortcut
+        },
+        locale: i18n::normalized_locale(settings.locale),
+        ..settings
+    }
+}
+
+/// Replace all settings, see [`modify`]
+pub async fn update(app: &AppHandle, settings: Settings) -> Result<Settings, Error> {
+    modify(app, |current| *current = settings).await
+}
+
+/// Change settings in effect, then validate, persist and publish them.
+/// Returns settings as they are applied.
+///
+/// Changes are made one at a time, each one starts from settings the
+/// previous one left. Network settings, login item and global shortcut are
+/// applied right away, nothing is saved when client can't be built from
+/// them or OS refuses the change.
+pub async fn modify(
+    app: &AppHandle,
+    change: impl FnOnce(&mut Settings),
+) -> Result<Settings, Error> {
+    let state = app.state::<SettingsState>();
+    let _changing = state.changing.lock().await;
+    let previous = state.get();
+    let mut settings = previous.clone();
+    change(&mut settings);
+    let settings = normalized(settings);
+    apply_external(app, &settings, &previous)?;
+    let stored = settings.clone();
+    let saved: Result<(), Error> = app
+        .state::<StorageHandle>()
+        .run(move |storage| storage.set_settings(&stored))
+        .await
+        .map_err(Error::from);
+    if let Err(e) = saved {
+        // Keep client and OS consistent with settings which stay in effect
+        apply_external(app, &previous, &settings).ok();
+        return Err(e);
+    }
+    state.current.send_replace(settings.clone());
+    app.emit_all(SETTINGS_CHANGED_EVENT, &settings).ok();
+    Ok(settings)
+}
+
+/// Apply changed settings kept by HTTP client and OS, already applied ones
+/// are reverted when any of them fails
+fn apply_external(app: &AppHandle, settings: &Settings, previous: &Settings) -> Result<(), Error> {
+    let network_changed = settings.network != previous.network;
+    let autostart_changed = settings.startup.launch_at_login != previous.startup.launch_at_login;
+    if network_changed {
+        network::configure(&settings.network)?;
+    }
+    let mut result = Ok(());
+    if autostart_changed {
+        result = autostart::set_enabled(settings.startup.launch_at_login);
+    }
+    if result.is_ok() && settings.shortcut != previous.shortcut {
+        result = shortcut::apply(app, &settings.shortcut);
+        if result.is_err() {
+            shortcut::apply(app, &previous.shortcut).ok();
+            if autostart_changed {
+                autostart::set_enabled(previous.startup.launch_at_login).ok();
+            }
+        }
+    }
+    if result.is_err() && network_changed {
+        network::configure(&previous.network).ok();
+    }
+    result
+}
+
+/// Keep background sync and webview following changed settings
+pub fn spawn(app: AppHandle) {
+    let mut receiver = app.state::<SettingsState>().subscribe();
+    spellcheck::apply(&app, &receiver.borrow().spellcheck);
+    i18n::apply(receiver.borrow().locale.as_deref());
+    tauri::async_runtime::spawn(async move {
+        let mut spellcheck = receiver.borrow().spellcheck.clone();
+        let mut locale = receiver.borrow().locale.clone();
+        while receiver.changed().await.is_ok() {
+            if shutdown::is_shutting_down() {
+                break;
+            }
+            let settings = receiver.borrow_and_update().clone();
+            app.state::<Scheduler>()
+                .set_intervals(settings.sync_intervals);
+            unread::refresh(&app);
+            if settings.spellcheck != spellcheck {
+                spellcheck = settings.spellcheck;
+                spellcheck::apply(&app, &spellcheck);
+            }
+            if settings.locale != locale {
+                locale = settings.locale;
+                i18n::apply(locale.as_deref());
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod check {
+    use models::{NetworkSettings, SyncIntervals};
+
+    use super::*;
+
+    #[test]
+    fn normalizes_input() {
+        let settings = normalized(Settings {
+            sync_intervals: SyncIntervals {
+                unreads: 1,
+                ..Default::default()
+            },
+            network: NetworkSettings {
+                proxy: Some("  ".to_owned()),
+                ca_certificate: None,
+                insecure_hosts: vec![" Chat.Example.com ".to_owned(), String::new()],
+            },
+            ..Default::default()
+        });
+        assert_eq!(settings.sync_intervals.unreads, SyncIntervals::MIN_SECS);
+        assert_eq!(settings.network.proxy, None);
+        assert_eq!(settings.network.insecure_hosts, ["chat.example.com"]);
+
+        let stored: Settings =
+            serde_json::from_str(r#"{"theme": "dark", "notifications": {"sound": false}}"#)
+                .unwrap();
+        assert_eq!(stored.theme, models::ColorScheme::Dark);
+        assert!(!stored.notifications.sound);
+        assert!(stored.notifications.enabled);
+        assert_eq!(stored.sync_intervals, SyncIntervals::default());
+    }
+}
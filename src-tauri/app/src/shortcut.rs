@@ -0,0 +1,75 @@
+use std::sync::Mutex;
+
+use models::ShortcutSettings;
+use tauri::{AppHandle, GlobalShortcutManager, Manager};
+
+use crate::errors::{Error, NativeError};
+
+/// Frontend opens channel switcher when it receives this
+pub const OPEN_SWITCHER_EVENT: &str = "open-channel-switcher";
+
+/// Accelerator currently registered with OS
+static REGISTERED: Mutex<Option<String>> = Mutex::new(None);
+
+/// Trimmed accelerator, `None` when it's blank
+pub fn normalized_accelerator(accelerator: Option<String>) -> Option<String> {
+    accelerator
+        .map(|accelerator| accelerator.trim().to_owned())
+        .filter(|accelerator| !accelerator.is_empty())
+}
+
+/// Replace global shortcut by one from `settings`, none stays registered when
+/// accelerator is invalid or taken by another application
+pub fn apply(app: &AppHandle, settings: &ShortcutSettings) -> Result<(), Error> {
+    let mut registered = REGISTERED.lock().unwrap();
+    let mut manager = app.global_shortcut_manager();
+    if let Some(previous) = registered.take() {
+        if let Err(e) = manager.unregister(&previous) {
+            tracing::warn!("Failed to unregister global shortcut {previous}: {e}");
+        }
+    }
+    let Some(accelerator) = settings.accelerator.clone() else {
+        return Ok(());
+    };
+    let handle = app.clone();
+    let open_switcher = settings.open_switcher;
+    if let Err(e) = manager.register(&accelerator, move || toggle(&handle, open_switcher)) {
+        tracing::warn!("Failed to register global shortcut {accelerator}: {e}");
+        return Err(NativeError::InvalidShortcut.into());
+    }
+    *registered = Some(accelerator);
+    Ok(())
+}
+
+/// Hide focused main window, otherwise bring it to front
+fn toggle(app: &AppHandle, open_switcher: bool) {
+    let Some(window) = app.get_window("main") else {
+        return;
+    };
+    let visible = window.is_visible().unwrap_or(false);
+    let minimized = window.is_minimized().unwrap_or(false);
+    if visible && !minimized && window.is_focused().unwrap_or(false) {
+        window.hide().ok();
+        return;
+    }
+    window.unminimize().ok();
+    window.show().ok();
+    window.set_focus().ok();
+    if open_switcher {
+        app.emit_all(OPEN_SWITCHER_EVENT, ()).ok();
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn blank_accelerator_disables() {
+        assert_eq!(normalized_accelerator(Some("  ".to_owned())), None);
+        assert_eq!(
+            normalized_accelerator(Some(" CmdOrCtrl+Shift+Space ".to_owned())).as_deref(),
+            Some("CmdOrCtrl+Shift+Space")
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager};
+
+use crate::errors::StorageError;
+use crate::outbox::Outbox;
+use crate::storage_handle::StorageHandle;
+use crate::websocket::WebSocket;
+use crate::{audit, bandwidth};
+
+/// Application exits after this time even if shutdown sequence didn't finish
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Leave enough of [`SHUTDOWN_TIMEOUT`] for closing storage when server is
+/// slow to accept queued posts
+const OUTBOX_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Background loops check this before every iteration and stop once it's set
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::SeqCst)
+}
+
+/// Run shutdown sequence in background and exit process once it's done.
+///
+/// Called when main window is closed or application quit is requested,
+/// subsequent calls are ignored.
+pub fn begin(app: &AppHandle) {
+    if SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tracing::info!("Shutting down");
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, shutdown(&app))
+            .await
+            .is_err()
+        {
+            tracing::warn!("Shutdown didn't finish within {SHUTDOWN_TIMEOUT:?}, exiting anyway");
+        }
+        app.exit(0);
+    });
+}
+
+async fn shutdown(app: &AppHandle) {
+    // Posts which can't be delivered now stay persisted in outbox and are
+    // sent after next start
+    match tokio::time::timeout(OUTBOX_FLUSH_TIMEOUT, app.state::<Outbox>().flush(app)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::warn!("Failed to flush outbox: {e}"),
+        Err(_) => tracing::warn!("Server didn't accept queued posts in time, keeping them"),
+    }
+
+    app.state::<WebSocket>().close();
+
+    let closed = app
+        .state::<StorageHandle>()
+        .run(|storage| {
+            if let Err(e) = bandwidth::persist(storage) {
+                tracing::warn!("Failed to persist bandwidth usage: {e}");
+            }
+            if let Err(e) = audit::persist(storage) {
+                tracing::warn!("Failed to persist audit log: {e}");
+            }
+            storage.close();
+            Ok::<_, StorageError>(())
+        })
+        .await;
+    if let Err(e) = closed {
+        tracing::error!("Failed to close storage: {e}");
+    }
+}
@@ -0,0 +1,231 @@
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use interprocess::local_socket::{LocalSocketListener, LocalSocketStream, NameTypeSupport};
+use tauri::{AppHandle, Manager};
+use url::Url;
+
+pub const DEEP_LINK_EVENT: &str = "deep-link";
+
+/// Scheme of links opened by official desktop app
+const DEEP_LINK_SCHEME: &str = "mattermost";
+
+/// Invocation which didn't arrive in time is dropped so next one isn't held
+/// up by it
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Arguments are a handful of flags and a link, anything larger isn't one
+const MAX_INVOCATION_LEN: u64 = 64 * 1024;
+
+/// Result of checking for already running instance
+pub enum Instance {
+    /// This is the only instance, it accepts invocations of later ones
+    Primary(LocalSocketListener),
+    /// Arguments were handed over to running instance, this one should exit
+    Forwarded,
+    /// Another instance runs but arguments couldn't be handed over to it,
+    /// this one should exit as well
+    Unreachable(io::Error),
+}
+
+/// Socket is per OS account, different users run their own instances
+fn socket_name() -> String {
+    if NameTypeSupport::query().paths_supported() {
+        let dir = directories::BaseDirs::new()
+            .map(|dirs| dirs.config_dir().join("worryless"))
+            .unwrap_or_else(std::env::temp_dir);
+        std::fs::create_dir_all(&dir).ok();
+        dir.join("instance.sock").display().to_string()
+    } else {
+        let user = std::env::var("USERNAME").unwrap_or_default();
+        format!("@worryless-{user}")
+    }
+}
+
+/// Become primary instance or forward `args` to one which already runs.
+///
+/// Must be called before storage is opened, second instance has to exit
+/// without touching vault.
+pub fn acquire(args: &[String]) -> io::Result<Instance> {
+    acquire_at(&socket_name(), args)
+}
+
+fn acquire_at(name: &str, args: &[String]) -> io::Result<Instance> {
+    match forward(name, args) {
+        Ok(()) => return Ok(Instance::Forwarded),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        // Socket file of crashed instance nobody listens on
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+            if NameTypeSupport::query().paths_supported() {
+                std::fs::remove_file(name).ok();
+            }
+        }
+        // Whoever listens is alive, it may be just slow
+        Err(e) => return Ok(Instance::Unreachable(e)),
+    }
+    match LocalSocketListener::bind(name) {
+        Ok(listener) => Ok(Instance::Primary(listener)),
+        // Other instance started at the same time won the race
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => match forward(name, args) {
+            Ok(()) => Ok(Instance::Forwarded),
+            Err(e) => Ok(Instance::Unreachable(e)),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+fn forward(name: &str, args: &[String]) -> io::Result<()> {
+    let mut stream = LocalSocketStream::connect(name)?;
+    stream.write_all(&serde_json::to_vec(args)?)?;
+    stream.flush()
+}
+
+/// Arguments sent by [`forward`], gives up after [`READ_TIMEOUT`]
+fn receive(stream: LocalSocketStream) -> io::Result<Vec<String>> {
+    stream.set_nonblocking(true)?;
+    let content = read_until(
+        stream.take(MAX_INVOCATION_LEN),
+        Instant::now() + READ_TIMEOUT,
+    )?;
+    Ok(serde_json::from_slice(&content)?)
+}
+
+fn read_until(mut reader: impl Read, deadline: Instant) -> io::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(content),
+            Ok(n) => content.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Err(io::ErrorKind::TimedOut.into());
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// First `mattermost://` link among command line arguments
+pub fn deep_link(args: &[String]) -> Option<String> {
+    args.iter()
+        .filter_map(|arg| Url::parse(arg).ok())
+        .find(|url| url.scheme() == DEEP_LINK_SCHEME)
+        .map(String::from)
+}
+
+/// Deep link application was started with, kept until frontend is ready to
+/// take it. Links arriving later are sent as [`DEEP_LINK_EVENT`].
+#[derive(Default)]
+pub struct DeepLinks(Mutex<Option<String>>);
+
+impl DeepLinks {
+    pub fn new(link: Option<String>) -> Self {
+        Self(Mutex::new(link))
+    }
+
+    pub fn take(&self) -> Option<String> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// Accept invocations of later instances, focus main window and pass their
+/// deep link to frontend
+pub fn spawn(app: AppHandle, listener: LocalSocketListener) {
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let args = match stream.and_then(receive) {
+                Ok(args) => args,
+                Err(e) => {
+                    tracing::warn!("Failed to read invocation of another instance: {e}");
+                    continue;
+                }
+            };
+            tracing::info!("Another instance was started, focusing window");
+            if let Some(window) = app.get_window("main") {
+                window.unminimize().ok();
+                window.show().ok();
+                window.set_focus().ok();
+            }
+            if let Some(link) = deep_link(&args) {
+                app.emit_all(DEEP_LINK_EVENT, link).ok();
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn finds_deep_link() {
+        let args = [
+            "--minimized".to_owned(),
+            "https://chat.example.com/team/pl/abc".to_owned(),
+            "mattermost://chat.example.com/team/channels/town-square".to_owned(),
+        ];
+        assert_eq!(
+            deep_link(&args).as_deref(),
+            Some("mattermost://chat.example.com/team/channels/town-square")
+        );
+        assert_eq!(deep_link(&args[..2]), None);
+    }
+
+    fn socket(test: &str) -> String {
+        let name =
+            std::env::temp_dir().join(format!("worryless-{test}-{}.sock", std::process::id()));
+        std::fs::remove_file(&name).ok();
+        name.display().to_string()
+    }
+
+    #[test]
+    fn forwards_to_primary() {
+        let name = socket("forward");
+        let args = vec!["mattermost://chat.example.com/team".to_owned()];
+        let Instance::Primary(listener) = acquire_at(&name, &[]).unwrap() else {
+            panic!("first instance isn't primary");
+        };
+        assert!(matches!(
+            acquire_at(&name, &args).unwrap(),
+            Instance::Forwarded
+        ));
+        let stream = listener.incoming().next().unwrap().unwrap();
+        assert_eq!(receive(stream).unwrap(), args);
+        std::fs::remove_file(&name).ok();
+    }
+
+    #[test]
+    fn takes_over_socket_nobody_listens_on() {
+        let name = socket("stale");
+        drop(LocalSocketListener::bind(name.as_str()).unwrap());
+        assert!(matches!(
+            acquire_at(&name, &[]).unwrap(),
+            Instance::Primary(_)
+        ));
+        std::fs::remove_file(&name).ok();
+    }
+
+    #[test]
+    fn gives_up_on_silent_client() {
+        struct Silent;
+        impl Read for Silent {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+        }
+        let started = Instant::now();
+        let e = read_until(Silent, started + Duration::from_millis(50)).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < READ_TIMEOUT);
+        assert_eq!(
+            read_until(&b"[]"[..], started).unwrap(),
+            b"[]",
+            "data already there is read past deadline"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use crate::envelope;
+use crate::errors::NativeError;
+
+/// Post prop holding encrypted snippet
+pub const SNIPPET_PROP: &str = "secure_snippet";
+/// Message of snippet post, it's what other clients show
+pub const SNIPPET_MESSAGE: &str =
+    "🔒 Secure snippet, it can be read only in light-mattermost-desktop with shared passphrase";
+
+const FORMAT_VERSION: u8 = 1;
+
+/// Encrypt `text` with key derived from passphrase, result is base64 of
+/// [`envelope`] without prefix
+pub fn encrypt(text: &str, passphrase: &str) -> Result<String, NativeError> {
+    if passphrase.is_empty() {
+        return Err(NativeError::SnippetPassphrase);
+    }
+    let sealed = envelope::seal(&[], FORMAT_VERSION, text.as_bytes(), passphrase).map_err(|e| {
+        tracing::error!("Failed to encrypt snippet: {e}");
+        NativeError::SnippetEncrypt
+    })?;
+    Ok(STANDARD.encode(sealed))
+}
+
+pub fn decrypt(encoded: &str, passphrase: &str) -> Result<String, NativeError> {
+    let sealed = STANDARD
+        .decode(encoded.trim())
+        .map_err(|_| NativeError::SnippetDecrypt)?;
+    let text = envelope::open(&sealed, &[], FORMAT_VERSION, passphrase)
+        .map_err(|_| NativeError::SnippetDecrypt)?;
+    String::from_utf8(text).map_err(|_| NativeError::SnippetDecrypt)
+}
+
+pub fn props(encoded: String) -> Value {
+    json!({ SNIPPET_PROP: encoded })
+}
+
+/// Encrypted snippet of post, `None` when post isn't a snippet
+pub fn from_props(props: &Value) -> Option<&str> {
+    props.get(SNIPPET_PROP)?.as_str()
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let encoded = encrypt("db password: hunter2", "correct horse").unwrap();
+        let props = props(encoded);
+        let encoded = from_props(&props).unwrap();
+        assert_eq!(
+            decrypt(encoded, "correct horse").unwrap(),
+            "db password: hunter2"
+        );
+        assert!(matches!(
+            decrypt(encoded, "wrong horse"),
+            Err(NativeError::SnippetDecrypt)
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        assert!(matches!(
+            encrypt("text", ""),
+            Err(NativeError::SnippetPassphrase)
+        ));
+        assert!(decrypt("not base64!", "x").is_err());
+        assert!(decrypt(&STANDARD.encode([FORMAT_VERSION, 0, 0]), "x").is_err());
+        assert!(from_props(&json!({ "from_bot": "true" })).is_none());
+    }
+}
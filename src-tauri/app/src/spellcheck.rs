@@ -0,0 +1,71 @@
+use models::SpellcheckSettings;
+use tauri::{AppHandle, Manager};
+
+/// Dictionary names as spellcheckers expect them, `en_US` rather than
+/// `en-US`, without duplicates
+pub fn normalized_languages(languages: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(languages.len());
+    for language in languages {
+        let mut parts = language.trim().split(['-', '_']);
+        let Some(lang) = parts.next().filter(|lang| !lang.is_empty()) else {
+            continue;
+        };
+        let mut language = lang.to_ascii_lowercase();
+        if let Some(region) = parts.next().filter(|region| !region.is_empty()) {
+            language.push('_');
+            language.push_str(&region.to_ascii_uppercase());
+        }
+        if !normalized.contains(&language) {
+            normalized.push(language);
+        }
+    }
+    normalized
+}
+
+/// Configure spellcheck of main window webview.
+///
+/// Only WebKitGTK lets application pick dictionaries, WebView2 and WKWebView
+/// check spelling in languages of OS and frontend switches them off with
+/// `spellcheck` attribute.
+pub fn apply(app: &AppHandle, settings: &SpellcheckSettings) {
+    let Some(window) = app.get_window("main") else {
+        return;
+    };
+    #[cfg(target_os = "linux")]
+    {
+        let enabled = settings.enabled;
+        let mut languages = settings.languages.clone();
+        // WebKitGTK checks nothing until it's given at least one language
+        if languages.is_empty() {
+            languages = crate::i18n::system_language().into_iter().collect();
+        }
+        let applied = window.with_webview(move |webview| {
+            use webkit2gtk::{WebContextExt, WebViewExt};
+
+            let Some(context) = webview.inner().context() else {
+                return;
+            };
+            context.set_spell_checking_enabled(enabled);
+            let languages: Vec<&str> = languages.iter().map(String::as_str).collect();
+            context.set_spell_checking_languages(&languages);
+        });
+        if let Err(e) = applied {
+            tracing::warn!("Failed to configure spellcheck: {e}");
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (window, settings);
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn normalizes_languages() {
+        let languages = ["en-us", " de_DE ", "en_US", "", "fr"].map(String::from);
+        assert_eq!(normalized_languages(&languages), ["en_US", "de_DE", "fr"]);
+    }
+}
@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+use models::*;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use url::Url;
+
+use crate::errors::{Error, NativeError};
+
+/// User has this much time to finish login in browser
+pub const SSO_TIMEOUT: Duration = Duration::from_secs(300);
+const CALLBACK_PATH: &str = "/callback";
+const TOKEN_PARAM: &str = "MMAUTHTOKEN";
+/// Length of random state ending callback path
+const STATE_LENGTH: usize = 32;
+
+const SUCCESS_PAGE: &str = "<!doctype html><title>Logged in</title>\
+    <p>You are logged in, this window can be closed now.</p>";
+const FAILURE_PAGE: &str = "<!doctype html><title>Login failed</title>\
+    <p>Login didn't finish, please try again from application.</p>";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SsoProvider {
+    Gitlab,
+    Google,
+    Office365,
+    Openid,
+    Saml,
+}
+
+/// Page starting SSO login in browser, server redirects to `redirect_to`
+/// with session token once user logs in.
+///
+/// It's the flow used by mobile apps, so server accepts the redirect only
+/// when scheme of `redirect_to` is allowed in its native app settings.
+pub fn login_url(server: &Url, provider: SsoProvider, redirect_to: &Url) -> Result<Url, Error> {
+    let mut url = match provider {
+        SsoProvider::Saml => {
+            let mut url = server.join("login/sso/saml")?;
+            url.query_pairs_mut().append_pair("action", "mobile");
+            url
+        }
+        SsoProvider::Gitlab => server.join("oauth/gitlab/mobile_login")?,
+        SsoProvider::Google => server.join("oauth/google/mobile_login")?,
+        SsoProvider::Office365 => server.join("oauth/office365/mobile_login")?,
+        SsoProvider::Openid => server.join("oauth/openid/mobile_login")?,
+    };
+    url.query_pairs_mut()
+        .append_pair("redirect_to", redirect_to.as_str());
+    Ok(url)
+}
+
+/// Local endpoint browser is redirected to after login.
+///
+/// Its path ends with random state only server got in `redirect_to`, so
+/// other local pages can't log user into account of their choice.
+pub struct Callback {
+    listener: TcpListener,
+    path: String,
+    pub redirect_to: Url,
+}
+
+impl Callback {
+    pub async fn bind() -> Result<Self, Error> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let port = listener.local_addr()?.port();
+        let state: String = thread_rng()
+            .sample_iter(Alphanumeric)
+            .take(STATE_LENGTH)
+            .map(char::from)
+            .collect();
+        let path = format!("{CALLBACK_PATH}/{state}");
+        let redirect_to = Url::parse(&format!("http://127.0.0.1:{port}{path}"))?;
+        Ok(Self {
+            listener,
+            path,
+            redirect_to,
+        })
+    }
+
+    /// Wait until browser brings session token, unrelated requests (e.g.
+    /// favicon) and broken connections are answered or logged and ignored
+    pub async fn token(self) -> Result<AccessToken, Error> {
+        let wait = async {
+            loop {
+                let (stream, peer) = self.listener.accept().await?;
+                match answer(stream, &self.path).await {
+                    Ok(Some(token)) => return Ok::<_, Error>(token),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("Ignoring SSO callback connection from {peer}: {e}"),
+                }
+            }
+        };
+        tokio::time::timeout(SSO_TIMEOUT, wait)
+            .await
+            .map_err(|_| NativeError::SsoTimeout)?
+    }
+}
+
+async fn answer(mut stream: TcpStream, path: &str) -> Result<Option<AccessToken>, Error> {
+    let mut request_line = String::new();
+    // Connection which never sends its request doesn't hold login
+    tokio::time::timeout(
+        Duration::from_secs(10),
+        BufReader::new(&mut stream).read_line(&mut request_line),
+    )
+    .await
+    .map_err(|_| NativeError::SsoTimeout)??;
+    let token = callback_token(&request_line, path);
+    let (status, page) = match (&token, request_line.contains(CALLBACK_PATH)) {
+        (Some(_), _) => ("200 OK", SUCCESS_PAGE),
+        (None, true) => ("400 Bad Request", FAILURE_PAGE),
+        (None, false) => ("404 Not Found", ""),
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{page}",
+        page.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await.ok();
+    Ok(token)
+}
+
+/// Session token from request line of callback at `path`, e.g.
+/// `GET /callback/<state>?MMAUTHTOKEN=abc&MMCSRF=def HTTP/1.1`
+fn callback_token(request_line: &str, path: &str) -> Option<AccessToken> {
+    let target = request_line.strip_prefix("GET ")?.split(' ').next()?;
+    let url = Url::parse("http://127.0.0.1").ok()?.join(target).ok()?;
+    if url.path() != path {
+        return None;
+    }
+    url.query_pairs()
+        .find(|(name, _)| name == TOKEN_PARAM)
+        .and_then(|(_, token)| AccessToken::new(token.into_owned()).ok())
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn provider_urls() {
+        let server = Url::parse("https://mm.example.com/").unwrap();
+        let redirect_to = Url::parse("http://127.0.0.1:4000/callback").unwrap();
+        assert_eq!(
+            login_url(&server, SsoProvider::Gitlab, &redirect_to)
+                .unwrap()
+                .as_str(),
+            "https://mm.example.com/oauth/gitlab/mobile_login\
+             ?redirect_to=http%3A%2F%2F127.0.0.1%3A4000%2Fcallback"
+        );
+        assert_eq!(
+            login_url(&server, SsoProvider::Saml, &redirect_to)
+                .unwrap()
+                .as_str(),
+            "https://mm.example.com/login/sso/saml?action=mobile\
+             &redirect_to=http%3A%2F%2F127.0.0.1%3A4000%2Fcallback"
+        );
+    }
+
+    #[test]
+    fn token_from_callback() {
+        let path = "/callback/s1";
+        let token = callback_token(
+            "GET /callback/s1?MMAUTHTOKEN=abc123&MMCSRF=x HTTP/1.1\r\n",
+            path,
+        );
+        assert_eq!(token.as_deref().map(String::as_str), Some("abc123"));
+        assert!(callback_token("GET /favicon.ico HTTP/1.1\r\n", path).is_none());
+        assert!(callback_token("GET /callback/s1?error=denied HTTP/1.1\r\n", path).is_none());
+        assert!(callback_token("GET /callback/s1?MMAUTHTOKEN= HTTP/1.1\r\n", path).is_none());
+        // Token of other state or without any is forged
+        assert!(callback_token("GET /callback/s2?MMAUTHTOKEN=a HTTP/1.1\r\n", path).is_none());
+        assert!(callback_token("GET /callback?MMAUTHTOKEN=a HTTP/1.1\r\n", path).is_none());
+    }
+
+    #[tokio::test]
+    async fn receives_token() {
+        let callback = Callback::bind().await.unwrap();
+        let addr = format!("127.0.0.1:{}", callback.redirect_to.port().unwrap());
+        let redirected = format!("{}?MMAUTHTOKEN=tok", callback.redirect_to.path());
+        let browser = tokio::spawn(async move {
+            // Port scan closes connection without request
+            drop(TcpStream::connect(&addr).await.unwrap());
+            let forged = "/callback?MMAUTHTOKEN=forged";
+            for target in ["/favicon.ico", forged, &redirected] {
+                let mut stream = TcpStream::connect(&addr).await.unwrap();
+                let request = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+                stream.write_all(request.as_bytes()).await.unwrap();
+                let mut response = String::new();
+                tokio::io::AsyncReadExt::read_to_string(&mut stream, &mut response)
+                    .await
+                    .unwrap();
+            }
+        });
+        let token = callback.token().await.unwrap();
+        assert_eq!(token.as_str(), "tok");
+        browser.await.unwrap();
+    }
+}
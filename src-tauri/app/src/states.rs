@@ -1,7 +1,16 @@
 use models::{AccessToken, *};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use url::Url;
 
+use crate::autocomplete::AutocompleteCache;
+use crate::errors::NativeError;
+use crate::fetches::Fetches;
+use crate::mentions::Mentions;
+use crate::post_store::PostStore;
+
+/// Open channels caught up after WebSocket reconnects, each costs a request
+pub(crate) const MAX_OPEN_CHANNELS: usize = 5;
+
 #[derive(Serialize, Clone, Default)]
 pub(crate) struct UserState {
     #[serde(skip_serializing)]
@@ -11,12 +20,177 @@ pub(crate) struct UserState {
     pub(crate) teams: Option<Vec<Team>>,
     pub(crate) team_members: Option<Vec<TeamMember>>,
     pub(crate) channels: Option<Vec<Channel>>,
+    /// Memberships of user in channels, they hold user's notify props
+    #[serde(skip)]
+    pub(crate) channel_members: Option<Vec<ChannelMember>>,
+    /// Team shown in sidebar, `None` until user picks one
+    pub(crate) current_team: Option<TeamId>,
+    pub(crate) preferences: Option<Preferences>,
+    /// Channels opened recently, most recent last. Posts missed while
+    /// WebSocket was down are fetched for them.
+    #[serde(skip)]
+    pub(crate) open_channels: Vec<ChannelId>,
+    #[serde(skip)]
+    pub(crate) autocomplete: AutocompleteCache,
+    #[serde(skip)]
+    pub(crate) fetches: Fetches,
+    /// Posts of recently opened channels, bounded by memory settings
+    #[serde(skip)]
+    pub(crate) posts: PostStore,
+    /// Recent mentions of user, see [`crate::mentions`]
+    #[serde(skip)]
+    pub(crate) mentions: Mentions,
 }
 
-#[derive(Serialize, Clone, Debug)]
+impl UserState {
+    /// Replace known channel with its new version, channel not known yet is
+    /// added
+    pub(crate) fn store_channel(&mut self, channel: Channel) {
+        let channels = self.channels.get_or_insert_with(Vec::new);
+        match channels.iter_mut().find(|known| known.id == channel.id) {
+            Some(known) => *known = channel,
+            None => channels.push(channel),
+        }
+    }
+
+    /// Replace known channels of `team_id` by `channels`, direct and group
+    /// messages in them are stored as well
+    pub(crate) fn store_team_channels(&mut self, team_id: &TeamId, channels: &[Channel]) {
+        if let Some(known) = &mut self.channels {
+            known.retain(|channel| channel.team_id.as_deref() != Some(team_id.as_str()));
+        }
+        for channel in channels {
+            self.store_channel(channel.clone());
+        }
+    }
+
+    /// Remember channel was opened, only [`MAX_OPEN_CHANNELS`] are kept
+    pub(crate) fn open_channel(&mut self, channel_id: &ChannelId) {
+        self.open_channels.retain(|open| open != channel_id);
+        self.open_channels.push(channel_id.clone());
+        if self.open_channels.len() > MAX_OPEN_CHANNELS {
+            self.open_channels.remove(0);
+        }
+    }
+
+    /// Merge changed notify props into membership of channel and muted
+    /// channels
+    pub(crate) fn apply_notify_props(&mut self, channel_id: &ChannelId, patch: &NotifyPropsPatch) {
+        let member = self
+            .channel_members
+            .iter_mut()
+            .flatten()
+            .find(|member| &member.channel_id == channel_id);
+        if let Some(member) = member {
+            member.notify_props.apply(patch);
+        }
+        let (Some(preferences), Some(mark_unread)) = (&mut self.preferences, &patch.mark_unread)
+        else {
+            return;
+        };
+        preferences
+            .muted_channels
+            .retain(|muted| muted != channel_id);
+        if mark_unread == "mention" {
+            preferences.muted_channels.push(channel_id.clone());
+        }
+    }
+
+    /// Notify props of user in channel, defaults when membership isn't known.
+    /// Channel is muted when preferences list it.
+    pub(crate) fn notify_props(&self, channel_id: &ChannelId) -> NotifyProps {
+        let mut props = self
+            .channel_members
+            .iter()
+            .flatten()
+            .find(|member| &member.channel_id == channel_id)
+            .map(|member| member.notify_props.clone())
+            .unwrap_or_default();
+        if let Some(preferences) = &self.preferences {
+            let muted = preferences.muted_channels.contains(channel_id);
+            if muted != props.is_muted() {
+                props.apply(&NotifyPropsPatch::muted(muted));
+            }
+        }
+        props
+    }
+
+    /// Replace known membership of user in channel with its new version
+    pub(crate) fn store_channel_member(&mut self, member: ChannelMember) {
+        let members = self.channel_members.get_or_insert_with(Vec::new);
+        members.retain(|known| known.channel_id != member.channel_id);
+        members.push(member);
+    }
+
+    /// Count post just created in its channel. It's unread unless user wrote
+    /// it, `mentioned` adds to mention count.
+    pub(crate) fn count_post(&mut self, post: &Post, mentioned: bool) {
+        let Some(channel) = self
+            .channels
+            .iter_mut()
+            .flatten()
+            .find(|channel| channel.id.as_ref() == Some(&post.channel_id))
+        else {
+            return;
+        };
+        channel.total_msg_count += 1;
+        channel.last_post_at = channel.last_post_at.max(post.create_at);
+        let total = channel.total_msg_count;
+        let Some(member) = self
+            .channel_members
+            .iter_mut()
+            .flatten()
+            .find(|member| member.channel_id == post.channel_id)
+        else {
+            return;
+        };
+        if self.id.is_some() && self.id == post.user_id {
+            member.msg_count = total;
+            member.last_viewed_at = member.last_viewed_at.max(post.create_at);
+        } else if mentioned {
+            member.mention_count += 1;
+        }
+    }
+
+    /// Channel was read up to its latest post, here or on another device
+    pub(crate) fn mark_viewed(&mut self, channel_id: &ChannelId, viewed_at: Timestamp) {
+        let total = self
+            .channels
+            .iter()
+            .flatten()
+            .find(|channel| channel.id.as_ref() == Some(channel_id))
+            .map(|channel| channel.total_msg_count);
+        let member = self
+            .channel_members
+            .iter_mut()
+            .flatten()
+            .find(|member| &member.channel_id == channel_id);
+        if let (Some(total), Some(member)) = (total, member) {
+            member.msg_count = total;
+            member.mention_count = 0;
+            member.last_viewed_at = member.last_viewed_at.max(viewed_at);
+        }
+    }
+
+    /// Forget channel user is no longer member of
+    pub(crate) fn remove_channel(&mut self, channel_id: &ChannelId) {
+        if let Some(channels) = &mut self.channels {
+            channels.retain(|channel| channel.id.as_ref() != Some(channel_id));
+        }
+        if let Some(members) = &mut self.channel_members {
+            members.retain(|member| &member.channel_id != channel_id);
+        }
+        self.open_channels.retain(|open| open != channel_id);
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub(crate) struct Server {
     pub(crate) name: String,
     pub(crate) url: Url,
+    /// `None` until server is probed
+    #[serde(default)]
+    pub(crate) capabilities: Option<ServerCapabilities>,
 }
 
 #[derive(Serialize, Clone)]
@@ -28,19 +202,239 @@ pub(crate) struct ServerState {
 
 impl Default for ServerState {
     fn default() -> Self {
-        let current = Some(Server {
+        let current = Server {
             name: "localhost".to_owned(),
             url: Url::parse("http://localhost:8065").ok().unwrap(),
-        });
+            capabilities: None,
+        };
         Self {
-            current: current.to_owned(), // TODO add dev env
+            current: Some(current.to_owned()), // TODO add dev env
             servers: vec![
-                current.unwrap(),
+                current,
                 Server {
                     name: "ITA".to_string(),
                     url: Url::parse("https://mm.ita-prog.pl").unwrap(),
+                    capabilities: None,
                 },
             ],
         }
     }
 }
+
+impl ServerState {
+    /// Remove server called `name`, when it was current the one following it
+    /// becomes current. Returns removed server and whether it was current.
+    pub(crate) fn remove(&mut self, name: &str) -> Result<(Server, bool), NativeError> {
+        let index = self
+            .servers
+            .iter()
+            .position(|server| server.name == name)
+            .ok_or(NativeError::UnknownServer)?;
+        let removed = self.servers.remove(index);
+        let was_current = self
+            .current
+            .as_ref()
+            .is_some_and(|current| current.url == removed.url);
+        if was_current {
+            self.current = self
+                .servers
+                .get(index)
+                .or_else(|| self.servers.first())
+                .cloned();
+        }
+        Ok((removed, was_current))
+    }
+
+    /// Rename server called `name`, keeping current server in sync.
+    pub(crate) fn rename(&mut self, name: &str, new_name: &str) -> Result<(), NativeError> {
+        let new_name = new_name.trim();
+        if new_name.is_empty() {
+            return Err(NativeError::InvalidServerName);
+        }
+        if new_name != name && self.servers.iter().any(|server| server.name == new_name) {
+            return Err(NativeError::DuplicateServer);
+        }
+        let server = self
+            .servers
+            .iter_mut()
+            .find(|server| server.name == name)
+            .ok_or(NativeError::UnknownServer)?;
+        new_name.clone_into(&mut server.name);
+        if let Some(current) = self
+            .current
+            .as_mut()
+            .filter(|current| current.url == server.url)
+        {
+            new_name.clone_into(&mut current.name);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+    use crate::test_support;
+
+    fn servers(names: &[&str]) -> ServerState {
+        let servers: Vec<Server> = names
+            .iter()
+            .map(|name| Server {
+                name: (*name).to_owned(),
+                url: Url::parse(&format!("https://{name}.example.com")).unwrap(),
+                capabilities: None,
+            })
+            .collect();
+        ServerState {
+            current: servers.first().cloned(),
+            servers,
+        }
+    }
+
+    #[test]
+    fn removes_servers() {
+        let mut state = servers(&["a", "b", "c"]);
+        assert!(matches!(state.remove("d"), Err(NativeError::UnknownServer)));
+        let (removed, was_current) = state.remove("c").unwrap();
+        assert_eq!(removed.name, "c");
+        assert!(!was_current);
+        assert_eq!(state.current.as_ref().unwrap().name, "a");
+        let (_, was_current) = state.remove("a").unwrap();
+        assert!(was_current);
+        assert_eq!(state.current.as_ref().unwrap().name, "b");
+        let (_, was_current) = state.remove("b").unwrap();
+        assert!(was_current);
+        assert!(state.current.is_none());
+    }
+
+    #[test]
+    fn renames_servers() {
+        let mut state = servers(&["a", "b"]);
+        assert!(matches!(
+            state.rename("a", "  "),
+            Err(NativeError::InvalidServerName)
+        ));
+        assert!(matches!(
+            state.rename("a", "b"),
+            Err(NativeError::DuplicateServer)
+        ));
+        assert!(matches!(
+            state.rename("c", "d"),
+            Err(NativeError::UnknownServer)
+        ));
+        state.rename("a", " a ").unwrap();
+        state.rename("a", " z ").unwrap();
+        assert_eq!(state.servers[0].name, "z");
+        assert_eq!(state.current.as_ref().unwrap().name, "z");
+        state.rename("b", "y").unwrap();
+        assert_eq!(state.current.as_ref().unwrap().name, "z");
+    }
+
+    /// Membership as `GET /users/{user_id}/channel_members` returns it
+    fn member(channel_id: &str, desktop: &str, mark_unread: &str) -> ChannelMember {
+        serde_json::from_value(serde_json::json!({
+            "channel_id": channel_id,
+            "user_id": "9ciscaqbrpd6d8s68k76xb9bte",
+            "roles": "channel_user",
+            "last_viewed_at": 1699000000000i64,
+            "msg_count": 12,
+            "mention_count": 0,
+            "mention_count_root": 0,
+            "urgent_mention_count": 0,
+            "msg_count_root": 10,
+            "notify_props": {
+                "channel_auto_follow_threads": "off",
+                "desktop": desktop,
+                "desktop_sound": "default",
+                "desktop_threads": "default",
+                "email": "default",
+                "ignore_channel_mentions": "default",
+                "mark_unread": mark_unread,
+                "push": "default",
+                "push_threads": "default"
+            },
+            "last_update_at": 1699000000000i64,
+            "scheme_guest": false,
+            "scheme_user": true,
+            "scheme_admin": false,
+            "explicit_roles": ""
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn notify_props_of_membership() {
+        let loud = ChannelId::new("4xp9fdt77pncbef59f4k1qe83o".to_owned());
+        let muted = ChannelId::new("kfg8shiwnjrxxbsnk7ycz6xp4w".to_owned());
+        let members = vec![
+            member(loud.as_str(), "all", "all"),
+            member(muted.as_str(), "default", "mention"),
+        ];
+        let mut user_state = UserState {
+            preferences: Some(crate::preferences::decode(&[], &members)),
+            channel_members: Some(members),
+            ..UserState::default()
+        };
+        assert!(user_state.notify_props(&loud).notifies_desktop(false));
+        assert!(!user_state.notify_props(&muted).notifies_desktop(true));
+        let unknown = ChannelId::new("other".to_owned());
+        assert!(user_state.notify_props(&unknown).notifies_desktop(true));
+        assert!(!user_state.notify_props(&unknown).notifies_desktop(false));
+
+        user_state.apply_notify_props(&muted, &NotifyPropsPatch::muted(false));
+        user_state.apply_notify_props(
+            &loud,
+            &NotifyPropsPatch {
+                desktop: Some("none".to_owned()),
+                ..NotifyPropsPatch::default()
+            },
+        );
+        assert!(user_state.notify_props(&muted).notifies_desktop(true));
+        assert!(!user_state.notify_props(&loud).notifies_desktop(true));
+
+        user_state.remove_channel(&loud);
+        assert!(user_state.notify_props(&loud).notifies_desktop(true));
+    }
+
+    #[test]
+    fn counts_posts_and_reads() {
+        let channel_id = "4xp9fdt77pncbef59f4k1qe83o";
+        let channel: Channel = serde_json::from_value(serde_json::json!({
+            "id": channel_id,
+            "team_id": "t1",
+            "type": "O",
+            "total_msg_count": 12,
+        }))
+        .unwrap();
+        let mut user_state = UserState {
+            id: Some(UserId::new("9ciscaqbrpd6d8s68k76xb9bte".to_owned())),
+            channels: Some(vec![channel]),
+            channel_members: Some(vec![member(channel_id, "default", "all")]),
+            ..UserState::default()
+        };
+        let unreads = |user_state: &UserState| {
+            let badge = crate::sessions::badge(
+                ServerUrl::from(Url::parse("https://mm.example.com").unwrap()),
+                user_state.channels.as_deref().unwrap(),
+                user_state.channel_members.as_deref().unwrap(),
+            );
+            (badge.unread_channels, badge.mentions)
+        };
+        let post = |user_id: &str| Post {
+            user_id: Some(UserId::new(user_id.to_owned())),
+            channel_id: ChannelId::new(channel_id.to_owned()),
+            ..test_support::post("p1", 1699000001000)
+        };
+        assert_eq!(unreads(&user_state), (0, 0));
+
+        user_state.count_post(&post("other"), true);
+        assert_eq!(unreads(&user_state), (1, 1));
+
+        user_state.mark_viewed(&ChannelId::new(channel_id.to_owned()), 1699000002000);
+        assert_eq!(unreads(&user_state), (0, 0));
+
+        // Own post is read as it's written
+        user_state.count_post(&post("9ciscaqbrpd6d8s68k76xb9bte"), false);
+        assert_eq!(unreads(&user_state), (0, 0));
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+
+use crate::websocket::{WebSocket, WsAction};
+use crate::{dnd, shutdown};
+
+/// Same as in official desktop app
+const AWAY_AFTER: Duration = Duration::from_secs(5 * 60);
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Automatic away detection. User is active while window has focus and
+/// frontend reports input, after [`AWAY_AFTER`] without either server is
+/// told user is inactive.
+///
+/// Activity is reported over WebSocket as not manual, so status set by user
+/// (e.g. do not disturb) is never overwritten by it. The same loop turns on
+/// do not disturb of [`models::DndSchedule`].
+pub struct StatusManager {
+    last_activity: Mutex<Instant>,
+    away: AtomicBool,
+    /// Wakes checking loop when user comes back
+    returned: Notify,
+}
+
+impl Default for StatusManager {
+    fn default() -> Self {
+        Self {
+            last_activity: Mutex::new(Instant::now()),
+            away: AtomicBool::new(false),
+            returned: Notify::new(),
+        }
+    }
+}
+
+impl StatusManager {
+    pub fn activity(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+        if self.away.load(Ordering::Relaxed) {
+            self.returned.notify_one();
+        }
+    }
+
+    /// Gaining focus counts as activity, idle period starts with the last
+    /// input before focus is lost
+    pub fn set_focused(&self, focused: bool) {
+        if focused {
+            self.activity();
+        }
+    }
+
+    pub fn is_away(&self) -> bool {
+        self.away.load(Ordering::Relaxed)
+    }
+
+    fn idle(&self) -> Duration {
+        self.last_activity.lock().unwrap().elapsed()
+    }
+}
+
+/// New activity state to report, `None` when it didn't change
+fn transition(idle: Duration, away: bool) -> Option<bool> {
+    match (idle >= AWAY_AFTER, away) {
+        (true, false) => Some(true),
+        (false, true) => Some(false),
+        _ => None,
+    }
+}
+
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let manager = app.state::<StatusManager>();
+        // End of do not disturb window already applied
+        let mut scheduled_dnd = None;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+                _ = manager.returned.notified() => {}
+            }
+            if shutdown::is_shutting_down() {
+                break;
+            }
+            dnd::follow_schedule(&app, &mut scheduled_dnd).await;
+            let Some(away) = transition(manager.idle(), manager.is_away()) else {
+                continue;
+            };
+            manager.away.store(away, Ordering::Relaxed);
+            tracing::debug!("User is {}", if away { "away" } else { "back" });
+            app.state::<WebSocket>()
+                .send(WsAction::ActiveStatus {
+                    user_is_active: !away,
+                    manual: false,
+                })
+                .await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn away_after_idle() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(transition(minute, false), None);
+        assert_eq!(transition(6 * minute, false), Some(true));
+        assert_eq!(transition(6 * minute, true), None);
+        assert_eq!(transition(Duration::ZERO, true), Some(false));
+    }
+}
@@ -2,13 +2,118 @@ use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 use models::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use zbox::{init_env, Repo, RepoOpener};
 
+use crate::app_data::ImportedAppData;
 use crate::errors::StorageError;
+use crate::states::Server;
+use crate::{repo_lock, vault_key};
 
 pub struct Inner {
-    _app_config_dir: PathBuf,
-    vault: Repo,
+    app_config_dir: PathBuf,
+    /// Password kept in OS keyring, vault password unless it's bound to OS
+    /// account. `None` until keyring gives it.
+    base_password: Option<String>,
+    password: String,
+    /// `None` once storage is closed during shutdown
+    vault: Option<Repo>,
+    /// PID of another running instance which has vault open
+    held_by: Option<u32>,
+    /// Why vault didn't open, `None` when it did or was closed
+    failure: Option<OpenFailure>,
+}
+
+/// Vault which didn't open is rebuilt only when it's damaged, the other
+/// failures leave its data as they are
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OpenFailure {
+    /// Corrupted even after its super block was repaired
+    Damaged,
+    UnsupportedSchema {
+        found: u32,
+        supported: u32,
+    },
+    WrongPassword,
+    /// Secret of vault bound to OS account can't be read
+    Keyring(String),
+    /// Directory vault lives in can't be found or created
+    ConfigDir(String),
+}
+
+impl OpenFailure {
+    fn of(error: &StorageError) -> Self {
+        match error {
+            StorageError::UnsupportedSchema { found, supported } => Self::UnsupportedSchema {
+                found: *found,
+                supported: *supported,
+            },
+            StorageError::WrongPassword => Self::WrongPassword,
+            StorageError::Keyring(reason) => Self::Keyring(reason.clone()),
+            StorageError::ConfigDir(reason) => Self::ConfigDir(reason.clone()),
+            _ => Self::Damaged,
+        }
+    }
+
+    fn error(self) -> StorageError {
+        match self {
+            Self::Damaged => StorageError::Damaged,
+            Self::UnsupportedSchema { found, supported } => {
+                StorageError::UnsupportedSchema { found, supported }
+            }
+            Self::WrongPassword => StorageError::WrongPassword,
+            Self::Keyring(reason) => StorageError::Keyring(reason),
+            Self::ConfigDir(reason) => StorageError::ConfigDir(reason),
+        }
+    }
+}
+
+impl Inner {
+    fn base_password(&mut self) -> Result<String, StorageError> {
+        if let Some(base) = &self.base_password {
+            return Ok(base.clone());
+        }
+        let base = vault_key::base_password(&self.app_config_dir)?;
+        self.base_password = Some(base.clone());
+        Ok(base)
+    }
+
+    fn vault(&mut self) -> Result<&mut Repo, StorageError> {
+        let (held_by, failure) = (self.held_by, self.failure.clone());
+        self.vault.as_mut().ok_or(match (held_by, failure) {
+            (Some(pid), _) => StorageError::AlreadyRunning { pid },
+            (None, Some(failure)) => failure.error(),
+            (None, None) => StorageError::Closed,
+        })
+    }
+}
+
+/// Result of [`Storage::doctor`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StorageHealth {
+    /// Vault opens and every document in it can be read
+    pub healthy: bool,
+    /// Super block was restored from its backup
+    pub repaired: bool,
+    /// Vault was created anew, readable documents were carried over and
+    /// damaged one was moved aside
+    pub rebuilt: bool,
+    /// Documents which can't be read, they are dropped by rebuild
+    pub unreadable: Vec<String>,
+}
+
+/// What [`Storage::forget_server`] removed
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ForgottenData {
+    pub credentials: bool,
+    /// Channels which had cached posts
+    pub cached_channels: usize,
+    pub outbox_posts: usize,
+    pub scheduled_posts: usize,
+    pub watch_later: usize,
+    pub reminders: usize,
 }
 
 /// ZBox file system mounted to directry. Entire FS journal is stored inside
@@ -18,11 +123,21 @@ pub struct Inner {
 #[derive(Clone)]
 pub struct Storage(Arc<Mutex<Inner>>);
 
+impl Default for Storage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Storage {
     /// Open zbox file system repository
     ///
-    /// This method will panic and kill entire application if anything goes
-    /// wrong and it must do so since FS access is required for application.
+    /// Never fails, application runs without storage when it doesn't open
+    /// and every operation returns the cause. Damaged vault is repaired from
+    /// backup of its super block if possible, otherwise application runs
+    /// without storage until [`Storage::doctor`] rebuilds it. Without vault
+    /// password, e.g. when OS keyring is locked, it runs without storage
+    /// until [`Storage::doctor`] opens it.
     ///
     /// Repository remains open through application lifetime but stored values
     /// are accessible only when read methods are called
@@ -30,109 +145,1090 @@ impl Storage {
     /// # Examples
     ///
     /// ```
-    /// let vault = Storage::new();
+    /// let storage = Storage::new();
+    /// // Blocking operations run on thread of handle
+    /// let handle = StorageHandle::new(storage.clone());
     /// ```
     pub fn new() -> Self {
-        init_env();
-
-        let user_dirs = directories::BaseDirs::new().expect(
-            "Home directory is not configured. Please check your OS Distribution instruction",
-        );
-        let root = user_dirs.config_dir();
-        Self::open_with_root(root.to_owned())
+        match directories::BaseDirs::new() {
+            Some(user_dirs) => Self::open_with_root(user_dirs.config_dir().to_owned()),
+            None => {
+                tracing::error!("Home directory is not configured, storage is unavailable");
+                Self::unavailable(
+                    PathBuf::new(),
+                    OpenFailure::ConfigDir("home directory is not configured".to_owned()),
+                )
+            }
+        }
     }
 
     #[doc(hidden)]
     pub fn open_with_root(root: PathBuf) -> Self {
-        let id = std::process::id().to_be_bytes();
+        init_env();
 
         let app_config_dir = root.join("worryless");
-        std::fs::create_dir_all(&app_config_dir).expect("Failed to create config directory");
+        if let Err(e) = std::fs::create_dir_all(&app_config_dir) {
+            tracing::error!(
+                "Unable to create {}, storage is unavailable: {e}",
+                app_config_dir.display()
+            );
+            return Self::unavailable(app_config_dir, OpenFailure::ConfigDir(e.to_string()));
+        }
 
-        let zbox_pass = if let Ok(pass) = std::fs::read_to_string(app_config_dir.join(".sec")) {
-            pass
-        } else {
-            use rand::distributions::Alphanumeric;
-            use rand::{thread_rng, Rng};
+        let path = vault_uri(&app_config_dir);
 
-            let mut rng = thread_rng();
-            let pass: String = (0..50).map(|_| rng.sample(Alphanumeric) as char).collect();
-            std::fs::write(app_config_dir.join(".sec"), &pass).expect("Failed to save vault pass");
-            pass
+        tracing::info!("Storage path is: {path}");
+        let mut inner = Inner {
+            app_config_dir,
+            base_password: None,
+            password: String::new(),
+            vault: None,
+            held_by: None,
+            failure: None,
         };
-
-        let uri = format!("file://{}", app_config_dir.display());
-        let path = format!("{uri}/secure");
-        std::fs::remove_file(&app_config_dir.join("secure").join(".repo_lock")).ok();
-
-        println!("Storage path is: {path}");
-        let vault = match RepoOpener::new().create(true).open(&path, &zbox_pass) {
-            Ok(s) => s,
+        // Opening vault of another instance would corrupt it, this one stays
+        // without storage and tells frontend why
+        if let Some(owner) = claim_vault(&inner.app_config_dir) {
+            tracing::warn!("Vault is already opened by process {}", owner.pid);
+            inner.held_by = Some(owner.pid);
+            return Self(Arc::new(Mutex::new(inner)));
+        }
+        let opened = inner
+            .base_password()
+            .and_then(|base| open_keyed_vault(&inner.app_config_dir, &base));
+        match opened {
+            Ok((vault, password, _)) => {
+                inner.vault = Some(vault);
+                inner.password = password;
+            }
             Err(e) => {
-                eprintln!("Unable to build secret vault: {e}");
-                panic!("Unable to build secret vault");
+                tracing::error!("Unable to open secret vault, storage is unavailable: {e}");
+                // Vault rebuilt by doctor gets base password
+                inner.password = inner.base_password.clone().unwrap_or_default();
+                inner.failure = Some(OpenFailure::of(&e));
             }
-        };
-        std::fs::write(&app_config_dir.join("secure").join(".repo_lock"), &id).ok();
+        }
+        Self(Arc::new(Mutex::new(inner)))
+    }
 
+    /// Storage without vault, every operation fails with `failure`
+    fn unavailable(app_config_dir: PathBuf, failure: OpenFailure) -> Self {
         Self(Arc::new(Mutex::new(Inner {
-            _app_config_dir: app_config_dir,
-            vault,
+            app_config_dir,
+            base_password: None,
+            password: String::new(),
+            vault: None,
+            held_by: None,
+            failure: Some(failure),
         })))
     }
 
-    /// Read stored credentials from encrypted IO
+    /// Vault can be unlocked only by current OS account on this machine
+    pub fn account_bound(&self) -> bool {
+        vault_key::is_bound(&self.0.lock().unwrap().app_config_dir)
+    }
+
+    /// Bind vault password to OS account or release it again, vault is
+    /// re-keyed either way
+    pub fn set_account_bound(&self, bound: bool) -> Result<(), StorageError> {
+        let mut inner = self.0.lock().unwrap();
+        if vault_key::is_bound(&inner.app_config_dir) == bound {
+            return Ok(());
+        }
+        let base = inner.base_password()?;
+        let password = if bound {
+            vault_key::new_binding(&base)?
+        } else {
+            base
+        };
+        let old = inner.password.clone();
+        let dir = inner.app_config_dir.clone();
+        let vault = inner.vault()?;
+        let info = vault.info()?;
+        // Crash before marker follows leaves journal, start then tries
+        // password of both states
+        vault_key::begin_rebind(&dir, bound)?;
+        if let Err(e) = vault.reset_password(&old, &password, info.ops_limit(), info.mem_limit()) {
+            vault_key::abort_rebind(&dir);
+            return Err(e.into());
+        }
+        inner.password = password;
+        vault_key::mark_bound(&dir, bound)
+    }
+
+    /// Read stored credentials of all servers from encrypted IO, ordered by
+    /// server URL
     ///
     /// # Examples
     ///
     /// ```
-    /// async fn load_creds(storage: Storage, creds: Vec<ServerCredentials>) {
-    ///     let creds = tokio::spawn_blocking(move || storage.credentials(&creds).unwrap());
+    /// async fn load_creds(storage: StorageHandle) {
+    ///     let creds = storage.run(|storage| storage.credentials()).await.unwrap();
     /// }
     /// ```
     pub fn credentials(&self) -> Result<Vec<ServerCredentials>, StorageError> {
         let mut inner = self.0.lock().unwrap();
+        read_credentials_in(inner.vault()?)
+    }
 
-        let f = zbox::OpenOptions::new()
-            .create(true)
-            .open(&mut inner.vault, "/credentials")?;
-
-        Ok(bincode::deserialize_from(f)?)
+    /// Stored credentials of single server
+    pub fn server_credentials(
+        &self,
+        server: &ServerUrl,
+    ) -> Result<Option<ServerCredentials>, StorageError> {
+        let mut inner = self.0.lock().unwrap();
+        read_server_credentials_in(inner.vault()?, &credentials_path(CREDENTIALS_DIR, server))
     }
 
-    /// Store all credentials in encrypted safe zbox storage
+    /// Store credentials in encrypted safe zbox storage, replacing ones of
+    /// the same server. Each server has document of its own, so writing one
+    /// never touches tokens of others.
     ///
     /// Be aware this is IO & crypto operation so it will requires considerable
-    /// processing power. To prevent chocking tokio runtime you must use
-    /// `spawn_blocking` or `spawn_local`.
+    /// processing power. From async code it's called through
+    /// [`StorageHandle`](crate::storage_handle::StorageHandle), which runs it
+    /// off tokio runtime.
     ///
     /// # Examples
     ///
     /// ```
-    /// async fn save_creds(storage: Storage, creds: Vec<ServerCredentials>) {
-    ///     tokio::spawn_blocking(move || storage.store_credentials(&creds).unwrap());
+    /// async fn save_creds(storage: StorageHandle, creds: ServerCredentials) {
+    ///     storage.run(move |storage| storage.store_credentials(&creds)).await.unwrap();
     /// }
     /// ```
-    pub fn store_credentials(
+    pub fn store_credentials(&self, credentials: &ServerCredentials) -> Result<(), StorageError> {
+        let mut inner = self.0.lock().unwrap();
+        write_credentials_in(inner.vault()?, CREDENTIALS_DIR, credentials)
+    }
+
+    /// Remove stored credentials of `server`, returns whether there were any
+    pub fn remove_credentials(&self, server: &ServerUrl) -> Result<bool, StorageError> {
+        let mut inner = self.0.lock().unwrap();
+        remove_credentials_in(inner.vault()?, server)
+    }
+
+    /// Read posts of channel cached during last successful fetch
+    ///
+    /// Returns `None` if channel was never opened on this server. Like all
+    /// other storage operations this one is blocking.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// async fn cached(storage: StorageHandle, server: ServerUrl, channel_id: ChannelId) {
+    ///     let posts = storage
+    ///         .run(move |storage| storage.cached_posts(&server, &channel_id))
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    pub fn cached_posts(
         &self,
-        credentials: &Vec<ServerCredentials>,
+        server: &ServerUrl,
+        channel_id: &ChannelId,
+    ) -> Result<Option<PostThread>, StorageError> {
+        self.read_json(&format!("{}/{channel_id}/posts", server_cache_dir(server)))
+    }
+
+    /// Replace cached posts of channel
+    pub fn store_cached_posts(
+        &self,
+        server: &ServerUrl,
+        channel_id: &ChannelId,
+        posts: &PostThread,
     ) -> Result<(), StorageError> {
-        use std::io::Write;
+        self.write_json(
+            &format!("{}/{channel_id}/posts", server_cache_dir(server)),
+            posts,
+        )
+    }
+
+    /// Time cached posts of channel were last synced up to, see
+    /// [`crate::threads::watermark`]
+    pub fn channel_watermark(
+        &self,
+        server: &ServerUrl,
+        channel_id: &ChannelId,
+    ) -> Result<Option<Timestamp>, StorageError> {
+        self.read_json(&format!("{}/{channel_id}/since", server_cache_dir(server)))
+    }
+
+    pub fn store_channel_watermark(
+        &self,
+        server: &ServerUrl,
+        channel_id: &ChannelId,
+        since: Timestamp,
+    ) -> Result<(), StorageError> {
+        self.write_json(
+            &format!("{}/{channel_id}/since", server_cache_dir(server)),
+            &since,
+        )
+    }
+
+    /// Post user had in view when they last left channel
+    pub fn last_viewed(
+        &self,
+        server: &ServerUrl,
+        channel_id: &ChannelId,
+    ) -> Result<Option<LastViewed>, StorageError> {
+        self.read_json(&format!("{}/{channel_id}/viewed", server_cache_dir(server)))
+    }
+
+    pub fn store_last_viewed(
+        &self,
+        server: &ServerUrl,
+        channel_id: &ChannelId,
+        viewed: &LastViewed,
+    ) -> Result<(), StorageError> {
+        self.write_json(
+            &format!("{}/{channel_id}/viewed", server_cache_dir(server)),
+            viewed,
+        )
+    }
+
+    pub fn post_density(&self) -> Result<PostDensity, StorageError> {
+        Ok(self
+            .read_json("/settings/post_density")?
+            .unwrap_or_default())
+    }
+
+    pub fn set_post_density(&self, density: PostDensity) -> Result<(), StorageError> {
+        self.write_json("/settings/post_density", &density)
+    }
+
+    pub fn settings(&self) -> Result<Settings, StorageError> {
+        if let Some(settings) = self.read_json("/settings/app")? {
+            return Ok(settings);
+        }
+        // Intervals and network were stored on their own before there were
+        // other settings
+        Ok(Settings {
+            sync_intervals: self
+                .read_json("/settings/sync_intervals")?
+                .unwrap_or_default(),
+            network: self.read_json("/settings/network")?.unwrap_or_default(),
+            ..Default::default()
+        })
+    }
+
+    pub fn set_settings(&self, settings: &Settings) -> Result<(), StorageError> {
+        self.write_json("/settings/app", settings)
+    }
+
+    pub fn secret_guard(&self) -> Result<bool, StorageError> {
+        Ok(self
+            .read_json("/settings/secret_guard")?
+            .unwrap_or_default())
+    }
+
+    pub fn set_secret_guard(&self, enabled: bool) -> Result<(), StorageError> {
+        self.write_json("/settings/secret_guard", &enabled)
+    }
+
+    /// Whether responses are compared with models and differences logged
+    pub fn strict_schema(&self) -> Result<bool, StorageError> {
+        Ok(self
+            .read_json("/settings/strict_schema")?
+            .unwrap_or_default())
+    }
+
+    pub fn set_strict_schema(&self, enabled: bool) -> Result<(), StorageError> {
+        self.write_json("/settings/strict_schema", &enabled)
+    }
+
+    /// Servers added by user, `None` until list is changed for the first
+    /// time
+    pub(crate) fn servers(&self) -> Result<Option<Vec<Server>>, StorageError> {
+        self.read_json("/servers")
+    }
+
+    pub(crate) fn store_servers(&self, servers: &[Server]) -> Result<(), StorageError> {
+        self.write_json("/servers", &servers)
+    }
+
+    /// Posts waiting for connectivity to be sent, oldest first
+    pub fn outbox(&self) -> Result<Vec<OutboxItem>, StorageError> {
+        Ok(self.read_json("/outbox")?.unwrap_or_default())
+    }
+
+    /// Replace entire outbox queue
+    pub fn store_outbox(&self, items: &[OutboxItem]) -> Result<(), StorageError> {
+        self.write_json("/outbox", &items)
+    }
+
+    /// Posts waiting for their time, of all servers
+    pub fn scheduled_posts(&self) -> Result<Vec<ScheduledPost>, StorageError> {
+        Ok(self.read_json("/scheduled_posts")?.unwrap_or_default())
+    }
+
+    pub fn add_scheduled_post(&self, item: ScheduledPost) -> Result<(), StorageError> {
+        self.update_json("/scheduled_posts", |items: &mut Vec<ScheduledPost>| {
+            items.push(item);
+        })
+    }
+
+    /// Remove scheduled posts of `server` by pending post id, returns how
+    /// many were removed
+    pub fn remove_scheduled_posts(
+        &self,
+        server: &ServerUrl,
+        ids: &[PostId],
+    ) -> Result<usize, StorageError> {
+        self.update_json("/scheduled_posts", |items: &mut Vec<ScheduledPost>| {
+            let before = items.len();
+            items
+                .retain(|item| &item.server != server || !ids.contains(&item.post.pending_post_id));
+            before - items.len()
+        })
+    }
+
+    /// Locally saved posts, never synchronized with server
+    pub fn watch_later(&self) -> Result<Vec<WatchLaterItem>, StorageError> {
+        Ok(self.read_json("/watch_later")?.unwrap_or_default())
+    }
+
+    /// Add post to watch later queue, replacing previous entry of same post
+    pub fn add_watch_later(&self, item: WatchLaterItem) -> Result<(), StorageError> {
+        self.update_json("/watch_later", |items: &mut Vec<WatchLaterItem>| {
+            items.retain(|saved| !saved.is_same_post(&item.server, &item.post.id));
+            items.push(item);
+        })
+    }
+
+    pub fn remove_watch_later(
+        &self,
+        server: &ServerUrl,
+        post_id: &PostId,
+    ) -> Result<(), StorageError> {
+        self.update_json("/watch_later", |items: &mut Vec<WatchLaterItem>| {
+            items.retain(|saved| !saved.is_same_post(server, post_id));
+        })
+    }
+
+    /// Reminders of all servers, fired ones included
+    pub fn reminders(&self) -> Result<Vec<Reminder>, StorageError> {
+        Ok(self.read_json("/reminders")?.unwrap_or_default())
+    }
+
+    /// Add reminder, replacing earlier reminder about the same post
+    pub fn add_reminder(&self, reminder: Reminder) -> Result<(), StorageError> {
+        self.update_json("/reminders", |all: &mut Vec<Reminder>| {
+            all.retain(|known| {
+                known.server != reminder.server || known.post.id != reminder.post.id
+            });
+            all.push(reminder);
+        })
+    }
+
+    /// Change reminders about posts of `server` by `change`, returns changed
+    /// reminders. Reminders `change` returns `false` for are removed.
+    pub fn update_reminders(
+        &self,
+        server: &ServerUrl,
+        post_ids: &[PostId],
+        mut change: impl FnMut(&mut Reminder) -> bool,
+    ) -> Result<Vec<Reminder>, StorageError> {
+        self.update_json("/reminders", |all: &mut Vec<Reminder>| {
+            let mut changed = Vec::new();
+            all.retain_mut(|reminder| {
+                if &reminder.server != server || !post_ids.contains(&reminder.post.id) {
+                    return true;
+                }
+                let keep = change(reminder);
+                changed.push(reminder.clone());
+                keep
+            });
+            changed
+        })
+    }
+
+    /// Request signing configuration of all servers
+    pub fn request_signing(&self) -> Result<Vec<RequestSigning>, StorageError> {
+        Ok(self.read_json("/request_signing")?.unwrap_or_default())
+    }
+
+    /// Enable or replace (`Some`) or disable (`None`) request signing for
+    /// server, returns configuration of all servers
+    pub fn set_request_signing(
+        &self,
+        server: &ServerUrl,
+        signing: Option<RequestSigning>,
+    ) -> Result<Vec<RequestSigning>, StorageError> {
+        self.update_json("/request_signing", |all: &mut Vec<RequestSigning>| {
+            all.retain(|current| &current.server != server);
+            all.extend(signing);
+            all.clone()
+        })
+    }
+
+    /// Daily traffic rollups of all servers
+    pub fn bandwidth_usage(&self) -> Result<Vec<BandwidthUsage>, StorageError> {
+        Ok(self.read_json("/bandwidth")?.unwrap_or_default())
+    }
+
+    pub fn update_bandwidth_usage<F>(&self, f: F) -> Result<(), StorageError>
+    where
+        F: FnOnce(&mut Vec<BandwidthUsage>),
+    {
+        self.update_json("/bandwidth", f)
+    }
+
+    /// Audit trail of commands, oldest first
+    pub fn audit_log(&self) -> Result<Vec<AuditEntry>, StorageError> {
+        Ok(self.read_json("/audit")?.unwrap_or_default())
+    }
+
+    pub fn update_audit_log<F>(&self, f: F) -> Result<(), StorageError>
+    where
+        F: FnOnce(&mut Vec<AuditEntry>),
+    {
+        self.update_json("/audit", f)
+    }
+
+    pub fn audit_log_enabled(&self) -> Result<bool, StorageError> {
+        Ok(self.read_json("/settings/audit_log")?.unwrap_or(true))
+    }
+
+    pub fn set_audit_log_enabled(&self, enabled: bool) -> Result<(), StorageError> {
+        self.write_json("/settings/audit_log", &enabled)
+    }
+
+    /// Remove everything stored for account on `server`: its credentials,
+    /// cached posts, queued and scheduled posts, reminders and watch later
+    /// items. Settings of server itself, e.g. request signing, are kept.
+    pub fn forget_server(&self, server: &ServerUrl) -> Result<ForgottenData, StorageError> {
         let mut inner = self.0.lock().unwrap();
+        let vault = inner.vault()?;
+        let mut forgotten = ForgottenData {
+            credentials: remove_credentials_in(vault, server)?,
+            ..Default::default()
+        };
 
-        let mut file = zbox::OpenOptions::new()
-            .create(true)
-            .open(&mut inner.vault, "/credentials")
-            .unwrap();
+        let cache = server_cache_dir(server);
+        if vault.path_exists(&cache)? {
+            forgotten.cached_channels = vault.read_dir(&cache)?.len();
+            vault.remove_dir_all(&cache)?;
+        }
 
-        let bin = bincode::serialize(credentials)?;
+        let mut outbox: Vec<OutboxItem> = read_json_in(vault, "/outbox")?.unwrap_or_default();
+        let before = outbox.len();
+        outbox.retain(|item| &item.server != server);
+        forgotten.outbox_posts = before - outbox.len();
+        if forgotten.outbox_posts > 0 {
+            write_json_in(vault, "/outbox", &outbox)?;
+        }
 
-        file.write_all(bin.as_slice())?;
+        let mut scheduled: Vec<ScheduledPost> =
+            read_json_in(vault, "/scheduled_posts")?.unwrap_or_default();
+        let before = scheduled.len();
+        scheduled.retain(|item| &item.server != server);
+        forgotten.scheduled_posts = before - scheduled.len();
+        if forgotten.scheduled_posts > 0 {
+            write_json_in(vault, "/scheduled_posts", &scheduled)?;
+        }
+
+        let mut reminders: Vec<Reminder> = read_json_in(vault, "/reminders")?.unwrap_or_default();
+        let before = reminders.len();
+        reminders.retain(|reminder| &reminder.server != server);
+        forgotten.reminders = before - reminders.len();
+        if forgotten.reminders > 0 {
+            write_json_in(vault, "/reminders", &reminders)?;
+        }
 
-        Ok(file.finish()?)
+        let mut watch_later: Vec<WatchLaterItem> =
+            read_json_in(vault, "/watch_later")?.unwrap_or_default();
+        let before = watch_later.len();
+        watch_later.retain(|item| &item.server != server);
+        forgotten.watch_later = before - watch_later.len();
+        if forgotten.watch_later > 0 {
+            write_json_in(vault, "/watch_later", &watch_later)?;
+        }
+        Ok(forgotten)
+    }
+
+    /// Add imported credentials and undelivered posts to stored ones.
+    /// Credentials of the same server are replaced, posts already queued
+    /// here are skipped.
+    pub(crate) fn import_app_data(
+        &self,
+        credentials: Vec<ServerCredentials>,
+        outbox: Vec<OutboxItem>,
+        scheduled_posts: Vec<ScheduledPost>,
+    ) -> Result<ImportedAppData, StorageError> {
+        let mut inner = self.0.lock().unwrap();
+        let vault = inner.vault()?;
+        for credentials in &credentials {
+            write_credentials_in(vault, CREDENTIALS_DIR, credentials)?;
+        }
+        let mut imported = ImportedAppData {
+            credentials: credentials.len(),
+            ..Default::default()
+        };
+
+        let mut stored: Vec<OutboxItem> = read_json_in(vault, "/outbox")?.unwrap_or_default();
+        let before = stored.len();
+        for item in outbox {
+            let queued = stored.iter().any(|known| {
+                known.server == item.server
+                    && known.post.pending_post_id == item.post.pending_post_id
+            });
+            if !queued {
+                stored.push(item);
+            }
+        }
+        imported.outbox_posts = stored.len() - before;
+        write_json_in(vault, "/outbox", &stored)?;
+
+        let mut stored: Vec<ScheduledPost> =
+            read_json_in(vault, "/scheduled_posts")?.unwrap_or_default();
+        let before = stored.len();
+        for item in scheduled_posts {
+            let queued = stored.iter().any(|known| {
+                known.server == item.server
+                    && known.post.pending_post_id == item.post.pending_post_id
+            });
+            if !queued {
+                stored.push(item);
+            }
+        }
+        imported.scheduled_posts = stored.len() - before;
+        write_json_in(vault, "/scheduled_posts", &stored)?;
+        Ok(imported)
+    }
+
+    /// Check that vault opens and all its documents can be read, damaged
+    /// super block is repaired on the way.
+    ///
+    /// With `rebuild`, vault with unreadable documents or one which doesn't
+    /// open at all is moved aside and created anew with documents which
+    /// were still readable. Whatever is lost has to be fetched from server
+    /// or entered again. Vault of newer version or one the password doesn't
+    /// unlock isn't damaged and is never rebuilt.
+    pub fn doctor(&self, rebuild: bool) -> Result<StorageHealth, StorageError> {
+        let mut inner = self.0.lock().unwrap();
+        if let Some(pid) = inner.held_by {
+            return Err(StorageError::AlreadyRunning { pid });
+        }
+        if let Some(OpenFailure::ConfigDir(reason)) = &inner.failure {
+            return Err(StorageError::ConfigDir(reason.clone()));
+        }
+        let mut health = StorageHealth::default();
+        let uri = vault_uri(&inner.app_config_dir);
+        if inner.vault.is_none() {
+            if let Some(owner) = claim_vault(&inner.app_config_dir) {
+                return Err(StorageError::AlreadyRunning { pid: owner.pid });
+            }
+            let opened = inner
+                .base_password()
+                .and_then(|base| open_keyed_vault(&inner.app_config_dir, &base));
+            match opened {
+                Ok((vault, password, repaired)) => {
+                    inner.vault = Some(vault);
+                    inner.password = password;
+                    inner.failure = None;
+                    health.repaired = repaired;
+                }
+                Err(e) if rebuild && OpenFailure::of(&e) == OpenFailure::Damaged => {
+                    tracing::warn!("Vault doesn't open, rebuilding it: {e}")
+                }
+                Err(e) => {
+                    inner.failure = Some(OpenFailure::of(&e));
+                    return Err(e);
+                }
+            }
+        }
+        let readable = match inner.vault.as_mut() {
+            Some(vault) => {
+                let check = check_documents(vault)?;
+                health.unreadable = check.unreadable;
+                check.readable
+            }
+            None => Vec::new(),
+        };
+        if inner.vault.is_some() && health.unreadable.is_empty() {
+            health.healthy = true;
+            return Ok(health);
+        }
+        if !rebuild {
+            return Ok(health);
+        }
+
+        // Dropping repository closes it, so its directory can be moved
+        inner.vault = None;
+        inner.failure = Some(OpenFailure::Damaged);
+        let dir = inner.app_config_dir.join("secure");
+        if dir.exists() {
+            let aside = inner
+                .app_config_dir
+                .join(format!("secure.damaged-{}", chrono::Utc::now().timestamp()));
+            std::fs::rename(&dir, &aside)?;
+            tracing::warn!("Damaged vault moved to {}", aside.display());
+        }
+        let (mut vault, _) = open_vault(&uri, &inner.password)?;
+        for (path, payload) in &readable {
+            write_document_in(&mut vault, path, payload)?;
+        }
+        tracing::info!("Vault rebuilt with {} documents", readable.len());
+        inner.vault = Some(vault);
+        inner.failure = None;
+        health.rebuilt = true;
+        health.healthy = true;
+        Ok(health)
+    }
+
+    /// Close repository so its index is written and lock released. Every
+    /// later access fails with [`StorageError::Closed`].
+    pub fn close(&self) {
+        let mut inner = self.0.lock().unwrap();
+        if inner.vault.take().is_some() {
+            tracing::info!("Storage closed");
+        }
+        repo_lock::release(&inner.app_config_dir.join(VAULT_LOCK));
+    }
+
+    /// Values holding `serde_json::Value` can't be stored with bincode, so
+    /// cache entries are kept as JSON documents
+    fn read_json<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, StorageError> {
+        let mut inner = self.0.lock().unwrap();
+        read_json_in(inner.vault()?, path)
+    }
+
+    fn write_json<T: Serialize>(&self, path: &str, value: &T) -> Result<(), StorageError> {
+        let mut inner = self.0.lock().unwrap();
+        write_json_in(inner.vault()?, path, value)
+    }
+
+    /// Read, modify and write back JSON document without releasing vault lock
+    /// in between, so concurrent updates are not lost
+    fn update_json<T, F, R>(&self, path: &str, f: F) -> Result<R, StorageError>
+    where
+        T: Serialize + DeserializeOwned + Default,
+        F: FnOnce(&mut T) -> R,
+    {
+        let mut inner = self.0.lock().unwrap();
+        let mut value = read_json_in(inner.vault()?, path)?.unwrap_or_default();
+        let result = f(&mut value);
+        write_json_in(inner.vault()?, path, &value)?;
+        Ok(result)
     }
 }
 
+/// Layout version of stored data. Raise it together with new entry of
+/// [`MIGRATIONS`] whenever stored type changes shape.
+pub const SCHEMA_VERSION: u32 = 3;
+const SCHEMA_VERSION_PATH: &str = "/schema_version";
+/// Every document starts with this followed by layout version it was written
+/// with as big-endian `u32`. Documents written before versioning have none.
+const HEADER_MAGIC: &[u8; 4] = b"LMDV";
+const HEADER_LEN: usize = HEADER_MAGIC.len() + 4;
+
+/// Upgrade of vault content from previous layout version to `version`
+struct Migration {
+    version: u32,
+    description: &'static str,
+    run: fn(&mut Repo) -> Result<(), StorageError>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "add version header to documents",
+        run: add_headers,
+    },
+    Migration {
+        version: 2,
+        description: "split credentials into document per server",
+        run: split_credentials,
+    },
+    Migration {
+        version: 3,
+        description: "drop cache of servers named after their address",
+        run: drop_cache,
+    },
+];
+
+/// Bring vault to [`SCHEMA_VERSION`], each finished migration is recorded
+/// so interrupted upgrade continues where it stopped
+fn migrate(vault: &mut Repo) -> Result<(), StorageError> {
+    let current = match read_document_in(vault, SCHEMA_VERSION_PATH)? {
+        Some((_, payload)) => serde_json::from_slice(&payload)?,
+        None => 0,
+    };
+    if current > SCHEMA_VERSION {
+        return Err(StorageError::UnsupportedSchema {
+            found: current,
+            supported: SCHEMA_VERSION,
+        });
+    }
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        tracing::info!(
+            "Migrating storage to version {}: {}",
+            migration.version,
+            migration.description
+        );
+        (migration.run)(vault)?;
+        write_document_in(
+            vault,
+            SCHEMA_VERSION_PATH,
+            &serde_json::to_vec(&migration.version)?,
+        )?;
+    }
+    Ok(())
+}
+
+/// Version 1: documents written before versioning get header
+fn add_headers(vault: &mut Repo) -> Result<(), StorageError> {
+    let mut dirs = vec![std::path::PathBuf::from("/")];
+    while let Some(dir) = dirs.pop() {
+        for entry in vault.read_dir(&dir)? {
+            if entry.metadata().is_dir() {
+                dirs.push(entry.path().to_owned());
+                continue;
+            }
+            let path = entry.path().to_string_lossy().into_owned();
+            let raw = read_raw_in(vault, &path)?;
+            if !raw.is_empty() && !raw.starts_with(HEADER_MAGIC) {
+                write_document_in(vault, &path, &raw)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Version 2: single `/credentials` document holding all servers becomes
+/// directory with document per server. Entries are written aside first and
+/// moved in place after old document is gone, so interrupted split loses no
+/// token.
+fn split_credentials(vault: &mut Repo) -> Result<(), StorageError> {
+    const SPLIT_DIR: &str = "/credentials.split";
+
+    if vault.path_exists(CREDENTIALS_DIR)? && vault.metadata(CREDENTIALS_DIR)?.is_file() {
+        if vault.path_exists(SPLIT_DIR)? {
+            vault.remove_dir_all(SPLIT_DIR)?;
+        }
+        let all: Vec<ServerCredentials> = match read_document_in(vault, CREDENTIALS_DIR)? {
+            Some((_, payload)) => bincode::deserialize(&payload)?,
+            None => Vec::new(),
+        };
+        vault.create_dir(SPLIT_DIR)?;
+        for credentials in &all {
+            write_credentials_in(vault, SPLIT_DIR, credentials)?;
+        }
+        vault.remove_file(CREDENTIALS_DIR)?;
+    }
+    if vault.path_exists(SPLIT_DIR)? {
+        vault.rename(SPLIT_DIR, CREDENTIALS_DIR)?;
+    }
+    Ok(())
+}
+
+/// Version 3: cache directories were named after server address with
+/// punctuation replaced, so servers could share one. It's only cache, posts
+/// are fetched again.
+fn drop_cache(vault: &mut Repo) -> Result<(), StorageError> {
+    if vault.path_exists(CACHE_DIR)? {
+        vault.remove_dir_all(CACHE_DIR)?;
+    }
+    Ok(())
+}
+
+/// Lock of instance using vault, kept next to vault directory so it exists
+/// before vault is created and stays when damaged vault is moved aside
+const VAULT_LOCK: &str = "vault.lock";
+
+/// Take vault for this instance, owner of vault is returned when another
+/// instance holds it. Lock zbox keeps in vault directory is left by crashed
+/// instance then, it's removed so vault opens.
+fn claim_vault(app_config_dir: &std::path::Path) -> Option<repo_lock::LockOwner> {
+    let zbox_lock = app_config_dir.join("secure").join(".repo_lock");
+    // Older versions record their owner only in lock of zbox
+    if let Some(owner) = repo_lock::recorded_owner(&zbox_lock) {
+        return Some(owner);
+    }
+    match repo_lock::claim(&app_config_dir.join(VAULT_LOCK)) {
+        Ok(Some(owner)) => return Some(owner),
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Unable to lock vault: {e}"),
+    }
+    if zbox_lock.exists() {
+        tracing::info!("Removing lock of vault left by crashed instance");
+        std::fs::remove_file(&zbox_lock).ok();
+    }
+    None
+}
+
+/// zbox URI of vault in application config directory
+fn vault_uri(app_config_dir: &std::path::Path) -> String {
+    format!("file://{}/secure", app_config_dir.display())
+}
+
+/// Open vault, creating it on first start, and bring it to current layout.
+/// When it doesn't open because it's corrupted, its super block is restored
+/// from backup and opening is tried once more. Returns whether repair was
+/// needed.
+fn open_vault(uri: &str, password: &str) -> Result<(Repo, bool), StorageError> {
+    let open = || RepoOpener::new().create(true).open(uri, password);
+    let (mut vault, repaired) = match open() {
+        Ok(vault) => (vault, false),
+        Err(e) if is_corrupted(&e) => {
+            tracing::warn!("Unable to open vault, repairing its super block: {e}");
+            match Repo::repair_super_block(uri, password) {
+                Ok(()) => {}
+                // Neither copy of super block decrypts, so it's the password
+                // which is wrong
+                Err(zbox::Error::Decrypt) => return Err(StorageError::WrongPassword),
+                Err(e) => return Err(e.into()),
+            }
+            (open()?, true)
+        }
+        Err(e) => return Err(e.into()),
+    };
+    // Running on data it doesn't understand could destroy it
+    migrate(&mut vault)?;
+    Ok((vault, repaired))
+}
+
+/// Open vault with password of its binding state. Re-keying interrupted by
+/// crash leaves vault keyed for either state, the one it opens in is
+/// recorded. Returns password vault opened with and whether it had to be
+/// repaired.
+fn open_keyed_vault(
+    config_dir: &std::path::Path,
+    base: &str,
+) -> Result<(Repo, String, bool), StorageError> {
+    let uri = vault_uri(config_dir);
+    let mut failure = StorageError::WrongPassword;
+    for bound in vault_key::candidate_states(config_dir) {
+        let password = match vault_key::password(base, bound) {
+            Ok(password) => password,
+            Err(e) => {
+                tracing::warn!("Unable to derive vault password (bound: {bound}): {e}");
+                failure = e;
+                continue;
+            }
+        };
+        match open_vault(&uri, &password) {
+            Ok((vault, repaired)) => {
+                if let Err(e) = vault_key::mark_bound(config_dir, bound) {
+                    tracing::warn!("Unable to record binding of vault: {e}");
+                }
+                return Ok((vault, password, repaired));
+            }
+            Err(StorageError::WrongPassword) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Err(failure)
+}
+
+/// Errors of damaged vault, wrong password fails decryption too
+fn is_corrupted(error: &zbox::Error) -> bool {
+    matches!(
+        error,
+        zbox::Error::Decrypt
+            | zbox::Error::InvalidSuperBlk
+            | zbox::Error::Corrupted
+            | zbox::Error::Decode(_)
+    )
+}
+
+/// Documents found by [`check_documents`]
+#[derive(Default)]
+struct VaultCheck {
+    /// Path and content of documents which can be read
+    readable: Vec<(String, Vec<u8>)>,
+    unreadable: Vec<String>,
+}
+
+/// Documents of newer layout aren't unreadable, they fail the whole check
+/// instead of being dropped
+fn check_documents(vault: &mut Repo) -> Result<VaultCheck, StorageError> {
+    let VaultCheck {
+        mut readable,
+        mut unreadable,
+    } = VaultCheck::default();
+    let mut dirs = vec![std::path::PathBuf::from("/")];
+    while let Some(dir) = dirs.pop() {
+        let entries = match vault.read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!("Unable to read vault directory {}: {e}", dir.display());
+                unreadable.push(dir.to_string_lossy().into_owned());
+                continue;
+            }
+        };
+        for entry in entries {
+            if entry.metadata().is_dir() {
+                dirs.push(entry.path().to_owned());
+                continue;
+            }
+            let path = entry.path().to_string_lossy().into_owned();
+            match read_document_in(vault, &path) {
+                Ok(Some((_, payload))) => readable.push((path, payload)),
+                Ok(None) => {}
+                Err(e @ StorageError::UnsupportedSchema { .. }) => return Err(e),
+                Err(e) => {
+                    tracing::warn!("Unable to read vault document {path}: {e}");
+                    unreadable.push(path);
+                }
+            }
+        }
+    }
+    Ok(VaultCheck {
+        readable,
+        unreadable,
+    })
+}
+
+fn read_raw_in(vault: &mut Repo, path: &str) -> Result<Vec<u8>, StorageError> {
+    use std::io::Read;
+
+    let mut raw = Vec::new();
+    zbox::OpenOptions::new()
+        .open(vault, path)?
+        .read_to_end(&mut raw)?;
+    Ok(raw)
+}
+
+/// Layout version and content of document, `None` when it doesn't exist or
+/// is empty
+fn read_document_in(vault: &mut Repo, path: &str) -> Result<Option<(u32, Vec<u8>)>, StorageError> {
+    if !vault.path_exists(path)? {
+        return Ok(None);
+    }
+    let mut raw = read_raw_in(vault, path)?;
+    if raw.is_empty() {
+        return Ok(None);
+    }
+    if !raw.starts_with(HEADER_MAGIC) || raw.len() < HEADER_LEN {
+        return Ok(Some((0, raw)));
+    }
+    let version = u32::from_be_bytes(raw[HEADER_MAGIC.len()..HEADER_LEN].try_into().unwrap());
+    if version > SCHEMA_VERSION {
+        return Err(StorageError::UnsupportedSchema {
+            found: version,
+            supported: SCHEMA_VERSION,
+        });
+    }
+    Ok(Some((version, raw.split_off(HEADER_LEN))))
+}
+
+/// File is truncated so removed data, e.g. tokens of removed credentials,
+/// don't linger after shorter content
+fn write_document_in(vault: &mut Repo, path: &str, payload: &[u8]) -> Result<(), StorageError> {
+    use std::io::Write;
+
+    // zbox fails to create directory which exists already, root included
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        if !vault.path_exists(parent)? {
+            vault.create_dir_all(parent)?;
+        }
+    }
+    let mut file = zbox::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .open(vault, path)?;
+    file.write_all(HEADER_MAGIC)?;
+    file.write_all(&SCHEMA_VERSION.to_be_bytes())?;
+    file.write_all(payload)?;
+    Ok(file.finish()?)
+}
+
+/// Vault directory with credentials, one document per server
+const CREDENTIALS_DIR: &str = "/credentials";
+/// Vault directory with cached data, directory per server
+const CACHE_DIR: &str = "/cache";
+/// Suffix of document written by [`replace_document_in`] before it's moved
+/// in place
+const STAGED_SUFFIX: &str = ".staged";
+
+/// Write document next to `path` and move it over, readers see either old
+/// or new content, never partly written one
+fn replace_document_in(vault: &mut Repo, path: &str, payload: &[u8]) -> Result<(), StorageError> {
+    let staged = format!("{path}{STAGED_SUFFIX}");
+    write_document_in(vault, &staged, payload)?;
+    Ok(vault.rename(&staged, path)?)
+}
+
+/// Document of server credentials in `dir`, named by hash of server URL so
+/// the name is safe and doesn't reveal server
+fn credentials_path(dir: &str, server: &ServerUrl) -> String {
+    let digest = Sha256::digest(server.as_str().as_bytes());
+    format!("{dir}/{}", hex::encode(&digest[..16]))
+}
+
+fn read_credentials_in(vault: &mut Repo) -> Result<Vec<ServerCredentials>, StorageError> {
+    if !vault.path_exists(CREDENTIALS_DIR)? {
+        return Ok(Vec::new());
+    }
+    let mut all = Vec::new();
+    for entry in vault.read_dir(CREDENTIALS_DIR)? {
+        let path = entry.path().to_string_lossy().into_owned();
+        // Left behind by write interrupted before its move
+        if path.ends_with(STAGED_SUFFIX) {
+            continue;
+        }
+        all.extend(read_server_credentials_in(vault, &path)?);
+    }
+    all.sort_by(|a, b| a.url.as_str().cmp(b.url.as_str()));
+    Ok(all)
+}
+
+fn read_server_credentials_in(
+    vault: &mut Repo,
+    path: &str,
+) -> Result<Option<ServerCredentials>, StorageError> {
+    match read_document_in(vault, path)? {
+        Some((_, payload)) => Ok(Some(bincode::deserialize(&payload)?)),
+        None => Ok(None),
+    }
+}
+
+fn write_credentials_in(
+    vault: &mut Repo,
+    dir: &str,
+    credentials: &ServerCredentials,
+) -> Result<(), StorageError> {
+    replace_document_in(
+        vault,
+        &credentials_path(dir, &credentials.url),
+        &bincode::serialize(credentials)?,
+    )
+}
+
+fn remove_credentials_in(vault: &mut Repo, server: &ServerUrl) -> Result<bool, StorageError> {
+    let path = credentials_path(CREDENTIALS_DIR, server);
+    if !vault.path_exists(&path)? {
+        return Ok(false);
+    }
+    vault.remove_file(&path)?;
+    Ok(true)
+}
+
+fn read_json_in<T: DeserializeOwned>(
+    vault: &mut Repo,
+    path: &str,
+) -> Result<Option<T>, StorageError> {
+    match read_document_in(vault, path)? {
+        Some((_, payload)) => Ok(Some(serde_json::from_slice(&payload)?)),
+        None => Ok(None),
+    }
+}
+
+fn write_json_in<T: Serialize>(
+    vault: &mut Repo,
+    path: &str,
+    value: &T,
+) -> Result<(), StorageError> {
+    write_document_in(vault, path, &serde_json::to_vec(value)?)
+}
+
+/// Vault directory with cached data of single server, named by hash of
+/// server URL like [credentials](credentials_path) so no two servers share it
+fn server_cache_dir(server: &ServerUrl) -> String {
+    credentials_path(CACHE_DIR, server)
+}
+
 #[cfg(test)]
 mod check {
     use tempdir::TempDir;
@@ -145,12 +1241,12 @@ mod check {
         let root = TempDir::new("rwr").unwrap();
         let creds = vec![
             ServerCredentials {
-                url: Url::parse("http://me.mm.so").unwrap(),
+                url: Url::parse("http://me.mm.so").unwrap().into(),
                 access_token: AccessToken::try_from("hs8das8dg8asgd").unwrap(),
             },
             ServerCredentials {
-                url: Url::parse("http://me.mm.so").unwrap(),
-                access_token: AccessToken::try_from("hs8das8dg8asgd").unwrap(),
+                url: Url::parse("http://you.mm.so").unwrap().into(),
+                access_token: AccessToken::try_from("kd9sd7f6gs7dfg").unwrap(),
             },
         ];
 
@@ -160,7 +1256,9 @@ mod check {
             let loaded = storage.credentials().unwrap();
             assert_eq!(loaded, vec![]);
 
-            storage.store_credentials(&creds).unwrap();
+            for creds in creds.iter().rev() {
+                storage.store_credentials(creds).unwrap();
+            }
             let loaded = storage.credentials().unwrap();
             assert_eq!(loaded, creds);
         }
@@ -168,6 +1266,478 @@ mod check {
             let storage = Storage::open_with_root(root.path().to_owned());
             let loaded = storage.credentials().unwrap();
             assert_eq!(loaded, creds);
+
+            let replaced = ServerCredentials {
+                access_token: AccessToken::try_from("a8sd7f6as8d7f6").unwrap(),
+                ..creds[0].clone()
+            };
+            storage.store_credentials(&replaced).unwrap();
+            assert_eq!(
+                storage.server_credentials(&creds[0].url).unwrap(),
+                Some(replaced)
+            );
+            assert!(storage.remove_credentials(&creds[0].url).unwrap());
+            assert!(!storage.remove_credentials(&creds[0].url).unwrap());
+            assert_eq!(storage.credentials().unwrap(), creds[1..]);
+        }
+    }
+
+    #[test]
+    fn cached_posts() {
+        let root = TempDir::new("cached_posts").unwrap();
+        let server = ServerUrl::parse("http://me.mm.so:8065").unwrap();
+        let channel_id = ChannelId::new("town-square".to_owned());
+        let posts = PostThread {
+            order: vec![PostId::new("a".to_owned())],
+            posts: Default::default(),
+            next_post_id: None,
+            prev_post_id: None,
+            has_next: false,
+        };
+
+        {
+            let storage = Storage::open_with_root(root.path().to_owned());
+            assert!(storage
+                .cached_posts(&server, &channel_id)
+                .unwrap()
+                .is_none());
+            storage
+                .store_cached_posts(&server, &channel_id, &posts)
+                .unwrap();
+            assert_eq!(
+                storage.channel_watermark(&server, &channel_id).unwrap(),
+                None
+            );
+            storage
+                .store_channel_watermark(&server, &channel_id, 1_700_000_000_000)
+                .unwrap();
+        }
+        {
+            let storage = Storage::open_with_root(root.path().to_owned());
+            let loaded = storage.cached_posts(&server, &channel_id).unwrap().unwrap();
+            assert_eq!(loaded.order, posts.order);
+            // Same address once punctuation is dropped
+            let lookalike = ServerUrl::parse("http://me-mm.so:8065").unwrap();
+            assert!(storage
+                .cached_posts(&lookalike, &channel_id)
+                .unwrap()
+                .is_none());
+            assert_eq!(
+                storage.channel_watermark(&server, &channel_id).unwrap(),
+                Some(1_700_000_000_000)
+            );
+        }
+    }
+
+    #[test]
+    fn forget_server() {
+        let root = TempDir::new("forget_server").unwrap();
+        let storage = Storage::open_with_root(root.path().to_owned());
+        let forgotten = ServerUrl::parse("https://old.example.com").unwrap();
+        let kept = ServerUrl::parse("https://mm.example.com").unwrap();
+        let credentials = |url: &ServerUrl| ServerCredentials {
+            url: url.clone(),
+            access_token: AccessToken::try_from("hs8das8dg8asgd").unwrap(),
+        };
+        storage.store_credentials(&credentials(&forgotten)).unwrap();
+        storage.store_credentials(&credentials(&kept)).unwrap();
+        let channel_id = ChannelId::new("town-square".to_owned());
+        for server in [&forgotten, &kept] {
+            storage
+                .store_cached_posts(server, &channel_id, &PostThread::default())
+                .unwrap();
         }
+
+        assert_eq!(
+            storage.forget_server(&forgotten).unwrap(),
+            ForgottenData {
+                credentials: true,
+                cached_channels: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(storage.credentials().unwrap(), [credentials(&kept)]);
+        assert!(storage
+            .cached_posts(&forgotten, &channel_id)
+            .unwrap()
+            .is_none());
+        assert!(storage.cached_posts(&kept, &channel_id).unwrap().is_some());
+        assert_eq!(
+            storage.forget_server(&forgotten).unwrap(),
+            ForgottenData::default()
+        );
+    }
+
+    #[test]
+    fn import_app_data() {
+        let root = TempDir::new("import_app_data").unwrap();
+        let storage = Storage::open_with_root(root.path().to_owned());
+        let server = ServerUrl::parse("https://mm.example.com").unwrap();
+        let credentials = |token: &str| ServerCredentials {
+            url: server.clone(),
+            access_token: AccessToken::try_from(token).unwrap(),
+        };
+        let queued = |id: &str| OutboxItem {
+            server: server.clone(),
+            post: CreatePostRequest {
+                channel_id: ChannelId::new("c1".to_owned()),
+                message: Message::new("offline".to_owned()),
+                root_id: None,
+                pending_post_id: PostId::new(id.to_owned()),
+                props: None,
+            },
+            queued_at: 1_000,
+        };
+        storage
+            .store_credentials(&credentials("hs8das8dg8asgd"))
+            .unwrap();
+        storage.store_outbox(&[queued("p1")]).unwrap();
+
+        let imported = storage
+            .import_app_data(
+                vec![credentials("kd9sd7f6gs7dfg")],
+                vec![queued("p1"), queued("p2")],
+                Vec::new(),
+            )
+            .unwrap();
+        assert_eq!(
+            imported,
+            ImportedAppData {
+                credentials: 1,
+                outbox_posts: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            storage.credentials().unwrap(),
+            [credentials("kd9sd7f6gs7dfg")]
+        );
+        assert_eq!(storage.outbox().unwrap(), [queued("p1"), queued("p2")]);
+    }
+
+    #[test]
+    fn reminders() {
+        let root = TempDir::new("reminders").unwrap();
+        let storage = Storage::open_with_root(root.path().to_owned());
+        let server = ServerUrl::parse("https://mm.example.com").unwrap();
+        let post: Post = serde_json::from_str(include_str!("api/fixtures/posts.json"))
+            .map(|thread: PostThread| thread.posts.into_values().next().unwrap())
+            .unwrap();
+        let reminder = |remind_at| Reminder {
+            server: server.clone(),
+            post: post.clone(),
+            remind_at,
+            created_at: 1_000,
+            fired: false,
+        };
+        storage.add_reminder(reminder(2_000)).unwrap();
+        storage.add_reminder(reminder(3_000)).unwrap();
+        assert_eq!(storage.reminders().unwrap(), [reminder(3_000)]);
+
+        let snoozed = storage
+            .update_reminders(&server, &[post.id.clone()], |reminder| {
+                reminder.remind_at = 4_000;
+                true
+            })
+            .unwrap();
+        assert_eq!(snoozed, [reminder(4_000)]);
+        storage
+            .update_reminders(&server, &[post.id.clone()], |_| false)
+            .unwrap();
+        assert!(storage.reminders().unwrap().is_empty());
+    }
+
+    #[test]
+    fn scheduled_posts() {
+        let root = TempDir::new("scheduled_posts").unwrap();
+        let storage = Storage::open_with_root(root.path().to_owned());
+        let server = ServerUrl::parse("https://mm.example.com").unwrap();
+        let other = ServerUrl::parse("https://other.example.com").unwrap();
+        let scheduled = |server: &ServerUrl, id: &str| ScheduledPost {
+            server: server.clone(),
+            post: CreatePostRequest {
+                channel_id: ChannelId::new("c1".to_owned()),
+                message: Message::new("later".to_owned()),
+                root_id: None,
+                pending_post_id: PostId::new(id.to_owned()),
+                props: None,
+            },
+            send_at: 2_000,
+            scheduled_at: 1_000,
+        };
+        storage
+            .add_scheduled_post(scheduled(&server, "p1"))
+            .unwrap();
+        storage
+            .add_scheduled_post(scheduled(&server, "p2"))
+            .unwrap();
+        storage.add_scheduled_post(scheduled(&other, "p1")).unwrap();
+
+        let removed = storage
+            .remove_scheduled_posts(&server, &[PostId::new("p1".to_owned())])
+            .unwrap();
+        assert_eq!(removed, 1);
+        assert_eq!(
+            storage.scheduled_posts().unwrap(),
+            [scheduled(&server, "p2"), scheduled(&other, "p1")]
+        );
+        assert_eq!(storage.forget_server(&other).unwrap().scheduled_posts, 1);
+        assert_eq!(
+            storage.scheduled_posts().unwrap(),
+            [scheduled(&server, "p2")]
+        );
+    }
+
+    #[test]
+    fn migrates_documents_without_header() {
+        let root = TempDir::new("migrates").unwrap();
+        {
+            let storage = Storage::open_with_root(root.path().to_owned());
+            let mut inner = storage.0.lock().unwrap();
+            let vault = inner.vault().unwrap();
+            // Layout of versions before documents had header
+            if !vault.path_exists("/settings").unwrap() {
+                vault.create_dir_all("/settings").unwrap();
+            }
+            let mut file = zbox::OpenOptions::new()
+                .create(true)
+                .open(vault, "/settings/post_density")
+                .unwrap();
+            std::io::Write::write_all(&mut file, b"\"high\"").unwrap();
+            file.finish().unwrap();
+            write_json_in(vault, SCHEMA_VERSION_PATH, &0).unwrap();
+        }
+        let storage = Storage::open_with_root(root.path().to_owned());
+        assert_eq!(storage.post_density().unwrap(), PostDensity::High);
+        let mut inner = storage.0.lock().unwrap();
+        let vault = inner.vault().unwrap();
+        assert!(read_raw_in(vault, "/settings/post_density")
+            .unwrap()
+            .starts_with(HEADER_MAGIC));
+
+        write_document_in(vault, SCHEMA_VERSION_PATH, b"99").unwrap();
+        assert!(matches!(
+            migrate(vault),
+            Err(StorageError::UnsupportedSchema { found: 99, .. })
+        ));
+    }
+
+    #[test]
+    fn splits_credentials() {
+        let root = TempDir::new("splits_credentials").unwrap();
+        let creds = vec![
+            ServerCredentials {
+                url: ServerUrl::parse("https://mm.example.com").unwrap(),
+                access_token: AccessToken::try_from("hs8das8dg8asgd").unwrap(),
+            },
+            ServerCredentials {
+                url: ServerUrl::parse("https://community.mattermost.com").unwrap(),
+                access_token: AccessToken::try_from("kd9sd7f6gs7dfg").unwrap(),
+            },
+        ];
+        {
+            let storage = Storage::open_with_root(root.path().to_owned());
+            let mut inner = storage.0.lock().unwrap();
+            let vault = inner.vault().unwrap();
+            // Layout of version 1, all servers in one document
+            vault.remove_dir_all(CREDENTIALS_DIR).ok();
+            write_document_in(vault, CREDENTIALS_DIR, &bincode::serialize(&creds).unwrap())
+                .unwrap();
+            write_json_in(vault, SCHEMA_VERSION_PATH, &1).unwrap();
+        }
+        let storage = Storage::open_with_root(root.path().to_owned());
+        let mut expected = creds.clone();
+        expected.reverse();
+        assert_eq!(storage.credentials().unwrap(), expected);
+        let mut inner = storage.0.lock().unwrap();
+        let vault = inner.vault().unwrap();
+        assert!(vault.metadata(CREDENTIALS_DIR).unwrap().is_dir());
+        assert_eq!(vault.read_dir(CREDENTIALS_DIR).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn runs_without_config_dir() {
+        let root = TempDir::new("runs_without_config_dir").unwrap();
+        // Directory can't be created where file is
+        let file = root.path().join("file");
+        std::fs::write(&file, b"").unwrap();
+        let storage = Storage::open_with_root(file);
+        assert!(matches!(
+            storage.post_density(),
+            Err(StorageError::ConfigDir(_))
+        ));
+        assert!(matches!(
+            storage.doctor(true),
+            Err(StorageError::ConfigDir(_))
+        ));
+    }
+
+    #[test]
+    fn doctor() {
+        let root = TempDir::new("doctor").unwrap();
+        let secure = root.path().join("worryless/secure");
+        let corrupt = |name: &str| std::fs::write(secure.join(name), b"garbage").unwrap();
+        {
+            let storage = Storage::open_with_root(root.path().to_owned());
+            storage.set_post_density(PostDensity::High).unwrap();
+            assert_eq!(
+                storage.doctor(false).unwrap(),
+                StorageHealth {
+                    healthy: true,
+                    ..Default::default()
+                }
+            );
+            storage.close();
+        }
+
+        // Backup of super block is used
+        corrupt("super_blk.0");
+        {
+            let storage = Storage::open_with_root(root.path().to_owned());
+            assert_eq!(storage.post_density().unwrap(), PostDensity::High);
+            storage.close();
+        }
+
+        corrupt("super_blk.0");
+        corrupt("super_blk.1");
+        let storage = Storage::open_with_root(root.path().to_owned());
+        assert!(matches!(storage.post_density(), Err(StorageError::Damaged)));
+        assert!(storage.doctor(false).is_err());
+        let health = storage.doctor(true).unwrap();
+        assert!(health.rebuilt && health.healthy);
+        assert_eq!(storage.post_density().unwrap(), PostDensity::default());
+        storage.set_post_density(PostDensity::Low).unwrap();
+        assert_eq!(storage.post_density().unwrap(), PostDensity::Low);
+    }
+
+    /// Vault which is fine but can't be used is left as it is
+    fn assert_not_rebuilt(root: &std::path::Path, storage: &Storage) {
+        assert!(storage.doctor(true).is_err());
+        let moved_aside = std::fs::read_dir(root.join("worryless"))
+            .unwrap()
+            .filter_map(Result::ok)
+            .any(|entry| entry.file_name().to_string_lossy().contains("damaged"));
+        assert!(!moved_aside);
+    }
+
+    #[test]
+    fn refuses_newer_schema() {
+        let root = TempDir::new("refuses_newer_schema").unwrap();
+        {
+            let storage = Storage::open_with_root(root.path().to_owned());
+            storage.set_post_density(PostDensity::High).unwrap();
+            let mut inner = storage.0.lock().unwrap();
+            write_document_in(inner.vault().unwrap(), SCHEMA_VERSION_PATH, b"99").unwrap();
+        }
+        let storage = Storage::open_with_root(root.path().to_owned());
+        assert!(matches!(
+            storage.post_density(),
+            Err(StorageError::UnsupportedSchema { found: 99, .. })
+        ));
+        assert_not_rebuilt(root.path(), &storage);
+        assert!(matches!(
+            storage.doctor(false),
+            Err(StorageError::UnsupportedSchema { found: 99, .. })
+        ));
+    }
+
+    #[test]
+    fn refuses_wrong_password() {
+        let root = TempDir::new("refuses_wrong_password").unwrap();
+        {
+            let storage = Storage::open_with_root(root.path().to_owned());
+            storage.set_post_density(PostDensity::High).unwrap();
+            storage.close();
+        }
+        let storage = Storage(Arc::new(Mutex::new(Inner {
+            app_config_dir: root.path().join("worryless"),
+            base_password: Some("wrong".to_owned()),
+            password: "wrong".to_owned(),
+            vault: None,
+            held_by: None,
+            failure: Some(OpenFailure::Damaged),
+        })));
+        assert!(matches!(
+            open_vault(&vault_uri(&root.path().join("worryless")), "wrong"),
+            Err(StorageError::WrongPassword)
+        ));
+        assert_not_rebuilt(root.path(), &storage);
+        assert!(matches!(
+            storage.post_density(),
+            Err(StorageError::WrongPassword)
+        ));
+
+        // Data is still there for the right password
+        let storage = Storage::open_with_root(root.path().to_owned());
+        assert_eq!(storage.post_density().unwrap(), PostDensity::High);
+    }
+
+    #[test]
+    fn recovers_interrupted_rebind() {
+        let root = TempDir::new("recovers_interrupted_rebind").unwrap();
+        let config_dir = root.path().join("worryless");
+        {
+            let storage = Storage::open_with_root(root.path().to_owned());
+            storage.set_post_density(PostDensity::High).unwrap();
+            storage.close();
+        }
+        // Vault went back to base password, marker wasn't removed yet
+        std::fs::write(config_dir.join(".account_bound"), []).unwrap();
+        vault_key::begin_rebind(&config_dir, false).unwrap();
+        {
+            let storage = Storage::open_with_root(root.path().to_owned());
+            assert_eq!(storage.post_density().unwrap(), PostDensity::High);
+            assert!(!storage.account_bound());
+            storage.close();
+        }
+        assert_eq!(vault_key::candidate_states(&config_dir), [false]);
+
+        // Marker without secret in keyring doesn't bring application down
+        std::fs::write(config_dir.join(".account_bound"), []).unwrap();
+        let storage = Storage::open_with_root(root.path().to_owned());
+        assert!(matches!(
+            storage.post_density(),
+            Err(StorageError::Keyring(_) | StorageError::WrongPassword)
+        ));
+        assert_not_rebuilt(root.path(), &storage);
+    }
+
+    #[test]
+    fn closed() {
+        let root = TempDir::new("closed").unwrap();
+        let storage = Storage::open_with_root(root.path().to_owned());
+        storage.close();
+        assert!(matches!(storage.credentials(), Err(StorageError::Closed)));
+        assert!(!root.path().join("worryless/secure/.repo_lock").exists());
+        assert!(!root.path().join("worryless/vault.lock").exists());
+    }
+
+    #[test]
+    fn respects_vault_lock() {
+        let root = TempDir::new("respects_vault_lock").unwrap();
+        let config_dir = root.path().join("worryless");
+        {
+            let storage = Storage::open_with_root(root.path().to_owned());
+            storage.set_post_density(PostDensity::High).unwrap();
+            storage.close();
+        }
+        // Lock of crashed zbox doesn't keep vault closed
+        std::fs::write(config_dir.join("secure/.repo_lock"), []).unwrap();
+        let storage = Storage::open_with_root(root.path().to_owned());
+        assert_eq!(storage.post_density().unwrap(), PostDensity::High);
+        storage.close();
+
+        // Instance which is just taking lock holds it
+        std::fs::write(config_dir.join(VAULT_LOCK), []).unwrap();
+        let storage = Storage::open_with_root(root.path().to_owned());
+        assert!(matches!(
+            storage.post_density(),
+            Err(StorageError::AlreadyRunning { pid: 0 })
+        ));
+        assert!(matches!(
+            storage.doctor(true),
+            Err(StorageError::AlreadyRunning { .. })
+        ));
+        assert!(config_dir.join(VAULT_LOCK).exists());
     }
 }
@@ -0,0 +1,88 @@
+use std::panic::AssertUnwindSafe;
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::errors::StorageError;
+use crate::storage::Storage;
+
+type Job = Box<dyn FnOnce(&Storage) + Send>;
+
+/// Async access to [`Storage`]. Operations are queued to thread of their
+/// own and run there one after another, so vault crypto and disk IO never
+/// block async runtime and commands don't have to remember
+/// `spawn_blocking`.
+#[derive(Clone)]
+pub struct StorageHandle(mpsc::UnboundedSender<Job>);
+
+impl StorageHandle {
+    pub fn new(storage: Storage) -> Self {
+        let (queue, mut jobs) = mpsc::unbounded_channel::<Job>();
+        std::thread::Builder::new()
+            .name("storage".to_owned())
+            .spawn(move || {
+                while let Some(job) = jobs.blocking_recv() {
+                    // Operation which panicked must not take the queue down
+                    if std::panic::catch_unwind(AssertUnwindSafe(|| job(&storage))).is_err() {
+                        tracing::error!("Storage operation panicked");
+                    }
+                }
+            })
+            .expect("Failed to start storage thread");
+        Self(queue)
+    }
+
+    /// Run `operation` on storage thread and wait for its result
+    pub async fn run<T, E>(
+        &self,
+        operation: impl FnOnce(&Storage) -> Result<T, E> + Send + 'static,
+    ) -> Result<T, E>
+    where
+        T: Send + 'static,
+        E: From<StorageError> + Send + 'static,
+    {
+        let (reply, result) = oneshot::channel();
+        self.spawn(move |storage| {
+            reply.send(operation(storage)).ok();
+        });
+        result.await.map_err(|_| StorageError::Closed)?
+    }
+
+    /// Queue `operation` without waiting for it, e.g. write of cache whose
+    /// failure is only logged. Later operations see its changes.
+    pub fn spawn(&self, operation: impl FnOnce(&Storage) + Send + 'static) {
+        if self.0.send(Box::new(operation)).is_err() {
+            tracing::warn!("Storage thread is gone, operation dropped");
+        }
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use models::PostDensity;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn runs_operations_in_order() {
+        let root = TempDir::new("storage_handle").unwrap();
+        let handle = StorageHandle::new(Storage::open_with_root(root.path().to_owned()));
+
+        handle.spawn(|storage| storage.set_post_density(PostDensity::Low).unwrap());
+        let density = handle.run(|storage| storage.post_density()).await.unwrap();
+        assert_eq!(density, PostDensity::Low);
+
+        handle.spawn(|_| panic!("broken operation"));
+        let result: Result<(), StorageError> = handle
+            .run(|storage| {
+                storage.close();
+                Ok(())
+            })
+            .await;
+        assert!(result.is_ok());
+        assert!(matches!(
+            handle.run(|storage| storage.post_density()).await,
+            Err(StorageError::Closed)
+        ));
+    }
+}
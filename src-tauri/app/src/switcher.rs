@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use models::*;
+use serde::Serialize;
+
+/// Targets returned by quick switcher
+pub const SWITCHER_RESULTS: usize = 20;
+
+/// Recently visited channels get bonus, the most recent one the biggest
+const RECENT_BONUS: &[u32] = &[60, 50, 42, 35, 29, 24, 20, 16, 12, 8];
+const UNREAD_BONUS: u32 = 25;
+const MENTION_BONUS: u32 = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwitchKind {
+    Channel,
+    /// Direct or group message
+    DirectMessage,
+    Team,
+}
+
+/// Conversation or team quick switcher offers
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwitchTarget {
+    pub kind: SwitchKind,
+    pub team_id: Option<TeamId>,
+    /// `None` for teams
+    pub channel_id: Option<ChannelId>,
+    pub display_name: String,
+    pub unread: bool,
+    pub mentions: i64,
+}
+
+/// How well `query` matches `name`, `None` when it doesn't. Characters of
+/// query must appear in name in order, whole prefix beats word start which
+/// beats substring which beats scattered characters.
+fn match_score(query: &str, name: &str) -> Option<u32> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    let name = name.to_lowercase();
+    if name.starts_with(query) {
+        return Some(300);
+    }
+    if let Some(index) = name.find(query) {
+        let word_start = name[..index].ends_with([' ', '-', '_', '.', ',']);
+        return Some(if word_start { 200 } else { 150 });
+    }
+    let mut gaps = 0;
+    let mut rest = name.chars();
+    for wanted in query.chars() {
+        loop {
+            match rest.next() {
+                Some(c) if c == wanted => break,
+                Some(_) => gaps += 1,
+                None => return None,
+            }
+        }
+    }
+    Some(100u32.saturating_sub(gaps))
+}
+
+/// Best score of any of `names`
+fn best_score<'a>(query: &str, names: impl IntoIterator<Item = &'a str>) -> Option<u32> {
+    names
+        .into_iter()
+        .filter_map(|name| match_score(query, name))
+        .max()
+}
+
+/// Top `limit` channels and teams matching `query`, better matches first
+/// and among equal ones recently visited and unread ones first. `recent`
+/// is most recently visited first.
+pub fn rank(
+    query: &str,
+    channels: &[Channel],
+    teams: &[Team],
+    members: &[ChannelMember],
+    recent: &[ChannelId],
+    limit: usize,
+) -> Vec<SwitchTarget> {
+    let query = query.trim().to_lowercase();
+    let members: HashMap<&str, &ChannelMember> = members
+        .iter()
+        .map(|member| (member.channel_id.as_str(), member))
+        .collect();
+    let mut scored: Vec<(u32, SwitchTarget)> = Vec::new();
+    for channel in channels.iter().filter(|channel| channel.delete_at == 0) {
+        let Some(channel_id) = &channel.id else {
+            continue;
+        };
+        let display_name = channel
+            .display_name
+            .as_deref()
+            .map(String::as_str)
+            .unwrap_or_default();
+        let name = channel
+            .name
+            .as_deref()
+            .map(String::as_str)
+            .unwrap_or_default();
+        let Some(mut score) = best_score(&query, [display_name, name]) else {
+            continue;
+        };
+        let member = members.get(channel_id.as_str());
+        let mentions = member.map_or(0, |member| member.mention_count);
+        let unread = member.is_some_and(|member| {
+            !member.notify_props.is_muted() && channel.total_msg_count > member.msg_count
+        });
+        if let Some(rank) = recent.iter().position(|recent| recent == channel_id) {
+            score += RECENT_BONUS.get(rank).copied().unwrap_or_default();
+        }
+        score += u32::from(unread) * UNREAD_BONUS + u32::from(mentions > 0) * MENTION_BONUS;
+        let direct = matches!(
+            channel.r#type.as_deref().map(String::as_str),
+            Some("D" | "G")
+        );
+        scored.push((
+            score,
+            SwitchTarget {
+                kind: if direct {
+                    SwitchKind::DirectMessage
+                } else {
+                    SwitchKind::Channel
+                },
+                team_id: channel
+                    .team_id
+                    .clone()
+                    .filter(|team_id| !team_id.is_empty())
+                    .map(TeamId::new),
+                channel_id: Some(channel_id.clone()),
+                display_name: Some(display_name)
+                    .filter(|display_name| !display_name.is_empty())
+                    .unwrap_or(name)
+                    .to_owned(),
+                unread,
+                mentions,
+            },
+        ));
+    }
+    for team in teams {
+        let Some(team_id) = &team.id else {
+            continue;
+        };
+        let display_name = team
+            .display_name
+            .as_deref()
+            .map(String::as_str)
+            .unwrap_or_default();
+        let name = team.name.as_deref().map(String::as_str).unwrap_or_default();
+        // Teams aren't conversations, they show up only when asked for
+        let Some(score) = best_score(&query, [display_name, name]).filter(|_| !query.is_empty())
+        else {
+            continue;
+        };
+        scored.push((
+            score,
+            SwitchTarget {
+                kind: SwitchKind::Team,
+                team_id: Some(team_id.clone()),
+                channel_id: None,
+                display_name: display_name.to_owned(),
+                unread: false,
+                mentions: 0,
+            },
+        ));
+    }
+    scored.sort_by(|(a_score, a), (b_score, b)| {
+        b_score
+            .cmp(a_score)
+            .then_with(|| a.display_name.len().cmp(&b.display_name.len()))
+            .then_with(|| a.display_name.cmp(&b.display_name))
+    });
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, target)| target)
+        .collect()
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn channel(id: &str, display_name: &str, r#type: &str, total_msg_count: i64) -> Channel {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "team_id": if r#type == "D" { "" } else { "t1" },
+            "type": r#type,
+            "display_name": display_name,
+            "name": display_name.to_lowercase().replace(' ', "-"),
+            "total_msg_count": total_msg_count,
+        }))
+        .unwrap()
+    }
+
+    fn member(channel_id: &str, msg_count: i64, mention_count: i64) -> ChannelMember {
+        serde_json::from_value(serde_json::json!({
+            "channel_id": channel_id,
+            "user_id": "me",
+            "last_viewed_at": 0,
+            "msg_count": msg_count,
+            "mention_count": mention_count,
+            "notify_props": {},
+        }))
+        .unwrap()
+    }
+
+    fn names(targets: &[SwitchTarget]) -> Vec<&str> {
+        targets
+            .iter()
+            .map(|target| target.display_name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn fuzzy_scores() {
+        assert_eq!(match_score("dev", "Developers"), Some(300));
+        assert_eq!(match_score("ops", "Dev Ops"), Some(200));
+        assert_eq!(match_score("ops", "devops"), Some(150));
+        assert_eq!(match_score("tst", "town square test"), Some(90));
+        assert_eq!(match_score("xyz", "town square"), None);
+    }
+
+    #[test]
+    fn ranks_matches_recent_and_unread() {
+        let channels = [
+            channel("c1", "Town Square", "O", 10),
+            channel("c2", "Off-Topic", "O", 5),
+            channel("c3", "Design Team", "P", 3),
+            channel("d1", "maria.k", "D", 7),
+        ];
+        let teams: Vec<Team> = vec![serde_json::from_value(serde_json::json!({
+            "id": "t1",
+            "display_name": "Design",
+            "name": "design",
+        }))
+        .unwrap()];
+        let members = [member("c1", 10, 0), member("c2", 2, 0), member("d1", 6, 1)];
+        let recent = [ChannelId::new("c1".to_owned())];
+
+        let all = rank("", &channels, &teams, &members, &recent, SWITCHER_RESULTS);
+        assert_eq!(
+            names(&all),
+            ["maria.k", "Town Square", "Off-Topic", "Design Team"]
+        );
+        assert_eq!(all[0].kind, SwitchKind::DirectMessage);
+        assert!(all[0].unread);
+        assert_eq!(all[0].mentions, 1);
+
+        let design = rank(
+            "des",
+            &channels,
+            &teams,
+            &members,
+            &recent,
+            SWITCHER_RESULTS,
+        );
+        assert_eq!(names(&design), ["Design", "Design Team"]);
+        assert_eq!(design[0].kind, SwitchKind::Team);
+
+        let limited = rank("o", &channels, &teams, &members, &recent, 1);
+        assert_eq!(names(&limited), ["Off-Topic"]);
+    }
+}
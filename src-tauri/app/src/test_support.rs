@@ -0,0 +1,28 @@
+//! Fixtures shared by unit tests of several modules
+
+use models::*;
+
+/// Post `id` in town square created at `create_at`, its message is its id.
+/// Tests override fields they care about.
+pub(crate) fn post(id: &str, create_at: Timestamp) -> Post {
+    Post {
+        id: PostId::new(id.to_owned()),
+        edit_at: 0,
+        update_at: create_at,
+        delete_at: 0,
+        create_at,
+        user_id: None,
+        channel_id: ChannelId::new("town-square".to_owned()),
+        root_id: String::new(),
+        original_id: String::new(),
+        message: Message::new(id.to_owned()),
+        post_type: PostType::new(String::new()),
+        hashtag: None,
+        file_ids: None,
+        pending_post_id: PostId::new(String::new()),
+        props: serde_json::Value::Null,
+        metadata: None,
+        is_pinned: false,
+        message_ast: None,
+    }
+}
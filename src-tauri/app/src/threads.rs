@@ -0,0 +1,239 @@
+use models::*;
+
+/// Post after which next page of replies should be fetched, the newest
+/// reply known locally
+pub fn newest_reply(thread: &PostThread) -> Option<&Post> {
+    thread.posts.values().max_by_key(|post| post.create_at)
+}
+
+/// Merge page of replies fetched after [`newest_reply`] into `thread`.
+///
+/// Returns `false` without touching `thread` when page doesn't continue it,
+/// i.e. its `prev_post_id` points at post which isn't known locally. Whole
+/// thread has to be fetched again in such case.
+pub fn merge_replies(thread: &mut PostThread, page: PostThread) -> bool {
+    let continues = match page.prev_post_id.as_deref() {
+        Some(prev) if !prev.is_empty() => thread.posts.contains_key(prev),
+        _ => true,
+    };
+    if !continues {
+        return false;
+    }
+    let newest_first = newest_first(thread);
+    for (id, post) in page.posts {
+        thread.posts.insert(id, post);
+    }
+    let mut order: Vec<&Post> = thread.posts.values().collect();
+    order.sort_by_key(|post| post.create_at);
+    if newest_first {
+        order.reverse();
+    }
+    thread.order = order.into_iter().map(|post| post.id.clone()).collect();
+    thread.next_post_id = page.next_post_id;
+    thread.has_next = page.has_next;
+    true
+}
+
+/// Append older page of channel posts to newest-first `posts`, keeping at
+/// most `retain` newest of them
+pub fn append_older(posts: &mut PostThread, page: PostThread, retain: usize) {
+    for id in page.order {
+        if !posts.posts.contains_key(id.as_str()) {
+            posts.order.push(id);
+        }
+    }
+    posts.posts.extend(page.posts);
+    posts.prev_post_id = page.prev_post_id;
+    retain_newest(posts, retain);
+}
+
+/// Latest change to any of `posts`, posts changed after it are fetched
+/// with `since` query
+pub fn watermark(posts: &PostThread) -> Option<Timestamp> {
+    posts
+        .posts
+        .values()
+        .map(|post| post.create_at.max(post.update_at).max(post.delete_at))
+        .max()
+}
+
+/// Merge channel posts changed since [`watermark`] into newest-first
+/// `posts`. Edited posts replace cached ones, deleted posts are dropped and
+/// new ones take their place by creation time.
+pub fn merge_since(posts: &mut PostThread, changes: PostThread, retain: usize) {
+    for id in changes.order {
+        if !posts.posts.contains_key(id.as_str()) && !posts.order.contains(&id) {
+            posts.order.push(id);
+        }
+    }
+    for (id, post) in changes.posts {
+        if post.delete_at > 0 {
+            posts.posts.remove(&id);
+        } else {
+            posts.posts.insert(id, post);
+        }
+    }
+    let mut order = std::mem::take(&mut posts.order);
+    order.retain(|id| posts.posts.contains_key(id.as_str()));
+    order.sort_by_key(|id| std::cmp::Reverse(posts.posts[id.as_str()].create_at));
+    posts.order = order;
+    retain_newest(posts, retain);
+}
+
+/// Drop everything except `retain` newest posts of newest-first `posts`
+pub fn retain_newest(posts: &mut PostThread, retain: usize) {
+    if posts.order.len() <= retain {
+        return;
+    }
+    // Dropped posts become the older ones which can be fetched again
+    posts.prev_post_id = Some(posts.order[retain].clone());
+    posts.order.truncate(retain);
+    let kept: Vec<&str> = posts.order.iter().map(|id| id.as_str()).collect();
+    posts.posts.retain(|id, _| kept.contains(&id.as_str()));
+}
+
+/// Oldest post of channel created after `last_viewed_at` by someone else
+/// than `user_id`, "New messages" divider is put above it
+pub fn first_unread(
+    posts: &PostThread,
+    last_viewed_at: Timestamp,
+    user_id: Option<&UserId>,
+) -> Option<PostId> {
+    posts
+        .posts
+        .values()
+        .filter(|post| post.create_at > last_viewed_at && post.delete_at == 0)
+        .filter(|post| user_id.is_none() || post.user_id.as_ref() != user_id)
+        .min_by_key(|post| post.create_at)
+        .map(|post| post.id.clone())
+}
+
+/// Server sorts thread either way depending on endpoint, keep what was
+/// already received
+fn newest_first(thread: &PostThread) -> bool {
+    let created_at = |id: Option<&PostId>| {
+        id.and_then(|id| thread.posts.get(id.as_str()))
+            .map(|post| post.create_at)
+    };
+    match (
+        created_at(thread.order.first()),
+        created_at(thread.order.last()),
+    ) {
+        (Some(first), Some(last)) => first > last,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::test_support;
+
+    fn post(id: &str, create_at: Timestamp) -> Post {
+        Post {
+            root_id: if id == "root" {
+                String::new()
+            } else {
+                "root".to_owned()
+            },
+            ..test_support::post(id, create_at)
+        }
+    }
+
+    fn thread(posts: &[(&str, Timestamp)], prev: &str, has_next: bool) -> PostThread {
+        PostThread {
+            order: posts
+                .iter()
+                .map(|(id, _)| PostId::new((*id).to_owned()))
+                .collect(),
+            posts: posts
+                .iter()
+                .map(|(id, create_at)| ((*id).to_owned(), post(id, *create_at)))
+                .collect::<HashMap<_, _>>(),
+            next_post_id: None,
+            prev_post_id: Some(PostId::new(prev.to_owned())),
+            has_next,
+        }
+    }
+
+    fn order(thread: &PostThread) -> Vec<&str> {
+        thread.order.iter().map(|id| id.as_str()).collect()
+    }
+
+    #[test]
+    fn appends_new_replies() {
+        let mut current = thread(&[("root", 1), ("a", 2)], "", true);
+        assert_eq!(newest_reply(&current).unwrap().id.as_str(), "a");
+
+        let page = thread(&[("b", 3), ("c", 4)], "a", false);
+        assert!(merge_replies(&mut current, page));
+        assert_eq!(order(&current), ["root", "a", "b", "c"]);
+        assert!(!current.has_next);
+    }
+
+    #[test]
+    fn keeps_newest_first_order() {
+        let mut current = thread(&[("a", 2), ("root", 1)], "", false);
+        let page = thread(&[("root", 1), ("b", 3)], "a", false);
+        assert!(merge_replies(&mut current, page));
+        assert_eq!(order(&current), ["b", "a", "root"]);
+        assert_eq!(current.posts.len(), 3);
+    }
+
+    #[test]
+    fn older_page_within_retention() {
+        let mut current = thread(&[("d", 4), ("c", 3)], "", false);
+        let page = thread(&[("b", 2), ("a", 1)], "", false);
+        append_older(&mut current, page, 3);
+        assert_eq!(order(&current), ["d", "c", "b"]);
+        assert!(!current.posts.contains_key("a"));
+        assert_eq!(
+            current.prev_post_id.as_deref().map(String::as_str),
+            Some("a")
+        );
+    }
+
+    #[test]
+    fn merges_changes_since_watermark() {
+        let mut current = thread(&[("c", 3), ("b", 2), ("a", 1)], "", false);
+        assert_eq!(watermark(&current), Some(3));
+
+        let mut changes = thread(&[("e", 5), ("d", 4), ("b", 2), ("a", 1)], "", false);
+        changes.posts.get_mut("b").unwrap().update_at = 6;
+        changes.posts.get_mut("a").unwrap().delete_at = 7;
+        assert_eq!(watermark(&changes), Some(7));
+
+        merge_since(&mut current, changes, 4);
+        assert_eq!(order(&current), ["e", "d", "c", "b"]);
+        assert_eq!(current.posts["b"].update_at, 6);
+        assert!(!current.posts.contains_key("a"));
+    }
+
+    #[test]
+    fn first_unread_post() {
+        let me = UserId::new("me".to_owned());
+        let mut posts = thread(&[("d", 4), ("c", 3), ("b", 2), ("a", 1)], "", false);
+        posts.posts.get_mut("c").unwrap().user_id = Some(me.clone());
+        assert_eq!(
+            first_unread(&posts, 2, Some(&me))
+                .as_deref()
+                .map(String::as_str),
+            Some("d")
+        );
+        assert_eq!(
+            first_unread(&posts, 2, None).as_deref().map(String::as_str),
+            Some("c")
+        );
+        assert_eq!(first_unread(&posts, 4, Some(&me)), None);
+    }
+
+    #[test]
+    fn rejects_gap() {
+        let mut current = thread(&[("root", 1), ("a", 2)], "", false);
+        let page = thread(&[("c", 4)], "b", false);
+        assert!(!merge_replies(&mut current, page));
+        assert_eq!(order(&current), ["root", "a"]);
+    }
+}
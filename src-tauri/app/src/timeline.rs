@@ -0,0 +1,26 @@
+use models::*;
+use serde::Serialize;
+
+pub const TIMELINE_REFRESHED_EVENT: &str = "timeline-refreshed";
+
+/// Cached timeline returned right after channel switch, fresh one follows
+/// as [`TIMELINE_REFRESHED_EVENT`] with the same generation
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelinePreview {
+    pub channel_id: ChannelId,
+    pub generation: u64,
+    /// `None` when channel was never opened on this server
+    pub posts: Option<PostThread>,
+    /// Posts come from cache and refresh is running
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineRefreshed {
+    pub channel_id: ChannelId,
+    pub generation: u64,
+    /// `None` when refresh failed, cached posts stay in place
+    pub posts: Option<PostThread>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use models::*;
+use tauri::{AppHandle, Manager};
+
+use crate::sessions::{self, ServerBadge};
+use crate::settings::SettingsState;
+use crate::states::UserState;
+
+/// Unread counts of every server with session, summed into badge of dock
+/// icon on macOS, overlay of taskbar button on Windows and count of Unity
+/// launcher entry on Linux
+#[derive(Default)]
+pub struct UnreadTracker(Mutex<Totals>);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ServerTotals {
+    mentions: i64,
+    unread_channels: usize,
+}
+
+#[derive(Debug, Default)]
+struct Totals {
+    /// By server URL
+    servers: HashMap<String, ServerTotals>,
+    /// Count OS shows, `None` until first applied
+    shown: Option<u64>,
+}
+
+impl Totals {
+    /// Count for badge, zero hides it
+    fn count(&self, mentions_only: bool) -> u64 {
+        self.servers
+            .values()
+            .map(|totals| match mentions_only {
+                true => u64::try_from(totals.mentions).unwrap_or_default(),
+                false => totals.unread_channels as u64,
+            })
+            .sum()
+    }
+
+    /// Count to show when it differs from the shown one
+    fn changed(&mut self, mentions_only: bool) -> Option<u64> {
+        let count = self.count(mentions_only);
+        if self.shown == Some(count) {
+            return None;
+        }
+        self.shown = Some(count);
+        Some(count)
+    }
+}
+
+/// Take new counts of server, badge follows when totals change
+pub fn update(app: &AppHandle, badge: &ServerBadge) {
+    let totals = ServerTotals {
+        mentions: badge.mentions,
+        unread_channels: badge.unread_channels,
+    };
+    let tracker = app.state::<UnreadTracker>();
+    let mut state = tracker.0.lock().unwrap();
+    if state
+        .servers
+        .insert(badge.server.as_str().to_owned(), totals)
+        != Some(totals)
+    {
+        apply(app, &mut state);
+    }
+}
+
+/// Count current server again from channels and memberships held in
+/// memory, after posts or reads changed them
+pub async fn recount(app: &AppHandle, server: ServerUrl) {
+    let badge = {
+        let user_state = app.state::<tokio::sync::Mutex<UserState>>();
+        let user_state = user_state.lock().await;
+        let (Some(channels), Some(members)) = (&user_state.channels, &user_state.channel_members)
+        else {
+            return;
+        };
+        sessions::badge(server, channels, members)
+    };
+    update(app, &badge);
+}
+
+/// Stop counting server which was logged out of or removed
+pub fn remove(app: &AppHandle, server: &ServerUrl) {
+    let tracker = app.state::<UnreadTracker>();
+    let mut state = tracker.0.lock().unwrap();
+    if state.servers.remove(server.as_str()).is_some() {
+        apply(app, &mut state);
+    }
+}
+
+/// Show badge again by current settings, e.g. after they changed
+pub fn refresh(app: &AppHandle) {
+    let tracker = app.state::<UnreadTracker>();
+    apply(app, &mut tracker.0.lock().unwrap());
+}
+
+fn apply(app: &AppHandle, totals: &mut Totals) {
+    let mentions_only = app
+        .state::<SettingsState>()
+        .get()
+        .notifications
+        .badge_mentions_only;
+    let Some(count) = totals.changed(mentions_only) else {
+        return;
+    };
+    let handle = app.clone();
+    // Dock, taskbar and GTK all want to be touched from main thread
+    let shown = app.run_on_main_thread(move || {
+        if let Err(e) = platform::show(&handle, count) {
+            tracing::warn!("Failed to show unread badge: {e}");
+        }
+    });
+    if let Err(e) = shown {
+        tracing::warn!("Failed to schedule unread badge: {e}");
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use cocoa::appkit::NSApp;
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::NSString;
+    use objc::{msg_send, sel, sel_impl};
+    use tauri::AppHandle;
+
+    pub(super) fn show(_app: &AppHandle, count: u64) -> Result<(), String> {
+        unsafe {
+            let label = match count {
+                0 => nil,
+                count => NSString::alloc(nil).init_str(&count.to_string()),
+            };
+            let tile: id = msg_send![NSApp(), dockTile];
+            let _: () = msg_send![tile, setBadgeLabel: label];
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use tauri::{AppHandle, Manager};
+    use windows::core::PCWSTR;
+    use windows::Win32::Graphics::Gdi::{CreateBitmap, DeleteObject};
+    use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
+    use windows::Win32::UI::Shell::{ITaskbarList3, TaskbarList};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        CreateIconIndirect, DestroyIcon, HICON, ICONINFO,
+    };
+
+    /// Side of overlay icon, taskbar draws it in the corner of button
+    const SIZE: usize = 16;
+
+    pub(super) fn show(app: &AppHandle, count: u64) -> Result<(), String> {
+        let window = app.get_window("main").ok_or("main window is gone")?;
+        let hwnd = window.hwnd().map_err(|e| e.to_string())?;
+        unsafe {
+            let taskbar: ITaskbarList3 = CoCreateInstance(&TaskbarList, None, CLSCTX_INPROC_SERVER)
+                .map_err(|e| e.to_string())?;
+            // Other methods fail until taskbar list is initialized
+            taskbar.HrInit().map_err(|e| e.to_string())?;
+            if count == 0 {
+                return taskbar
+                    .SetOverlayIcon(hwnd, HICON::default(), PCWSTR::null())
+                    .map_err(|e| e.to_string());
+            }
+            let params = crate::i18n::Params::from([("count", count.into())]);
+            let description: Vec<u16> = crate::i18n::text("badge-unread", &params)
+                .encode_utf16()
+                .chain([0])
+                .collect();
+            let icon = dot_icon()?;
+            let result = taskbar.SetOverlayIcon(hwnd, icon, PCWSTR(description.as_ptr()));
+            DestroyIcon(icon);
+            result.map_err(|e| e.to_string())
+        }
+    }
+
+    /// Red dot, taskbar overlay is too small for readable count so it goes
+    /// to description read by screen readers
+    unsafe fn dot_icon() -> Result<HICON, String> {
+        let center = (SIZE as f32 - 1.0) / 2.0;
+        let radius = SIZE as f32 / 2.0;
+        let pixels: Vec<u32> = (0..SIZE * SIZE)
+            .map(|index| {
+                let (x, y) = ((index % SIZE) as f32, (index / SIZE) as f32);
+                let inside = (x - center).powi(2) + (y - center).powi(2) <= radius.powi(2);
+                // BGRA
+                if inside {
+                    0xFF_D2_2B_2B
+                } else {
+                    0
+                }
+            })
+            .collect();
+        let mask = vec![0u8; SIZE * SIZE / 8];
+        let color = CreateBitmap(SIZE as i32, SIZE as i32, 1, 32, pixels.as_ptr().cast());
+        let mask = CreateBitmap(SIZE as i32, SIZE as i32, 1, 1, mask.as_ptr().cast());
+        let icon = CreateIconIndirect(&ICONINFO {
+            fIcon: true.into(),
+            xHotspot: 0,
+            yHotspot: 0,
+            hbmMask: mask,
+            hbmColor: color,
+        });
+        DeleteObject(color);
+        DeleteObject(mask);
+        icon.map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::collections::HashMap;
+
+    use gio::glib::{ToVariant, Variant};
+    use sha2::{Digest, Sha256};
+    use tauri::AppHandle;
+
+    /// Launcher entry of application is found by its desktop file, bundle
+    /// names it after executable
+    fn app_uri() -> Option<String> {
+        let exe = std::env::current_exe().ok()?;
+        let name = exe.file_stem()?.to_str()?;
+        Some(format!("application://{name}.desktop"))
+    }
+
+    pub(super) fn show(_app: &AppHandle, count: u64) -> Result<(), String> {
+        let uri = app_uri().ok_or("executable has no name")?;
+        let connection = gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>)
+            .map_err(|e| e.to_string())?;
+        let path = format!(
+            "/com/canonical/unity/launcherentry/{}",
+            hex::encode(&Sha256::digest(uri.as_bytes())[..8])
+        );
+        let properties = HashMap::from([
+            ("count".to_owned(), (count as i64).to_variant()),
+            ("count-visible".to_owned(), (count > 0).to_variant()),
+        ]);
+        let parameters: Variant = (uri, properties).to_variant();
+        connection
+            .emit_signal(
+                None,
+                &path,
+                "com.canonical.Unity.LauncherEntry",
+                "Update",
+                Some(&parameters),
+            )
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows, target_os = "linux")))]
+mod platform {
+    use tauri::AppHandle;
+
+    pub(super) fn show(_app: &AppHandle, _count: u64) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn sums_servers() {
+        let mut totals = Totals::default();
+        assert_eq!(totals.changed(false), Some(0));
+        assert_eq!(totals.changed(false), None);
+
+        totals.servers.insert(
+            "https://mm.example.com/".to_owned(),
+            ServerTotals {
+                mentions: 2,
+                unread_channels: 5,
+            },
+        );
+        totals.servers.insert(
+            "https://community.mattermost.com/".to_owned(),
+            ServerTotals {
+                mentions: 1,
+                unread_channels: 1,
+            },
+        );
+        assert_eq!(totals.changed(false), Some(6));
+        assert_eq!(totals.changed(true), Some(3));
+        assert_eq!(totals.changed(true), None);
+
+        totals.servers.remove("https://mm.example.com/");
+        assert_eq!(totals.changed(true), Some(1));
+    }
+}
@@ -0,0 +1,360 @@
+use std::path::Path;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::errors::StorageError;
+
+const KEYRING_SERVICE: &str = "light-mattermost-desktop";
+const KEYRING_BINDING: &str = "vault-binding";
+const KEYRING_PASSWORD: &str = "vault-password";
+/// Plaintext password file of older versions, kept only when OS keyring is
+/// unavailable
+const LEGACY_PASSWORD_FILE: &str = ".sec";
+/// Presence of this file in config directory means vault password is bound
+/// to OS account, the option has to be known before vault is opened
+const BINDING_MARKER: &str = ".account_bound";
+/// Written before vault is re-keyed and removed after marker follows, while
+/// it exists vault may be keyed for either state. Holds the state re-keying
+/// goes to.
+const REBIND_JOURNAL: &str = ".account_bound.pending";
+/// Directory of vault in config directory
+const VAULT_DIR: &str = "secure";
+
+/// Base password of vault in `config_dir`, kept in OS keyring.
+///
+/// Password of older versions is imported from `.sec` file once and the
+/// file is deleted. Without usable keyring, e.g. on Linux without Secret
+/// Service, the file is used as before. New password is only made up for
+/// vault which doesn't exist yet, keyring error is returned otherwise.
+pub fn base_password(config_dir: &Path) -> Result<String, StorageError> {
+    match password_entry(config_dir) {
+        Ok(entry) => keyring_password(config_dir, &entry),
+        Err(e) => {
+            tracing::warn!("OS keyring is unavailable, vault password stays in file: {e}");
+            legacy_password(config_dir, e)
+        }
+    }
+}
+
+fn keyring_password(config_dir: &Path, entry: &keyring::Entry) -> Result<String, StorageError> {
+    let legacy = config_dir.join(LEGACY_PASSWORD_FILE);
+    match entry.get_password() {
+        Ok(password) => {
+            // Left behind when import was interrupted
+            remove_if_exists(&legacy)?;
+            return Ok(password);
+        }
+        Err(keyring::Error::NoEntry) => {}
+        Err(e) => {
+            tracing::warn!("OS keyring is unavailable, vault password stays in file: {e}");
+            return legacy_password(config_dir, StorageError::Keyring(e.to_string()));
+        }
+    }
+    let password = match std::fs::read_to_string(&legacy) {
+        Ok(password) => password,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => new_password(
+            config_dir,
+            StorageError::Keyring("Vault password is missing".into()),
+        )?,
+        Err(e) => return Err(e)?,
+    };
+    // File is deleted only once keyring gives password back
+    let stored = entry
+        .set_password(&password)
+        .and_then(|()| entry.get_password());
+    match stored {
+        Ok(stored) if stored == password => {
+            if legacy.exists() {
+                std::fs::remove_file(&legacy)?;
+                tracing::info!("Vault password moved from file to OS keyring");
+            }
+        }
+        stored => {
+            match stored {
+                Ok(_) => tracing::warn!("OS keyring returned different vault password"),
+                Err(e) => tracing::warn!("Failed to store vault password in OS keyring: {e}"),
+            }
+            entry.delete_password().ok();
+            std::fs::write(&legacy, &password)?;
+        }
+    }
+    Ok(password)
+}
+
+/// Password kept in file when keyring failed with `keyring_error`
+fn legacy_password(config_dir: &Path, keyring_error: StorageError) -> Result<String, StorageError> {
+    let path = config_dir.join(LEGACY_PASSWORD_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(password) => Ok(password),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let password = new_password(config_dir, keyring_error)?;
+            std::fs::write(path, &password)?;
+            Ok(password)
+        }
+        Err(e) => Err(e)?,
+    }
+}
+
+/// Password of vault which is about to be created, `missing` when vault
+/// exists and its password was lost
+fn new_password(config_dir: &Path, missing: StorageError) -> Result<String, StorageError> {
+    if config_dir.join(VAULT_DIR).exists() {
+        return Err(missing);
+    }
+    Ok(random_password())
+}
+
+fn random_password() -> String {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+
+    thread_rng()
+        .sample_iter(Alphanumeric)
+        .take(50)
+        .map(char::from)
+        .collect()
+}
+
+pub fn is_bound(config_dir: &Path) -> bool {
+    config_dir.join(BINDING_MARKER).exists()
+}
+
+/// Binding states vault may be keyed for, recorded one first and the one
+/// of interrupted re-keying second
+pub fn candidate_states(config_dir: &Path) -> Vec<bool> {
+    let bound = is_bound(config_dir);
+    let pending = std::fs::read(config_dir.join(REBIND_JOURNAL))
+        .ok()
+        .map(|journal| journal == b"1");
+    let mut states = vec![bound];
+    states.extend(pending.filter(|pending| *pending != bound));
+    states
+}
+
+/// Password vault is opened with in binding state `bound`.
+///
+/// Bound password mixes in secret kept in OS keyring of current account
+/// together with machine id and account home directory. Copy of config
+/// directory alone can't unlock vault, neither on another machine nor under
+/// another account.
+pub fn password(base: &str, bound: bool) -> Result<String, StorageError> {
+    if !bound {
+        return Ok(base.to_owned());
+    }
+    let secret = binding_entry()?
+        .get_password()
+        .map_err(|e| StorageError::Keyring(e.to_string()))?;
+    Ok(derive(base, &secret, &machine_id()?, &account()?))
+}
+
+/// Create keyring secret and return password derived from it, vault has to
+/// be re-keyed to it before [`mark_bound`]
+pub fn new_binding(base: &str) -> Result<String, StorageError> {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+
+    let secret: String = thread_rng()
+        .sample_iter(Alphanumeric)
+        .take(64)
+        .map(char::from)
+        .collect();
+    let password = derive(base, &secret, &machine_id()?, &account()?);
+    binding_entry()?
+        .set_password(&secret)
+        .map_err(|e| StorageError::Keyring(e.to_string()))?;
+    Ok(password)
+}
+
+/// Journal re-keying to `bound` before vault password changes
+pub fn begin_rebind(config_dir: &Path, bound: bool) -> Result<(), StorageError> {
+    let journal = config_dir.join(REBIND_JOURNAL);
+    let staged = journal.with_extension("tmp");
+    std::fs::write(&staged, if bound { b"1" } else { b"0" })?;
+    std::fs::rename(staged, journal)?;
+    Ok(())
+}
+
+/// Re-keying failed and vault stays on its password
+pub fn abort_rebind(config_dir: &Path) {
+    remove_if_exists(&config_dir.join(REBIND_JOURNAL)).ok();
+}
+
+/// Record state vault is keyed for and close journal, keyring secret is
+/// forgotten once vault is back on base password
+pub fn mark_bound(config_dir: &Path, bound: bool) -> Result<(), StorageError> {
+    let journal = config_dir.join(REBIND_JOURNAL);
+    if bound {
+        std::fs::write(config_dir.join(BINDING_MARKER), [])?;
+        remove_if_exists(&journal)?;
+        return Ok(());
+    }
+    let was_bound = is_bound(config_dir) || journal.exists();
+    remove_if_exists(&config_dir.join(BINDING_MARKER))?;
+    remove_if_exists(&journal)?;
+    if was_bound {
+        if let Err(e) = binding_entry().and_then(|entry| {
+            entry
+                .delete_password()
+                .map_err(|e| StorageError::Keyring(e.to_string()))
+        }) {
+            tracing::warn!("Failed to remove vault binding from keyring: {e}");
+        }
+    }
+    Ok(())
+}
+
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Entry is per config directory, so vaults in different directories never
+/// share password
+fn password_entry(config_dir: &Path) -> Result<keyring::Entry, StorageError> {
+    let user = format!("{KEYRING_PASSWORD} {}", config_dir.display());
+    keyring::Entry::new(KEYRING_SERVICE, &user).map_err(|e| StorageError::Keyring(e.to_string()))
+}
+
+fn binding_entry() -> Result<keyring::Entry, StorageError> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_BINDING)
+        .map_err(|e| StorageError::Keyring(e.to_string()))
+}
+
+fn machine_id() -> Result<String, StorageError> {
+    machine_uid::get().map_err(|e| StorageError::Keyring(format!("Unknown machine id: {e}")))
+}
+
+/// Home directory identifies OS account, unlike user name it differs between
+/// accounts of the same name on different domains
+fn account() -> Result<String, StorageError> {
+    let dirs = directories::BaseDirs::new()
+        .ok_or_else(|| StorageError::Keyring("Home directory is not configured".to_owned()))?;
+    Ok(dirs.home_dir().display().to_string())
+}
+
+fn derive(base: &str, secret: &str, machine: &str, account: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key");
+    for part in [machine, account, base] {
+        // Length prefix keeps ("ab", "c") and ("a", "bc") apart
+        mac.update(&(part.len() as u64).to_be_bytes());
+        mac.update(part.as_bytes());
+    }
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn derived_from_every_part() {
+        let password = derive("base", "secret", "machine", "/home/maria");
+        assert_eq!(password.len(), 64);
+        assert_eq!(password, derive("base", "secret", "machine", "/home/maria"));
+        for other in [
+            derive("base", "secret", "other machine", "/home/maria"),
+            derive("base", "secret", "machine", "/home/jan"),
+            derive("base", "other secret", "machine", "/home/maria"),
+            derive("bas", "secret", "machine", "e/home/maria"),
+        ] {
+            assert_ne!(password, other);
+        }
+    }
+
+    fn keyring_error() -> StorageError {
+        StorageError::Keyring("unavailable".to_owned())
+    }
+
+    #[test]
+    fn legacy_file_is_kept_stable() {
+        let dir = tempdir::TempDir::new("vault_key").unwrap();
+        let password = legacy_password(dir.path(), keyring_error()).unwrap();
+        assert_eq!(password.len(), 50);
+        assert_eq!(
+            legacy_password(dir.path(), keyring_error()).unwrap(),
+            password
+        );
+
+        // Password of existing vault isn't made up again
+        std::fs::remove_file(dir.path().join(LEGACY_PASSWORD_FILE)).unwrap();
+        std::fs::create_dir(dir.path().join(VAULT_DIR)).unwrap();
+        assert!(matches!(
+            legacy_password(dir.path(), keyring_error()),
+            Err(StorageError::Keyring(_))
+        ));
+    }
+
+    fn mock_entry() -> keyring::Entry {
+        keyring::Entry::new_with_credential(Box::<keyring::mock::MockCredential>::default())
+    }
+
+    /// Next operation on `entry` fails
+    fn fail_once(entry: &keyring::Entry) {
+        let mock: &keyring::mock::MockCredential = entry.get_credential().downcast_ref().unwrap();
+        mock.set_error(keyring::Error::PlatformFailure("locked".into()));
+    }
+
+    #[test]
+    fn imports_legacy_file() {
+        let dir = tempdir::TempDir::new("vault_key").unwrap();
+        let legacy = dir.path().join(LEGACY_PASSWORD_FILE);
+        std::fs::create_dir(dir.path().join(VAULT_DIR)).unwrap();
+        std::fs::write(&legacy, "legacy").unwrap();
+
+        // Transient error neither loses file nor makes password up
+        let entry = mock_entry();
+        fail_once(&entry);
+        assert_eq!(keyring_password(dir.path(), &entry).unwrap(), "legacy");
+        assert!(legacy.exists());
+
+        assert_eq!(keyring_password(dir.path(), &entry).unwrap(), "legacy");
+        assert!(!legacy.exists());
+        assert_eq!(entry.get_password().unwrap(), "legacy");
+        assert_eq!(keyring_password(dir.path(), &entry).unwrap(), "legacy");
+
+        // Keyring failing after file is gone is reported
+        fail_once(&entry);
+        assert!(matches!(
+            keyring_password(dir.path(), &entry),
+            Err(StorageError::Keyring(_))
+        ));
+        assert!(!legacy.exists());
+        // So is password of existing vault missing from keyring
+        assert!(matches!(
+            keyring_password(dir.path(), &mock_entry()),
+            Err(StorageError::Keyring(_))
+        ));
+
+        let fresh = tempdir::TempDir::new("vault_key").unwrap();
+        let entry = mock_entry();
+        let password = keyring_password(fresh.path(), &entry).unwrap();
+        assert_eq!(password.len(), 50);
+        assert_eq!(entry.get_password().unwrap(), password);
+        assert!(!fresh.path().join(LEGACY_PASSWORD_FILE).exists());
+    }
+
+    #[test]
+    fn unbound_uses_base() {
+        let dir = tempdir::TempDir::new("vault_key").unwrap();
+        assert!(!is_bound(dir.path()));
+        assert_eq!(candidate_states(dir.path()), [false]);
+        assert_eq!(password("base", false).unwrap(), "base");
+    }
+
+    #[test]
+    fn journals_rebind() {
+        let dir = tempdir::TempDir::new("vault_key").unwrap();
+        begin_rebind(dir.path(), true).unwrap();
+        assert_eq!(candidate_states(dir.path()), [false, true]);
+        mark_bound(dir.path(), true).unwrap();
+        assert_eq!(candidate_states(dir.path()), [true]);
+
+        begin_rebind(dir.path(), false).unwrap();
+        assert_eq!(candidate_states(dir.path()), [true, false]);
+        abort_rebind(dir.path());
+        assert_eq!(candidate_states(dir.path()), [true]);
+    }
+}
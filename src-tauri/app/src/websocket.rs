@@ -0,0 +1,689 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use futures::{SinkExt, StreamExt};
+use models::*;
+use rand::{thread_rng, Rng};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::sync::{Mutex, Notify, RwLock};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{header, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::Connector;
+use url::Url;
+
+use crate::api::call_event::{ApiEvent, Response};
+use crate::api::{handle_request, network};
+use crate::commands::now_millis;
+use crate::connection::{self, Signal};
+use crate::errors::{Error, NativeError};
+use crate::settings::SettingsState;
+use crate::states::{ServerState, UserState};
+use crate::storage_handle::StorageHandle;
+use crate::{mentions, post_store, shutdown, unread};
+
+/// Server events are forwarded to frontend as they are
+pub const WEBSOCKET_EVENT: &str = "websocket-event";
+/// Posts of open channel created while WebSocket was down
+pub const POSTS_MISSED_EVENT: &str = "posts-missed";
+
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+/// Missed posts are fetched from a bit before last event, clocks of client
+/// and server are rarely in sync
+const CATCH_UP_MARGIN: Timestamp = 30_000;
+/// How often logged in user and selected server are checked, connection is
+/// reopened when either of them changes
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_QUEUED_ACTIONS: usize = 32;
+/// Typing indicator replayed later than this would show someone typing
+/// who already stopped
+const TYPING_EXPIRY: Duration = Duration::from_secs(5);
+const STATUS_EXPIRY: Duration = Duration::from_secs(60);
+/// Typing in the same channel or thread is published at most this often,
+/// other clients keep indicator on for a few seconds anyway
+const TYPING_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Action sent by client to server over WebSocket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", content = "data", rename_all = "snake_case")]
+pub enum WsAction {
+    UserTyping {
+        channel_id: ChannelId,
+        /// Root post id when typing in thread, empty otherwise
+        #[serde(default)]
+        parent_id: String,
+    },
+    #[serde(rename = "user_update_active_status")]
+    ActiveStatus { user_is_active: bool, manual: bool },
+}
+
+impl WsAction {
+    fn expiry(&self) -> Duration {
+        match self {
+            Self::UserTyping { .. } => TYPING_EXPIRY,
+            Self::ActiveStatus { .. } => STATUS_EXPIRY,
+        }
+    }
+
+    /// Only the latest state matters, older queued actions describing the
+    /// same thing are dropped
+    fn supersedes(&self, older: &Self) -> bool {
+        match (self, older) {
+            (
+                Self::UserTyping {
+                    channel_id,
+                    parent_id,
+                },
+                Self::UserTyping {
+                    channel_id: older_channel_id,
+                    parent_id: older_parent_id,
+                },
+            ) => channel_id == older_channel_id && parent_id == older_parent_id,
+            (Self::ActiveStatus { .. }, Self::ActiveStatus { .. }) => true,
+            _ => false,
+        }
+    }
+
+    fn to_message(&self, seq: u64) -> Message {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        value["seq"] = seq.into();
+        Message::Text(value.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PostsMissed {
+    pub channel_id: ChannelId,
+    pub since: Timestamp,
+    pub posts: PostThread,
+}
+
+/// Delay before next reconnect, doubled after each failed attempt
+#[derive(Debug, Default)]
+struct Backoff {
+    failures: u32,
+}
+
+impl Backoff {
+    fn next_delay(&mut self) -> Duration {
+        let delay = MIN_RECONNECT_DELAY
+            .saturating_mul(1 << self.failures.min(6))
+            .min(MAX_RECONNECT_DELAY);
+        self.failures = self.failures.saturating_add(1);
+        // Clients cut off by the same outage shouldn't come back all at once
+        let jitter = thread_rng().gen_range(0..=delay.as_millis() as u64 / 4);
+        delay + Duration::from_millis(jitter)
+    }
+
+    fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+/// Place in event stream of session, kept across reconnects so posts
+/// missed in between can be fetched
+#[derive(Debug, Default)]
+struct StreamPosition {
+    session: Option<Session>,
+    /// `seq` of last event, server numbers events of each connection from 0
+    last_seq: Option<u64>,
+    /// Local time of last event
+    last_event_at: Option<Timestamp>,
+}
+
+impl StreamPosition {
+    /// New connection was opened, returns time missed posts have to be
+    /// fetched from when session was connected before
+    fn connected(&mut self, session: &Session, now: Timestamp) -> Option<Timestamp> {
+        self.last_seq = None;
+        if self.session.as_ref() != Some(session) {
+            *self = Self {
+                session: Some(session.clone()),
+                last_seq: None,
+                last_event_at: Some(now),
+            };
+            return None;
+        }
+        self.last_event_at
+            .map(|at| at.saturating_sub(CATCH_UP_MARGIN))
+    }
+
+    /// Event was received, returns time missed posts have to be fetched
+    /// from when server skipped some events
+    fn received(&mut self, seq: Option<u64>, now: Timestamp) -> Option<Timestamp> {
+        let skipped = match (self.last_seq, seq) {
+            (Some(last), Some(seq)) => seq != last + 1,
+            _ => false,
+        };
+        let since = self
+            .last_event_at
+            .filter(|_| skipped)
+            .map(|at| at.saturating_sub(CATCH_UP_MARGIN));
+        if seq.is_some() {
+            self.last_seq = seq;
+        }
+        self.last_event_at = Some(now);
+        since
+    }
+}
+
+/// Last time typing was published, keyed by channel and root post
+#[derive(Debug, Default)]
+struct TypingThrottle(HashMap<(String, String), Instant>);
+
+impl TypingThrottle {
+    /// `false` when typing in the same place was published too recently
+    fn allow(&mut self, channel_id: &ChannelId, parent_id: &str, now: Instant) -> bool {
+        self.0
+            .retain(|_, sent_at| now.duration_since(*sent_at) < TYPING_INTERVAL);
+        let key = (channel_id.to_string(), parent_id.to_owned());
+        if self.0.contains_key(&key) {
+            return false;
+        }
+        self.0.insert(key, now);
+        true
+    }
+}
+
+#[derive(Debug)]
+struct Queued {
+    queued_at: Instant,
+    action: WsAction,
+}
+
+/// Bounded queue of actions waiting for connection, oldest are dropped
+/// when it's full
+#[derive(Debug, Default)]
+struct ActionQueue(VecDeque<Queued>);
+
+impl ActionQueue {
+    fn push(&mut self, action: WsAction, now: Instant) {
+        self.0.retain(|queued| !action.supersedes(&queued.action));
+        self.0.push_back(Queued {
+            queued_at: now,
+            action,
+        });
+        while self.0.len() > MAX_QUEUED_ACTIONS {
+            self.0.pop_front();
+        }
+    }
+
+    /// Remove all queued actions, expired ones are discarded
+    fn take_fresh(&mut self, now: Instant) -> Vec<Queued> {
+        self.0
+            .drain(..)
+            .filter(|queued| now.duration_since(queued.queued_at) < queued.action.expiry())
+            .collect()
+    }
+
+    /// Put back actions which couldn't be sent, unless something newer
+    /// replaced them in the meantime
+    fn requeue(&mut self, actions: Vec<Queued>) {
+        for queued in actions.into_iter().rev() {
+            if self
+                .0
+                .iter()
+                .any(|newer| newer.action.supersedes(&queued.action))
+            {
+                continue;
+            }
+            self.0.push_front(queued);
+        }
+        while self.0.len() > MAX_QUEUED_ACTIONS {
+            self.0.pop_front();
+        }
+    }
+}
+
+/// WebSocket connection to currently selected server.
+///
+/// Connection is kept open by background task while user is logged in,
+/// actions are queued and sent once it's available.
+#[derive(Default)]
+pub struct WebSocket {
+    queue: Mutex<ActionQueue>,
+    position: Mutex<StreamPosition>,
+    typing: Mutex<TypingThrottle>,
+    queued: Notify,
+    closed: Notify,
+}
+
+impl WebSocket {
+    pub async fn send(&self, action: WsAction) {
+        self.queue.lock().await.push(action, Instant::now());
+        self.queued.notify_one();
+    }
+
+    /// Let others know user is typing in channel, or in thread when
+    /// `parent_id` isn't empty. Returns `false` when it was throttled.
+    pub async fn send_typing(&self, channel_id: ChannelId, parent_id: String) -> bool {
+        let allowed = self
+            .typing
+            .lock()
+            .await
+            .allow(&channel_id, &parent_id, Instant::now());
+        if allowed {
+            self.send(WsAction::UserTyping {
+                channel_id,
+                parent_id,
+            })
+            .await;
+        }
+        allowed
+    }
+
+    /// Close connection for good, used on shutdown
+    pub fn close(&self) {
+        self.closed.notify_one();
+    }
+
+    /// Drop connection of session which just ended, actions queued for it
+    /// are discarded. Next session connects again.
+    pub async fn disconnect(&self) {
+        self.queue.lock().await.0.clear();
+        *self.position.lock().await = StreamPosition::default();
+        self.typing.lock().await.0.clear();
+        self.closed.notify_one();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Session {
+    server: Url,
+    token: AccessToken,
+}
+
+async fn current_session(app: &AppHandle) -> Option<Session> {
+    let token = app.state::<Mutex<UserState>>().lock().await.token.clone()?;
+    let server = app
+        .state::<Mutex<ServerState>>()
+        .lock()
+        .await
+        .current
+        .as_ref()?
+        .url
+        .clone();
+    Some(Session { server, token })
+}
+
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let websocket = app.state::<WebSocket>();
+        let mut backoff = Backoff::default();
+        while !shutdown::is_shutting_down() {
+            let Some(session) = current_session(&app).await else {
+                tokio::time::sleep(SESSION_CHECK_INTERVAL).await;
+                continue;
+            };
+            match run(&app, &websocket, &session, &mut backoff).await {
+                Ok(()) => continue,
+                Err(e) if !shutdown::is_shutting_down() => {
+                    tracing::warn!("WebSocket connection failed: {e}");
+                    connection::report(Signal::WebSocket { healthy: false });
+                }
+                Err(_) => break,
+            }
+            tokio::time::sleep(backoff.next_delay()).await;
+        }
+    });
+}
+
+/// Post carried by `posted`, `post_edited` and `post_deleted` events, server
+/// sends it as JSON in a string
+fn event_post(event: &serde_json::Value) -> Option<Post> {
+    match event.get("event")?.as_str()? {
+        "posted" | "post_edited" | "post_deleted" => {}
+        _ => return None,
+    }
+    let post = event.get("data")?.get("post")?.as_str()?;
+    match serde_json::from_str(post) {
+        Ok(post) => Some(post),
+        Err(e) => {
+            tracing::warn!("Malformed post in WebSocket event: {e}");
+            None
+        }
+    }
+}
+
+/// Keep posts of channels held in memory up to date with server
+async fn remember_posts(app: &AppHandle, server: &Url, posts: impl IntoIterator<Item = Post>) {
+    let server = ServerUrl::from(server.clone());
+    let memory = app.state::<SettingsState>().get().memory;
+    let mut evicted = Vec::new();
+    let user_state_mutex = app.state::<Mutex<UserState>>();
+    {
+        let mut user_state = user_state_mutex.lock().await;
+        for post in posts {
+            evicted.extend(user_state.posts.update(&server, post, memory));
+        }
+    }
+    let density = *app.state::<RwLock<PostDensity>>().read().await;
+    post_store::persist(
+        &app.state::<StorageHandle>(),
+        evicted,
+        density.retained_posts(),
+    );
+}
+
+/// Keep unread counts of current server in step with posts and reads
+/// server reports, badge follows
+async fn count_unreads(
+    app: &AppHandle,
+    server: &Url,
+    event: &serde_json::Value,
+    post: Option<&Post>,
+) {
+    let data = event.get("data");
+    let viewed: Vec<ChannelId> = match event.get("event").and_then(serde_json::Value::as_str) {
+        Some("posted") => Vec::new(),
+        Some("channel_viewed") => data
+            .and_then(|data| data.get("channel_id"))
+            .and_then(serde_json::Value::as_str)
+            .map(|channel_id| ChannelId::new(channel_id.to_owned()))
+            .into_iter()
+            .collect(),
+        Some("multiple_channels_viewed") => data
+            .and_then(|data| data.get("channel_times"))
+            .and_then(serde_json::Value::as_object)
+            .into_iter()
+            .flat_map(|times| times.keys())
+            .map(|channel_id| ChannelId::new(channel_id.clone()))
+            .collect(),
+        _ => return,
+    };
+    {
+        let user_state_mutex = app.state::<Mutex<UserState>>();
+        let mut user_state = user_state_mutex.lock().await;
+        if let Some(post) = post {
+            let mentioned = user_state
+                .id
+                .as_ref()
+                .is_some_and(|user_id| mentions::mentions_user(event, user_id));
+            user_state.count_post(post, mentioned);
+        }
+        for channel_id in &viewed {
+            user_state.mark_viewed(channel_id, now_millis());
+        }
+    }
+    unread::recount(app, server.clone().into()).await;
+}
+
+/// Fetch posts of open channels created since `since` and send them to
+/// frontend as [`POSTS_MISSED_EVENT`]
+fn catch_up(app: &AppHandle, since: Timestamp) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let Some(session) = current_session(&app).await else {
+            return;
+        };
+        let channels = app
+            .state::<Mutex<UserState>>()
+            .lock()
+            .await
+            .open_channels
+            .clone();
+        for channel_id in channels {
+            let event = ApiEvent::ChannelPostsSince {
+                channel_id: channel_id.clone(),
+                since,
+            };
+            let client = app.state::<Client>();
+            match handle_request(&client, &session.server, &event, Some(&session.token)).await {
+                Ok(Response::ChannelPosts(posts)) if !posts.order.is_empty() => {
+                    tracing::info!(
+                        "Recovered {} posts of channel {channel_id} missed by WebSocket",
+                        posts.order.len()
+                    );
+                    remember_posts(&app, &session.server, posts.posts.values().cloned()).await;
+                    let missed = PostsMissed {
+                        channel_id,
+                        since,
+                        posts,
+                    };
+                    app.emit_all(POSTS_MISSED_EVENT, missed).ok();
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Failed to fetch posts missed in {channel_id}: {e}"),
+            }
+        }
+    });
+}
+
+/// Keep connection open until it fails, session changes or shutdown
+/// closes it
+async fn run(
+    app: &AppHandle,
+    websocket: &WebSocket,
+    session: &Session,
+    backoff: &mut Backoff,
+) -> Result<(), Error> {
+    let mut url = session.server.join("api/v4/websocket")?;
+    let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+    // Only fails for URLs which can't have host, server URL always has one
+    url.set_scheme(scheme).ok();
+    let mut request = url.as_str().into_client_request()?;
+    let authorization = HeaderValue::from_str(&format!("Bearer {}", session.token))
+        .map_err(|_| NativeError::NotLoggedIn)?;
+    request
+        .headers_mut()
+        .insert(header::AUTHORIZATION, authorization);
+
+    let connector = network::tls_connector(&url).map(Connector::NativeTls);
+    let tunnel = network::connect(&url).await?;
+    let (stream, _) =
+        tokio_tungstenite::client_async_tls_with_config(request, tunnel, None, connector).await?;
+    tracing::info!("WebSocket connected to {}", session.server);
+    connection::report(Signal::WebSocket { healthy: true });
+    backoff.reset();
+    let since = websocket
+        .position
+        .lock()
+        .await
+        .connected(session, now_millis());
+    if let Some(since) = since {
+        catch_up(app, since);
+    }
+    let (mut sink, mut stream) = stream.split();
+    let mut seq = 1;
+    let mut session_check = tokio::time::interval(SESSION_CHECK_INTERVAL);
+
+    loop {
+        let mut pending = websocket.queue.lock().await.take_fresh(Instant::now());
+        while !pending.is_empty() {
+            let message = pending[0].action.to_message(seq);
+            if let Err(e) = sink.send(message).await {
+                websocket.queue.lock().await.requeue(pending);
+                return Err(e)?;
+            }
+            pending.remove(0);
+            seq += 1;
+        }
+
+        tokio::select! {
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    match serde_json::from_str::<serde_json::Value>(&text) {
+                        Ok(event) => {
+                            let seq = event.get("seq").and_then(serde_json::Value::as_u64);
+                            let skipped = websocket.position.lock().await.received(seq, now_millis());
+                            if let Some(since) = skipped {
+                                tracing::warn!("WebSocket skipped events before {seq:?}");
+                                catch_up(app, since);
+                            }
+                            let post = event_post(&event);
+                            count_unreads(app, &session.server, &event, post.as_ref()).await;
+                            if let Some(post) = post {
+                                mentions::receive(app, &event, &post).await;
+                                remember_posts(app, &session.server, [post]).await;
+                            }
+                            app.emit_all(WEBSOCKET_EVENT, event).ok();
+                        }
+                        Err(e) => tracing::warn!("Malformed WebSocket event: {e}"),
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => return Err(NativeError::WebSocketClosed)?,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e)?,
+            },
+            _ = websocket.queued.notified() => {}
+            _ = websocket.closed.notified() => {
+                sink.close().await.ok();
+                return Ok(());
+            }
+            _ = session_check.tick() => {
+                if current_session(app).await.as_ref() != Some(session) {
+                    tracing::info!("Session changed, closing WebSocket");
+                    sink.close().await.ok();
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    fn typing(channel: &str) -> WsAction {
+        WsAction::UserTyping {
+            channel_id: ChannelId::new(channel.to_owned()),
+            parent_id: String::new(),
+        }
+    }
+
+    fn actions(queued: Vec<Queued>) -> Vec<WsAction> {
+        queued.into_iter().map(|queued| queued.action).collect()
+    }
+
+    #[test]
+    fn stale_typing_is_discarded() {
+        let start = Instant::now();
+        let mut queue = ActionQueue::default();
+        queue.push(typing("a"), start);
+        let status = WsAction::ActiveStatus {
+            user_is_active: false,
+            manual: false,
+        };
+        queue.push(status.clone(), start);
+
+        let later = start + TYPING_EXPIRY + Duration::from_secs(1);
+        assert_eq!(actions(queue.take_fresh(later)), [status]);
+        assert!(queue.take_fresh(later).is_empty());
+    }
+
+    #[test]
+    fn newer_action_replaces_older() {
+        let now = Instant::now();
+        let mut queue = ActionQueue::default();
+        queue.push(typing("a"), now);
+        queue.push(typing("b"), now);
+        queue.push(typing("a"), now);
+        assert_eq!(actions(queue.take_fresh(now)), [typing("b"), typing("a")]);
+    }
+
+    #[test]
+    fn bounded_and_requeued_in_order() {
+        let now = Instant::now();
+        let mut queue = ActionQueue::default();
+        for n in 0..MAX_QUEUED_ACTIONS + 5 {
+            queue.push(typing(&n.to_string()), now);
+        }
+        let taken = queue.take_fresh(now);
+        assert_eq!(taken.len(), MAX_QUEUED_ACTIONS);
+        assert_eq!(taken[0].action, typing("5"));
+
+        queue.push(typing("6"), now);
+        queue.requeue(taken);
+        let requeued = actions(queue.take_fresh(now));
+        assert_eq!(requeued.len(), MAX_QUEUED_ACTIONS);
+        assert_eq!(requeued[0], typing("5"));
+        assert_eq!(requeued.last(), Some(&typing("6")));
+    }
+
+    #[test]
+    fn typing_is_throttled_per_thread() {
+        let start = Instant::now();
+        let channel = ChannelId::new("a".to_owned());
+        let mut throttle = TypingThrottle::default();
+        assert!(throttle.allow(&channel, "", start));
+        assert!(!throttle.allow(&channel, "", start + Duration::from_secs(1)));
+        assert!(throttle.allow(&channel, "root", start + Duration::from_secs(1)));
+        assert!(throttle.allow(&channel, "", start + TYPING_INTERVAL));
+    }
+
+    #[test]
+    fn backoff_grows_to_cap() {
+        let mut backoff = Backoff::default();
+        let delays: Vec<Duration> = (0..10).map(|_| backoff.next_delay()).collect();
+        assert!(delays[0] >= MIN_RECONNECT_DELAY && delays[0] <= MIN_RECONNECT_DELAY * 5 / 4);
+        assert!(delays[3] >= Duration::from_secs(8));
+        assert!(delays[9] >= MAX_RECONNECT_DELAY && delays[9] <= MAX_RECONNECT_DELAY * 5 / 4);
+        backoff.reset();
+        assert!(backoff.next_delay() <= MIN_RECONNECT_DELAY * 5 / 4);
+    }
+
+    #[test]
+    fn catches_up_after_gap() {
+        let session = Session {
+            server: Url::parse("https://mm.example.com").unwrap(),
+            token: AccessToken::new("token".to_owned()).unwrap(),
+        };
+        let mut position = StreamPosition::default();
+        assert_eq!(position.connected(&session, 100_000), None);
+        assert_eq!(position.received(Some(0), 101_000), None);
+        assert_eq!(position.received(Some(1), 102_000), None);
+        // Event 2 never arrived
+        assert_eq!(position.received(Some(3), 103_000), Some(72_000));
+
+        // Reconnect numbers events from 0 again
+        assert_eq!(position.connected(&session, 200_000), Some(73_000));
+        assert_eq!(position.received(Some(0), 201_000), None);
+
+        let other = Session {
+            token: AccessToken::new("other".to_owned()).unwrap(),
+            ..session
+        };
+        assert_eq!(position.connected(&other, 300_000), None);
+    }
+
+    #[test]
+    fn wire_format() {
+        let Message::Text(text) = typing("a").to_message(3) else {
+            panic!("expected text message");
+        };
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "action": "user_typing",
+                "seq": 3,
+                "data": { "channel_id": "a", "parent_id": "" },
+            })
+        );
+    }
+
+    #[test]
+    fn post_of_event() {
+        let post = serde_json::json!({
+            "id": "p1", "create_at": 1, "update_at": 2, "edit_at": 2, "delete_at": 0,
+            "user_id": "u1", "channel_id": "c1", "root_id": "", "original_id": "",
+            "message": "edited", "type": "", "file_ids": null, "pending_post_id": "",
+            "props": {},
+        });
+        let event = |name: &str| {
+            serde_json::json!({
+                "event": name,
+                "data": { "post": post.to_string(), "channel_display_name": "Town" },
+                "seq": 4,
+            })
+        };
+        let edited = event_post(&event("post_edited")).unwrap();
+        assert_eq!(edited.id.as_str(), "p1");
+        assert_eq!(edited.message.as_str(), "edited");
+        assert!(event_post(&event("reaction_added")).is_none());
+    }
+}
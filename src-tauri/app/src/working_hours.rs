@@ -0,0 +1,176 @@
+use chrono::{DateTime, Datelike, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use models::*;
+use serde::{Deserialize, Serialize};
+
+/// Heuristic describing when a person is expected to be at work.
+///
+/// Hours are expressed in the recipient's local time, `end_hour` is exclusive.
+/// Ranges crossing midnight (e.g. 22 - 6 for night shifts) are supported.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkingHours {
+    pub start_hour: u32,
+    pub end_hour: u32,
+    pub working_days: Vec<Weekday>,
+}
+
+impl Default for WorkingHours {
+    fn default() -> Self {
+        Self {
+            start_hour: 9,
+            end_hour: 17,
+            working_days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+        }
+    }
+}
+
+impl WorkingHours {
+    pub fn contains<T: Datelike + Timelike>(&self, time: &T) -> bool {
+        if !self.working_days.contains(&time.weekday()) {
+            return false;
+        }
+        let hour = time.hour();
+        if self.start_hour <= self.end_hour {
+            (self.start_hour..self.end_hour).contains(&hour)
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+}
+
+/// Current time of DM recipient, used to warn before pinging someone at night
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecipientLocalTime {
+    pub user_id: UserId,
+    pub display_name: String,
+    pub timezone: String,
+    /// RFC 3339 timestamp with recipient's UTC offset
+    pub local_time: String,
+    /// Wall clock time, e.g. `02:30`
+    pub clock: String,
+    pub within_working_hours: bool,
+}
+
+impl RecipientLocalTime {
+    /// Returns `None` when user has no timezone set or it's not a valid IANA
+    /// name
+    pub fn resolve(
+        user: &UserResponse,
+        now: DateTime<Utc>,
+        working_hours: &WorkingHours,
+    ) -> Option<Self> {
+        let timezone = user.timezone.as_ref()?.effective()?;
+        let tz = timezone.parse::<Tz>().ok()?;
+        let local = now.with_timezone(&tz);
+        Some(Self {
+            user_id: UserId::new(user.id.clone()),
+            display_name: user.display_name().to_owned(),
+            timezone: timezone.to_owned(),
+            local_time: local.to_rfc3339(),
+            clock: local.format("%H:%M").to_string(),
+            within_working_hours: working_hours.contains(&local),
+        })
+    }
+}
+
+/// Direct message channels are named `{user_id}__{user_id}`, returns the
+/// participant which is not the current user
+pub fn dm_recipient(channel_name: &str, me: &UserId) -> Option<UserId> {
+    let (first, second) = channel_name.split_once("__")?;
+    let other = if first == me.as_str() { second } else { first };
+    Some(UserId::new(other.to_owned()))
+}
+
+#[cfg(test)]
+mod check {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn user(timezone: &str) -> UserResponse {
+        UserResponse {
+            id: "maria".into(),
+            username: "maria.k".into(),
+            auth_data: String::new(),
+            auth_service: String::new(),
+            email: String::new(),
+            nickname: String::new(),
+            first_name: "Maria".into(),
+            last_name: String::new(),
+            position: String::new(),
+            roles: String::new(),
+            timezone: Some(Timezone {
+                automatic_timezone: String::new(),
+                manual_timezone: timezone.into(),
+                use_automatic_timezone: "false".into(),
+            }),
+            notify_props: UserNotifyProps::default(),
+        }
+    }
+
+    #[test]
+    fn night_for_recipient() {
+        // Wednesday 00:30 UTC is 02:30 in Warsaw (CEST)
+        let now = Utc.with_ymd_and_hms(2024, 5, 15, 0, 30, 0).unwrap();
+        let local =
+            RecipientLocalTime::resolve(&user("Europe/Warsaw"), now, &WorkingHours::default())
+                .unwrap();
+        assert_eq!(local.clock, "02:30");
+        assert_eq!(local.display_name, "Maria");
+        assert!(!local.within_working_hours);
+    }
+
+    #[test]
+    fn working_day() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 15, 12, 0, 0).unwrap();
+        let local =
+            RecipientLocalTime::resolve(&user("Europe/Warsaw"), now, &WorkingHours::default())
+                .unwrap();
+        assert!(local.within_working_hours);
+    }
+
+    #[test]
+    fn weekend_and_overnight() {
+        // Saturday noon
+        let now = Utc.with_ymd_and_hms(2024, 5, 18, 12, 0, 0).unwrap();
+        assert!(!WorkingHours::default().contains(&now));
+
+        let night_shift = WorkingHours {
+            start_hour: 22,
+            end_hour: 6,
+            ..WorkingHours::default()
+        };
+        let now = Utc.with_ymd_and_hms(2024, 5, 15, 3, 0, 0).unwrap();
+        assert!(night_shift.contains(&now));
+    }
+
+    #[test]
+    fn unknown_timezone() {
+        let now = Utc::now();
+        assert!(RecipientLocalTime::resolve(&user(""), now, &WorkingHours::default()).is_none());
+        assert!(
+            RecipientLocalTime::resolve(&user("Mars/Base"), now, &WorkingHours::default())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn recipient_of_dm() {
+        let me = UserId::new("me".to_owned());
+        assert_eq!(
+            dm_recipient("me__other", &me),
+            Some(UserId::new("other".to_owned()))
+        );
+        assert_eq!(
+            dm_recipient("other__me", &me),
+            Some(UserId::new("other".to_owned()))
+        );
+        assert_eq!(dm_recipient("town-square", &me), None);
+    }
+}
@@ -22,29 +22,32 @@ impl ServerUrl {
 pub struct Login(String);
 
 #[nutype(
-    derive(Debug, Clone, PartialEq, Serialize, Deserialize, Deref, TryFrom),
+    derive(Clone, PartialEq, Serialize, Deserialize, Deref, TryFrom),
     sanitize(trim),
     validate(not_empty)
 )]
 pub struct Pass(String);
 
+impl std::fmt::Debug for Pass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Pass(***)")
+    }
+}
+
 /// Non-empty, no-white character access token used to communicate with
 /// MatterMost server
 #[nutype(
-    derive(
-        Debug,
-        Display,
-        Clone,
-        PartialEq,
-        Serialize,
-        Deserialize,
-        Deref,
-        TryFrom
-    ),
+    derive(Display, Clone, PartialEq, Serialize, Deserialize, Deref, TryFrom),
     sanitize(trim),
     validate(not_empty)
 )]
 pub struct AccessToken(String);
+
+impl std::fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AccessToken(***)")
+    }
+}
 #[nutype(
     derive(
         Debug,
@@ -73,6 +76,488 @@ pub struct ServerCredentials {
     pub access_token: AccessToken,
 }
 
+/// Post composed while server was unreachable, kept in vault until delivered
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OutboxItem {
+    pub server: ServerUrl,
+    pub post: CreatePostRequest,
+    pub queued_at: Timestamp,
+}
+
+/// Post composed to be sent later, kept in vault until its time comes
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ScheduledPost {
+    pub server: ServerUrl,
+    /// Pending post id identifies scheduled post until it's sent
+    pub post: CreatePostRequest,
+    pub send_at: Timestamp,
+    pub scheduled_at: Timestamp,
+}
+
+/// Post saved locally to read later, independent of server side flags
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WatchLaterItem {
+    pub server: ServerUrl,
+    /// Snapshot of post so the queue can be displayed offline
+    pub post: Post,
+    pub added_at: Timestamp,
+}
+
+/// Local reminder about post, never synchronized with server
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Reminder {
+    pub server: ServerUrl,
+    /// Snapshot of post shown in notification
+    pub post: Post,
+    pub remind_at: Timestamp,
+    pub created_at: Timestamp,
+    /// Notification was shown, reminder stays until it's deleted or snoozed
+    #[serde(default)]
+    pub fired: bool,
+}
+
+impl WatchLaterItem {
+    pub fn is_same_post(&self, server: &ServerUrl, post_id: &PostId) -> bool {
+        &self.server == server && &self.post.id == post_id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum SigningAlgorithm {
+    HmacSha256,
+    HmacSha512,
+}
+
+/// Shared secret used to sign every request sent to server, required by some
+/// auditing proxies
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RequestSigning {
+    pub server: ServerUrl,
+    pub algorithm: SigningAlgorithm,
+    pub header_name: String,
+    pub secret: String,
+}
+
+impl std::fmt::Debug for RequestSigning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestSigning")
+            .field("server", &self.server)
+            .field("algorithm", &self.algorithm)
+            .field("header_name", &self.header_name)
+            .field("secret", &"***")
+            .finish()
+    }
+}
+
+/// Traffic exchanged with server during single day
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BandwidthUsage {
+    pub server: ServerUrl,
+    /// Local date, `YYYY-MM-DD`
+    pub day: String,
+    pub sent_bytes: u64,
+    pub received_bytes: u64,
+}
+
+/// Request budget of server as reported by its last response
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RateLimitStatus {
+    pub server: ServerUrl,
+    /// Requests allowed per window, `None` when server didn't say
+    pub limit: Option<u64>,
+    pub remaining: u64,
+    /// Milliseconds, when budget is replenished
+    pub reset_at: Timestamp,
+    /// Background sync waits for reset
+    pub throttled: bool,
+}
+
+/// State-changing command run on behalf of user. Only names and ids are
+/// recorded, never content of messages or settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Milliseconds
+    pub at: Timestamp,
+    pub command: String,
+    /// Server, channel or post command was run for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    pub outcome: AuditOutcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Succeeded,
+    Failed { reason: String },
+}
+
+/// Version and enabled features of server, probed when server is added
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ServerCapabilities {
+    /// e.g. `9.5.1`, empty when server doesn't report it
+    pub version: String,
+    pub build_number: String,
+    pub custom_emoji: bool,
+    pub file_uploads: bool,
+    /// Size limit of single uploaded file in bytes
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+    /// Lowercase extensions without dot, `None` when any file type is
+    /// accepted
+    #[serde(default)]
+    pub allowed_file_extensions: Option<Vec<String>>,
+    pub collapsed_threads: bool,
+    pub password_login: bool,
+    pub gitlab_login: bool,
+    pub saml_login: bool,
+}
+
+impl ServerCapabilities {
+    /// Unknown or malformed version is treated as the oldest one
+    pub fn version_at_least(&self, major: u32, minor: u32) -> bool {
+        let mut parts = self.version.split('.').map(|part| part.parse::<u32>());
+        match (parts.next(), parts.next()) {
+            (Some(Ok(found_major)), Some(Ok(found_minor))) => {
+                (found_major, found_minor) >= (major, minor)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Presence {
+    Online,
+    Away,
+    Dnd,
+    Offline,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserStatus {
+    pub user_id: UserId,
+    pub status: Presence,
+    /// Set by user rather than by activity
+    #[serde(default)]
+    pub manual: bool,
+    #[serde(default)]
+    pub last_activity_at: Timestamp,
+    /// Unix time in seconds when do not disturb ends, 0 when it doesn't
+    #[serde(default)]
+    pub dnd_end_time: Timestamp,
+}
+
+/// Limits of cloud workspace, none of them apply to self-hosted servers
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CloudLimits {
+    #[serde(default)]
+    pub files: Option<FilesLimits>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FilesLimits {
+    /// Bytes all uploaded files of workspace can take
+    #[serde(default)]
+    pub total_storage: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StorageUsage {
+    pub bytes_used: u64,
+}
+
+/// Page metadata read by server from link, fields page doesn't provide are
+/// empty
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpenGraph {
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub site_name: String,
+    /// Server sends `null` when page has no images
+    #[serde(default)]
+    pub images: Option<Vec<OpenGraphImage>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpenGraphImage {
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub secure_url: String,
+    #[serde(default)]
+    pub width: u32,
+    #[serde(default)]
+    pub height: u32,
+}
+
+/// How much channel history is fetched at once and kept around. Lower
+/// density saves memory at the cost of shorter scrollback.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostDensity {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl PostDensity {
+    /// Posts fetched per page
+    pub fn per_page(self) -> u32 {
+        match self {
+            Self::Low => 30,
+            Self::Medium => 60,
+            Self::High => 200,
+        }
+    }
+
+    /// Posts retained per channel, both in cache and by frontend
+    pub fn retained_posts(self) -> usize {
+        match self {
+            Self::Low => 150,
+            Self::Medium => 600,
+            Self::High => 2000,
+        }
+    }
+}
+
+/// Network environment of all servers, e.g. corporate proxy intercepting
+/// TLS
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkSettings {
+    /// `http://`, `https://` or `socks5://` URL, system proxy is used when
+    /// it's `None`
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// PEM of root certificate trusted on top of system ones
+    #[serde(default)]
+    pub ca_certificate: Option<String>,
+    /// Hosts whose certificates are not verified at all
+    #[serde(default)]
+    pub insecure_hosts: Vec<String>,
+}
+
+/// Seconds between background refreshes of active server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncIntervals {
+    /// Unread and mention counts of channels
+    pub unreads: u64,
+    /// Teams and channels lists
+    pub channels: u64,
+    /// Statuses of direct message partners
+    pub statuses: u64,
+}
+
+impl SyncIntervals {
+    /// Shorter intervals would only add load on server
+    pub const MIN_SECS: u64 = 15;
+
+    pub fn clamped(self) -> Self {
+        Self {
+            unreads: self.unreads.max(Self::MIN_SECS),
+            channels: self.channels.max(Self::MIN_SECS),
+            statuses: self.statuses.max(Self::MIN_SECS),
+        }
+    }
+}
+
+impl Default for SyncIntervals {
+    fn default() -> Self {
+        Self {
+            unreads: 60,
+            channels: 300,
+            statuses: 120,
+        }
+    }
+}
+
+/// Posts kept in memory while application runs, least recently opened
+/// channels are moved to disk cache beyond these
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemorySettings {
+    /// Newest posts kept of each channel
+    pub posts_per_channel: usize,
+    /// Channels whose posts are kept
+    pub channels: usize,
+}
+
+impl MemorySettings {
+    /// Fewer posts than that wouldn't fill a window
+    pub const MIN_POSTS_PER_CHANNEL: usize = 50;
+
+    pub fn clamped(self) -> Self {
+        Self {
+            posts_per_channel: self.posts_per_channel.max(Self::MIN_POSTS_PER_CHANNEL),
+            channels: self.channels.max(1),
+        }
+    }
+}
+
+impl Default for MemorySettings {
+    fn default() -> Self {
+        Self {
+            posts_per_channel: 200,
+            channels: 20,
+        }
+    }
+}
+
+/// Desktop notifications of new messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    pub sound: bool,
+    /// Flash taskbar entry until window is focused
+    pub flash_window: bool,
+    /// Show text of message, otherwise only its author
+    pub show_preview: bool,
+    pub dnd_schedule: DndSchedule,
+    /// Dock, taskbar or launcher badge counts only mentions instead of
+    /// unread channels
+    pub badge_mentions_only: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sound: true,
+            flash_window: true,
+            show_preview: true,
+            dnd_schedule: DndSchedule::default(),
+            badge_mentions_only: false,
+        }
+    }
+}
+
+/// Do not disturb turned on automatically outside working time, times are
+/// local
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DndSchedule {
+    pub enabled: bool,
+    /// Minutes after midnight, window continues over midnight when `end`
+    /// is earlier. Equal `start` and `end` leave only weekends.
+    pub start: u16,
+    pub end: u16,
+    /// Whole Saturday and Sunday
+    pub weekends: bool,
+}
+
+impl DndSchedule {
+    pub const MINUTES_PER_DAY: u16 = 24 * 60;
+
+    pub fn clamped(self) -> Self {
+        Self {
+            start: self.start % Self::MINUTES_PER_DAY,
+            end: self.end % Self::MINUTES_PER_DAY,
+            ..self
+        }
+    }
+}
+
+impl Default for DndSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: 18 * 60,
+            end: 9 * 60,
+            weekends: true,
+        }
+    }
+}
+
+/// Light or dark appearance of application, unlike [`Theme`] it's not synced
+/// with server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorScheme {
+    /// Follow light or dark mode of OS
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+/// What happens when application is started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StartupSettings {
+    /// Registered with login items of OS
+    pub launch_at_login: bool,
+    /// Start hidden, e.g. when launched at login
+    pub start_minimized: bool,
+    /// Open channel which was open when application was closed
+    pub restore_last_channel: bool,
+    /// Open developer tools with the page, debug builds always do
+    pub open_devtools: bool,
+}
+
+impl Default for StartupSettings {
+    fn default() -> Self {
+        Self {
+            launch_at_login: false,
+            start_minimized: false,
+            restore_last_channel: true,
+            open_devtools: false,
+        }
+    }
+}
+
+/// Spellcheck of message composer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpellcheckSettings {
+    pub enabled: bool,
+    /// Dictionaries like `en_US`, language of OS when empty
+    pub languages: Vec<String>,
+}
+
+impl Default for SpellcheckSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            languages: Vec::new(),
+        }
+    }
+}
+
+/// System-wide hotkey bringing application to front
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShortcutSettings {
+    /// E.g. `CmdOrCtrl+Shift+Space`, no shortcut when `None`
+    pub accelerator: Option<String>,
+    /// Open channel switcher once window is shown
+    pub open_switcher: bool,
+}
+
+/// Application configuration shared by all servers
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub notifications: NotificationSettings,
+    pub theme: ColorScheme,
+    pub startup: StartupSettings,
+    pub spellcheck: SpellcheckSettings,
+    pub shortcut: ShortcutSettings,
+    pub sync_intervals: SyncIntervals,
+    pub network: NetworkSettings,
+    pub memory: MemorySettings,
+    /// Language of messages application produces, like `de` or `pt-BR`,
+    /// language of OS when `None`
+    pub locale: Option<String>,
+}
+
 pub type Timestamp = u64;
 pub type FileDimension = usize;
 
@@ -109,6 +594,9 @@ pub struct EmojiName(String);
 #[nutype(derive(Debug, Display, Clone, PartialEq, Serialize, Deserialize, Deref, From))]
 pub struct EmojiId(String);
 
+#[nutype(derive(Debug, Display, Clone, PartialEq, Serialize, Deserialize, Deref, From))]
+pub struct CategoryId(String);
+
 #[nutype(derive(Debug, Display, Clone, PartialEq, Serialize, Deserialize, Deref, From))]
 pub struct EmbedUrl(Url);
 
@@ -167,16 +655,24 @@ pub struct FileSize(FileDimension);
 pub struct MetaEmbed {
     #[serde(rename = "type")]
     pub embed_type: EmbedType,
-    pub url: EmbedUrl,
+    /// Missing for message attachment embeds of bots and webhooks
+    #[serde(default)]
+    pub url: Option<EmbedUrl>,
+    /// Missing for plain image and message attachment embeds
+    #[serde(default)]
     pub data: serde_json::Value,
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct MetaReaction {
-    #[serde(rename = "type")]
-    pub embed_type: EmbedType,
-    pub url: EmbedUrl,
-    pub data: serde_json::Value,
+    pub user_id: UserId,
+    pub post_id: PostId,
+    pub emoji_name: EmojiName,
+    pub create_at: Timestamp,
+    #[serde(default)]
+    pub update_at: Timestamp,
+    #[serde(default)]
+    pub delete_at: Timestamp,
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -184,7 +680,9 @@ pub struct MetaEmoji {
     pub id: EmojiId,
     pub creator_id: UserId,
     pub name: EmojiName,
+    #[serde(default)]
     pub update_at: Timestamp,
+    #[serde(default)]
     pub delete_at: Timestamp,
     pub create_at: Timestamp,
 }
@@ -194,22 +692,69 @@ pub struct MetaFile {
     pub id: FileId,
     pub user_id: UserId,
     pub post_id: PostId,
+    #[serde(default)]
     pub update_at: Timestamp,
+    #[serde(default)]
     pub delete_at: Timestamp,
     pub create_at: Timestamp,
     pub name: FileName,
     pub extension: FileExt,
     pub size: FileSize,
+    #[serde(default)]
     pub width: Option<FileWidth>,
+    #[serde(default)]
     pub height: Option<FileHeight>,
     pub mime_type: MimeType,
+    #[serde(default)]
     pub has_preview_image: bool,
 }
 
+/// State of channel kept by Calls plugin
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CallChannelState {
+    pub channel_id: ChannelId,
+    /// `None` when channel follows default of server
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    /// Call running in channel
+    #[serde(default)]
+    pub call: Option<CallInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CallInfo {
+    pub id: String,
+    pub start_at: Timestamp,
+    pub owner_id: UserId,
+    #[serde(default)]
+    pub thread_id: Option<PostId>,
+    #[serde(default)]
+    pub sessions: Vec<CallSession>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CallSession {
+    pub session_id: String,
+    pub user_id: UserId,
+}
+
+/// Files stored by server, posts refer to them by id
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FileUploadResponse {
+    pub file_infos: Vec<MetaFile>,
+    #[serde(default)]
+    pub client_ids: Vec<String>,
+}
+
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct MetaPriority {
+    /// `important` or `urgent`, empty for standard priority
+    #[serde(default)]
     pub priority: String,
+    #[serde(default)]
     pub requested_ack: bool,
+    #[serde(default)]
+    pub persistent_notifications: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -219,14 +764,33 @@ pub struct MetaAcknowledgement {
     pub acknowledged_at: Option<Timestamp>,
 }
 
+/// Dimensions of image linked from post
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MetaImage {
+    #[serde(default)]
+    pub width: FileDimension,
+    #[serde(default)]
+    pub height: FileDimension,
+    #[serde(default)]
+    pub format: String,
+    /// More than one for animated images
+    #[serde(default)]
+    pub frame_count: u32,
+}
+
+/// Data server attaches to post. Every part is left out by server when it's
+/// empty and older servers don't know some of them.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct PostMetadata {
     pub embeds: Vec<MetaEmbed>,
     pub emojis: Vec<MetaEmoji>,
     pub files: Vec<MetaFile>,
     pub reactions: Vec<MetaReaction>,
-    pub priorities: Vec<MetaPriority>,
-    pub acknowledges: Vec<MetaAcknowledgement>,
+    /// Keyed by image URL
+    pub images: HashMap<String, MetaImage>,
+    pub priority: Option<MetaPriority>,
+    pub acknowledgements: Vec<MetaAcknowledgement>,
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -243,27 +807,143 @@ pub struct Post {
     pub message: Message,
     #[serde(rename = "type")]
     pub post_type: PostType,
+    /// Sent by server as `hashtags`
+    #[serde(default, alias = "hashtags")]
     pub hashtag: Option<HashTag>,
     pub file_ids: Option<Vec<FileId>>,
     pub pending_post_id: PostId,
     pub props: serde_json::Value,
-    pub metadata: Option<MetaAcknowledgement>,
+    #[serde(default)]
+    pub metadata: Option<PostMetadata>,
+    #[serde(default)]
+    pub is_pinned: bool,
+    /// Parsed `message`, filled in by app before posts reach frontend
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_ast: Option<Vec<MarkdownBlock>>,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Block of Mattermost-flavored Markdown message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MarkdownBlock {
+    Paragraph {
+        inlines: Vec<MarkdownInline>,
+    },
+    Heading {
+        level: u8,
+        inlines: Vec<MarkdownInline>,
+    },
+    CodeBlock {
+        language: Option<String>,
+        code: String,
+    },
+    BlockQuote {
+        blocks: Vec<MarkdownBlock>,
+    },
+    List {
+        /// Number of first item of ordered list
+        start: Option<u64>,
+        items: Vec<Vec<MarkdownBlock>>,
+    },
+    Table {
+        alignments: Vec<TableAlignment>,
+        header: Vec<Vec<MarkdownInline>>,
+        rows: Vec<Vec<Vec<MarkdownInline>>>,
+    },
+    ThematicBreak,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MarkdownInline {
+    Text {
+        text: String,
+    },
+    Code {
+        code: String,
+    },
+    Emphasis {
+        inlines: Vec<MarkdownInline>,
+    },
+    Strong {
+        inlines: Vec<MarkdownInline>,
+    },
+    Strikethrough {
+        inlines: Vec<MarkdownInline>,
+    },
+    Link {
+        url: String,
+        inlines: Vec<MarkdownInline>,
+    },
+    Image {
+        url: String,
+        alt: String,
+    },
+    /// `@username`, also `@here`, `@channel` and `@all`
+    Mention {
+        username: String,
+    },
+    /// `~channel-name`
+    ChannelLink {
+        name: String,
+    },
+    /// `:emoji_name:`
+    Emoji {
+        name: String,
+    },
+    LineBreak,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TableAlignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct PostThread {
     pub order: Vec<PostId>,
     pub posts: HashMap<String, Post>,
     pub next_post_id: Option<PostId>,
     pub prev_post_id: Option<PostId>,
+    #[serde(default)]
     pub has_next: bool,
 }
 
+/// Posts of "Pinned" or "Saved posts" panel, newest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PostsPanel {
+    pub posts: Vec<Post>,
+    /// More posts can be loaded with next page
+    pub has_more: bool,
+}
+
+/// Collapsed reply thread followed by user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserThread {
+    pub id: PostId,
+    pub reply_count: i64,
+    pub last_reply_at: Timestamp,
+    pub last_viewed_at: Timestamp,
+    #[serde(default)]
+    pub unread_replies: i64,
+    #[serde(default)]
+    pub unread_mentions: i64,
+    #[serde(default)]
+    pub is_urgent: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Channel {
     pub id: Option<ChannelId>,
+    #[serde(default)]
     pub create_at: Timestamp,
+    #[serde(default)]
     pub update_at: Timestamp,
+    #[serde(default)]
     pub delete_at: Timestamp,
     pub team_id: Option<String>,
     #[serde(rename = "type")]
@@ -272,8 +952,11 @@ pub struct Channel {
     pub name: Option<ChannelName>,
     pub header: Option<ChannelHeader>,
     pub purpose: Option<ChannelPurpose>,
+    #[serde(default)]
     pub last_post_at: Timestamp,
+    #[serde(default)]
     pub total_msg_count: i64,
+    #[serde(default)]
     pub extra_update_at: Timestamp,
     pub creator_id: Option<UserId>,
     pub scheme_id: Option<SchemaId>,
@@ -283,13 +966,51 @@ pub struct Channel {
     pub last_root_post_at: Option<Timestamp>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateChannelRequest {
+    pub team_id: TeamId,
+    pub name: ChannelName,
+    pub display_name: ChannelDisplayName,
+    /// `O` for public channel, `P` for private one
+    #[serde(rename = "type")]
+    pub r#type: ChannelType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<ChannelPurpose>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<ChannelHeader>,
+}
+
+/// Changed fields of channel, fields left `None` keep their value
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChannelPatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<ChannelDisplayName>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<ChannelHeader>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<ChannelPurpose>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreatePostRequest {
+    pub channel_id: ChannelId,
+    pub message: Message,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root_id: Option<PostId>,
+    /// Client generated id, `{user_id}:{timestamp}`, used to match queued
+    /// post with the one created by server
+    pub pending_post_id: PostId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub props: Option<serde_json::Value>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginRequest {
     pub login_id: Login,
     pub password: Pass,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Timezone {
     #[serde(rename(serialize = "automaticTimezone", deserialize = "automaticTimezone"))]
     pub automatic_timezone: String,
@@ -302,18 +1023,72 @@ pub struct Timezone {
     pub use_automatic_timezone: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Timezone {
+    /// IANA name of the timezone user has selected, either detected
+    /// automatically by the client or picked manually in profile settings
+    pub fn effective(&self) -> Option<&str> {
+        let name = if self.use_automatic_timezone == "true" {
+            &self.automatic_timezone
+        } else {
+            &self.manual_timezone
+        };
+        Some(name.as_str()).filter(|name| !name.is_empty())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct UserResponse {
     pub id: String,
     pub username: String,
+    #[serde(default)]
     pub auth_data: String,
+    #[serde(default)]
     pub auth_service: String,
+    #[serde(default)]
     pub email: String,
+    #[serde(default)]
     pub nickname: String,
+    #[serde(default)]
     pub first_name: String,
+    #[serde(default)]
     pub last_name: String,
+    #[serde(default)]
     pub position: String,
     pub roles: String,
+    #[serde(default)]
+    pub timezone: Option<Timezone>,
+    #[serde(default)]
+    pub notify_props: UserNotifyProps,
+}
+
+/// Notification settings of account, only sent for current user
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UserNotifyProps {
+    /// Words besides username which mention user, comma separated
+    #[serde(default)]
+    pub mention_keys: String,
+    /// `"true"` when first name mentions user
+    #[serde(default)]
+    pub first_name: String,
+}
+
+/// Users matching `@mention` typed in composer
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UserAutocomplete {
+    pub users: Vec<UserResponse>,
+    /// Matching users which are not members of channel
+    #[serde(default)]
+    pub out_of_channel: Vec<UserResponse>,
+}
+
+impl UserResponse {
+    /// Name shown to other users: nickname, first name or username
+    pub fn display_name(&self) -> &str {
+        [&self.nickname, &self.first_name]
+            .into_iter()
+            .find(|name| !name.is_empty())
+            .unwrap_or(&self.username)
+    }
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -337,10 +1112,15 @@ pub struct TeamMember {
     pub team_id: String,
     pub user_id: String,
     pub roles: String,
+    #[serde(default)]
     pub delete_at: Timestamp,
+    #[serde(default)]
     pub scheme_guest: bool,
+    #[serde(default)]
     pub scheme_user: bool,
+    #[serde(default)]
     pub scheme_admin: bool,
+    #[serde(default)]
     pub explicit_roles: String,
 }
 
@@ -362,14 +1142,269 @@ pub struct User {
     explicit_roles: String,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SidebarCategoryType {
+    Favorites,
+    Channels,
+    DirectMessages,
+    Custom,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CategorySorting {
+    /// Server picks sorting depending on category type
+    #[default]
+    #[serde(rename = "")]
+    Default,
+    Alpha,
+    Recent,
+    Manual,
+}
+
+/// Group of channels shown in sidebar, channel ids are in display order when
+/// sorting is manual
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SidebarCategory {
+    pub id: CategoryId,
+    pub user_id: UserId,
+    pub team_id: TeamId,
+    #[serde(default)]
+    pub sort_order: i64,
+    #[serde(default)]
+    pub sorting: CategorySorting,
+    #[serde(rename = "type")]
+    pub category_type: SidebarCategoryType,
+    pub display_name: String,
+    #[serde(default)]
+    pub muted: bool,
+    #[serde(default)]
+    pub collapsed: bool,
+    pub channel_ids: Vec<ChannelId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SidebarCategories {
+    pub categories: Vec<SidebarCategory>,
+    pub order: Vec<CategoryId>,
+}
+
+impl SidebarCategories {
+    /// Categories in sidebar order, those missing in `order` go last
+    pub fn into_ordered(self) -> Vec<SidebarCategory> {
+        let Self {
+            mut categories,
+            order,
+        } = self;
+        categories.sort_by_key(|category| {
+            order
+                .iter()
+                .position(|id| id == &category.id)
+                .unwrap_or(order.len())
+        });
+        categories
+    }
+}
+
+/// Membership of user in channel
 #[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChannelMember {
+    pub channel_id: ChannelId,
+    pub user_id: UserId,
+    #[serde(default)]
+    pub roles: String,
+    pub last_viewed_at: Timestamp,
+    #[serde(default)]
+    pub msg_count: i64,
+    #[serde(default)]
+    pub mention_count: i64,
+    pub notify_props: NotifyProps,
+    #[serde(default)]
+    pub last_update_at: Timestamp,
+    #[serde(default)]
+    pub scheme_guest: bool,
+    #[serde(default)]
+    pub scheme_user: bool,
+    #[serde(default)]
+    pub scheme_admin: bool,
+    #[serde(default)]
+    pub explicit_roles: String,
+}
+
+/// Answer of server to channel being viewed
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ChannelViewed {
+    /// New `last_viewed_at` of viewed channel, keyed by channel id
+    #[serde(default)]
+    pub last_viewed_at_times: HashMap<String, Timestamp>,
+}
+
+/// Where channel is scrolled to when it's opened again
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ScrollAnchor {
+    /// Oldest post created after channel was last viewed, "New messages"
+    /// divider goes above it. `None` when there's nothing new.
+    pub first_unread: Option<PostId>,
+    /// Post user had in view when they left channel on this machine
+    pub last_viewed_post: Option<PostId>,
+    /// Later of local and server time channel was viewed at
+    pub last_viewed_at: Timestamp,
+}
+
+/// Post in view when channel was left, kept locally
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LastViewed {
+    pub post_id: PostId,
+    pub viewed_at: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChannelStats {
+    pub channel_id: ChannelId,
+    pub member_count: i64,
+    #[serde(default)]
+    pub guest_count: i64,
+    #[serde(default)]
+    pub pinnedpost_count: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct NotifyProps {
-    channel_auto_follow_threads: Option<String>,
-    desktop: Option<String>,
-    email: Option<String>,
-    ignore_channel_mentions: Option<String>,
-    mark_unread: Option<String>,
-    push: Option<String>,
+    pub channel_auto_follow_threads: Option<String>,
+    pub desktop: Option<String>,
+    pub email: Option<String>,
+    pub ignore_channel_mentions: Option<String>,
+    pub mark_unread: Option<String>,
+    pub push: Option<String>,
+}
+
+impl NotifyProps {
+    /// Muted channels are marked unread only on mention
+    pub fn is_muted(&self) -> bool {
+        self.mark_unread.as_deref() == Some("mention")
+    }
+
+    /// Desktop notification is shown for new message, `mentioned` when it
+    /// mentions user. `default` follows account setting, which is mentions
+    /// only unless user changed it in another client.
+    pub fn notifies_desktop(&self, mentioned: bool) -> bool {
+        if self.is_muted() {
+            return false;
+        }
+        match self.desktop.as_deref() {
+            Some("all") => true,
+            Some("none") => false,
+            _ => mentioned,
+        }
+    }
+
+    pub fn apply(&mut self, patch: &NotifyPropsPatch) {
+        let fields = [
+            (&mut self.desktop, &patch.desktop),
+            (&mut self.email, &patch.email),
+            (&mut self.push, &patch.push),
+            (&mut self.mark_unread, &patch.mark_unread),
+            (
+                &mut self.ignore_channel_mentions,
+                &patch.ignore_channel_mentions,
+            ),
+        ];
+        for (field, changed) in fields {
+            if let Some(value) = changed {
+                *field = Some(value.clone());
+            }
+        }
+    }
+}
+
+/// Changed notification props of channel membership, fields left `None`
+/// keep their value. Values are the ones server uses: `default`, `all`,
+/// `mention` or `none`, `mark_unread` is `all` or `mention`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct NotifyPropsPatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desktop: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub push: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mark_unread: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignore_channel_mentions: Option<String>,
+}
+
+impl NotifyPropsPatch {
+    pub fn muted(muted: bool) -> Self {
+        Self {
+            mark_unread: Some(if muted { "mention" } else { "all" }.to_owned()),
+            ..Self::default()
+        }
+    }
+}
+
+/// Single user preference as stored by server, `value` is always a string
+/// even for booleans and JSON documents
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Preference {
+    pub user_id: UserId,
+    pub category: String,
+    pub name: String,
+    pub value: String,
+}
+
+/// Preferences relevant for this client, decoded from [`Preference`] list
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Preferences {
+    pub favorite_channels: Vec<ChannelId>,
+    /// Derived from channel membership notify props, not stored as
+    /// preference
+    pub muted_channels: Vec<ChannelId>,
+    pub display: DisplaySettings,
+    pub theme: Option<Theme>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DisplaySettings {
+    pub use_military_time: bool,
+    /// `clean` or `compact`
+    pub message_display: String,
+    pub collapse_previews: bool,
+    pub colorize_usernames: bool,
+    /// `username`, `nickname_full_name` or `full_name`
+    pub name_format: String,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            use_military_time: false,
+            message_display: "clean".to_owned(),
+            collapse_previews: false,
+            colorize_usernames: true,
+            name_format: "username".to_owned(),
+        }
+    }
+}
+
+/// Web app theme, colors are CSS color strings
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Theme {
+    #[serde(rename = "type", default)]
+    pub name: Option<String>,
+    pub sidebar_bg: String,
+    pub sidebar_text: String,
+    pub center_channel_bg: String,
+    pub center_channel_color: String,
+    pub link_color: String,
+    pub button_bg: String,
+    pub button_color: String,
+    /// Remaining colors which are not used by this client but must survive
+    /// round trip to server
+    #[serde(flatten)]
+    pub other: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, thiserror::Error)]
@@ -385,3 +1420,156 @@ impl std::fmt::Display for ServerApiError {
         formatter.write_str(&serde_json::to_string(self).unwrap())
     }
 }
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    /// Post of Mattermost 9.x with every kind of metadata
+    const RECENT_SERVER_POST: &str = r#"{
+        "id": "7qnwu1w1ojgjzpmgjhcb1r1rrh",
+        "create_at": 1714125421000,
+        "update_at": 1714125477000,
+        "edit_at": 0,
+        "delete_at": 0,
+        "is_pinned": false,
+        "user_id": "ex7hmfcgc7rubq1r3yb7ozfqpc",
+        "channel_id": "4yx3hbxi6fbcfy4w1zo4unybwa",
+        "root_id": "",
+        "original_id": "",
+        "message": "Release notes https://mattermost.com/blog :party_parrot:",
+        "type": "",
+        "props": {"disable_group_highlight": true},
+        "hashtags": "",
+        "pending_post_id": "",
+        "reply_count": 0,
+        "last_reply_at": 0,
+        "participants": null,
+        "file_ids": ["pqosgbhqu3ft7xfqhgi3x9suyw"],
+        "metadata": {
+            "embeds": [{
+                "type": "opengraph",
+                "url": "https://mattermost.com/blog",
+                "data": {"type": "website", "url": "https://mattermost.com/blog", "title": "Blog"}
+            }],
+            "emojis": [{
+                "id": "u1uh9u5tb7ddbc1qyiyphmdnjo",
+                "create_at": 1700000000000,
+                "update_at": 1700000000000,
+                "delete_at": 0,
+                "creator_id": "ex7hmfcgc7rubq1r3yb7ozfqpc",
+                "name": "party_parrot"
+            }],
+            "files": [{
+                "id": "pqosgbhqu3ft7xfqhgi3x9suyw",
+                "user_id": "ex7hmfcgc7rubq1r3yb7ozfqpc",
+                "post_id": "7qnwu1w1ojgjzpmgjhcb1r1rrh",
+                "channel_id": "4yx3hbxi6fbcfy4w1zo4unybwa",
+                "create_at": 1714125420000,
+                "update_at": 1714125420000,
+                "delete_at": 0,
+                "name": "screenshot.png",
+                "extension": "png",
+                "size": 48213,
+                "mime_type": "image/png",
+                "width": 1280,
+                "height": 720,
+                "has_preview_image": true,
+                "mini_preview": "/9j/2wCEAAMCAgMCAgMDAwMEAwMEBQgFBQQEBQoHBwYIDA",
+                "remote_id": "",
+                "archived": false
+            }],
+            "reactions": [{
+                "user_id": "tkjqbbtnnfbdxr7fhyrbdecfrc",
+                "post_id": "7qnwu1w1ojgjzpmgjhcb1r1rrh",
+                "emoji_name": "+1",
+                "create_at": 1714125477000,
+                "update_at": 1714125477000,
+                "delete_at": 0,
+                "remote_id": "",
+                "channel_id": "4yx3hbxi6fbcfy4w1zo4unybwa"
+            }],
+            "images": {
+                "https://mattermost.com/og.png": {"width": 1200, "height": 630, "format": "png", "frame_count": 0}
+            },
+            "priority": {"priority": "important", "requested_ack": true},
+            "acknowledgements": [{
+                "user_id": "tkjqbbtnnfbdxr7fhyrbdecfrc",
+                "post_id": "7qnwu1w1ojgjzpmgjhcb1r1rrh",
+                "acknowledged_at": 1714125500000
+            }]
+        }
+    }"#;
+
+    #[test]
+    fn full_post_metadata() {
+        let post: Post = serde_json::from_str(RECENT_SERVER_POST).unwrap();
+        let metadata = post.metadata.unwrap();
+        assert_eq!(metadata.embeds.len(), 1);
+        assert_eq!(metadata.emojis[0].name.to_string(), "party_parrot");
+        assert_eq!(metadata.files[0].name.to_string(), "screenshot.png");
+        assert!(metadata.files[0].has_preview_image);
+        assert_eq!(metadata.reactions[0].emoji_name.to_string(), "+1");
+        assert_eq!(metadata.images["https://mattermost.com/og.png"].width, 1200);
+        let priority = metadata.priority.unwrap();
+        assert_eq!(priority.priority, "important");
+        assert!(priority.requested_ack);
+        assert!(!priority.persistent_notifications);
+        assert_eq!(
+            metadata.acknowledgements[0].acknowledged_at,
+            Some(1714125500000)
+        );
+    }
+
+    #[test]
+    fn sparse_post_metadata() {
+        let mut post: serde_json::Value = serde_json::from_str(RECENT_SERVER_POST).unwrap();
+
+        // Servers leave out empty parts, 5.x knows only embeds and files
+        // and sends image embeds without data
+        post["metadata"] = serde_json::json!({
+            "embeds": [{"type": "image", "url": "https://example.com/cat.gif"}]
+        });
+        let parsed: Post = serde_json::from_value(post.clone()).unwrap();
+        let metadata = parsed.metadata.unwrap();
+        assert_eq!(metadata.embeds[0].data, serde_json::Value::Null);
+        assert!(metadata.files.is_empty());
+        assert!(metadata.images.is_empty());
+        assert_eq!(metadata.priority, None);
+
+        // Attachments of bots and webhooks have no URL
+        post["metadata"] = serde_json::json!({
+            "embeds": [{"type": "message_attachment"}]
+        });
+        let parsed: Post = serde_json::from_value(post.clone()).unwrap();
+        let embed = &parsed.metadata.unwrap().embeds[0];
+        assert_eq!(embed.embed_type.as_str(), "message_attachment");
+        assert_eq!(embed.url, None);
+
+        post["metadata"] = serde_json::json!({});
+        let parsed: Post = serde_json::from_value(post.clone()).unwrap();
+        assert_eq!(parsed.metadata, Some(PostMetadata::default()));
+
+        post.as_object_mut().unwrap().remove("metadata");
+        let parsed: Post = serde_json::from_value(post).unwrap();
+        assert_eq!(parsed.metadata, None);
+    }
+
+    #[test]
+    fn channel_notify_props() {
+        let mut props = NotifyProps::default();
+        assert!(props.notifies_desktop(true));
+        assert!(!props.notifies_desktop(false));
+
+        props.apply(&NotifyPropsPatch {
+            desktop: Some("all".to_owned()),
+            ..NotifyPropsPatch::default()
+        });
+        assert!(props.notifies_desktop(false));
+
+        props.apply(&NotifyPropsPatch::muted(true));
+        assert_eq!(props.desktop.as_deref(), Some("all"));
+        assert!(props.is_muted());
+        assert!(!props.notifies_desktop(true));
+    }
+}
@@ -125,7 +125,9 @@ mod trans;
 mod version;
 mod volume;
 
-pub use self::base::crypto::{Cipher, MemLimit, OpsLimit};
+pub use self::base::crypto::{
+    Cipher, Cost, Crypto, MemLimit, OpsLimit, Salt, SALT_SIZE,
+};
 pub use self::base::{init_env, zbox_version};
 pub use self::error::{Error, Result};
 pub use self::file::{File, VersionReader};
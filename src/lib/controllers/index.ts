@@ -12,8 +12,8 @@ import type { PostThread } from '$lib/types/post.thread.model';
 type CommandCallback<T> = Promise<Either<ApiErrorModel | string, T>>
 
 const parse_error = (error: ApiErrorModel | undefined): ApiErrorModel | string => {
-	if (error?.message) {
-		return JSON.parse(`${error}`) as ApiErrorModel;
+	if (error?.kind) {
+		return error;
 	}
 	return `${error}`;
 };
@@ -1,6 +1,10 @@
 export type ApiErrorModel = {
-	id: string,
-	message: string, // the reason for the error
-	request_id: string, // the ID of the request
-	status_code: number, // the HTTP status code
+	kind: string, // stable identifier of the error, e.g. `network` or `permission_denied`
+	key: string, // catalog key of the message, e.g. `error-permission-denied`
+	params: Record<string, string | number>, // values of `{ $param }` placeholders in the message of `key`
+	message: string, // the reason for the error, translated when the current locale has the key
+	status_code: number | null, // the HTTP status code, null when error didn't come from server
+	request_id: string | null, // the ID of the request
+	error_id: string | null, // the ID of the server's error message, e.g. `api.context.permissions.app_error`
+	retry_after: number | null, // seconds to wait before retrying a rate limited request
 }
@@ -5,4 +5,7 @@ export type NotifyPropsModel = {
 	ignore_channel_mentions: string,
 	mark_unread: string,
 	push: string,
-}
\ No newline at end of file
+}
+/** Changed props only, others keep their value */
+export type NotifyPropsPatch = Partial<Pick<NotifyPropsModel,
+	'desktop' | 'email' | 'push' | 'mark_unread' | 'ignore_channel_mentions'>>
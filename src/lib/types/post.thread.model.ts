@@ -15,6 +15,91 @@ export type MetaAcknowledgement = {
 	acknowledged_at: Date,
 }
 
+export type MetaEmbed = {
+	type: string,
+	url: string,
+	data?: unknown,
+}
+
+export type MetaEmoji = {
+	id: string,
+	creator_id: UserId,
+	name: string,
+	create_at: Date,
+	update_at: Date,
+	delete_at: Date,
+}
+
+export type MetaFile = {
+	id: FileId,
+	user_id: UserId,
+	post_id: PostId,
+	create_at: Date,
+	update_at: Date,
+	delete_at: Date,
+	name: string,
+	extension: string,
+	size: number,
+	width?: number,
+	height?: number,
+	mime_type: string,
+	has_preview_image: boolean,
+}
+
+export type MetaReaction = {
+	user_id: UserId,
+	post_id: PostId,
+	emoji_name: string,
+	create_at: Date,
+	update_at: Date,
+	delete_at: Date,
+}
+
+export type MetaImage = {
+	width: number,
+	height: number,
+	format: string,
+	frame_count: number,
+}
+
+export type MetaPriority = {
+	priority: string,
+	requested_ack: boolean,
+	persistent_notifications: boolean,
+}
+
+export type PostMetadata = {
+	embeds: MetaEmbed[],
+	emojis: MetaEmoji[],
+	files: MetaFile[],
+	reactions: MetaReaction[],
+	images: Record<string, MetaImage>,
+	priority?: MetaPriority,
+	acknowledgements: MetaAcknowledgement[],
+}
+
+export type TableAlignment = 'none' | 'left' | 'center' | 'right'
+
+export type MarkdownInline =
+	| { type: 'text', text: string }
+	| { type: 'code', code: string }
+	| { type: 'emphasis' | 'strong' | 'strikethrough', inlines: MarkdownInline[] }
+	| { type: 'link', url: string, inlines: MarkdownInline[] }
+	| { type: 'image', url: string, alt: string }
+	| { type: 'mention', username: string }
+	| { type: 'channel_link', name: string }
+	| { type: 'emoji', name: string }
+	| { type: 'line_break' }
+
+export type MarkdownBlock =
+	| { type: 'paragraph', inlines: MarkdownInline[] }
+	| { type: 'heading', level: number, inlines: MarkdownInline[] }
+	| { type: 'code_block', language?: string, code: string }
+	| { type: 'block_quote', blocks: MarkdownBlock[] }
+	| { type: 'list', start?: number, items: MarkdownBlock[][] }
+	| { type: 'table', alignments: TableAlignment[], header: MarkdownInline[][], rows: MarkdownInline[][][] }
+	| { type: 'thematic_break' }
+
 export interface PostModel extends IndexedModel {
 	id: PostId,
 	edit_at: Date,
@@ -31,7 +116,8 @@ export interface PostModel extends IndexedModel {
 	file_ids?: FileId[],
 	pending_post_id: PostId,
 	props: unknown,
-	metadata?: MetaAcknowledgement,
+	metadata?: PostMetadata,
+	message_ast?: MarkdownBlock[],
 }
 
 export type PostThread = {